@@ -0,0 +1,139 @@
+use crate::compile::{ConfigValue, Edition, Overflow, SemanticAnalyzer};
+use crate::interp::{self, Engine};
+use crate::manifest::Manifest;
+use crate::metadata::Metadata;
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Trailer layout: the encoded `Metadata` (source included) followed by the
+/// payload length (u64, little endian) and this magic.
+const MAGIC: &[u8; 8] = b"NTFPBNDL";
+const TRAILER_LEN: u64 = 16;
+
+/// Prefix of the metadata fields holding the `[config]` values; strings are
+/// stored as JSON so that they stay on one line.
+const CONFIG_PREFIX: &str = "config.";
+
+pub struct Bundle {
+    pub metadata: Metadata,
+    pub edition: Edition,
+}
+
+/// 复制当前的ntfp可执行文件并在末尾附加源码，生成可独立运行的启动器
+pub fn write_launcher(dest: &Path, mut metadata: Metadata, source: &str) -> Result<()> {
+    let exe = std::env::current_exe().context("无法定位ntfp可执行文件")?;
+    fs::copy(&exe, dest).with_context(|| format!("无法写入文件: {:?}", dest))?;
+
+    metadata.source = Some(source.to_string());
+    let payload = metadata.encode();
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(dest)
+        .with_context(|| format!("无法写入文件: {:?}", dest))?;
+    file.write_all(payload.as_bytes())
+        .and_then(|_| file.write_all(&(payload.len() as u64).to_le_bytes()))
+        .and_then(|_| file.write_all(MAGIC))
+        .with_context(|| format!("无法写入文件: {:?}", dest))
+}
+
+/// `[config]`中的常量，写入启动器的元数据
+pub fn config_fields(manifest: &Manifest) -> Result<Vec<(String, String)>> {
+    manifest
+        .config_values()?
+        .into_iter()
+        .map(|(key, value)| {
+            let encoded = match value {
+                ConfigValue::Int(n) => n.to_string(),
+                ConfigValue::Str(text) => serde_json::to_string(&text)?,
+            };
+            Ok((format!("{}{}", CONFIG_PREFIX, key), encoded))
+        })
+        .collect()
+}
+
+/// 读取当前可执行文件中附加的源码，普通的ntfp返回`None`
+pub fn load_embedded() -> Result<Option<Bundle>> {
+    match std::env::current_exe() {
+        Ok(exe) => read_bundle(&exe),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn read_bundle(exe: &Path) -> Result<Option<Bundle>> {
+    let Ok(mut file) = File::open(exe) else {
+        return Ok(None);
+    };
+    let len = file.metadata()?.len();
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    if &trailer[8..] != MAGIC {
+        return Ok(None);
+    }
+
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if payload_len > len - TRAILER_LEN {
+        anyhow::bail!("嵌入的源码已损坏: {:?}", exe);
+    }
+    let mut payload = vec![0u8; payload_len as usize];
+    file.seek(SeekFrom::End(-((TRAILER_LEN + payload_len) as i64)))?;
+    file.read_exact(&mut payload)?;
+
+    let payload = String::from_utf8(payload).context("嵌入的源码不是有效的UTF-8")?;
+    let metadata = Metadata::decode(&payload)?;
+    let (Some(edition), Some(_)) = (metadata.get("edition"), &metadata.source) else {
+        anyhow::bail!("嵌入的源码已损坏: {:?}", exe);
+    };
+    let edition = Edition::parse(edition)
+        .ok_or_else(|| anyhow::anyhow!("不支持的edition: \"{}\"", edition))?;
+
+    Ok(Some(Bundle { metadata, edition }))
+}
+
+impl Bundle {
+    pub fn run(&self) -> Result<()> {
+        let source = self.metadata.source.as_deref().unwrap_or_default();
+        // The source already passed the project's feature checks when it was bundled.
+        let script = self.metadata.get("top-level-statements") == Some("true");
+        let ast = interp::load_program_with(source, self.edition, &Default::default(), script, self.analyzer()?)?;
+        // Launchers bundled before the bytecode engine existed carry no `engine` field.
+        let engine = match self.metadata.get("engine") {
+            Some(name) => Engine::parse(name)
+                .ok_or_else(|| anyhow::anyhow!("不支持的解释器引擎: \"{}\"", name))?,
+            None => Engine::default(),
+        };
+        // Launchers bundled before `overflow-checks` existed always checked.
+        let overflow = match self.metadata.get("overflow-checks") {
+            Some("false") => Overflow::Wrapping,
+            _ => Overflow::Checked,
+        };
+
+        interp::execute(&ast, engine, overflow, None)
+            .map_err(|e| anyhow::anyhow!("运行错误: {}", e))
+    }
+
+    /// 已声明打包时`[config]`常量的语义分析器
+    fn analyzer(&self) -> Result<SemanticAnalyzer> {
+        let mut analyzer = SemanticAnalyzer::new();
+        for (field, encoded) in &self.metadata.fields {
+            let Some(key) = field.strip_prefix(CONFIG_PREFIX) else {
+                continue;
+            };
+            let value = if encoded.starts_with('"') {
+                ConfigValue::Str(serde_json::from_str(encoded)?)
+            } else {
+                ConfigValue::Int(encoded.parse()?)
+            };
+            analyzer.declare_config(key, value).map_err(|e| anyhow::anyhow!("[config] {}", e))?;
+        }
+        Ok(analyzer)
+    }
+}
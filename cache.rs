@@ -0,0 +1,139 @@
+use crate::{config, size::format_size};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// 每次写入缓存后自动清理到这个大小以内
+pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// 每个缓存条目中记录最近使用时间的文件，清理时先删除最久未使用的条目
+const LAST_USED: &str = "last-used";
+
+/// 缓存键: 内容的FNV-1a哈希。与`std`的哈希不同，它在不同版本的ntfp之间保持稳定
+pub fn key(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        // 分隔符避免 ["ab", "c"] 和 ["a", "bc"] 得到相同的键
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+fn root() -> Result<PathBuf> {
+    config::cache_dir().context("无法确定缓存目录: 请设置HOME或NTFP_HOME")
+}
+
+/// 返回`~/.ntfp/cache/<kind>/<key>`，不存在时创建，并把它标记为刚刚使用过
+pub fn entry(kind: &str, key: &str) -> Result<PathBuf> {
+    let dir = root()?.join(kind).join(key);
+    fs::create_dir_all(&dir).with_context(|| format!("无法创建缓存目录: {:?}", dir))?;
+    let marker = dir.join(LAST_USED);
+    fs::File::create(&marker)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .with_context(|| format!("无法写入文件: {:?}", marker))?;
+    Ok(dir)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// 一次清理的结果
+pub struct GcStats {
+    pub kept: usize,
+    pub kept_size: u64,
+    pub removed: usize,
+    pub removed_size: u64,
+}
+
+/// 从最近使用的条目开始保留，总大小将超过`max_size`时删除该条目和所有更久未使用的条目
+pub fn gc(max_size: u64) -> Result<GcStats> {
+    let root = root()?;
+    let mut entries = Vec::new();
+    for kind in fs::read_dir(&root).into_iter().flatten().flatten() {
+        for entry in fs::read_dir(kind.path()).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            // 没有标记文件的条目 (例如写入到一半) 视为最旧
+            let last_used = fs::metadata(path.join(LAST_USED))
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((last_used, dir_size(&path), path));
+        }
+    }
+    entries.sort_by_key(|(last_used, _, _)| std::cmp::Reverse(*last_used));
+
+    let mut stats = GcStats { kept: 0, kept_size: 0, removed: 0, removed_size: 0 };
+    for (_, size, path) in entries {
+        if stats.removed == 0 && stats.kept_size + size <= max_size {
+            stats.kept += 1;
+            stats.kept_size += size;
+        } else {
+            fs::remove_dir_all(&path).with_context(|| format!("无法删除缓存目录: {:?}", path))?;
+            stats.removed += 1;
+            stats.removed_size += size;
+        }
+    }
+    Ok(stats)
+}
+
+/// 解析`512M`、`2G`、`100K`或字节数
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("无法识别的单位: {:?} (可用: K、M、G)", unit)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("无效的大小: {:?}", s))
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// 删除最久未使用的缓存，直到总大小不超过上限
+    Gc {
+        /// 缓存大小上限，例如512M、2G，0表示清空缓存
+        #[arg(long, default_value = "512M", value_parser = parse_size)]
+        max_size: u64,
+    },
+}
+
+pub fn run(command: CacheCommands) -> Result<()> {
+    match command {
+        CacheCommands::Gc { max_size } => {
+            let stats = gc(max_size)?;
+            println!("删除了 {} 个缓存条目 ({})", stats.removed, format_size(stats.removed_size));
+            println!("保留了 {} 个缓存条目 ({})，位于 {}", stats.kept, format_size(stats.kept_size), root()?.display());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,40 @@
+fn area(w: i32, h: i32) -> i32 {
+    return __ntfp_multiply(w, h, 2);
+}
+fn main() {
+    let x = __ntfp_add(1, __ntfp_multiply(2, 3, 6), 6);
+    print!("{}", __ntfp_divide(__ntfp_subtract(x, 1, 7), 2, 7));
+    print!("{}", area(__ntfp_add(x, 1, 8), __ntfp_subtract(10, __ntfp_subtract(4, 2, 8), 8)));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_subtract(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_sub(b).unwrap_or_else(|| {
+        eprintln!("line {}: subtraction overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_divide(dividend: i32, divisor: i32, line: u32) -> i32 {
+    if divisor == 0 {
+        eprintln!("line {}: division by zero", line);
+        std::process::exit(101);
+    }
+    dividend.checked_div(divisor).unwrap_or_else(|| {
+        eprintln!("line {}: division overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,45 @@
+fn first(xs: Vec<String>) -> String {
+    return xs[0 as usize].clone();
+}
+fn squares(n: i32) -> Vec<i32> {
+    return vec![__ntfp_multiply(n, n, 6), __ntfp_multiply(__ntfp_add(n, 1, 6), __ntfp_add(n, 1, 6), 6), __ntfp_multiply(__ntfp_add(n, 2, 6), __ntfp_add(n, 2, 6), 6)];
+}
+fn main() {
+    let mut primes = vec![2, 3, 5, 7];
+    let names = vec![String::from("ann"), String::from("bob\n")];
+    println!("{:?}", primes);
+    println!("{:?}", names);
+    println!("{}", __ntfp_add(primes[0 as usize], primes[3 as usize], 14));
+    println!("{}", first(names.clone()));
+    let mut total = 0;
+    for i in 0..3 {
+        total = __ntfp_add(total, squares(2)[i as usize], 18);
+    }
+    println!("{}", total);
+    let i = 1;
+    println!("{}", __ntfp_negate(primes[__ntfp_add(i, 1, 22) as usize], 22));
+    println!("{}", (vec![10, 20, 30])[primes[0 as usize] as usize]);
+    primes = squares(1);
+    println!("{}", primes[2 as usize]);
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_negate(a: i32, line: u32) -> i32 {
+    a.checked_neg().unwrap_or_else(|| {
+        eprintln!("line {}: negation overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,31 @@
+fn max(a: i32, b: i32) -> i32 {
+    if a >= b {
+        return a;
+    }
+    return b;
+}
+fn main() {
+    let mut i = 0;
+    while i < 3 {
+        print!("{}", i32::from(i == 1));
+        i = __ntfp_add(i, 1, 12);
+    }
+    print!("{}", i32::from(max(4, 9) != 9));
+    print!("{}", __ntfp_add(i32::from(1 < 2), __ntfp_multiply(i32::from(2 <= 2), 10, 15), 15));
+    print!("{}", i32::from("abc" > "abd"));
+    print!("{}", i32::from(i32::from(3 > 2) == i32::from(2 > 1)));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
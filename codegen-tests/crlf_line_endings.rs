@@ -0,0 +1,6 @@
+fn main() {
+    print!("first\nsecond");
+    print!(" ");
+    let n = 2;
+    print!("{}", n);
+}
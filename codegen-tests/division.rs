@@ -0,0 +1,40 @@
+fn ratio(a: i32, b: i32) -> i32 {
+    return __ntfp_multiply(a, __ntfp_divide(b, a, 2), 2);
+}
+fn main() {
+    let xs = vec![10, 20, 30];
+    let m = std::collections::HashMap::from([(1, String::from("one")), (2, String::from("two"))]);
+    let n = 7;
+    print!("{}", ratio(2, 9));
+    print!("{}", __ntfp_negate(__ntfp_divide(n, 2, 10), 10));
+    print!("{}", xs[__ntfp_divide(n, 3, 11) as usize]);
+    print!("{}", m[&__ntfp_divide(n, 3, 12)]);
+    if __ntfp_divide(n, 2, 13) == 3 {
+        print!("three");
+    }
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_negate(a: i32, line: u32) -> i32 {
+    a.checked_neg().unwrap_or_else(|| {
+        eprintln!("line {}: negation overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_divide(dividend: i32, divisor: i32, line: u32) -> i32 {
+    if divisor == 0 {
+        eprintln!("line {}: division by zero", line);
+        std::process::exit(101);
+    }
+    dividend.checked_div(divisor).unwrap_or_else(|| {
+        eprintln!("line {}: division overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,50 @@
+#[derive(Debug, Clone, Copy)]
+enum Color {
+    Red,
+    Green,
+    Blue,
+}
+fn warm(c: Color) -> i32 {
+    match c {
+        Color::Red => {
+            return 1;
+        }
+        _ => {
+            return 0;
+        }
+    }
+}
+fn name(c: Color) -> String {
+    match c {
+        Color::Red => {
+            return String::from("red");
+        }
+        Color::Green => {
+            return String::from("green");
+        }
+        Color::Blue => {
+            return String::from("blue");
+        }
+    }
+}
+fn main() {
+    let c = Color::Green;
+    println!("{:?}", c);
+    println!("{}", __ntfp_add(warm(c), warm(Color::Red), 35));
+    println!("{}", name(Color::Blue));
+    match c {
+        Color::Green => {
+            let n = 2;
+            println!("{}", n);
+        }
+        _ => {
+        }
+    }
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,20 @@
+extern "C" {
+    #[link_name = "abs"]
+    fn __ntfp_c_abs(n: std::os::raw::c_int) -> i32;
+}
+fn abs(n: i32) -> i32 {
+    unsafe { __ntfp_c_abs(n) }
+}
+extern "C" {
+    #[link_name = "puts"]
+    fn __ntfp_c_puts(s: *const std::os::raw::c_char) -> i32;
+}
+fn puts(s: String) -> i32 {
+    let s = std::ffi::CString::new(s).expect("string passed to extern c function puts contains a NUL byte");
+    unsafe { __ntfp_c_puts(s.as_ptr()) }
+}
+fn main() {
+    let a = abs(3);
+    let _r = puts(String::from("from c"));
+    print!("{}", a);
+}
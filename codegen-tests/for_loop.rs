@@ -0,0 +1,42 @@
+fn sum_to(n: i32) -> i32 {
+    let mut total = 0;
+    for i in 0..__ntfp_add(n, 1, 3) {
+        total = __ntfp_add(total, i, 4);
+    }
+    return total;
+}
+fn find(limit: i32) -> i32 {
+    for k in 1..limit {
+        if __ntfp_multiply(k, k, 11) > 50 {
+            return k;
+        }
+    }
+    return 0;
+}
+fn main() {
+    print!("{}", sum_to(10));
+    print!(" ");
+    print!("{}", find(100));
+    print!(" ");
+    for i in 3..1 {
+        print!("never");
+    }
+    for i in 0..3 {
+        let i = __ntfp_multiply(i, 10, 27);
+        print!("{}", i);
+    }
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,9 @@
+fn main() {
+    let n = 255;
+    print!("{:08}", n);
+    print!("{:#x}", n);
+    print!("{:*^10}", n);
+    print!("{:+}", n);
+    let s = format!("{:b}", n);
+    print!("{}", s);
+}
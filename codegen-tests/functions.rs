@@ -0,0 +1,12 @@
+fn twice(a: i32, b: i32) -> i32 {
+    return b;
+}
+fn greet(name: String) {
+    print!("{}", name);
+    return;
+}
+fn main() {
+    let r = twice(1, 2);
+    greet(String::from("netflu"));
+    print!("{}", r);
+}
@@ -0,0 +1,31 @@
+fn sign(n: i32) -> i32 {
+    if __ntfp_subtract(n, 1, 2) != 0 {
+        if n != 0 {
+            return 2;
+        }
+        return 0;
+    } else {
+        return 1;
+    }
+}
+fn main() {
+    let x = 0;
+    let mut label = String::from("zero");
+    if x != 0 {
+        label = String::from("nonzero");
+    } else if sign(1) != 0 {
+        let x = 5;
+        print!("{}", x);
+    } else {
+        print!("unreachable");
+    }
+    print!("{}", label);
+    print!("{}", sign(7));
+}
+
+fn __ntfp_subtract(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_sub(b).unwrap_or_else(|| {
+        eprintln!("line {}: subtraction overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,29 @@
+fn square(n: i32) -> i32 {
+    __ntfp_multiply(n, n, 2)
+}
+fn shout(text: String) -> String {
+    let loud = format!("{}{}", text, "!");
+    loud
+}
+fn pick(c: i32, a: i32, b: i32) -> i32 {
+    if c != 0 {
+        return a;
+    }
+    b
+}
+fn greet() {
+    println!("hello");
+}
+fn main() {
+    println!("{}", square(7));
+    println!("{}", shout(String::from("hey")));
+    println!("{}", pick(0, 1, 2));
+    greet()
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
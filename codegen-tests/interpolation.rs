@@ -0,0 +1,13 @@
+fn label(name: String, count: i32) -> String {
+    return format!("{}: {}", name, count);
+}
+fn main() {
+    let name = String::from("Netflu");
+    let year = 2026;
+    print!("Hello, {}! It is {}.\n", name, year);
+    print!("{}", format!("{}{}", label(name.clone(), year), "\n"));
+    let braces = format!("{{literal}} {}", name);
+    print!("{}", braces);
+    print!("\n");
+    print!("{{}}\n");
+}
@@ -0,0 +1,38 @@
+fn price(prices: std::collections::HashMap<String, i32>, item: String) -> i32 {
+    return prices[&item];
+}
+fn names() -> std::collections::HashMap<i32, String> {
+    return std::collections::HashMap::from([(1, String::from("one")), (2, String::from("two")), (-1, String::from("minus one"))]);
+}
+fn main() {
+    let mut prices = std::collections::HashMap::from([(String::from("apple"), 3), (String::from("pear"), 5), (String::from("plum"), 2)]);
+    println!("{}", price(prices.clone(), String::from("pear")));
+    println!("{}", __ntfp_add(prices["apple"], prices["plum"], 12));
+    println!("{}", names()[&-1]);
+    let mut total = 0;
+    for i in 1..3 {
+        total = __ntfp_add(total, (std::collections::HashMap::from([(1, 10), (2, 20)]))[&i], 16);
+    }
+    println!("{}", total);
+    let fruit = format!("{}{}", "ap", "ple");
+    println!("{}", __ntfp_multiply(prices[&fruit], 2, 20));
+    let codes = std::collections::HashMap::from([(String::from("a"), String::from("alpha")), (String::from("b"), String::from("beta"))]);
+    println!("{}", format!("{}{}", codes["b"], "!"));
+    println!("{}", names()[&__ntfp_add(1, 1, 23)]);
+    prices = std::collections::HashMap::from([(String::from("fig"), 7)]);
+    println!("{}", prices["fig"]);
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
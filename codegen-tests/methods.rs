@@ -0,0 +1,43 @@
+fn twice(n: i32) -> i32 {
+    __ntfp_multiply(n, 2, 2)
+}
+fn add(a: i32, b: i32) -> i32 {
+    __ntfp_add(a, b, 6)
+}
+fn shout(text: String) {
+    println!("{}", format!("{}{}", text, "!"));
+}
+fn main() {
+    let names = vec![String::from("ann"), String::from("bob")];
+    println!("{}", (names.len() as i32));
+    println!("{}", twice((names[1 as usize].len() as i32)));
+    println!("{}", twice(add(3, 4)));
+    println!("{}", __ntfp_add(((std::collections::HashMap::from([(String::from("a"), 1), (String::from("b"), 2)])).len() as i32), ("hello".len() as i32), 18));
+    let word = format!("{}{}", "net", "flu");
+    if (word.len() as i32) > 5 {
+        shout(word.clone());
+    }
+    println!("{}", __ntfp_negate((names[0 as usize].len() as i32), 23));
+    twice(add((names.len() as i32), __ntfp_add(1, 2, 24)));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_negate(a: i32, line: u32) -> i32 {
+    a.checked_neg().unwrap_or_else(|| {
+        eprintln!("line {}: negation overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,39 @@
+fn flip(n: i32) -> i32 {
+    return __ntfp_negate(n, 2);
+}
+fn main() {
+    let x = -5;
+    let y = __ntfp_multiply(__ntfp_negate(x, 7), 2, 7);
+    print!("{}", __ntfp_negate(__ntfp_add(x, y, 8), 8));
+    print!("{}", __ntfp_subtract(y, __ntfp_negate(x, 9), 9));
+    print!("{}", flip(__ntfp_add(-2147483648, 1, 10)));
+    print!("{}", __ntfp_negate(__ntfp_negate(x, 11), 11));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_subtract(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_sub(b).unwrap_or_else(|| {
+        eprintln!("line {}: subtraction overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_negate(a: i32, line: u32) -> i32 {
+    a.checked_neg().unwrap_or_else(|| {
+        eprintln!("line {}: negation overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,47 @@
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Low,
+    High,
+}
+fn rank(l: Level) -> i32 {
+    match l {
+        Level::Low => {
+            return 1;
+        }
+        Level::High => {
+            return 2;
+        }
+    }
+}
+fn eq(a: Level, b: Level) -> i32 {
+    return i32::from(rank(a) == rank(b));
+}
+impl PartialEq<Level> for Level {
+    fn eq(&self, other: &Level) -> bool {
+        eq(*self, *other) != 0
+    }
+}
+fn add(a: Level, b: Level) -> i32 {
+    return __ntfp_add(rank(a), rank(b), 22);
+}
+impl std::ops::Add<Level> for Level {
+    type Output = i32;
+    fn add(self, other: Level) -> i32 {
+        add(self, other)
+    }
+}
+fn main() {
+    let a = Level::Low;
+    println!("{}", a + Level::High);
+    println!("{}", i32::from(a == Level::Low));
+    if a != Level::High {
+        println!("different");
+    }
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
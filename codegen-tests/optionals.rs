@@ -0,0 +1,43 @@
+fn find(names: Vec<String>, wanted: String) -> Option<i32> {
+    for i in 0..(names.len() as i32) {
+        if names[i as usize] == wanted {
+            return Some(i);
+        }
+    }
+    None
+}
+fn or_zero(n: Option<i32>) -> i32 {
+    match n {
+        Some(value) => {
+            return value;
+        }
+        None => {
+            return 0;
+        }
+    }
+}
+fn main() {
+    let names = vec![String::from("ann"), String::from("bob")];
+    println!("{}", or_zero(find(names.clone(), String::from("bob"))));
+    println!("{}", or_zero(None));
+    let mut last = Some(String::from("ann"));
+    println!("{:?}", last);
+    last = None;
+    println!("{:?}", last);
+    match find(names.clone(), String::from("eve")) {
+        Some(mut i) => {
+            i = __ntfp_add(i, 1, 31);
+            println!("{}", i);
+        }
+        _ => {
+            println!("not found");
+        }
+    }
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -0,0 +1,11 @@
+fn main() {
+    let n = 42;
+    let name = String::from("Netflu");
+    print!("no newline, ");
+    println!("then one");
+    println!("{}", n);
+    println!("{}", name);
+    println!("{} is {}", name, n);
+    println!("{:>5}", n);
+    println!("{}", i32::from(n == 42));
+}
@@ -0,0 +1,7 @@
+#[no_mangle]
+pub extern "C" fn add_one(a: i32) -> i32 {
+    return a;
+}
+fn main() {
+    print!("{}", add_one(1));
+}
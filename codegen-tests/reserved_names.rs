@@ -0,0 +1,29 @@
+#[export_name = "super"]
+pub extern "C" fn __ntfp_super(__ntfp_self: i32, r#fn: i32) -> i32 {
+    return __ntfp_add(__ntfp_self, r#fn, 2);
+}
+fn r#loop(r#use: String, __ntfp_crate: i32) -> String {
+    if __ntfp_crate != 0 {
+        return r#use;
+    }
+    return String::from("none");
+}
+fn __ntfp_Self() {
+    print!("Self\n");
+}
+fn main() {
+    let mut r#type = 1;
+    let __ntfp__ = 2;
+    r#type = __ntfp_add(r#type, __ntfp__, 19);
+    let r = __ntfp_super(r#type, 4);
+    print!("{}", r#loop(String::from("use\n"), r));
+    print!("{}", r#loop(String::from("crate\n"), 0));
+    __ntfp_Self();
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
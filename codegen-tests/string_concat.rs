@@ -0,0 +1,11 @@
+fn greeting(name: String) -> String {
+    return format!("{}{}", "Hello, ", name);
+}
+fn main() {
+    let name = String::from("Netflu");
+    print!("{}", format!("{}{}", greeting(name.clone()), "!\n"));
+    let mut line = format!("{}{}{}{}", "a", "b", "c", format!("{:>4}", 42));
+    line = format!("{}{}", line, "\n");
+    print!("{}", line);
+    print!("{}", i32::from(format!("{}{}", "ab", "c") == "abc"));
+}
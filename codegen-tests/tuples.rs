@@ -0,0 +1,48 @@
+fn divide(a: i32, b: i32) -> (i32, i32) {
+    return (__ntfp_divide(a, b, 2), __ntfp_subtract(a, __ntfp_multiply(__ntfp_divide(a, b, 2), b, 2), 2));
+}
+fn label(pair: (i32, String)) -> String {
+    let (_n, name) = pair.clone();
+    return name;
+}
+fn main() {
+    let (q, mut r) = divide(17, 5);
+    println!("{}", __ntfp_add(q, r, 12));
+    r = __ntfp_add(r, 1, 13);
+    println!("{}", r);
+    let pair = (q, String::from("three"));
+    println!("{:?}", pair);
+    println!("{}", label(pair.clone()));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_subtract(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_sub(b).unwrap_or_else(|| {
+        eprintln!("line {}: subtraction overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_divide(dividend: i32, divisor: i32, line: u32) -> i32 {
+    if divisor == 0 {
+        eprintln!("line {}: division by zero", line);
+        std::process::exit(101);
+    }
+    dividend.checked_div(divisor).unwrap_or_else(|| {
+        eprintln!("line {}: division overflow", line);
+        std::process::exit(101);
+    })
+}
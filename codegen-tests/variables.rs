@@ -0,0 +1,7 @@
+fn main() {
+    let greeting = String::from("hi");
+    let mut n = 42;
+    n = 7;
+    print!("{}", greeting);
+    print!("{}", n);
+}
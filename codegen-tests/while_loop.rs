@@ -0,0 +1,54 @@
+fn first_multiple(of: i32, from: i32) -> i32 {
+    let mut n = from;
+    while n != 0 {
+        if __ntfp_subtract(n, __ntfp_multiply(__ntfp_divide(n, of, 4), of, 4), 4) != 0 {
+            n = __ntfp_add(n, 1, 5);
+        } else {
+            return n;
+        }
+    }
+    return 0;
+}
+fn main() {
+    let mut i = 3;
+    let mut total = 0;
+    while i != 0 {
+        let square = __ntfp_multiply(i, i, 17);
+        total = __ntfp_add(total, square, 18);
+        i = __ntfp_subtract(i, 1, 19);
+    }
+    print!("{}", total);
+    print!("{}", first_multiple(7, 20));
+}
+
+fn __ntfp_add(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_add(b).unwrap_or_else(|| {
+        eprintln!("line {}: addition overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_subtract(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_sub(b).unwrap_or_else(|| {
+        eprintln!("line {}: subtraction overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_multiply(a: i32, b: i32, line: u32) -> i32 {
+    a.checked_mul(b).unwrap_or_else(|| {
+        eprintln!("line {}: multiplication overflow", line);
+        std::process::exit(101);
+    })
+}
+
+fn __ntfp_divide(dividend: i32, divisor: i32, line: u32) -> i32 {
+    if divisor == 0 {
+        eprintln!("line {}: division by zero", line);
+        std::process::exit(101);
+    }
+    dividend.checked_div(divisor).unwrap_or_else(|| {
+        eprintln!("line {}: division overflow", line);
+        std::process::exit(101);
+    })
+}
@@ -1,13 +1,28 @@
 use regex::Regex;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::LazyLock;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TokenType {
     Let,
     Print,
+    Println,
     Method,
     Fun,
     Back,
+    Extern,
+    Pub,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Enum,
+    Match,
     Identifier,
     Number,
     String,
@@ -19,86 +34,331 @@ pub enum TokenType {
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Arrow,
     Multiply,
     Divide,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    DotDot,
+    Dot,
+    Question,
     Mismatch,
 }
 
+/// Language editions; older editions keep deprecated syntax working.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Edition {
+    #[serde(rename = "2025")]
+    E2025,
+    #[serde(rename = "2026")]
+    E2026,
+}
+
+impl Edition {
+    pub const LATEST: Edition = Edition::E2026;
+    pub const ALL: &[Edition] = &[Edition::E2025, Edition::E2026];
+
+    pub fn parse(s: &str) -> Option<Edition> {
+        Edition::ALL.iter().copied().find(|e| e.as_str() == s)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edition::E2025 => "2025",
+            Edition::E2026 => "2026",
+        }
+    }
+}
+
+/// Constructs a project can switch off through `[language]` in ntfp.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Functions other than `main`.
+    Functions,
+    Parameters,
+    /// `back` and `-> type`.
+    Returns,
+    /// Reassigning an existing variable.
+    Assignment,
+    /// `extern` declarations of functions implemented outside Netflu.
+    Extern,
+    /// `+ - * /` on int values.
+    Arithmetic,
+    /// `== != < > <= >=`.
+    Comparisons,
+    /// `if`/`else`.
+    Conditionals,
+    /// `while` and `for` loops.
+    Loops,
+    /// Array literals `[a, b]` and indexing `xs[i]`.
+    Arrays,
+    /// Map literals `{"a": 1}` and lookups `m["a"]`.
+    Maps,
+    /// `enum` declarations and `match`.
+    Enums,
+    /// Tuple literals `(a, b)` and `let (a, b) = ...`.
+    Tuples,
+    /// Optional types `int?`, `some(x)`, `none` and `match` on them.
+    Optionals,
+}
+
+impl Feature {
+    pub const ALL: &[Feature] = &[
+        Feature::Functions,
+        Feature::Parameters,
+        Feature::Returns,
+        Feature::Assignment,
+        Feature::Extern,
+        Feature::Arithmetic,
+        Feature::Comparisons,
+        Feature::Conditionals,
+        Feature::Loops,
+        Feature::Arrays,
+        Feature::Maps,
+        Feature::Enums,
+        Feature::Tuples,
+        Feature::Optionals,
+    ];
+
+    pub fn parse(s: &str) -> Option<Feature> {
+        Feature::ALL.iter().copied().find(|f| f.name() == s)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Functions => "functions",
+            Feature::Parameters => "parameters",
+            Feature::Returns => "returns",
+            Feature::Assignment => "assignment",
+            Feature::Extern => "extern",
+            Feature::Arithmetic => "arithmetic",
+            Feature::Comparisons => "comparisons",
+            Feature::Conditionals => "conditionals",
+            Feature::Loops => "loops",
+            Feature::Arrays => "arrays",
+            Feature::Maps => "maps",
+            Feature::Enums => "enums",
+            Feature::Tuples => "tuples",
+            Feature::Optionals => "optionals",
+        }
+    }
+}
+
+/// Warnings that a project can allow or deny in `[lints]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// Syntax kept only for older editions, such as `method`.
+    Deprecated,
+    /// A `let` binding whose value is never read.
+    UnusedVariable,
+    /// Reported by a compiler plugin from `[plugins]`.
+    Plugin,
+}
+
+impl Lint {
+    pub const ALL: &[Lint] = &[Lint::Deprecated, Lint::UnusedVariable, Lint::Plugin];
+
+    pub fn parse(s: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|l| l.name() == s)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::Deprecated => "deprecated",
+            Lint::UnusedVariable => "unused_variable",
+            Lint::Plugin => "plugin",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
+    #[serde(rename = "type")]
     pub type_: TokenType,
     pub value: String,
     pub line: u32,
     pub position: usize,
 }
 
-pub fn lexer(code: &str) -> Result<Vec<Token>, String> {
-    let token_specs = [
-        (TokenType::Let, r"let"),
-        (TokenType::Print, r"print"),
-        (TokenType::Method, r"method"),
-        (TokenType::Fun, r"fun"),
-        (TokenType::Back, r"back"),
-        (TokenType::Identifier, r"[a-zA-Z_][a-zA-Z0-9_]*"),
-        (TokenType::Number, r"\d+"),
-        (TokenType::String, r#""[^"]*""#),
-        (TokenType::Plus, r"\+"),
-        (TokenType::Minus, r"-"),
-        (TokenType::Assign, r"="),
-        (TokenType::Semicolon, r";"),
-        (TokenType::LParen, r"\("),
-        (TokenType::RParen, r"\)"),
-        (TokenType::LBrace, r"\{"),
-        (TokenType::RBrace, r"\}"),
-        (TokenType::Multiply, r"\*"),
-        (TokenType::Divide, r"/"),
-        (TokenType::Mismatch, r"."),
-    ];
+/// Source location of a node. Spans never affect AST equality, so reformatted code compares equal.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub line: u32,
+    /// Byte offset of the token the span was taken from.
+    pub start: usize,
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+impl Token {
+    pub fn span(&self) -> Span {
+        Span { line: self.line, start: self.position }
+    }
+}
+
+/// Version of the `ntfp build --emit ast-json` format. Bump it whenever a
+/// token, node or field is added, renamed or changes meaning.
+pub const AST_JSON_SCHEMA: u32 = 12;
+
+/// The document written by `ntfp build --emit ast-json`, for tools that read
+/// the compiler's output. `ast` is the program after semantic analysis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AstJson {
+    pub schema: u32,
+    pub edition: Edition,
+    pub tokens: Vec<Token>,
+    pub ast: Vec<ASTNode>,
+}
+
+/// Token patterns in match priority order, shared by the lexer and the grammar export.
+/// Keywords end at a word boundary so that `letter` or `iffy` stay identifiers.
+pub const TOKEN_SPECS: &[(TokenType, &str)] = &[
+    (TokenType::Let, r"let\b"),
+    (TokenType::Print, r"print\b"),
+    (TokenType::Println, r"println\b"),
+    (TokenType::Method, r"method\b"),
+    (TokenType::Fun, r"fun\b"),
+    (TokenType::Back, r"back\b"),
+    (TokenType::Extern, r"extern\b"),
+    (TokenType::Pub, r"pub\b"),
+    (TokenType::If, r"if\b"),
+    (TokenType::Else, r"else\b"),
+    (TokenType::While, r"while\b"),
+    (TokenType::For, r"for\b"),
+    (TokenType::In, r"in\b"),
+    (TokenType::Enum, r"enum\b"),
+    (TokenType::Match, r"match\b"),
+    (TokenType::Identifier, r"[a-zA-Z_][a-zA-Z0-9_]*"),
+    (TokenType::Number, r"\d+"),
+    (TokenType::String, r#""[^"]*""#),
+    (TokenType::Plus, r"\+"),
+    (TokenType::Arrow, r"->"),
+    (TokenType::Minus, r"-"),
+    (TokenType::Equal, r"=="),
+    (TokenType::NotEqual, r"!="),
+    (TokenType::LessEqual, r"<="),
+    (TokenType::GreaterEqual, r">="),
+    (TokenType::Less, r"<"),
+    (TokenType::Greater, r">"),
+    (TokenType::Assign, r"="),
+    (TokenType::Semicolon, r";"),
+    (TokenType::LParen, r"\("),
+    (TokenType::RParen, r"\)"),
+    (TokenType::LBrace, r"\{"),
+    (TokenType::RBrace, r"\}"),
+    (TokenType::LBracket, r"\["),
+    (TokenType::RBracket, r"\]"),
+    (TokenType::Comma, r","),
+    (TokenType::Colon, r":"),
+    (TokenType::Multiply, r"\*"),
+    (TokenType::Divide, r"/"),
+    (TokenType::DotDot, r"\.\."),
+    (TokenType::Dot, r"\."),
+    (TokenType::Question, r"\?"),
+    (TokenType::Mismatch, r"."),
+];
 
-    let pattern: String = token_specs
+/// One alternative per entry of `TOKEN_SPECS`, in order. Built once: compiling
+/// it costs more than lexing a small file, and the LSP lexes on every change.
+static TOKEN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let pattern: String = TOKEN_SPECS
         .iter()
         .map(|(t, r)| format!(r"(?P<{}>{})", token_type_to_name(t), r))
         .collect::<Vec<_>>()
         .join("|");
+    Regex::new(&pattern).expect("TOKEN_SPECS are valid regexes")
+});
+
+/// A `//` or `/* */` comment, which the lexer skips; `text` includes the delimiters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub line: u32,
+    pub position: usize,
+}
+
+pub fn lexer(code: &str) -> Result<Vec<Token>, String> {
+    lex(code).map(|(tokens, _)| tokens)
+}
 
-    let re = Regex::new(&pattern).map_err(|e| format!("Regex error: {}", e))?;
+/// Like `lexer`, but also returns the comments, for tools that keep them (`ntfp fmt`).
+#[tracing::instrument(skip_all, fields(bytes = code.len()))]
+pub fn lex(code: &str) -> Result<(Vec<Token>, Vec<Comment>), String> {
+    let re = &*TOKEN_REGEX;
+    let mut locations = re.capture_locations();
     let mut tokens = Vec::new();
+    let mut comments = Vec::new();
     let mut position = 0;
     let mut line = 1;
 
     while position < code.len() {
-        if code[position..].starts_with(|c: char| c.is_whitespace()) {
-            let c = code.chars().nth(position).unwrap();
+        if let Some(c) = code[position..].chars().next().filter(|c| c.is_whitespace()) {
             if c == '\n' {
                 line += 1;
             }
-            position += 1;
+            position += c.len_utf8();
             continue;
         }
 
-        let Some(captures) = re.captures(&code[position..]) else {
-            return Err(format!(
-                "Unexpected character at position {}",
-                position
-            ));
-        };
+        if code[position..].starts_with("//") {
+            // The newline is left for the whitespace branch, which counts it
+            let end = code[position..].find('\n').map_or(code.len(), |i| position + i);
+            comments.push(Comment {
+                text: code[position..end].trim_end().to_string(),
+                line,
+                position,
+            });
+            position = end;
+            continue;
+        }
+
+        if code[position..].starts_with("/*") {
+            let end = block_comment_end(code, position)
+                .ok_or_else(|| format!("line {}: unterminated block comment; close it with `*/`", line))?;
+            let text = &code[position..end];
+            comments.push(Comment { text: text.to_string(), line, position });
+            line += text.matches('\n').count() as u32;
+            position = end;
+            continue;
+        }
+        if code[position..].starts_with("*/") {
+            return Err(format!("line {}: `*/` without a matching `/*`", line));
+        }
 
-        let (token_type, value) = token_specs
+        // `Mismatch` matches any character, so there is always a match right at `position`
+        if re.captures_read_at(&mut locations, code, position).is_none() {
+            return Err(format!("line {}: unexpected character", line));
+        }
+        // Group `i + 1` is `TOKEN_SPECS[i]`
+        let (token_type, value) = TOKEN_SPECS
             .iter()
-            .find_map(|(t, _)| {
-                captures
-                    .name(token_type_to_name(t))
-                    .map(|m| (t.clone(), m.as_str().to_string()))
-            })
-            .ok_or_else(|| format!("Unexpected token at position {}", position))?;
+            .enumerate()
+            .find_map(|(i, (t, _))| locations.get(i + 1).map(|(start, end)| (t.clone(), code[start..end].to_string())))
+            .ok_or_else(|| format!("line {}: unexpected token", line))?;
 
         if token_type == TokenType::Mismatch {
-            return Err(format!(
-                "Unexpected character '{}' at position {}",
-                value, position
-            ));
+            return Err(format!("line {}: unexpected character '{}'", line, value));
         }
 
+        trace!(?token_type, %value, line, position, "token");
         tokens.push(Token {
             type_: token_type,
             value: value.clone(),
@@ -109,16 +369,49 @@ pub fn lexer(code: &str) -> Result<Vec<Token>, String> {
         position += value.len();
     }
 
-    Ok(tokens)
+    debug!(count = tokens.len(), comments = comments.len(), "lexing finished");
+    Ok((tokens, comments))
+}
+
+/// End of the block comment starting at `start`. Block comments nest, so
+/// commenting out code that already has one works.
+fn block_comment_end(code: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < code.len() {
+        if code[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if code[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += code[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
 }
 
-fn token_type_to_name(t: &TokenType) -> &str {
+pub fn token_type_to_name(t: &TokenType) -> &'static str {
     match t {
         TokenType::Let => "LET",
         TokenType::Print => "PRINT",
+        TokenType::Println => "PRINTLN",
         TokenType::Method => "METHOD",
         TokenType::Fun => "FUN",
         TokenType::Back => "BACK",
+        TokenType::Extern => "EXTERN",
+        TokenType::Pub => "PUB",
+        TokenType::If => "IF",
+        TokenType::Else => "ELSE",
+        TokenType::While => "WHILE",
+        TokenType::For => "FOR",
+        TokenType::In => "IN",
+        TokenType::Enum => "ENUM",
+        TokenType::Match => "MATCH",
         TokenType::Identifier => "IDENTIFIER",
         TokenType::Number => "NUMBER",
         TokenType::String => "STRING",
@@ -130,40 +423,120 @@ fn token_type_to_name(t: &TokenType) -> &str {
         TokenType::RParen => "RPAREN",
         TokenType::LBrace => "LBRACE",
         TokenType::RBrace => "RBRACE",
+        TokenType::LBracket => "LBRACKET",
+        TokenType::RBracket => "RBRACKET",
+        TokenType::Comma => "COMMA",
+        TokenType::Colon => "COLON",
+        TokenType::Arrow => "ARROW",
         TokenType::Multiply => "MULTIPLY",
         TokenType::Divide => "DIVIDE",
+        TokenType::Equal => "EQUAL",
+        TokenType::NotEqual => "NOT_EQUAL",
+        TokenType::LessEqual => "LESS_EQUAL",
+        TokenType::GreaterEqual => "GREATER_EQUAL",
+        TokenType::Less => "LESS",
+        TokenType::Greater => "GREATER",
+        TokenType::DotDot => "DOT_DOT",
+        TokenType::Dot => "DOT",
+        TokenType::Question => "QUESTION",
         TokenType::Mismatch => "MISMATCH",
     }
 }
 
-#[derive(Debug, Clone)]
+/// One arm of a `match`: the body runs when the value is `variant`. On an
+/// optional value the variants are `some`, which binds the value inside, and `none`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub variant: String,
+    /// The name in `some(name)`.
+    pub binding: Option<Binding>,
+    pub body: Vec<ASTNode>,
+    /// Position of `variant`.
+    pub span: Span,
+}
+
+/// One of the names a `let (a, b) = ...` binds, in element order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub name: String,
+    /// Set by the analyzer when the binding is reassigned later.
+    pub mutable: bool,
+    pub span: Span,
+}
+
+/// A function parameter; without an annotation (`name: type`) it is an int.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<String>,
+    pub span: Span,
+}
+
+impl Param {
+    pub fn type_name(&self) -> &str {
+        self.ty.as_deref().unwrap_or("int")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum ASTNode {
     Let {
         name: String,
         value: Box<ASTNode>,
+        /// Set by the analyzer when the binding is reassigned later.
+        mutable: bool,
+        span: Span,
     },
-    Print {
+    /// `let (a, b) = value;`, binding each element of a tuple; there are as
+    /// many bindings as the tuple has elements.
+    LetTuple {
+        bindings: Vec<Binding>,
         value: Box<ASTNode>,
+        /// Position of the `(`.
+        span: Span,
     },
-    Method {
-        name: String,
-        body: Vec<ASTNode>,
-        local_symbol_table: HashMap<String, i32>,
-        return_value: Option<String>,
+    /// `print` or, with `newline`, `println`.
+    Print {
+        value: Box<ASTNode>,
+        /// Inferred by the analyzer; codegen picks the format string from it.
+        value_type: Option<Type>,
+        newline: bool,
     },
-    Fun {
+    Function {
         name: String,
+        params: Vec<Param>,
+        return_type: Option<String>,
         body: Vec<ASTNode>,
+        /// `pub fun`: exported with the C ABI when the project is built as a library.
+        public: bool,
+        span: Span,
     },
+    /// `back value;`, or with `implicit` the expression a function body ends
+    /// with (edition 2026), which returns the same way.
     Back {
-        value: String,
+        value: Option<Box<ASTNode>>,
+        span: Span,
+        implicit: bool,
+    },
+    /// `extern <abi> fun name(params) -> type;`, implemented outside Netflu.
+    ExternFunction {
+        abi: String,
+        name: String,
+        params: Vec<Param>,
+        return_type: Option<String>,
+        span: Span,
     },
     FunctionCall {
         name: String,
         args: Vec<ASTNode>,
+        span: Span,
     },
     Identifier {
         name: String,
+        span: Span,
+        /// Inferred by the analyzer; codegen clones values whose Rust type is not `Copy`.
+        ty: Option<Type>,
     },
     Number {
         value: String,
@@ -171,524 +544,4407 @@ pub enum ASTNode {
     String {
         value: String,
     },
+    /// A string literal with `{name}` placeholders (edition 2026). `value` is
+    /// the literal as written; `args` holds one `Identifier` per placeholder.
+    Interpolation {
+        value: String,
+        args: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `[a, b, c]`; never empty, as the elements give the array its type.
+    Array {
+        elements: Vec<ASTNode>,
+        /// Position of the `[`.
+        span: Span,
+    },
+    /// `{key: value, ...}`; never empty, as the entries give the map its type.
+    Map {
+        entries: Vec<(ASTNode, ASTNode)>,
+        /// Position of the `{`.
+        span: Span,
+    },
+    /// `none` where the analyzer knows the optional type `ty` it must have. The
+    /// parser reads `none` as a name; the analyzer replaces it with this node.
+    None {
+        ty: Type,
+        span: Span,
+    },
+    /// `(a, b, ...)`, with at least two elements; `(a)` only groups.
+    Tuple {
+        elements: Vec<ASTNode>,
+        /// Position of the `(`.
+        span: Span,
+    },
+    /// `array[index]`, counting from 0, or `map[key]`.
+    Index {
+        collection: Box<ASTNode>,
+        index: Box<ASTNode>,
+        /// Type of `collection`, inferred by the analyzer; codegen converts an
+        /// array index to `usize` but borrows a map key, and clones elements and
+        /// values that are not `Copy`.
+        collection_type: Option<Type>,
+        /// Position of the `[`.
+        span: Span,
+    },
+    /// `receiver.name(args)`: the built-in `len()`, or a call of the function
+    /// `name` with `receiver` as its first argument.
+    MethodCall {
+        receiver: Box<ASTNode>,
+        name: String,
+        args: Vec<ASTNode>,
+        /// What `name` resolved to, set by the analyzer.
+        method: Option<Method>,
+        /// Position of `name`.
+        span: Span,
+    },
+    /// `object.name`. No value has fields yet; the valid forms are an enum
+    /// variant such as `Color.Red`, where `object` names the enum, and
+    /// `config.key`, which the analyzer replaces with the configured literal.
+    Field {
+        object: Box<ASTNode>,
+        name: String,
+        /// The enum when `name` is one of its variants, set by the analyzer.
+        enum_type: Option<Type>,
+        /// Position of `name`.
+        span: Span,
+    },
+    /// `enum Name { A; B; }`, at the top level; never empty.
+    Enum {
+        name: String,
+        variants: Vec<String>,
+        span: Span,
+    },
+    /// `match value { A { ... } B { ... } else { ... } }` over an enum value.
+    /// `else_body` runs for the variants without an arm; `None` without `else`.
+    Match {
+        value: Box<ASTNode>,
+        arms: Vec<MatchArm>,
+        else_body: Option<Vec<ASTNode>>,
+        /// Type of `value`, inferred by the analyzer; codegen spells the
+        /// variants with it.
+        value_type: Option<Type>,
+        /// Position of `match`.
+        span: Span,
+    },
     Assign {
         name: String,
         value: Box<ASTNode>,
+        span: Span,
+    },
+    /// `if condition { ... } else { ... }`. `else_body` is empty without an
+    /// `else`, and holds a single `If` for `else if`.
+    If {
+        condition: Box<ASTNode>,
+        then_body: Vec<ASTNode>,
+        else_body: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `while condition { ... }`.
+    While {
+        condition: Box<ASTNode>,
+        body: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `for var in start..end { ... }`, counting up from `start` to `end - 1`.
+    /// `span` is the loop variable's.
+    For {
+        var: String,
+        start: Box<ASTNode>,
+        end: Box<ASTNode>,
+        body: Vec<ASTNode>,
+        span: Span,
+    },
+    /// `left op right`; grouping parentheses leave no node of their own.
+    Binary {
+        op: BinaryOp,
+        left: Box<ASTNode>,
+        right: Box<ASTNode>,
+        /// Type of the left operand, inferred by the analyzer; `+` on str values
+        /// joins them, and enum operands call the top-level `add` or `eq`.
+        operand_type: Option<Type>,
+        /// Line of the operator.
+        span: Span,
+    },
+    /// `-operand`, for int values.
+    Negate {
+        operand: Box<ASTNode>,
+        /// Line of the `-`.
+        span: Span,
     },
 }
 
-pub struct Parser {
-    pub tokens: Vec<Token>,
-    pub pos: usize,
+/// What a method call resolved to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Method {
+    /// `len()` of a str (in bytes), an array or a map.
+    Len,
+    /// A function taking the receiver as its first parameter: `xs.sum()` is `sum(xs)`.
+    Function,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, pos: 0 }
+/// Arithmetic operators on int values, and comparisons, which give 1 when
+/// they hold and 0 otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+}
+
+impl BinaryOp {
+    fn from_token(token: &TokenType) -> Option<BinaryOp> {
+        match token {
+            TokenType::Plus => Some(BinaryOp::Add),
+            TokenType::Minus => Some(BinaryOp::Subtract),
+            TokenType::Multiply => Some(BinaryOp::Multiply),
+            TokenType::Divide => Some(BinaryOp::Divide),
+            TokenType::Equal => Some(BinaryOp::Equal),
+            TokenType::NotEqual => Some(BinaryOp::NotEqual),
+            TokenType::Less => Some(BinaryOp::Less),
+            TokenType::Greater => Some(BinaryOp::Greater),
+            TokenType::LessEqual => Some(BinaryOp::LessEqual),
+            TokenType::GreaterEqual => Some(BinaryOp::GreaterEqual),
+            _ => None,
+        }
     }
 
-    fn current_token(&self) -> Option<&Token> {
-        self.tokens.get(self.pos)
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::GreaterEqual => ">=",
+        }
     }
 
-    fn eat(&mut self, expected_type: TokenType) -> Result<(), String> {
-        if let Some(token) = self.current_token() {
-            if token.type_ == expected_type {
-                self.pos += 1;
-                Ok(())
-            } else {
-                Err(format!(
-                    "Expected {:?}, got {:?}",
-                    expected_type, token.type_
-                ))
-            }
-        } else {
-            Err(format!("Expected {:?}, got EOF", expected_type))
+    /// Higher binds tighter. Arithmetic is left-associative; comparisons
+    /// can't be chained, as in Rust.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Subtract => 2,
+            BinaryOp::Multiply | BinaryOp::Divide => 3,
+            _ => 1,
         }
     }
 
-    fn parse_expression(&mut self) -> Result<ASTNode, String> {
-        let token = self.current_token().ok_or("Unexpected EOF in expression")?;
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == 1
+    }
 
-        match token.type_ {
-            TokenType::Identifier => {
-                let name = token.value.clone();
-                self.eat(TokenType::Identifier)?;
+    fn feature(&self) -> Feature {
+        if self.is_comparison() { Feature::Comparisons } else { Feature::Arithmetic }
+    }
 
-                if let Some(next_token) = self.current_token() {
-                    if next_token.type_ == TokenType::LParen {
-                        self.parse_function_call(name)
-                    } else {
-                        Ok(ASTNode::Identifier { name })
-                    }
-                } else {
-                    Ok(ASTNode::Identifier { name })
-                }
-            }
-            TokenType::Number => {
-                let value = token.value.clone();
-                self.eat(TokenType::Number)?;
-                Ok(ASTNode::Number { value })
-            }
-            TokenType::String => {
-                let value = token.value.clone();
-                self.eat(TokenType::String)?;
-                Ok(ASTNode::String { value })
-            }
-            _ => Err(format!(
-                "Unexpected token {:?} in expression",
-                token.type_
-            )),
+    /// Name of the top-level function that defines the operator on enum
+    /// operands; `!=` negates `eq`.
+    pub fn overload(&self) -> Option<&'static str> {
+        match self {
+            BinaryOp::Add => Some("add"),
+            BinaryOp::Equal | BinaryOp::NotEqual => Some("eq"),
+            _ => None,
         }
     }
 
-    fn parse_let(&mut self) -> Result<ASTNode, String> {
-        self.eat(TokenType::Let)?;
-
-        let ident_token = self.current_token().ok_or("Expected identifier after let")?;
-        if ident_token.type_ != TokenType::Identifier {
-            return Err("Expected identifier after let".to_string());
+    /// Whether a comparison holds for operands that compare as `ordering`.
+    pub fn holds(&self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            BinaryOp::Equal => ordering == Equal,
+            BinaryOp::NotEqual => ordering != Equal,
+            BinaryOp::Less => ordering == Less,
+            BinaryOp::Greater => ordering == Greater,
+            BinaryOp::LessEqual => ordering != Greater,
+            BinaryOp::GreaterEqual => ordering != Less,
+            _ => unreachable!("{:?} is not a comparison", self),
         }
-        let name = ident_token.value.clone();
-        self.eat(TokenType::Identifier)?;
-
-        self.eat(TokenType::Assign)?;
-        let value = self.parse_expression()?;
-        self.eat(TokenType::Semicolon)?;
-
-        Ok(ASTNode::Let {
-            name,
-            value: Box::new(value),
-        })
     }
 
-    fn parse_print(&mut self) -> Result<ASTNode, String> {
-        self.eat(TokenType::Print)?;
-        self.eat(TokenType::LParen)?;
-        let expr = self.parse_expression()?;
-        self.eat(TokenType::RParen)?;
-        self.eat(TokenType::Semicolon)?;
-
-        Ok(ASTNode::Print {
-            value: Box::new(expr),
-        })
+    /// Evaluates the operator like generated code: overflow is an error unless
+    /// `overflow` wraps it, and a division that fails always is.
+    pub fn apply(&self, a: i32, b: i32, overflow: Overflow) -> Result<i32, String> {
+        let (checked, wrapped, what) = match self {
+            BinaryOp::Add => (a.checked_add(b), a.wrapping_add(b), "addition"),
+            BinaryOp::Subtract => (a.checked_sub(b), a.wrapping_sub(b), "subtraction"),
+            BinaryOp::Multiply => (a.checked_mul(b), a.wrapping_mul(b), "multiplication"),
+            BinaryOp::Divide if b == 0 => return Err("division by zero".to_string()),
+            BinaryOp::Divide => return a.checked_div(b).ok_or_else(|| "division overflow".to_string()),
+            _ => return Ok(i32::from(self.holds(a.cmp(&b)))),
+        };
+        overflow.resolve(checked, wrapped, what)
     }
+}
 
-    fn parse_function_call(&mut self, func_name: String) -> Result<ASTNode, String> {
-        self.eat(TokenType::LParen)?;
-        let mut args = Vec::new();
-
-        while let Some(token) = self.current_token() {
-            if token.type_ == TokenType::RParen {
-                break;
-            }
-
-            if token.type_ == TokenType::Semicolon {
-                self.eat(TokenType::Semicolon)?;
-                continue;
-            }
+/// What int arithmetic does when the result does not fit, set by
+/// `overflow-checks` in the `[profile.*]` of ntfp.toml.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// An error naming the line of the operator, like a failed division.
+    #[default]
+    Checked,
+    /// Wraps around, like Rust without overflow checks.
+    Wrapping,
+}
 
-            let arg = self.parse_expression()?;
-            args.push(arg);
+impl Overflow {
+    /// Negates `n` like generated code does.
+    pub fn negate(self, n: i32) -> Result<i32, String> {
+        self.resolve(n.checked_neg(), n.wrapping_neg(), "negation")
+    }
 
-            if let Some(next_token) = self.current_token() {
-                if next_token.type_ == TokenType::Semicolon {
-                    self.eat(TokenType::Semicolon)?;
-                } else if next_token.type_ != TokenType::RParen {
-                    self.eat(TokenType::Plus)?;
-                }
-            }
+    fn resolve(self, checked: Option<i32>, wrapped: i32, what: &str) -> Result<i32, String> {
+        match self {
+            Overflow::Checked => checked.ok_or_else(|| format!("{} overflow", what)),
+            Overflow::Wrapping => Ok(wrapped),
         }
+    }
+}
 
-        self.eat(TokenType::RParen)?;
-        Ok(ASTNode::FunctionCall { name: func_name, args })
+/// Whether an operand of `op` must be parenthesized to keep its meaning when
+/// printed; `right` for the right-hand operand, since operators associate left
+/// (and comparisons not at all).
+pub fn needs_parens(operand: &ASTNode, op: BinaryOp, right: bool) -> bool {
+    match operand {
+        ASTNode::Binary { op: inner, .. } => {
+            inner.precedence() < op.precedence()
+                || (inner.precedence() == op.precedence() && (right || op.is_comparison()))
+        }
+        _ => false,
     }
+}
 
-    fn parse_method(&mut self) -> Result<ASTNode, String> {
-        self.eat(TokenType::Method)?;
+/// Whether the operand of `-` must be parenthesized: a binary operation would
+/// otherwise lose its first operand to the `-`, and `--x` reads like a decrement.
+pub fn negate_needs_parens(operand: &ASTNode) -> bool {
+    matches!(operand, ASTNode::Binary { .. } | ASTNode::Negate { .. })
+}
 
-        let ident_token = self.current_token().ok_or("Expected method name")?;
-        if ident_token.type_ != TokenType::Identifier {
-            return Err("Expected method name".to_string());
+impl ASTNode {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ASTNode::Let { .. } => "Let",
+            ASTNode::LetTuple { .. } => "LetTuple",
+            ASTNode::Print { .. } => "Print",
+            ASTNode::Function { .. } => "Function",
+            ASTNode::Back { .. } => "Back",
+            ASTNode::ExternFunction { .. } => "ExternFunction",
+            ASTNode::FunctionCall { .. } => "FunctionCall",
+            ASTNode::Identifier { .. } => "Identifier",
+            ASTNode::Number { .. } => "Number",
+            ASTNode::String { .. } => "String",
+            ASTNode::Interpolation { .. } => "Interpolation",
+            ASTNode::Array { .. } => "Array",
+            ASTNode::Map { .. } => "Map",
+            ASTNode::None { .. } => "None",
+            ASTNode::Tuple { .. } => "Tuple",
+            ASTNode::Index { .. } => "Index",
+            ASTNode::MethodCall { .. } => "MethodCall",
+            ASTNode::Field { .. } => "Field",
+            ASTNode::Enum { .. } => "Enum",
+            ASTNode::Match { .. } => "Match",
+            ASTNode::Assign { .. } => "Assign",
+            ASTNode::Binary { .. } => "Binary",
+            ASTNode::Negate { .. } => "Negate",
+            ASTNode::If { .. } => "If",
+            ASTNode::While { .. } => "While",
+            ASTNode::For { .. } => "For",
         }
-        let name = ident_token.value.clone();
-        self.eat(TokenType::Identifier)?;
-
-        self.eat(TokenType::LBrace)?;
-        let mut body = Vec::new();
+    }
 
-        while let Some(token) = self.current_token() {
-            if token.type_ == TokenType::RBrace {
-                break;
+    /// Visits this node and all of its descendants in pre-order.
+    pub fn walk(&self, f: &mut dyn FnMut(&ASTNode)) {
+        f(self);
+        match self {
+            ASTNode::Let { value, .. }
+            | ASTNode::LetTuple { value, .. }
+            | ASTNode::Print { value, .. }
+            | ASTNode::Assign { value, .. } => value.walk(f),
+            ASTNode::Back { value, .. } => {
+                if let Some(value) = value {
+                    value.walk(f);
+                }
             }
-
-            if token.type_ == TokenType::Semicolon {
-                self.eat(TokenType::Semicolon)?;
-                continue;
+            ASTNode::Function { body, .. } => {
+                for stmt in body {
+                    stmt.walk(f);
+                }
             }
-
-            let stmt = self.parse_statement()?;
-            body.push(stmt);
-        }
-
-        self.eat(TokenType::RBrace)?;
-
-        if let Some(token) = self.current_token() {
-            if token.type_ == TokenType::Semicolon {
-                self.eat(TokenType::Semicolon)?;
+            ASTNode::FunctionCall { args, .. } | ASTNode::Interpolation { args, .. } => {
+                for arg in args {
+                    arg.walk(f);
+                }
+            }
+            ASTNode::Array { elements, .. } | ASTNode::Tuple { elements, .. } => {
+                for element in elements {
+                    element.walk(f);
+                }
+            }
+            ASTNode::Map { entries, .. } => {
+                for (key, value) in entries {
+                    key.walk(f);
+                    value.walk(f);
+                }
+            }
+            ASTNode::Index { collection, index, .. } => {
+                collection.walk(f);
+                index.walk(f);
+            }
+            ASTNode::MethodCall { receiver, args, .. } => {
+                receiver.walk(f);
+                for arg in args {
+                    arg.walk(f);
+                }
+            }
+            ASTNode::Field { object, .. } => object.walk(f),
+            ASTNode::Match { value, arms, else_body, .. } => {
+                value.walk(f);
+                for stmt in arms.iter().flat_map(|arm| &arm.body).chain(else_body.iter().flatten()) {
+                    stmt.walk(f);
+                }
+            }
+            ASTNode::Binary { left, right, .. } => {
+                left.walk(f);
+                right.walk(f);
+            }
+            ASTNode::Negate { operand, .. } => operand.walk(f),
+            ASTNode::If { condition, then_body, else_body, .. } => {
+                condition.walk(f);
+                for stmt in then_body.iter().chain(else_body.iter()) {
+                    stmt.walk(f);
+                }
+            }
+            ASTNode::While { condition, body, .. } => {
+                condition.walk(f);
+                for stmt in body {
+                    stmt.walk(f);
+                }
+            }
+            ASTNode::For { start, end, body, .. } => {
+                start.walk(f);
+                end.walk(f);
+                for stmt in body {
+                    stmt.walk(f);
+                }
             }
+            ASTNode::ExternFunction { .. }
+            | ASTNode::Enum { .. }
+            | ASTNode::Identifier { .. }
+            | ASTNode::Number { .. }
+            | ASTNode::String { .. }
+            | ASTNode::None { .. } => {}
         }
+    }
 
-        Ok(ASTNode::Method {
-            name,
-            body,
-            local_symbol_table: HashMap::new(),
-            return_value: None,
+    /// Mutable counterpart of [`ASTNode::walk`], visiting nodes in the same order.
+    pub fn walk_mut(&mut self, f: &mut dyn FnMut(&mut ASTNode)) {
+        f(self);
+        match self {
+            ASTNode::Let { value, .. }
+            | ASTNode::LetTuple { value, .. }
+            | ASTNode::Print { value, .. }
+            | ASTNode::Assign { value, .. } => value.walk_mut(f),
+            ASTNode::Back { value, .. } => {
+                if let Some(value) = value {
+                    value.walk_mut(f);
+                }
+            }
+            ASTNode::Function { body, .. } => {
+                for stmt in body {
+                    stmt.walk_mut(f);
+                }
+            }
+            ASTNode::FunctionCall { args, .. } | ASTNode::Interpolation { args, .. } => {
+                for arg in args {
+                    arg.walk_mut(f);
+                }
+            }
+            ASTNode::Array { elements, .. } | ASTNode::Tuple { elements, .. } => {
+                for element in elements {
+                    element.walk_mut(f);
+                }
+            }
+            ASTNode::Map { entries, .. } => {
+                for (key, value) in entries {
+                    key.walk_mut(f);
+                    value.walk_mut(f);
+                }
+            }
+            ASTNode::Index { collection, index, .. } => {
+                collection.walk_mut(f);
+                index.walk_mut(f);
+            }
+            ASTNode::MethodCall { receiver, args, .. } => {
+                receiver.walk_mut(f);
+                for arg in args {
+                    arg.walk_mut(f);
+                }
+            }
+            ASTNode::Field { object, .. } => object.walk_mut(f),
+            ASTNode::Match { value, arms, else_body, .. } => {
+                value.walk_mut(f);
+                for stmt in arms.iter_mut().flat_map(|arm| &mut arm.body).chain(else_body.iter_mut().flatten()) {
+                    stmt.walk_mut(f);
+                }
+            }
+            ASTNode::Binary { left, right, .. } => {
+                left.walk_mut(f);
+                right.walk_mut(f);
+            }
+            ASTNode::Negate { operand, .. } => operand.walk_mut(f),
+            ASTNode::If { condition, then_body, else_body, .. } => {
+                condition.walk_mut(f);
+                for stmt in then_body.iter_mut().chain(else_body.iter_mut()) {
+                    stmt.walk_mut(f);
+                }
+            }
+            ASTNode::While { condition, body, .. } => {
+                condition.walk_mut(f);
+                for stmt in body {
+                    stmt.walk_mut(f);
+                }
+            }
+            ASTNode::For { start, end, body, .. } => {
+                start.walk_mut(f);
+                end.walk_mut(f);
+                for stmt in body {
+                    stmt.walk_mut(f);
+                }
+            }
+            ASTNode::ExternFunction { .. }
+            | ASTNode::Enum { .. }
+            | ASTNode::Identifier { .. }
+            | ASTNode::Number { .. }
+            | ASTNode::String { .. }
+            | ASTNode::None { .. } => {}
+        }
+    }
+}
+
+/// Every pass over the AST (analysis, codegen, the interpreters, even dropping
+/// it) recurses into nested nodes, so deeper programs would overflow the stack.
+/// Blocks, parentheses, brackets and calls may nest this deep.
+pub const MAX_NESTING: usize = 200;
+
+/// Operator, postfix and `else if` chains are read in a loop, but each link
+/// still nests the AST one level deeper, so they have a limit of their own.
+/// Far longer than anyone writes by hand, it keeps generated code from
+/// overflowing the stack.
+pub const MAX_CHAIN_LINKS: usize = 4000;
+
+/// Stack size for threads that compile or run Netflu code: enough for the
+/// deepest AST the limits above allow, even in debug builds.
+pub const STACK_SIZE: usize = 512 << 20;
+
+pub struct Parser {
+    pub tokens: Vec<Token>,
+    pub pos: usize,
+    pub edition: Edition,
+    pub warnings: Vec<Warning>,
+    pub disabled: HashSet<Feature>,
+    /// Errors the parser recovered from; `parse` reports all of them at the end.
+    pub errors: Vec<String>,
+    /// Inside the arguments of an edition 2025 call, where `+` still separates
+    /// arguments rather than adding them.
+    legacy_args: bool,
+    /// Directly inside a function body of edition 2026 or later, where an
+    /// expression before the closing `}` is the return value.
+    tail_allowed: bool,
+    /// Levels of blocks, parentheses, brackets and calls around the current token.
+    depth: usize,
+    /// Links of the operator, postfix and `else if` chains around the current token.
+    links: usize,
+    /// Token range of each top-level statement returned by `parse`, for tools
+    /// that work on part of a file.
+    pub statement_tokens: Vec<std::ops::Range<usize>>,
+    /// Byte range of every statement, nested ones included, in source order;
+    /// enum variants and match arms count as statements here.
+    /// `ntfp fmt` uses these and `block_ends` to put comments back.
+    pub statement_bytes: Vec<std::ops::Range<usize>>,
+    /// Byte offset of the `}` closing each block, in source order.
+    pub block_ends: Vec<usize>,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser::with_edition(tokens, Edition::LATEST)
+    }
+
+    pub fn with_edition(mut tokens: Vec<Token>, edition: Edition) -> Self {
+        edition_keywords(&mut tokens, edition);
+        Parser {
+            tokens,
+            pos: 0,
+            edition,
+            warnings: Vec::new(),
+            disabled: HashSet::new(),
+            errors: Vec::new(),
+            legacy_args: false,
+            tail_allowed: false,
+            depth: 0,
+            links: 0,
+            statement_tokens: Vec::new(),
+            statement_bytes: Vec::new(),
+            block_ends: Vec::new(),
+        }
+    }
+
+    /// Goes one level deeper, failing past `MAX_NESTING`.
+    fn deeper(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return Err(format!(
+                "line {}: nested too deeply; blocks, parentheses, brackets and calls can be nested at most {} levels deep",
+                self.span().line,
+                MAX_NESTING
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds a link to the chain being read, failing past `MAX_CHAIN_LINKS`.
+    fn link(&mut self) -> Result<(), String> {
+        self.links += 1;
+        if self.links > MAX_CHAIN_LINKS {
+            return Err(format!(
+                "line {}: expression too long; operator, postfix and `else if` chains can have at most {} links in total",
+                self.span().line,
+                MAX_CHAIN_LINKS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs `parse` one level deeper.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let depth = self.depth;
+        let result = self.deeper().and_then(|()| parse(self));
+        self.depth = depth;
+        result
+    }
+
+    fn require(&self, feature: Feature, line: u32) -> Result<(), String> {
+        if self.disabled.contains(&feature) {
+            return Err(format!(
+                "line {}: feature `{}` is disabled in this project",
+                line,
+                feature.name()
+            ));
+        }
+        Ok(())
+    }
+
+    fn current_token(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn span(&self) -> Span {
+        self.current_token().map(Token::span).unwrap_or_default()
+    }
+
+    fn check(&self, expected_type: TokenType) -> bool {
+        self.current_token().is_some_and(|t| t.type_ == expected_type)
+    }
+
+    fn expect_identifier(&mut self, message: &str) -> Result<String, String> {
+        match self.current_token() {
+            Some(token) if token.type_ == TokenType::Identifier => {
+                let name = token.value.clone();
+                self.check_reserved(&name, token.line)?;
+                self.pos += 1;
+                Ok(name)
+            }
+            // Used to be reserved words, so name the problem as `check_reserved` did.
+            Some(token) if EDITION_2026_KEYWORDS.contains(&token.type_) => Err(format!(
+                "line {}: `{}` is a keyword since edition 2026 and cannot be used as a name",
+                token.line, token.value
+            )),
+            _ => Err(message.to_string()),
+        }
+    }
+
+    /// Rejects a name from `RESERVED_WORDS`. Edition 2025 only warns, so that
+    /// programs written before the reservation keep building there.
+    fn check_reserved(&mut self, name: &str, line: u32) -> Result<(), String> {
+        let Some((_, hint)) = RESERVED_WORDS.iter().find(|(word, _)| *word == name) else {
+            return Ok(());
+        };
+        if self.edition >= Edition::E2026 {
+            let hint = hint.map(|hint| format!(" ({})", hint)).unwrap_or_default();
+            return Err(format!("line {}: `{}` is reserved for future use{}", line, name, hint));
+        }
+        self.warnings.push(Warning {
+            lint: Lint::Deprecated,
+            message: format!(
+                "line {}: `{}` is reserved for future use and cannot be used as a name from edition 2026",
+                line, name
+            ),
+        });
+        Ok(())
+    }
+
+    fn eat(&mut self, expected_type: TokenType) -> Result<(), String> {
+        if let Some(token) = self.current_token() {
+            if token.type_ == expected_type {
+                self.pos += 1;
+                Ok(())
+            } else {
+                Err(format!(
+                    "Expected {:?}, got {:?}",
+                    expected_type, token.type_
+                ))
+            }
+        } else {
+            Err(format!("Expected {:?}, got EOF", expected_type))
+        }
+    }
+
+    /// Eats the `;` that ends a statement. When it is missing but the statement
+    /// has clearly ended (the next token is on a later line, a `}` or the end of
+    /// the file), records the error with a fix-it and carries on, so one forgotten
+    /// semicolon doesn't hide the rest of the file.
+    fn eat_semicolon(&mut self) -> Result<(), String> {
+        if self.check(TokenType::Semicolon) {
+            return self.eat(TokenType::Semicolon);
+        }
+        let previous = self.pos.checked_sub(1).and_then(|i| self.tokens.get(i));
+        let ended = match (self.current_token(), previous) {
+            (None, _) => true,
+            (Some(next), Some(previous)) => next.type_ == TokenType::RBrace || next.line > previous.line,
+            (Some(_), None) => false,
+        };
+        match (previous, self.tokens.get(self.pos)) {
+            (Some(previous), _) if ended => {
+                self.errors.push(format!(
+                    "line {}: missing `;` at the end of the statement\n  help: add `;` after `{}`",
+                    previous.line, previous.value
+                ));
+                Ok(())
+            }
+            (Some(previous), Some(next)) => Err(format!(
+                "line {}: expected `;` after `{}`, found `{}`",
+                next.line, previous.value, next.value
+            )),
+            _ => self.eat(TokenType::Semicolon),
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<ASTNode, String> {
+        self.parse_binary(0)
+    }
+
+    fn binary_operator(&self) -> Option<BinaryOp> {
+        let op = BinaryOp::from_token(&self.current_token()?.type_)?;
+        if op == BinaryOp::Add && self.legacy_args {
+            return None;
+        }
+        Some(op)
+    }
+
+    /// Precedence climbing: parses operands joined by operators that bind at
+    /// least as tightly as `min_precedence`. A chain like `1 + 2 + 3` is read
+    /// in a loop, so it does not count towards `MAX_NESTING`.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<ASTNode, String> {
+        let links = self.links;
+        let result = self.parse_operators(min_precedence);
+        self.links = links;
+        result
+    }
+
+    fn parse_operators(&mut self, min_precedence: u8) -> Result<ASTNode, String> {
+        let mut left = self.parse_primary()?;
+        let mut compared = false;
+        while let Some(op) = self.binary_operator().filter(|op| op.precedence() >= min_precedence) {
+            self.link()?;
+            let span = self.span();
+            self.require(op.feature(), span.line)?;
+            if op.is_comparison() && std::mem::replace(&mut compared, true) {
+                return Err(format!(
+                    "line {}: comparisons cannot be chained; combine them with parentheses, e.g. `(a < b) == 1`",
+                    span.line
+                ));
+            }
+            self.pos += 1;
+            let right = self.parse_binary(op.precedence() + 1)?;
+            left = ASTNode::Binary { op, left: Box::new(left), right: Box::new(right), operand_type: None, span };
+        }
+        Ok(left)
+    }
+
+    /// An operand and any `[index]`, `.field` and `.method(args)` after it.
+    fn parse_primary(&mut self) -> Result<ASTNode, String> {
+        let links = self.links;
+        let result = self.parse_operand().and_then(|operand| self.parse_postfix(operand));
+        self.links = links;
+        result
+    }
+
+    /// Applies the postfix operators after `operand` from left to right, so that
+    /// `a.b.c()[0]` indexes the result of `c` called on `a.b`.
+    fn parse_postfix(&mut self, mut operand: ASTNode) -> Result<ASTNode, String> {
+        while let Some(token) = self.current_token().filter(|t| matches!(t.type_, TokenType::LBracket | TokenType::Dot)) {
+            let (span, dot) = (token.span(), token.type_ == TokenType::Dot);
+            self.link()?;
+            if dot {
+                self.eat(TokenType::Dot)?;
+                let span = self.span();
+                let name = self.expect_identifier(&format!("line {}: expected a method or field name after `.`", span.line))?;
+                operand = if self.check(TokenType::LParen) {
+                    let args = self.parse_arguments()?;
+                    ASTNode::MethodCall { receiver: Box::new(operand), name, args, method: None, span }
+                } else {
+                    ASTNode::Field { object: Box::new(operand), name, enum_type: None, span }
+                };
+                continue;
+            }
+            // Arrays and maps are both indexed; which one is up to the analyzer
+            if self.disabled.contains(&Feature::Maps) {
+                self.require(Feature::Arrays, span.line)?;
+            }
+            self.eat(TokenType::LBracket)?;
+            let index = self.bracketed(Self::parse_expression, TokenType::RBracket)?;
+            operand = ASTNode::Index {
+                collection: Box::new(operand),
+                index: Box::new(index),
+                collection_type: None,
+                span,
+            };
+        }
+        Ok(operand)
+    }
+
+    /// Runs `parse` inside `[...]` or `{...}`, ending at `closing`, where `+` is
+    /// an operator even in call arguments of edition 2025.
+    fn bracketed<T>(&mut self, parse: fn(&mut Self) -> Result<T, String>, closing: TokenType) -> Result<T, String> {
+        let outer = std::mem::replace(&mut self.legacy_args, false);
+        let result = self.nested(parse);
+        self.legacy_args = outer;
+        let result = result?;
+        self.eat(closing)?;
+        Ok(result)
+    }
+
+    fn parse_elements(&mut self) -> Result<Vec<ASTNode>, String> {
+        let mut elements = Vec::new();
+        while let Some(token) = self.current_token() {
+            match token.type_ {
+                TokenType::RBracket => break,
+                TokenType::Comma => return Err(format!("line {}: expected an element before `,`", token.line)),
+                _ => {}
+            }
+            elements.push(self.parse_expression()?);
+            match self.current_token().map(|t| &t.type_) {
+                Some(TokenType::RBracket) | None => {}
+                Some(TokenType::Comma) => self.eat_list_comma("element")?,
+                Some(_) => self.eat(TokenType::Comma)?,
+            }
+        }
+        Ok(elements)
+    }
+
+    /// The expressions inside `(...)`: one when it only groups, more for a tuple.
+    fn parse_group(&mut self) -> Result<Vec<ASTNode>, String> {
+        let mut elements = vec![self.parse_expression()?];
+        while let Some(token) = self.current_token().filter(|t| t.type_ == TokenType::Comma) {
+            let line = token.line;
+            self.require(Feature::Tuples, line)?;
+            self.eat_list_comma("element")?;
+            if self.check(TokenType::RParen) {
+                if elements.len() == 1 {
+                    return Err(format!(
+                        "line {}: tuples have at least two elements; remove the `,` to group with parentheses",
+                        line
+                    ));
+                }
+                break;
+            }
+            elements.push(self.parse_expression()?);
+        }
+        Ok(elements)
+    }
+
+    fn parse_entries(&mut self) -> Result<Vec<(ASTNode, ASTNode)>, String> {
+        let mut entries = Vec::new();
+        while let Some(token) = self.current_token() {
+            match token.type_ {
+                TokenType::RBrace => break,
+                TokenType::Comma => return Err(format!("line {}: expected an entry before `,`", token.line)),
+                _ => {}
+            }
+            let key = self.parse_expression()?;
+            if !self.check(TokenType::Colon) {
+                return Err(format!("line {}: expected `:` between the key and the value of an entry", self.span().line));
+            }
+            self.eat(TokenType::Colon)?;
+            let value = self.parse_expression()?;
+            entries.push((key, value));
+            match self.current_token().map(|t| &t.type_) {
+                Some(TokenType::RBrace) | None => {}
+                Some(TokenType::Comma) => self.eat_list_comma("entry")?,
+                Some(_) => self.eat(TokenType::Comma)?,
+            }
+        }
+        Ok(entries)
+    }
+
+    fn parse_operand(&mut self) -> Result<ASTNode, String> {
+        let token = self.current_token().ok_or("Unexpected EOF in expression")?;
+
+        match token.type_ {
+            TokenType::Identifier => {
+                let name = token.value.clone();
+                let span = token.span();
+                self.check_reserved(&name, span.line)?;
+                self.eat(TokenType::Identifier)?;
+
+                if let Some(next_token) = self.current_token() {
+                    if next_token.type_ == TokenType::LParen {
+                        self.parse_function_call(name, span)
+                    } else {
+                        Ok(ASTNode::Identifier { name, span, ty: None })
+                    }
+                } else {
+                    Ok(ASTNode::Identifier { name, span, ty: None })
+                }
+            }
+            TokenType::Number => {
+                let value = token.value.clone();
+                self.eat(TokenType::Number)?;
+                Ok(ASTNode::Number { value })
+            }
+            TokenType::String => {
+                let value = token.value.clone();
+                let span = token.span();
+                self.eat(TokenType::String)?;
+                if self.edition < Edition::E2026 || !value.contains(['{', '}']) {
+                    return Ok(ASTNode::String { value });
+                }
+                let pieces = string_pieces(&value).map_err(|e| format!("line {}: {}", span.line, e))?;
+                let args = pieces
+                    .iter()
+                    .filter_map(|piece| match *piece {
+                        StringPiece::Name(name, offset) => Some(ASTNode::Identifier {
+                            name: name.to_string(),
+                            span: Span {
+                                line: span.line + value[..offset].matches('\n').count() as u32,
+                                start: span.start + offset,
+                            },
+                            ty: None,
+                        }),
+                        StringPiece::Text(_) => None,
+                    })
+                    .collect();
+                Ok(ASTNode::Interpolation { value, args, span })
+            }
+            TokenType::Minus => {
+                let span = token.span();
+                self.require(Feature::Arithmetic, span.line)?;
+                self.eat(TokenType::Minus)?;
+                // Binds tighter than any binary operator: `-a * b` is `(-a) * b`
+                let operand = self.nested(Self::parse_primary)?;
+                Ok(ASTNode::Negate { operand: Box::new(operand), span })
+            }
+            TokenType::LParen => {
+                let span = token.span();
+                self.eat(TokenType::LParen)?;
+                let outer = std::mem::replace(&mut self.legacy_args, false);
+                let elements = self.nested(Self::parse_group);
+                self.legacy_args = outer;
+                let mut elements = elements?;
+                self.eat(TokenType::RParen)?;
+                if elements.len() == 1 {
+                    return Ok(elements.remove(0));
+                }
+                Ok(ASTNode::Tuple { elements, span })
+            }
+            TokenType::LBracket => {
+                let span = token.span();
+                self.require(Feature::Arrays, span.line)?;
+                self.eat(TokenType::LBracket)?;
+                let elements = self.bracketed(Self::parse_elements, TokenType::RBracket)?;
+                Ok(ASTNode::Array { elements, span })
+            }
+            TokenType::LBrace => {
+                let span = token.span();
+                self.require(Feature::Maps, span.line)?;
+                self.eat(TokenType::LBrace)?;
+                let entries = self.bracketed(Self::parse_entries, TokenType::RBrace)?;
+                Ok(ASTNode::Map { entries, span })
+            }
+            _ => Err(format!(
+                "Unexpected token {:?} in expression",
+                token.type_
+            )),
+        }
+    }
+
+    fn parse_let(&mut self) -> Result<ASTNode, String> {
+        self.eat(TokenType::Let)?;
+
+        let span = self.span();
+        if self.check(TokenType::LParen) {
+            return self.parse_let_tuple(span);
+        }
+        let name = self.expect_identifier("Expected identifier after let")?;
+
+        self.eat(TokenType::Assign)?;
+        let value = self.parse_expression()?;
+        self.eat_semicolon()?;
+
+        Ok(ASTNode::Let {
+            name,
+            value: Box::new(value),
+            mutable: false,
+            span,
         })
     }
 
-    fn parse_back(&mut self) -> Result<ASTNode, String> {
-        self.eat(TokenType::Back)?;
+    /// `let (a, b) = value;` after the `let`; `span` is the `(`.
+    fn parse_let_tuple(&mut self, span: Span) -> Result<ASTNode, String> {
+        self.require(Feature::Tuples, span.line)?;
+        self.eat(TokenType::LParen)?;
+        let mut bindings = Vec::new();
+        while !self.check(TokenType::RParen) {
+            let span = self.span();
+            let name = self.expect_identifier(&format!("line {}: expected a name to bind in `let (...)`", span.line))?;
+            bindings.push(Binding { name, mutable: false, span });
+            if !self.check(TokenType::RParen) {
+                self.eat_list_comma("binding")?;
+            }
+        }
+        if bindings.len() < 2 {
+            return Err(format!("line {}: `let (...)` binds at least two names, as tuples have at least two elements", span.line));
+        }
+        self.eat(TokenType::RParen)?;
+        self.eat(TokenType::Assign)?;
+        let value = self.parse_expression()?;
+        self.eat_semicolon()?;
+        Ok(ASTNode::LetTuple { bindings, value: Box::new(value), span })
+    }
+
+    fn parse_print(&mut self) -> Result<ASTNode, String> {
+        let newline = self.current_token().is_some_and(|t| t.type_ == TokenType::Println);
+        self.eat(if newline { TokenType::Println } else { TokenType::Print })?;
+        self.eat(TokenType::LParen)?;
         let expr = self.parse_expression()?;
-        self.eat(TokenType::Semicolon)?;
+        self.eat(TokenType::RParen)?;
+        self.eat_semicolon()?;
+
+        Ok(ASTNode::Print {
+            value: Box::new(expr),
+            value_type: None,
+            newline,
+        })
+    }
+
+    fn parse_function_call(&mut self, func_name: String, span: Span) -> Result<ASTNode, String> {
+        if func_name == "some" {
+            self.require(Feature::Optionals, span.line)?;
+        }
+        let args = self.parse_arguments()?;
+        Ok(ASTNode::FunctionCall { name: func_name, args, span })
+    }
+
+    /// `(args)` of a function or method call.
+    fn parse_arguments(&mut self) -> Result<Vec<ASTNode>, String> {
+        self.eat(TokenType::LParen)?;
+        let outer = std::mem::replace(&mut self.legacy_args, self.edition < Edition::E2026);
+        let args = self.nested(Self::parse_call_args);
+        self.legacy_args = outer;
+        let args = args?;
+        self.eat(TokenType::RParen)?;
+        Ok(args)
+    }
+
+    fn parse_call_args(&mut self) -> Result<Vec<ASTNode>, String> {
+        let mut args = Vec::new();
+
+        while let Some(token) = self.current_token() {
+            if token.type_ == TokenType::RParen {
+                break;
+            }
+
+            if token.type_ == TokenType::Semicolon && self.edition < Edition::E2026 {
+                self.eat(TokenType::Semicolon)?;
+                continue;
+            }
+            if token.type_ == TokenType::Comma {
+                return Err(format!("line {}: expected an argument before `,`", token.line));
+            }
+
+            let arg = self.parse_expression()?;
+            args.push(arg);
+
+            let Some(next_token) = self.current_token() else { break };
+            match next_token.type_ {
+                TokenType::RParen => {}
+                TokenType::Comma => self.eat_list_comma("argument")?,
+                TokenType::Semicolon | TokenType::Plus => self.eat_legacy_separator()?,
+                _ => self.eat(TokenType::Comma)?,
+            }
+        }
+        Ok(args)
+    }
+
+    /// Eats the `,` after a list item. A trailing comma before `)` is fine; a
+    /// doubled comma gets one targeted error instead of "expected expression".
+    fn eat_list_comma(&mut self, item: &str) -> Result<(), String> {
+        self.eat(TokenType::Comma)?;
+        match self.current_token() {
+            Some(token) if token.type_ == TokenType::Comma => Err(format!(
+                "line {}: extra `,` in {} list; remove one of the commas",
+                token.line, item
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// `f(a; b)` and `f(a + b)` were the argument separators before commas existed.
+    /// Edition 2025 still accepts them with a warning; later editions need `,`.
+    fn eat_legacy_separator(&mut self) -> Result<(), String> {
+        let token = self.current_token().expect("caller checked the separator").clone();
+        if self.edition >= Edition::E2026 {
+            return Err(format!(
+                "line {}: call arguments are separated by `,`; `{}` only works in edition 2025",
+                token.line, token.value
+            ));
+        }
+        self.warnings.push(Warning {
+            lint: Lint::Deprecated,
+            message: format!(
+                "line {}: `{}` between call arguments is deprecated; use `,` instead",
+                token.line, token.value
+            ),
+        });
+        self.eat(token.type_)
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<ASTNode>, String> {
+        self.parse_block_with_tail(false)
+    }
+
+    /// A function body: from edition 2026 on, its last expression without `;` is the return value.
+    fn parse_function_body(&mut self) -> Result<Vec<ASTNode>, String> {
+        self.parse_block_with_tail(self.edition >= Edition::E2026)
+    }
+
+    fn parse_block_with_tail(&mut self, tail: bool) -> Result<Vec<ASTNode>, String> {
+        self.eat(TokenType::LBrace)?;
+        let outer = self.tail_allowed;
+        let body = self.nested(|parser| {
+            let mut body = Vec::new();
+            while let Some(token) = parser.current_token() {
+                if token.type_ == TokenType::RBrace {
+                    break;
+                }
+
+                if token.type_ == TokenType::Semicolon {
+                    parser.eat(TokenType::Semicolon)?;
+                    continue;
+                }
+
+                // Blocks nested in the previous statement set their own
+                parser.tail_allowed = tail;
+                let stmt = parser.parse_statement()?;
+                body.push(stmt);
+            }
+            Ok(body)
+        });
+        self.tail_allowed = outer;
+        let body = body?;
+
+        let end = self.span().start;
+        self.eat(TokenType::RBrace)?;
+        self.block_ends.push(end);
+
+        if self.check(TokenType::Semicolon) {
+            self.eat(TokenType::Semicolon)?;
+        }
+
+        Ok(body)
+    }
+
+    /// `method name { ... }` is the pre-2026 spelling of `fun name() -> int { ... }`.
+    fn parse_method(&mut self) -> Result<ASTNode, String> {
+        let line = self.current_token().map(|t| t.line).unwrap_or(0);
+        if self.edition >= Edition::E2026 {
+            return Err(format!(
+                "`method` is not available in edition {}; use `fun name() -> int` instead",
+                self.edition.as_str()
+            ));
+        }
+
+        self.eat(TokenType::Method)?;
+        let span = self.span();
+        let name = self.expect_identifier("Expected method name")?;
+        self.require(Feature::Functions, line)?;
+        self.require(Feature::Returns, line)?;
+        self.warnings.push(Warning {
+            lint: Lint::Deprecated,
+            message: format!("line {}: `method` is deprecated; use `fun {}() -> int` instead", line, name),
+        });
+
+        let body = self.parse_block()?;
+        Ok(ASTNode::Function {
+            name,
+            params: Vec::new(),
+            return_type: Some("int".to_string()),
+            body,
+            public: false,
+            span,
+        })
+    }
+
+    fn parse_back(&mut self) -> Result<ASTNode, String> {
+        let span = self.span();
+        self.require(Feature::Returns, span.line)?;
+        self.eat(TokenType::Back)?;
+
+        let value = match self.current_token() {
+            None => None,
+            Some(token) if matches!(token.type_, TokenType::Semicolon | TokenType::RBrace) => None,
+            _ => Some(Box::new(self.parse_expression()?)),
+        };
+        self.eat_semicolon()?;
+
+        Ok(ASTNode::Back { value, span, implicit: false })
+    }
+
+    /// `if` with any `else if` after it, read in a loop; each `if` of the chain
+    /// is nested in the previous one's `else`.
+    fn parse_if(&mut self) -> Result<ASTNode, String> {
+        let links = self.links;
+        let result = self.parse_if_chain();
+        self.links = links;
+        result
+    }
+
+    fn parse_if_chain(&mut self) -> Result<ASTNode, String> {
+        let mut branches = Vec::new();
+        let mut else_body = Vec::new();
+        loop {
+            let span = self.span();
+            self.require(Feature::Conditionals, span.line)?;
+            self.eat(TokenType::If)?;
+            let condition = self.parse_expression()?;
+            let then_body = self.parse_block()?;
+            branches.push((condition, then_body, span));
+
+            if !self.check(TokenType::Else) {
+                break;
+            }
+            self.eat(TokenType::Else)?;
+            if !self.check(TokenType::If) {
+                else_body = self.parse_block()?;
+                break;
+            }
+            self.link()?;
+        }
+
+        let mut chain = else_body;
+        for (condition, then_body, span) in branches.into_iter().rev() {
+            chain = vec![ASTNode::If {
+                condition: Box::new(condition),
+                then_body,
+                else_body: chain,
+                span,
+            }];
+        }
+        Ok(chain.remove(0))
+    }
+
+    fn parse_while(&mut self) -> Result<ASTNode, String> {
+        let span = self.span();
+        self.require(Feature::Loops, span.line)?;
+        self.eat(TokenType::While)?;
+        let condition = self.parse_expression()?;
+        let body = self.parse_block()?;
+        Ok(ASTNode::While { condition: Box::new(condition), body, span })
+    }
+
+    fn parse_for(&mut self) -> Result<ASTNode, String> {
+        self.require(Feature::Loops, self.span().line)?;
+        self.eat(TokenType::For)?;
+        let span = self.span();
+        let var = self.expect_identifier("Expected loop variable after `for`")?;
+        if !self.check(TokenType::In) {
+            return Err(format!("line {}: expected `in` after the loop variable, as in `for {} in 0..10`", span.line, var));
+        }
+        self.eat(TokenType::In)?;
+        let start = self.parse_expression()?;
+        if !self.check(TokenType::DotDot) {
+            return Err(format!("line {}: `for` loops over a range `start..end`, e.g. `for {} in 0..10`", span.line, var));
+        }
+        self.eat(TokenType::DotDot)?;
+        let end = self.parse_expression()?;
+        let body = self.parse_block()?;
+        Ok(ASTNode::For { var, start: Box::new(start), end: Box::new(end), body, span })
+    }
+
+    /// `enum Name { A; B; }`: only at the top level, with at least one variant.
+    fn parse_enum(&mut self) -> Result<ASTNode, String> {
+        self.require(Feature::Enums, self.span().line)?;
+        if self.depth > 0 {
+            return Err(format!("line {}: enums can only be declared at the top level", self.span().line));
+        }
+        self.eat(TokenType::Enum)?;
+        let span = self.span();
+        let name = self.expect_identifier("Expected enum name after `enum`")?;
+        self.eat(TokenType::LBrace)?;
+        let mut variants = Vec::new();
+        while !self.check(TokenType::RBrace) {
+            let variant = self.recording_bytes(|parser| {
+                let variant = parser.expect_identifier(&format!("line {}: expected a variant name in enum `{}`", parser.span().line, name))?;
+                if !parser.check(TokenType::RBrace) {
+                    parser.eat(TokenType::Semicolon)?;
+                }
+                Ok(variant)
+            })?;
+            variants.push(variant);
+        }
+        if variants.is_empty() {
+            return Err(format!("line {}: enum `{}` has no variants", span.line, name));
+        }
+        let end = self.span().start;
+        self.eat(TokenType::RBrace)?;
+        self.block_ends.push(end);
+        if self.check(TokenType::Semicolon) {
+            self.eat(TokenType::Semicolon)?;
+        }
+        Ok(ASTNode::Enum { name, variants, span })
+    }
+
+    /// `match value { A { ... } B { ... } else { ... } }`, with `else` last. The
+    /// `some` arm of an optional value names what it holds: `some(x) { ... }`.
+    fn parse_match(&mut self) -> Result<ASTNode, String> {
+        let span = self.span();
+        self.require(Feature::Enums, span.line)?;
+        self.eat(TokenType::Match)?;
+        let value = self.parse_expression()?;
+        self.eat(TokenType::LBrace)?;
+        let (arms, else_body) = self.nested(|parser| {
+            let mut arms = Vec::new();
+            let mut else_body = None;
+            while !parser.check(TokenType::RBrace) {
+                if else_body.is_some() {
+                    return Err(format!("line {}: `else` must be the last arm of a `match`", parser.span().line));
+                }
+                if parser.check(TokenType::Else) {
+                    else_body = Some(parser.recording_bytes(|parser| {
+                        parser.eat(TokenType::Else)?;
+                        parser.parse_block()
+                    })?);
+                    continue;
+                }
+                arms.push(parser.recording_bytes(Parser::parse_match_arm)?);
+            }
+            Ok((arms, else_body))
+        })?;
+        let end = self.span().start;
+        self.eat(TokenType::RBrace)?;
+        self.block_ends.push(end);
+        if self.check(TokenType::Semicolon) {
+            self.eat(TokenType::Semicolon)?;
+        }
+        Ok(ASTNode::Match { value: Box::new(value), arms, else_body, value_type: None, span })
+    }
+
+    /// `Variant { ... }` or `some(name) { ... }` in a `match`.
+    fn parse_match_arm(&mut self) -> Result<MatchArm, String> {
+        let span = self.span();
+        let variant = self.expect_identifier(&format!(
+            "line {}: expected a variant name or `else` in `match`",
+            span.line
+        ))?;
+        let binding = if self.check(TokenType::LParen) {
+            self.require(Feature::Optionals, span.line)?;
+            self.eat(TokenType::LParen)?;
+            let span = self.span();
+            let name = self.expect_identifier(&format!("line {}: expected a name to bind in `{}(...)`", span.line, variant))?;
+            self.eat(TokenType::RParen)?;
+            Some(Binding { name, mutable: false, span })
+        } else {
+            None
+        };
+        let body = self.parse_block()?;
+        Ok(MatchArm { variant, binding, body, span })
+    }
+
+    /// Parses `fun name(params) -> type`, shared by definitions and `extern` declarations.
+    fn parse_signature(&mut self) -> Result<(String, Vec<Param>, Option<String>, Span), String> {
+        self.eat(TokenType::Fun)?;
+        let span = self.span();
+        let name = self.expect_identifier("Expected function name")?;
+        if name != "main" {
+            self.require(Feature::Functions, span.line)?;
+        }
+
+        let mut params = Vec::new();
+        if self.check(TokenType::LParen) {
+            self.eat(TokenType::LParen)?;
+            while !self.check(TokenType::RParen) {
+                let span = self.span();
+                self.require(Feature::Parameters, span.line)?;
+                let name = self.expect_identifier("Expected parameter name")?;
+                let ty = if self.check(TokenType::Colon) {
+                    self.eat(TokenType::Colon)?;
+                    Some(self.parse_type("Expected parameter type after `:`")?)
+                } else {
+                    None
+                };
+                params.push(Param { name, ty, span });
+                if !self.check(TokenType::RParen) {
+                    self.eat_list_comma("parameter")?;
+                }
+            }
+            self.eat(TokenType::RParen)?;
+        }
+
+        let return_type = if self.check(TokenType::Arrow) {
+            self.require(Feature::Returns, self.span().line)?;
+            self.eat(TokenType::Arrow)?;
+            Some(self.parse_type("Expected return type after `->`")?)
+        } else {
+            None
+        };
+
+        Ok((name, params, return_type, span))
+    }
+
+    /// A type, where a trailing `?` makes it optional: `int?`, `(int, str)?`.
+    fn parse_type(&mut self, message: &str) -> Result<String, String> {
+        let ty = self.parse_plain_type(message)?;
+        if !self.check(TokenType::Question) {
+            return Ok(ty);
+        }
+        let line = self.span().line;
+        self.require(Feature::Optionals, line)?;
+        self.eat(TokenType::Question)?;
+        if self.check(TokenType::Question) {
+            return Err(format!("line {}: `{}??` is not a type; optionals cannot be nested", line, ty));
+        }
+        Ok(format!("{}?", ty))
+    }
+
+    /// A type annotation: a name such as `int`, `[int]` for an array,
+    /// `{str: int}` for a map or `(int, str)` for a tuple.
+    fn parse_plain_type(&mut self, message: &str) -> Result<String, String> {
+        if self.check(TokenType::LParen) {
+            self.require(Feature::Tuples, self.span().line)?;
+            self.eat(TokenType::LParen)?;
+            let mut elements = Vec::new();
+            while !self.check(TokenType::RParen) {
+                elements.push(self.nested(|parser| parser.parse_type("Expected element type in `(...)`"))?);
+                if !self.check(TokenType::RParen) {
+                    self.eat_list_comma("element type")?;
+                }
+            }
+            self.eat(TokenType::RParen)?;
+            return Ok(format!("({})", elements.join(", ")));
+        }
+        if self.check(TokenType::LBrace) {
+            self.require(Feature::Maps, self.span().line)?;
+            self.eat(TokenType::LBrace)?;
+            let key = self.expect_identifier("Expected key type after `{`")?;
+            self.eat(TokenType::Colon)?;
+            let value = self.expect_identifier("Expected value type after `:`")?;
+            self.eat(TokenType::RBrace)?;
+            return Ok(format!("{{{}: {}}}", key, value));
+        }
+        if !self.check(TokenType::LBracket) {
+            return self.expect_identifier(message);
+        }
+        self.require(Feature::Arrays, self.span().line)?;
+        self.eat(TokenType::LBracket)?;
+        let element = self.expect_identifier("Expected element type after `[`")?;
+        self.eat(TokenType::RBracket)?;
+        Ok(format!("[{}]", element))
+    }
+
+    fn parse_fun(&mut self, public: bool) -> Result<ASTNode, String> {
+        let (name, params, return_type, span) = self.parse_signature()?;
+        let body = self.parse_function_body()?;
+        Ok(ASTNode::Function {
+            name,
+            params,
+            return_type,
+            body,
+            public,
+            span,
+        })
+    }
+
+    fn parse_pub(&mut self) -> Result<ASTNode, String> {
+        let line = self.span().line;
+        self.eat(TokenType::Pub)?;
+        if !self.check(TokenType::Fun) {
+            return Err(format!("line {}: Expected `fun` after `pub`", line));
+        }
+        self.parse_fun(true)
+    }
+
+    fn parse_extern(&mut self) -> Result<ASTNode, String> {
+        self.require(Feature::Extern, self.span().line)?;
+        self.eat(TokenType::Extern)?;
+        let abi = self.expect_identifier("Expected ABI after `extern`, e.g. `extern rust`")?;
+        let (name, params, return_type, span) = self.parse_signature()?;
+        self.eat_semicolon()?;
+        Ok(ASTNode::ExternFunction { abi, name, params, return_type, span })
+    }
+
+    fn parse_statement(&mut self) -> Result<ASTNode, String> {
+        self.recording_bytes(Parser::parse_statement_kind)
+    }
+
+    /// Runs `parse` and adds the bytes it consumed to `statement_bytes`.
+    fn recording_bytes<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let first = self.pos;
+        // Reserve the slot now so that a statement comes before the ones nested in it
+        let index = self.statement_bytes.len();
+        self.statement_bytes.push(0..0);
+        let parsed = parse(self)?;
+        let last = &self.tokens[self.pos - 1];
+        self.statement_bytes[index] = self.tokens[first].position..last.position + last.value.len();
+        Ok(parsed)
+    }
+
+    // Every statement form here has a rule in `grammar::rules`; update both together.
+    fn parse_statement_kind(&mut self) -> Result<ASTNode, String> {
+        let token = self.current_token().ok_or("Unexpected EOF")?;
+        trace!(token = ?token.type_, pos = self.pos, line = token.line, "parse statement");
+
+        if self.tail_allowed && self.starts_expression() {
+            return self.parse_expression_statement();
+        }
+        match token.type_ {
+            TokenType::Let => self.parse_let(),
+            TokenType::Print | TokenType::Println => self.parse_print(),
+            TokenType::Method => self.parse_method(),
+            TokenType::Fun => self.parse_fun(false),
+            TokenType::Pub => self.parse_pub(),
+            TokenType::Back => self.parse_back(),
+            TokenType::Extern => self.parse_extern(),
+            TokenType::If => self.parse_if(),
+            TokenType::While => self.parse_while(),
+            TokenType::For => self.parse_for(),
+            TokenType::Enum => self.parse_enum(),
+            TokenType::Match => self.parse_match(),
+            TokenType::Else => Err(format!("line {}: `else` without a matching `if`", token.line)),
+            TokenType::Identifier => {
+                let span = token.span();
+                let name = token.value.clone();
+                self.check_reserved(&name, span.line)?;
+                if self.pos + 1 < self.tokens.len() && self.tokens[self.pos + 1].type_ == TokenType::Assign {
+                    self.require(Feature::Assignment, span.line)?;
+                    self.eat(TokenType::Identifier)?;
+                    self.eat(TokenType::Assign)?;
+                    let value = self.parse_expression()?;
+                    self.eat_semicolon()?;
+
+                    Ok(ASTNode::Assign {
+                        name,
+                        value: Box::new(value),
+                        span,
+                    })
+                } else {
+                    let func_name = name;
+                    // `fnu main() {` or `lte x = 1;`: a misspelled keyword, not a call
+                    let next = self.tokens.get(self.pos + 1).map(|t| &t.type_);
+                    let is_call = matches!(next, Some(TokenType::LParen | TokenType::Dot | TokenType::LBracket));
+                    let keyword = suggest(&func_name, STATEMENT_KEYWORDS.iter().copied());
+                    if let Some(keyword) = keyword.filter(|_| !is_call) {
+                        return Err(format!(
+                            "line {}: unexpected `{}` at the start of a statement (did you mean `{}`?)",
+                            span.line, func_name, keyword
+                        ));
+                    }
+                    if !is_call {
+                        // Fails at the missing `(`
+                        self.eat(TokenType::Identifier)?;
+                        return self.parse_function_call(func_name, span);
+                    }
+                    // `f(x)`, or a chain such as `xs.first().show()` or `fs[0].run()`
+                    let call = self.parse_primary()?;
+                    if !matches!(call, ASTNode::FunctionCall { .. } | ASTNode::MethodCall { .. }) {
+                        return Err(format!("line {}: this expression has no effect; only calls can be statements", span.line));
+                    }
+                    self.eat_semicolon()?;
+                    Ok(call)
+                }
+            }
+            _ => Err(format!("Unexpected token {:?}", token.type_)),
+        }
+    }
+
+    /// Whether the statement at the current token is an expression: anything
+    /// but a keyword or an assignment.
+    fn starts_expression(&self) -> bool {
+        match self.current_token().map(|t| &t.type_) {
+            Some(TokenType::Identifier) => {
+                self.tokens.get(self.pos + 1).is_none_or(|next| next.type_ != TokenType::Assign)
+            }
+            Some(
+                TokenType::Number
+                | TokenType::String
+                | TokenType::Minus
+                | TokenType::LParen
+                | TokenType::LBracket
+                | TokenType::LBrace,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// A call statement, or the expression a function body ends with, which
+    /// becomes an implicit `back`.
+    fn parse_expression_statement(&mut self) -> Result<ASTNode, String> {
+        let span = self.span();
+        let expr = self.parse_expression()?;
+        let Some(next) = self.current_token().map(|t| t.type_.clone()) else {
+            return Err(format!("line {}: Expected `;` or `}}` after the expression, got EOF", span.line));
+        };
+        if next == TokenType::RBrace {
+            self.require(Feature::Returns, span.line)?;
+            return Ok(ASTNode::Back { value: Some(Box::new(expr)), span, implicit: true });
+        }
+        if let ASTNode::FunctionCall { .. } | ASTNode::MethodCall { .. } = expr {
+            self.eat_semicolon()?;
+            return Ok(expr);
+        }
+        // `fnu main() {` or `lte x = 1;`: a misspelled keyword, not a value
+        let typo = match &expr {
+            ASTNode::Identifier { name, .. } => suggest(name, STATEMENT_KEYWORDS.iter().copied()).map(|k| (name, k)),
+            _ => None,
+        };
+        if let Some((name, keyword)) = typo {
+            return Err(format!(
+                "line {}: unexpected `{}` at the start of a statement (did you mean `{}`?)",
+                span.line, name, keyword
+            ));
+        }
+        match next {
+            TokenType::Semicolon => Err(format!(
+                "line {}: this expression has no effect; only the last expression of a function, without `;`, is its value",
+                span.line
+            )),
+            _ => Err(format!("line {}: Expected `;` or `}}` after the expression, got {:?}", self.span().line, next)),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(tokens = self.tokens.len()))]
+    pub fn parse(&mut self) -> Result<Vec<ASTNode>, String> {
+        let mut statements = Vec::new();
+
+        while self.pos < self.tokens.len() {
+            let start = self.pos;
+            match self.parse_statement() {
+                Ok(stmt) => {
+                    statements.push(stmt);
+                    self.statement_tokens.push(start..self.pos);
+                }
+                Err(e) => {
+                    self.errors.push(e);
+                    break;
+                }
+            }
+        }
+        if !self.errors.is_empty() {
+            return Err(self.errors.join("\n"));
+        }
+
+        debug!(statements = statements.len(), "parsing finished");
+        Ok(statements)
+    }
+
+    /// Parses the whole input as one expression, for hosts evaluating expressions.
+    pub fn parse_lone_expression(&mut self) -> Result<ASTNode, String> {
+        let expr = self.parse_expression()?;
+        match self.current_token() {
+            None => Ok(expr),
+            Some(token) => Err(format!(
+                "line {}: expected the end of the expression, found `{}`",
+                token.line, token.value
+            )),
+        }
+    }
+}
+
+/// Handles statements written outside any function. Scripts (`script == true`)
+/// run them, in order, as the body of a synthesized `main`; projects reject them
+/// unless `[language] top-level-statements` is set.
+pub fn lower_top_level(nodes: Vec<ASTNode>, script: bool) -> Result<Vec<ASTNode>, String> {
+    let (mut definitions, statements): (Vec<_>, Vec<_>) = nodes
+        .into_iter()
+        .partition(|node| matches!(node, ASTNode::Function { .. } | ASTNode::ExternFunction { .. } | ASTNode::Enum { .. }));
+    let Some(first) = statements.first() else {
+        return Ok(definitions);
+    };
+    let line = statement_line(first);
+    let location = line.map(|line| format!("line {}: ", line)).unwrap_or_default();
+
+    if !script {
+        return Err(format!(
+            "{}{} statement outside a function; move it into `fun main` \
+             (or set `top-level-statements = true` under [language] in ntfp.toml)",
+            location,
+            first.kind()
+        ));
+    }
+    if let Some(ASTNode::Function { span, .. }) =
+        definitions.iter().find(|node| matches!(node, ASTNode::Function { name, .. } if name == "main"))
+    {
+        let first_at = line.map(|line| format!(" (first at line {})", line)).unwrap_or_default();
+        return Err(format!(
+            "line {}: `fun main` cannot be combined with top-level statements{}",
+            span.line, first_at
+        ));
+    }
+
+    debug!(statements = statements.len(), "wrapping top-level statements into main");
+    definitions.push(ASTNode::Function {
+        name: "main".to_string(),
+        params: Vec::new(),
+        return_type: None,
+        body: statements,
+        public: false,
+        span: Span { line: line.unwrap_or(1), start: 0 },
+    });
+    Ok(definitions)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Type {
+    Int,
+    Str,
+    Unit,
+    /// `[int]`
+    IntArray,
+    /// `[str]`
+    StrArray,
+    /// `{int: int}`
+    IntIntMap,
+    /// `{int: str}`
+    IntStrMap,
+    /// `{str: int}`
+    StrIntMap,
+    /// `{str: str}`
+    StrStrMap,
+    /// An `enum` declared by the program, by name.
+    Enum(Rc<str>),
+    /// `(int, str)`: at least two elements, none of them a tuple.
+    Tuple(Rc<[Type]>),
+    /// `int?`: a value of the type inside, or `none`. Optionals cannot be nested.
+    Optional(Rc<Type>),
+}
+
+impl Type {
+    /// The type an annotation names. Any other identifier is taken for an
+    /// `enum`; the analyzer checks that the program declares it.
+    pub fn from_name(name: &str) -> Result<Type, String> {
+        match name {
+            "int" => Ok(Type::Int),
+            "str" => Ok(Type::Str),
+            "[int]" => Ok(Type::IntArray),
+            "[str]" => Ok(Type::StrArray),
+            "{int: int}" => Ok(Type::IntIntMap),
+            "{int: str}" => Ok(Type::IntStrMap),
+            "{str: int}" => Ok(Type::StrIntMap),
+            "{str: str}" => Ok(Type::StrStrMap),
+            _ if name.ends_with('?') => Type::from_name(&name[..name.len() - 1])?
+                .optional_of()
+                .ok_or_else(|| format!("Unknown type: {}; optionals cannot be nested", name)),
+            _ if name.starts_with('(') && name.ends_with(')') => {
+                let inner = &name[1..name.len() - 1];
+                if inner.contains(['(', ')']) {
+                    return Err(format!("Unknown type: {}; tuples cannot be nested", name));
+                }
+                let elements = inner
+                    .split(',')
+                    .map(|element| Type::from_name(element.trim()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Type::tuple_of(&elements).ok_or_else(|| format!("Unknown type: {}; tuples have at least two elements", name))
+            }
+            _ if is_identifier(name) => Ok(Type::Enum(name.into())),
+            _ => Err(format!("Unknown type: {}", name)),
+        }
+    }
+
+    /// How the type is written in Netflu, e.g. `{int: str}` or `(int, str)?`.
+    pub fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
+            Type::Int => "int",
+            Type::Str => "str",
+            Type::Unit => "()",
+            Type::IntArray => "[int]",
+            Type::StrArray => "[str]",
+            Type::IntIntMap => "{int: int}",
+            Type::IntStrMap => "{int: str}",
+            Type::StrIntMap => "{str: int}",
+            Type::StrStrMap => "{str: str}",
+            Type::Enum(name) => return Cow::Owned(name.to_string()),
+            Type::Tuple(elements) => {
+                let names: Vec<_> = elements.iter().map(Type::name).collect();
+                return Cow::Owned(format!("({})", names.join(", ")));
+            }
+            Type::Optional(inner) => return Cow::Owned(format!("{}?", inner.name())),
+        })
+    }
+
+    /// Whether generated code can format values of this type with `{}`.
+    /// The others print with `{:?}`, e.g. `[1, 2]` for an array.
+    pub fn is_displayable(&self) -> bool {
+        matches!(self, Type::Int | Type::Str)
+    }
+
+    /// Whether the Rust type is `Copy`; generated code clones the other values
+    /// where Netflu uses them again, so that every value keeps its own copy.
+    pub fn is_copy(&self) -> bool {
+        self.components()
+            .iter()
+            .all(|ty| matches!(ty, Type::Int | Type::Unit | Type::Enum(_) | Type::Tuple(_) | Type::Optional(_)))
+    }
+
+    pub fn rust(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
+            Type::Int => "i32",
+            Type::Str => "String",
+            Type::Unit => "()",
+            Type::IntArray => "Vec<i32>",
+            Type::StrArray => "Vec<String>",
+            Type::IntIntMap => "std::collections::HashMap<i32, i32>",
+            Type::IntStrMap => "std::collections::HashMap<i32, String>",
+            Type::StrIntMap => "std::collections::HashMap<String, i32>",
+            Type::StrStrMap => "std::collections::HashMap<String, String>",
+            Type::Enum(name) => return Cow::Owned(rust_name(name).into_owned()),
+            Type::Tuple(elements) => {
+                let elements: Vec<_> = elements.iter().map(Type::rust).collect();
+                return Cow::Owned(format!("({})", elements.join(", ")));
+            }
+            Type::Optional(inner) => return Cow::Owned(format!("Option<{}>", inner.rust())),
+        })
+    }
+
+    /// The optional type of values of this type; optionals cannot be nested.
+    pub fn optional_of(&self) -> Option<Type> {
+        match self {
+            Type::Optional(_) | Type::Unit => None,
+            _ => Some(Type::Optional(Rc::new(self.clone()))),
+        }
+    }
+
+    /// The type of the value inside, for optional types.
+    pub fn inner(&self) -> Option<&Type> {
+        match self {
+            Type::Optional(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// This type and the types it is made of: the elements of a tuple and
+    /// the value inside an optional, recursively.
+    pub fn components(&self) -> Vec<Type> {
+        let parts = match self {
+            Type::Tuple(elements) => elements,
+            Type::Optional(inner) => std::slice::from_ref(&**inner),
+            _ => &[],
+        };
+        std::iter::once(self.clone()).chain(parts.iter().flat_map(Type::components)).collect()
+    }
+
+    /// The tuple type with these elements; there must be at least two, and
+    /// none a tuple itself.
+    pub fn tuple_of(elements: &[Type]) -> Option<Type> {
+        if elements.len() < 2 || elements.iter().any(|ty| matches!(ty, Type::Tuple(_) | Type::Unit)) {
+            return None;
+        }
+        Some(Type::Tuple(elements.into()))
+    }
+
+    /// The types of the elements, for tuple types.
+    pub fn tuple_elements(&self) -> Option<&[Type]> {
+        match self {
+            Type::Tuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// The array type with elements of this type; arrays cannot be nested.
+    pub fn array_of(&self) -> Option<Type> {
+        match self {
+            Type::Int => Some(Type::IntArray),
+            Type::Str => Some(Type::StrArray),
+            _ => None,
+        }
+    }
+
+    /// The type of the elements, for array types.
+    pub fn element(&self) -> Option<Type> {
+        match self {
+            Type::IntArray => Some(Type::Int),
+            Type::StrArray => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    /// Whether values of this type have the built-in method `len()`.
+    pub fn has_len(&self) -> bool {
+        *self == Type::Str || self.element().is_some() || self.entry().is_some()
+    }
+
+    /// The map type from keys of type `key` to values of type `value`; both
+    /// must be int or str.
+    pub fn map_of(key: Type, value: Type) -> Option<Type> {
+        match (key, value) {
+            (Type::Int, Type::Int) => Some(Type::IntIntMap),
+            (Type::Int, Type::Str) => Some(Type::IntStrMap),
+            (Type::Str, Type::Int) => Some(Type::StrIntMap),
+            (Type::Str, Type::Str) => Some(Type::StrStrMap),
+            _ => None,
+        }
+    }
+
+    /// The types of the keys and the values, for map types.
+    pub fn entry(&self) -> Option<(Type, Type)> {
+        match self {
+            Type::IntIntMap => Some((Type::Int, Type::Int)),
+            Type::IntStrMap => Some((Type::Int, Type::Str)),
+            Type::StrIntMap => Some((Type::Str, Type::Int)),
+            Type::StrStrMap => Some((Type::Str, Type::Str)),
+            _ => None,
+        }
+    }
+}
+
+/// A value whose type differs from the one its context requires. Rendered with
+/// two locations: the expression and the declaration that imposed the expectation.
+/// Between an optional and the type inside it, a help line says how to convert.
+///
+/// ```text
+/// line 7: mismatched types: expected int, found str
+///   line 7: this argument is str
+///   line 2: expected int because of parameter 1 of `twice`
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypeMismatch {
+    pub expected: Type,
+    pub found: Type,
+    /// The mismatched expression, or the statement containing it when the
+    /// expression records no line of its own (literals).
+    pub found_at: Span,
+    /// What the expression is to its context, e.g. "argument".
+    pub found_what: &'static str,
+    /// The annotation that imposed `expected`; `None` for builtins.
+    pub context: Option<Span>,
+    /// Completes "expected T because of ...".
+    pub reason: String,
+}
+
+impl std::fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = |span: Option<Span>| span.map(|s| format!("line {}: ", s.line)).unwrap_or_default();
+        write!(
+            f,
+            "line {}: mismatched types: expected {}, found {}\n  line {}: this {} is {}",
+            self.found_at.line,
+            self.expected.name(),
+            self.found.name(),
+            self.found_at.line,
+            self.found_what,
+            self.found.name()
+        )?;
+        write!(f, "\n  {}expected {} because of {}", at(self.context), self.expected.name(), self.reason)?;
+        if self.found.inner() == Some(&self.expected) {
+            write!(f, "\n  help: `match` on the value to handle `none`: `some(x) {{ ... }} none {{ ... }}`")?;
+        } else if self.expected.inner() == Some(&self.found) {
+            write!(f, "\n  help: wrap the value with `some(...)`")?;
+        }
+        Ok(())
+    }
+}
+
+/// Line of an expression, for nodes that record one.
+fn expression_span(node: &ASTNode) -> Option<Span> {
+    match node {
+        ASTNode::Identifier { span, .. }
+        | ASTNode::FunctionCall { span, .. }
+        | ASTNode::Binary { span, .. }
+        | ASTNode::Negate { span, .. }
+        | ASTNode::Interpolation { span, .. }
+        | ASTNode::Array { span, .. }
+        | ASTNode::Map { span, .. }
+        | ASTNode::Tuple { span, .. }
+        | ASTNode::None { span, .. }
+        | ASTNode::Index { span, .. }
+        | ASTNode::MethodCall { span, .. }
+        | ASTNode::Field { span, .. } => Some(*span),
+        _ => None,
+    }
+}
+
+/// The value of an int expression made only of literals and operators, as
+/// rustc would work it out; `None` if it depends on anything else or overflows.
+fn constant_int(node: &ASTNode) -> Option<i32> {
+    match node {
+        ASTNode::Number { value } => value.parse().ok(),
+        ASTNode::Negate { operand, .. } => constant_int(operand)?.checked_neg(),
+        ASTNode::Binary { op, left, right, operand_type: Some(Type::Int), .. } => {
+            op.apply(constant_int(left)?, constant_int(right)?, Overflow::Checked).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Rejects two entries of a map literal with the same constant key, where
+/// the later one would silently replace the earlier.
+fn check_duplicate_keys(entries: &[(ASTNode, ASTNode)], span: Span) -> Result<(), String> {
+    let constant = |key: &ASTNode| match key {
+        ASTNode::Number { value } => value.parse::<i64>().ok().map(|n| n.to_string()),
+        ASTNode::Negate { operand, .. } => match &**operand {
+            ASTNode::Number { value } => value.parse::<i64>().ok().map(|n| (-n).to_string()),
+            _ => None,
+        },
+        ASTNode::String { value } => Some(value.clone()),
+        _ => None,
+    };
+    let mut seen = HashSet::new();
+    for (key, _) in entries {
+        let Some(constant) = constant(key) else { continue };
+        if seen.contains(&constant) {
+            return Err(format!(
+                "line {}: duplicate key {} in map; each key can appear only once",
+                expression_span(key).unwrap_or(span).line,
+                constant
+            ));
+        }
+        seen.insert(constant);
+    }
+    Ok(())
+}
+
+/// Whether every path through `body` ends in a `back`.
+fn always_backs(body: &[ASTNode]) -> bool {
+    body.iter().any(|node| match node {
+        ASTNode::Back { .. } => true,
+        ASTNode::If { then_body, else_body, .. } => always_backs(then_body) && always_backs(else_body),
+        // Exhaustive, so some arm always runs
+        ASTNode::Match { arms, else_body, .. } => {
+            arms.iter().all(|arm| always_backs(&arm.body)) && else_body.as_deref().is_none_or(always_backs)
+        }
+        _ => false,
+    })
+}
+
+/// Functions provided by the compiler; they cannot be redefined.
+pub const BUILTIN_FUNCTIONS: &[&str] = &["format", "some"];
+
+/// Keywords that start a statement, offered for misspelled statements.
+/// `method` is deprecated and never suggested.
+pub const STATEMENT_KEYWORDS: &[&str] =
+    &["let", "print", "println", "fun", "back", "extern", "pub", "if", "while", "for", "enum", "match"];
+
+/// Words kept free for syntax that is planned but not implemented, with a hint
+/// for what to write instead. Reserving them now means adding the syntax later
+/// breaks no program. `for`, `in`, `println`, `enum` and `match` are keywords from
+/// edition 2026 on; edition 2025 still reads them as names, see [`edition_keywords`].
+pub const RESERVED_WORDS: &[(&str, Option<&str>)] = &[
+    ("for", None),
+    ("in", None),
+    ("println", None),
+    ("break", Some("`back` leaves the whole function")),
+    ("continue", None),
+    ("return", Some("Netflu returns with `back`")),
+    ("struct", None),
+    ("class", None),
+    ("enum", None),
+    ("match", None),
+    ("import", None),
+];
+
+/// Keywords introduced by edition 2026, which used to be reserved words.
+const EDITION_2026_KEYWORDS: &[TokenType] =
+    &[TokenType::For, TokenType::In, TokenType::Println, TokenType::Enum, TokenType::Match];
+
+/// Turns keywords the edition does not have yet back into identifiers, so that
+/// older programs using them as names keep parsing (with a `deprecated` warning).
+pub fn edition_keywords(tokens: &mut [Token], edition: Edition) {
+    if edition >= Edition::E2026 {
+        return;
+    }
+    for token in tokens.iter_mut().filter(|t| EDITION_2026_KEYWORDS.contains(&t.type_)) {
+        token.type_ = TokenType::Identifier;
+    }
+}
+
+/// Edit distance between two strings, counting insertions, deletions,
+/// substitutions and swaps of adjacent characters (so `fnu` is one edit from `fun`).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The candidate closest to `name`, if it is close enough to be a likely typo.
+/// Shared by the parser (keywords), the analyzer (names in scope) and the CLI.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Short names are one edit away from too many unrelated ones.
+    let allowed = match name.chars().count() {
+        0..=2 => return None,
+        3..=5 => 1,
+        _ => 2,
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// ` (did you mean `x`?)`, or nothing when there is no close candidate.
+fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    suggest(name, candidates).map(|s| format!(" (did you mean `{}`?)", s)).unwrap_or_default()
+}
+
+/// The spec of `format(value, "spec")`, a subset of Rust's format spec for int values:
+/// `[[fill]align][+][#][0][width][x|X|o|b]`, e.g. `"05"`, `"*^9"` or `"#x"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSpec {
+    fill: char,
+    align: Option<char>,
+    sign: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    radix: Option<char>,
+    text: String,
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, String> {
+        let invalid = |reason: String| format!("Invalid format spec \"{}\": {}", spec, reason);
+        if spec.contains('\\') {
+            return Err(invalid("escape sequences are not allowed".to_string()));
+        }
+
+        let chars: Vec<char> = spec.chars().collect();
+        let is_align = |c: Option<&char>| matches!(c, Some('<' | '^' | '>'));
+        let mut parsed = FormatSpec {
+            fill: ' ',
+            align: None,
+            sign: false,
+            alternate: false,
+            zero: false,
+            width: 0,
+            radix: None,
+            text: spec.to_string(),
+        };
+        let mut i = 0;
+        if is_align(chars.get(1)) {
+            parsed.fill = chars[0];
+            parsed.align = Some(chars[1]);
+            i = 2;
+        } else if is_align(chars.first()) {
+            parsed.align = Some(chars[0]);
+            i = 1;
+        }
+        if matches!(parsed.fill, '{' | '}') {
+            return Err(invalid(format!("`{}` cannot be used as fill", parsed.fill)));
+        }
+
+        let mut flag = |c: char| {
+            let found = chars.get(i) == Some(&c);
+            if found {
+                i += 1;
+            }
+            found
+        };
+        parsed.sign = flag('+');
+        parsed.alternate = flag('#');
+        parsed.zero = flag('0');
+
+        let digits: String = chars[i..].iter().take_while(|c| c.is_ascii_digit()).collect();
+        if !digits.is_empty() {
+            parsed.width = digits.parse().map_err(|_| invalid("width is too large".to_string()))?;
+            i += digits.len();
+        }
+        match chars.get(i) {
+            Some('.') => return Err(invalid("precision only applies to floats; format values are int".to_string())),
+            Some(&c @ ('x' | 'X' | 'o' | 'b')) => {
+                parsed.radix = Some(c);
+                i += 1;
+            }
+            _ => {}
+        }
+        if let Some(c) = chars.get(i) {
+            return Err(invalid(format!("unexpected `{}`", c)));
+        }
+        Ok(parsed)
+    }
+
+    /// The spec as written, valid inside a Rust `{:...}` placeholder.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Formats like Rust's `format!` would, so interpreted and compiled programs agree.
+    pub fn apply(&self, value: i32) -> String {
+        let digits = match self.radix {
+            None => value.unsigned_abs().to_string(),
+            Some('x') => format!("{:x}", value),
+            Some('X') => format!("{:X}", value),
+            Some('o') => format!("{:o}", value),
+            Some(_) => format!("{:b}", value),
+        };
+        let sign = if self.radix.is_none() && value < 0 {
+            "-"
+        } else if self.sign {
+            "+"
+        } else {
+            ""
+        };
+        let prefix = match (self.alternate, self.radix) {
+            (true, Some('x' | 'X')) => "0x",
+            (true, Some('o')) => "0o",
+            (true, Some('b')) => "0b",
+            _ => "",
+        };
+
+        let pad = self.width.saturating_sub(sign.len() + prefix.len() + digits.len());
+        if self.zero {
+            return format!("{}{}{}{}", sign, prefix, "0".repeat(pad), digits);
+        }
+        let body = format!("{}{}{}", sign, prefix, digits);
+        let fill = |n: usize| self.fill.to_string().repeat(n);
+        match self.align.unwrap_or('>') {
+            '<' => body + &fill(pad),
+            '^' => fill(pad / 2) + &body + &fill(pad - pad / 2),
+            _ => fill(pad) + &body,
+        }
+    }
+}
+
+/// A piece of an interpolated string literal, see `string_pieces`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringPiece<'a> {
+    /// Text as written, escapes and doubled braces (`{{`, `}}`) included.
+    Text(&'a str),
+    /// A `{name}` placeholder and the byte offset of `name` in the literal.
+    Name(&'a str, usize),
+}
+
+/// Splits a string literal (quotes included) at its `{name}` placeholders.
+/// Text and placeholders alternate, starting and ending with a (maybe empty) text.
+pub fn string_pieces(literal: &str) -> Result<Vec<StringPiece<'_>>, String> {
+    let bytes = literal.as_bytes();
+    let end = literal.len() - 1;
+    let mut pieces = Vec::new();
+    let mut text_start = 1;
+    let mut i = 1;
+    while i < end {
+        match bytes[i] {
+            // No escape sequence contains a brace
+            b'\\' => i += 2,
+            b'{' | b'}' if bytes[i + 1] == bytes[i] => i += 2,
+            b'{' => {
+                let name_start = i + 1;
+                let name_end = literal[name_start..end]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .map_or(end, |n| name_start + n);
+                let name = &literal[name_start..name_end];
+                if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || bytes[name_end] != b'}' {
+                    return Err("`{` in a string starts a placeholder such as `{name}`; write `{{` for a literal `{`"
+                        .to_string());
+                }
+                pieces.push(StringPiece::Text(&literal[text_start..i]));
+                pieces.push(StringPiece::Name(name, name_start));
+                i = name_end + 1;
+                text_start = i;
+            }
+            b'}' => return Err("unmatched `}` in a string; write `}}` for a literal `}`".to_string()),
+            _ => i += 1,
+        }
+    }
+    pieces.push(StringPiece::Text(&literal[text_start..end]));
+    Ok(pieces)
+}
+
+/// Splits the arguments of a `format` call into the value and its spec literal.
+pub fn format_call_args(args: &[ASTNode]) -> Result<(&ASTNode, FormatSpec), String> {
+    match args {
+        [value, ASTNode::String { value: spec }] => Ok((value, FormatSpec::parse(&spec[1..spec.len() - 1])?)),
+        [_, _] => Err("the spec of format must be a string literal".to_string()),
+        _ => Err(format!("format expects 2 arguments (value, \"spec\"), got {}", args.len())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    /// A `let` binding; `binding` numbers the names `let` binds in pre-order so they can be found again in the AST.
+    Variable { binding: usize },
+    Parameter,
+    /// The variable of a `for` loop; it cannot be assigned.
+    LoopVariable,
+    Function { params: Vec<Type> },
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub kind: SymbolKind,
+    /// Value type for variables and parameters, return type for functions.
+    pub ty: Type,
+    pub mutable: bool,
+    /// Whether the value is read anywhere; feeds the `unused_variable` lint.
+    pub used: bool,
+    pub span: Span,
+}
+
+/// A value of the `[config]` table in ntfp.toml, read by programs as `config.key`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    Int(i32),
+    Str(String),
+}
+
+impl ConfigValue {
+    /// The literal that `config.key` is replaced with, at `span`.
+    fn literal(&self, span: Span) -> ASTNode {
+        match self {
+            ConfigValue::Int(n) if *n < 0 => ASTNode::Negate {
+                operand: Box::new(ASTNode::Number { value: n.unsigned_abs().to_string() }),
+                span,
+            },
+            ConfigValue::Int(n) => ASTNode::Number { value: n.to_string() },
+            ConfigValue::Str(text) => {
+                let mut value = String::from("\"");
+                for c in text.chars() {
+                    match c {
+                        '\n' => value.push_str("\\n"),
+                        '\t' => value.push_str("\\t"),
+                        '\r' => value.push_str("\\r"),
+                        '\0' => value.push_str("\\0"),
+                        '\\' => value.push_str("\\\\"),
+                        c => value.push(c),
+                    }
+                }
+                value.push('"');
+                ASTNode::String { value }
+            }
+        }
+    }
+}
+
+/// A name written in the source and the symbol it resolves to. The analyzer
+/// records one for every declaration and use, for editor tooling.
+#[derive(Debug, Clone)]
+pub struct NameRef {
+    pub name: String,
+    /// Where the name is written; `span.start` is the offset of its first byte.
+    pub span: Span,
+    pub kind: SymbolKind,
+    /// Value type for variables and parameters, return type for functions.
+    pub ty: Type,
+    /// Where the symbol is declared; `None` for builtins.
+    pub declared: Option<Span>,
+    pub declaration: bool,
+}
+
+pub struct SemanticAnalyzer {
+    scopes: Vec<HashMap<String, SymbolInfo>>,
+    /// Return type of the enclosing function; `None` at top level.
+    return_expected: Option<Type>,
+    /// Name and span of the enclosing function, for diagnostics.
+    function: Option<(String, Span)>,
+    let_count: usize,
+    reassigned: HashSet<usize>,
+    /// Lengths of the `let` bindings of array literals, by binding number.
+    array_lengths: HashMap<usize, usize>,
+    /// Constant indexes past the end of such a binding, reported once analysis
+    /// shows the binding is never reassigned.
+    out_of_bounds: Vec<(usize, String)>,
+    pub warnings: Vec<Warning>,
+    /// Filled in source order as far as analysis got, even when it failed.
+    pub names: Vec<NameRef>,
+    /// Functions declared by `declare_host_function`.
+    host_functions: HashSet<String>,
+    /// Variants and position of each `enum`, declared before anything else is analyzed.
+    enums: HashMap<String, (Vec<String>, Span)>,
+    /// Constants declared by `declare_config`.
+    config: BTreeMap<String, ConfigValue>,
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        SemanticAnalyzer::new()
+    }
+}
+
+impl SemanticAnalyzer {
+    pub fn new() -> Self {
+        SemanticAnalyzer {
+            scopes: vec![HashMap::new()],
+            return_expected: None,
+            function: None,
+            let_count: 0,
+            reassigned: HashSet::new(),
+            array_lengths: HashMap::new(),
+            out_of_bounds: Vec::new(),
+            warnings: Vec::new(),
+            names: Vec::new(),
+            host_functions: HashSet::new(),
+            enums: HashMap::new(),
+            config: BTreeMap::new(),
+        }
+    }
+
+    /// Declares a constant that code reads as `config.key` and that is embedded
+    /// where it is read. A value named `config` hides them all.
+    pub fn declare_config(&mut self, key: &str, value: ConfigValue) -> Result<(), String> {
+        if !is_identifier(key) {
+            return Err(format!("config key `{}` is not a valid name", key));
+        }
+        if matches!(&value, ConfigValue::Str(text) if text.contains('"')) {
+            return Err(format!("config.{} cannot contain `\"`, which Netflu strings have no escape for", key));
+        }
+        self.config.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Declares a function implemented by the program embedding Netflu. Code
+    /// can call it like a top-level function but cannot define one of the same name.
+    pub fn declare_host_function(&mut self, name: &str, params: Vec<Type>, returns: Type) -> Result<(), String> {
+        if BUILTIN_FUNCTIONS.contains(&name) || self.lookup(name).is_some() {
+            return Err(format!("function {} is already defined", name));
+        }
+        self.host_functions.insert(name.to_string());
+        self.declare(name, SymbolInfo {
+            kind: SymbolKind::Function { params },
+            ty: returns,
+            mutable: false,
+            used: false,
+            span: Span::default(),
+        });
+        Ok(())
+    }
+
+    /// Analyzes an expression on its own, outside any function, and returns its type.
+    pub fn analyze_expression(&mut self, node: &mut ASTNode) -> Result<Type, String> {
+        self.analyze_node(node)?;
+        self.type_of(node)
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.scopes[0].len()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&SymbolInfo> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Suggestion for an undefined name among the symbols in scope: functions
+    /// (plus builtins and `print`) for calls, values otherwise.
+    fn similar_name(&self, name: &str, function: bool) -> String {
+        let symbols = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.iter())
+            .filter(|(_, s)| matches!(s.kind, SymbolKind::Function { .. }) == function)
+            .map(|(n, _)| n.as_str());
+        let builtins = if function { BUILTIN_FUNCTIONS.iter().chain(&["print", "println"]).copied().collect() } else { vec![] };
+        did_you_mean(name, symbols.chain(builtins))
+    }
+
+    /// Records `name` at `span` as a declaration or use of the symbol it resolves to now.
+    fn record(&mut self, name: &str, span: Span, declaration: bool) {
+        if let Some(symbol) = self.lookup(name) {
+            self.names.push(NameRef {
+                name: name.to_string(),
+                span,
+                kind: symbol.kind.clone(),
+                ty: symbol.ty.clone(),
+                declared: Some(symbol.span),
+                declaration,
+            });
+        }
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut SymbolInfo> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    fn declare(&mut self, name: &str, info: SymbolInfo) {
+        let shadowed = self
+            .scopes
+            .last_mut()
+            .expect("global scope is never popped")
+            .insert(name.to_string(), info);
+        if let Some(shadowed) = shadowed {
+            self.warn_unused(std::iter::once((name.to_string(), shadowed)));
+        }
+    }
+
+    /// Reports `let` bindings that go out of scope (or are shadowed) without being read.
+    /// Names starting with `_` are exempt, as in Rust.
+    fn warn_unused(&mut self, symbols: impl Iterator<Item = (String, SymbolInfo)>) {
+        let mut unused: Vec<_> = symbols
+            .filter(|(name, s)| matches!(s.kind, SymbolKind::Variable { .. }) && !s.used && !name.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(_, s)| s.span.line);
+        for (name, symbol) in unused {
+            self.warnings.push(Warning {
+                lint: Lint::UnusedVariable,
+                message: format!("line {}: unused variable `{}`", symbol.span.line, name),
+            });
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(nodes = nodes.len()))]
+    pub fn analyze(&mut self, nodes: &mut [ASTNode]) -> Result<(), String> {
+        // Enums may be used before their declaration, in signatures too.
+        for node in nodes.iter() {
+            if let ASTNode::Enum { name, variants, span } = node {
+                self.declare_enum(name, variants, *span)?;
+            }
+        }
+        // Top-level functions may be called before their definition.
+        for node in nodes.iter() {
+            if let ASTNode::Function { name, params, return_type, span, .. }
+            | ASTNode::ExternFunction { name, params, return_type, span, .. } = node
+            {
+                if self.host_functions.contains(name) {
+                    return Err(format!("line {}: function {} is already provided by the host", span.line, name));
+                }
+                if let Some(existing) = self.lookup(name) {
+                    return Err(format!(
+                        "line {}: function {} is already defined at line {}",
+                        span.line, name, existing.span.line
+                    ));
+                }
+                self.declare_function(name, params, return_type, *span)?;
+                self.record(name, *span, true);
+            }
+        }
+
+        for node in nodes.iter_mut() {
+            self.analyze_node(node)?;
+        }
+        if let Some((_, error)) = self.out_of_bounds.iter().find(|(binding, _)| !self.reassigned.contains(binding)) {
+            return Err(error.clone());
+        }
+
+        let mut binding = 0;
+        for node in nodes.iter_mut() {
+            node.walk_mut(&mut |n| {
+                match n {
+                    ASTNode::Let { mutable, .. } => {
+                        *mutable = self.reassigned.contains(&binding);
+                        binding += 1;
+                    }
+                    ASTNode::LetTuple { bindings, .. } => {
+                        for b in bindings {
+                            b.mutable = self.reassigned.contains(&binding);
+                            binding += 1;
+                        }
+                    }
+                    // Numbered before the arm bodies, see the analysis of `match`.
+                    ASTNode::Match { arms, .. } => {
+                        for b in arms.iter_mut().filter_map(|arm| arm.binding.as_mut()) {
+                            b.mutable = self.reassigned.contains(&binding);
+                            binding += 1;
+                        }
+                    }
+                    _ => {}
+                }
+            });
+        }
+
+        debug!(symbols = self.symbol_count(), "semantic analysis finished");
+        Ok(())
+    }
+
+    fn analyze_node(&mut self, node: &mut ASTNode) -> Result<(), String> {
+        trace!(?node, "analyze node");
+        match node {
+            ASTNode::Function { name, params, return_type, body, public, span } => {
+                if name == "main" && (!params.is_empty() || return_type.is_some()) {
+                    return Err("`main` cannot take parameters or return a value".to_string());
+                }
+                if *public {
+                    if name == "main" {
+                        return Err(format!("line {}: `main` cannot be pub", span.line));
+                    }
+                    if self.scopes.len() > 1 {
+                        return Err(format!("line {}: only top-level functions can be pub", span.line));
+                    }
+                    let c_compatible = params.iter().all(|p| p.type_name() == "int")
+                        && return_type.as_deref().is_none_or(|ty| ty == "int");
+                    if !c_compatible {
+                        return Err(format!(
+                            "line {}: pub function {} can only take and return int, as it is exported with the C ABI",
+                            span.line, name
+                        ));
+                    }
+                }
+                let returns = match return_type {
+                    Some(ty) => self.resolve_type(ty)?,
+                    None => Type::Unit,
+                };
+                if returns != Type::Int && returns != Type::Unit && !always_backs(body) {
+                    return Err(format!("Function {} must `back` a value of type {}", name, returns.name()));
+                }
+
+                // Register the signature first so the body can call itself.
+                self.declare_function(name, params, return_type, *span)?;
+                if self.scopes.len() > 1 {
+                    // Top-level functions were recorded by `analyze`.
+                    self.record(name, *span, true);
+                }
+
+                self.scopes.push(HashMap::new());
+                for (i, param) in params.iter().enumerate() {
+                    if params[..i].iter().any(|p| p.name == param.name) {
+                        return Err(format!("Duplicate parameter {} in function {}", param.name, name));
+                    }
+                    self.declare(&param.name, SymbolInfo {
+                        kind: SymbolKind::Parameter,
+                        ty: self.resolve_type(param.type_name())?,
+                        mutable: false,
+                        used: false,
+                        span: param.span,
+                    });
+                    self.record(&param.name, param.span, true);
+                }
+
+                let outer = self.return_expected.replace(returns);
+                let outer_function = self.function.replace((name.clone(), *span));
+                for stmt in body {
+                    self.analyze_node(stmt)?;
+                }
+                self.return_expected = outer;
+                self.function = outer_function;
+                let scope = self.scopes.pop().expect("function scope was pushed above");
+                self.warn_unused(scope.into_iter());
+                Ok(())
+            }
+            ASTNode::ExternFunction { abi, name, params, return_type, span } => {
+                match abi.as_str() {
+                    "rust" => {}
+                    "c" if return_type.as_deref() == Some("str") => {
+                        return Err(format!(
+                            "line {}: extern c function {} cannot return str; C strings have no owner on the Netflu side",
+                            span.line, name
+                        ));
+                    }
+                    "c" if params.iter().map(Param::type_name).chain(return_type.as_deref()).any(|ty| ty.starts_with(['[', '{', '(']) || ty.ends_with('?')) => {
+                        return Err(format!(
+                            "line {}: extern c function {} cannot take or return arrays, maps, tuples or optionals; C has no slices, hash maps, tuples or options",
+                            span.line, name
+                        ));
+                    }
+                    "c" => {}
+                    _ => {
+                        return Err(format!(
+                            "line {}: Unsupported ABI `{}` for extern function {} (expected `rust` or `c`)",
+                            span.line, abi, name
+                        ))
+                    }
+                }
+                let types = params.iter().map(Param::type_name).chain(return_type.as_deref());
+                let mut types = types.filter_map(|ty| self.resolve_type(ty).ok());
+                if types.any(|ty| ty.components().iter().any(|ty| matches!(ty, Type::Enum(_)))) {
+                    return Err(format!(
+                        "line {}: extern function {} cannot take or return enums; they only exist in Netflu code",
+                        span.line, name
+                    ));
+                }
+                self.declare_function(name, params, return_type, *span)?;
+                if self.scopes.len() > 1 {
+                    self.record(name, *span, true);
+                }
+                // Extern parameters are never in scope; they only name the arguments.
+                for param in params.iter() {
+                    self.names.push(NameRef {
+                        name: param.name.clone(),
+                        span: param.span,
+                        kind: SymbolKind::Parameter,
+                        ty: self.resolve_type(param.type_name())?,
+                        declared: Some(param.span),
+                        declaration: true,
+                    });
+                }
+                Ok(())
+            }
+            ASTNode::FunctionCall { name, args, span } if name == "format" => {
+                self.names.push(NameRef {
+                    name: name.clone(),
+                    span: *span,
+                    kind: SymbolKind::Function { params: vec![Type::Int, Type::Str] },
+                    ty: Type::Str,
+                    declared: None,
+                    declaration: false,
+                });
+                if let [value, _] = args.as_mut_slice() {
+                    self.analyze_value(value)?;
+                    let found = self.type_of(value)?;
+                    if found != Type::Int {
+                        return Err(TypeMismatch {
+                            expected: Type::Int,
+                            found,
+                            found_at: expression_span(value).unwrap_or(*span),
+                            found_what: "value",
+                            context: None,
+                            reason: "`format`, which only supports int values".to_string(),
+                        }
+                        .to_string());
+                    }
+                }
+                format_call_args(args).map_err(|e| format!("line {}: {}", span.line, e))?;
+                Ok(())
+            }
+            ASTNode::FunctionCall { name, args, span } if name == "some" => {
+                let [value] = args.as_mut_slice() else {
+                    return Err(format!("line {}: some expects 1 argument (value), got {}", span.line, args.len()));
+                };
+                self.analyze_value(value)?;
+                let ty = self.type_of(value)?;
+                let Some(optional) = ty.optional_of() else {
+                    return Err(format!(
+                        "line {}: `some` cannot hold a value of type {}; optionals cannot be nested",
+                        expression_span(value).unwrap_or(*span).line,
+                        ty.name()
+                    ));
+                };
+                self.names.push(NameRef {
+                    name: name.clone(),
+                    span: *span,
+                    kind: SymbolKind::Function { params: vec![ty] },
+                    ty: optional,
+                    declared: None,
+                    declaration: false,
+                });
+                Ok(())
+            }
+            ASTNode::FunctionCall { name, args, span } => {
+                let (params, declared) = match self.lookup(name) {
+                    Some(SymbolInfo { kind: SymbolKind::Function { params }, span, .. }) => (params.clone(), *span),
+                    Some(_) => return Err(format!("line {}: {} is not a function", span.line, name)),
+                    None => {
+                        return Err(format!(
+                            "line {}: Undefined function: {}{}",
+                            span.line,
+                            name,
+                            self.similar_name(name, true)
+                        ))
+                    }
+                };
+                self.record(name, *span, false);
+
+                if params.len() != args.len() {
+                    return Err(format!(
+                        "line {}: Function {} expects {} argument(s), got {}",
+                        span.line, name, params.len(), args.len()
+                    ));
+                }
+                self.check_arguments(name, args, &params, 0, declared, *span)
+            }
+            ASTNode::MethodCall { receiver, name, args, method, span } => {
+                self.analyze_value(receiver)?;
+                let ty = self.type_of(receiver)?;
+                if name == "len" && ty.has_len() {
+                    if !args.is_empty() {
+                        return Err(format!("line {}: `len` takes no arguments, got {}", span.line, args.len()));
+                    }
+                    *method = Some(Method::Len);
+                    return Ok(());
+                }
+                let (params, declared) = match self.lookup(name) {
+                    Some(SymbolInfo { kind: SymbolKind::Function { params }, span, .. }) if !params.is_empty() => {
+                        (params.clone(), *span)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {}: {} has no method `{}`; a method is `len()` or a function whose first parameter is {}",
+                            span.line,
+                            ty.name(),
+                            name,
+                            ty.name()
+                        ))
+                    }
+                };
+                self.record(name, *span, false);
+                if params[0] != ty {
+                    return Err(TypeMismatch {
+                        expected: params[0].clone(),
+                        found: ty,
+                        found_at: expression_span(receiver).unwrap_or(*span),
+                        found_what: "receiver",
+                        context: Some(declared).filter(|_| !self.host_functions.contains(name.as_str())),
+                        reason: format!("parameter 1 of `{}`, which takes the value before `.{}`", name, name),
+                    }
+                    .to_string());
+                }
+                if params.len() != args.len() + 1 {
+                    return Err(format!(
+                        "line {}: Method {} expects {} argument(s) after the receiver, got {}",
+                        span.line,
+                        name,
+                        params.len() - 1,
+                        args.len()
+                    ));
+                }
+                *method = Some(Method::Function);
+                self.check_arguments(name, args, &params, 1, declared, *span)
+            }
+            ASTNode::Field { object, name, enum_type, span } => {
+                // `Color.Red`, unless a value named `Color` hides the enum
+                if let ASTNode::Identifier { name: enum_name, .. } = &**object {
+                    if let Some((variants, _)) = self.enums.get(enum_name).filter(|_| self.lookup(enum_name).is_none()) {
+                        if !variants.contains(name) {
+                            return Err(format!(
+                                "line {}: enum {} has no variant `{}`{}",
+                                span.line,
+                                enum_name,
+                                name,
+                                did_you_mean(name, variants.iter().map(String::as_str))
+                            ));
+                        }
+                        *enum_type = Some(Type::Enum(enum_name.as_str().into()));
+                        return Ok(());
+                    }
+                    if enum_name == "config" && self.lookup(enum_name).is_none() {
+                        let Some(value) = self.config.get(name) else {
+                            return Err(format!(
+                                "line {}: `config.{}` is not set in the [config] table of ntfp.toml{}",
+                                span.line,
+                                name,
+                                did_you_mean(name, self.config.keys().map(String::as_str))
+                            ));
+                        };
+                        *node = value.literal(*span);
+                        return Ok(());
+                    }
+                }
+                self.analyze_value(object)?;
+                let ty = self.type_of(object)?;
+                let method = (name == "len" && ty.has_len())
+                    || matches!(self.lookup(name), Some(SymbolInfo { kind: SymbolKind::Function { params }, .. }) if params.first() == Some(&ty));
+                Err(format!(
+                    "line {}: {} has no field `{}`{}",
+                    span.line,
+                    ty.name(),
+                    name,
+                    if method { format!(" (did you mean the method call `{}()`?)", name) } else { String::new() }
+                ))
+            }
+            ASTNode::Assign { name, value, span } => {
+                if let Some(symbol) = self.lookup(name) {
+                    self.infer_none(value, &symbol.ty);
+                }
+                self.analyze_value(value)?;
+                let found = self.type_of(value)?;
+                let line = span.line;
+
+                if self.lookup(name).is_none() {
+                    return Err(format!("line {}: Undefined variable: {}{}", line, name, self.similar_name(name, false)));
+                }
+                self.record(name, *span, false);
+                let symbol = self.lookup_mut(name).expect("looked up above");
+                match symbol.kind {
+                    SymbolKind::Function { .. } => Err(format!("line {}: Cannot assign to function {}", line, name)),
+                    SymbolKind::Parameter => Err(format!("line {}: Cannot assign to parameter {}", line, name)),
+                    SymbolKind::LoopVariable => Err(format!(
+                        "line {}: Cannot assign to loop variable {}; it takes the next value of the range each time around",
+                        line, name
+                    )),
+                    SymbolKind::Variable { binding } => {
+                        if symbol.ty != found {
+                            return Err(TypeMismatch {
+                                expected: symbol.ty.clone(),
+                                found,
+                                found_at: *span,
+                                found_what: "assigned value",
+                                context: Some(symbol.span),
+                                reason: format!("the `let` that gave `{}` its type", name),
+                            }
+                            .to_string());
+                        }
+                        symbol.mutable = true;
+                        self.reassigned.insert(binding);
+                        Ok(())
+                    }
+                }
+            }
+            ASTNode::Let { name, value, span, .. } => {
+                self.analyze_value(value)?;
+                let ty = self.type_of(value)?;
+                let binding = self.let_count;
+                self.let_count += 1;
+                if let ASTNode::Array { elements, .. } = &**value {
+                    self.array_lengths.insert(binding, elements.len());
+                }
+                self.declare(name, SymbolInfo {
+                    kind: SymbolKind::Variable { binding },
+                    ty,
+                    mutable: false,
+                    used: false,
+                    span: *span,
+                });
+                self.record(name, *span, true);
+                Ok(())
+            }
+            ASTNode::LetTuple { bindings, value, span } => {
+                self.analyze_value(value)?;
+                let ty = self.type_of(value)?;
+                let Some(elements) = ty.tuple_elements() else {
+                    return Err(format!("line {}: `let (...)` takes a tuple apart, but the value is {}", span.line, ty.name()));
+                };
+                if elements.len() != bindings.len() {
+                    return Err(format!(
+                        "line {}: `let (...)` binds {} names, but {} has {} elements",
+                        span.line,
+                        bindings.len(),
+                        ty.name(),
+                        elements.len()
+                    ));
+                }
+                if let Some((_, duplicate)) = bindings.iter().enumerate().find(|&(i, b)| bindings[..i].iter().any(|o| o.name == b.name)) {
+                    return Err(format!("line {}: `{}` is bound more than once in `let (...)`", duplicate.span.line, duplicate.name));
+                }
+                for (binding, ty) in bindings.iter().zip(elements) {
+                    let number = self.let_count;
+                    self.let_count += 1;
+                    self.declare(&binding.name, SymbolInfo {
+                        kind: SymbolKind::Variable { binding: number },
+                        ty: ty.clone(),
+                        mutable: false,
+                        used: false,
+                        span: binding.span,
+                    });
+                    self.record(&binding.name, binding.span, true);
+                }
+                Ok(())
+            }
+            ASTNode::Print { value, value_type, .. } => {
+                self.analyze_value(value)?;
+                let ty = self.type_of(value)?;
+                if ty.components().iter().any(|ty| ty.entry().is_some()) {
+                    // `HashMap` iterates in no particular order, so the output would vary between runs
+                    return Err(format!(
+                        "line {}: cannot print a map, as its entries have no order; print single entries with `map[key]`",
+                        expression_span(value).map_or(0, |span| span.line)
+                    ));
+                }
+                *value_type = Some(ty.clone());
+                Ok(())
+            }
+            ASTNode::Back { value, span, implicit } => match (self.return_expected.clone(), value) {
+                (None, _) => Err("`back` outside of a function".to_string()),
+                // `fun main() { greet() }`: a call without a value is fine as the last expression
+                (Some(Type::Unit), Some(value)) if *implicit => {
+                    self.analyze_node(value)?;
+                    let found = self.type_of(value)?;
+                    if found != Type::Unit {
+                        let (function, declared) = self.function.clone().expect("`back` is checked inside a function");
+                        return Err(TypeMismatch {
+                            expected: Type::Unit,
+                            found,
+                            found_at: expression_span(value).unwrap_or(*span),
+                            found_what: "last expression",
+                            context: Some(declared),
+                            reason: format!("`{}`, which has no return type; declare one with `->`", function),
+                        }
+                        .to_string());
+                    }
+                    Ok(())
+                }
+                (Some(Type::Unit), Some(_)) => Err("`back` cannot return a value in a function without a return type".to_string()),
+                (Some(Type::Unit), None) => Ok(()),
+                (Some(_), None) => Err("`back` must return a value in a function with a return type".to_string()),
+                (Some(expected), Some(value)) => {
+                    self.infer_none(value, &expected);
+                    self.analyze_value(value)?;
+                    let found = self.type_of(value)?;
+                    if found != expected {
+                        let (function, declared) = self.function.clone().expect("`back` is checked inside a function");
+                        return Err(TypeMismatch {
+                            expected,
+                            found,
+                            found_at: expression_span(value).unwrap_or(*span),
+                            found_what: if *implicit { "last expression" } else { "returned value" },
+                            context: Some(declared),
+                            reason: format!("the return type of `{}`", function),
+                        }
+                        .to_string());
+                    }
+                    Ok(())
+                }
+            },
+            ASTNode::Identifier { name, span, ty } => match self.lookup_mut(name) {
+                None if name == "none" => Err(format!(
+                    "line {}: cannot tell which optional type `none` has here; use it where one is expected, \
+                     such as in `back` or as an argument",
+                    span.line
+                )),
+                None => Err(format!(
+                    "line {}: Undefined identifier: {}{}",
+                    span.line,
+                    name,
+                    self.similar_name(name, false)
+                )),
+                Some(SymbolInfo { kind: SymbolKind::Function { .. }, .. }) => {
+                    Err(format!("line {}: {} is a function, not a value", span.line, name))
+                }
+                Some(symbol) => {
+                    symbol.used = true;
+                    *ty = Some(symbol.ty.clone());
+                    self.record(name, *span, false);
+                    Ok(())
+                }
+            },
+            ASTNode::Number { .. } => Ok(()),
+            ASTNode::String { .. } => Ok(()),
+            ASTNode::None { .. } => Ok(()),
+            ASTNode::Interpolation { args, .. } => {
+                for arg in args {
+                    self.analyze_value(arg)?;
+                    let ty = self.type_of(arg)?;
+                    if !ty.is_displayable() {
+                        let ASTNode::Identifier { name, span, .. } = arg else {
+                            unreachable!("placeholders are names");
+                        };
+                        return Err(format!(
+                            "line {}: `{{{}}}` is {}, which cannot be interpolated; only int and str values can",
+                            span.line,
+                            name,
+                            ty.name()
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            ASTNode::Array { elements, span } => {
+                let Some((first, rest)) = elements.split_first_mut() else {
+                    return Err(format!(
+                        "line {}: cannot infer the type of an empty array; write at least one element",
+                        span.line
+                    ));
+                };
+                self.analyze_value(first)?;
+                let expected = self.type_of(first)?;
+                if expected.array_of().is_none() {
+                    return Err(format!(
+                        "line {}: arrays can hold int or str values, not {}",
+                        expression_span(first).unwrap_or(*span).line,
+                        expected.name()
+                    ));
+                }
+                for element in rest {
+                    self.analyze_value(element)?;
+                    let found = self.type_of(element)?;
+                    if found != expected {
+                        return Err(TypeMismatch {
+                            expected,
+                            found,
+                            found_at: expression_span(element).unwrap_or(*span),
+                            found_what: "element",
+                            context: expression_span(first),
+                            reason: "the first element, as all elements of an array have the same type".to_string(),
+                        }
+                        .to_string());
+                    }
+                }
+                Ok(())
+            }
+            ASTNode::Tuple { elements, span } => {
+                for element in elements.iter_mut() {
+                    self.analyze_value(element)?;
+                    if let Type::Tuple(_) = self.type_of(element)? {
+                        return Err(format!(
+                            "line {}: tuples cannot be nested; bind the inner tuple's elements with `let (...)` first",
+                            expression_span(element).unwrap_or(*span).line
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            ASTNode::Map { entries, span } => {
+                let Some(((first_key, first_value), rest)) = entries.split_first_mut() else {
+                    return Err(format!(
+                        "line {}: cannot infer the type of an empty map; write at least one entry",
+                        span.line
+                    ));
+                };
+                self.analyze_value(first_key)?;
+                self.analyze_value(first_value)?;
+                let key_type = self.type_of(first_key)?;
+                let value_type = self.type_of(first_value)?;
+                for (ty, what, node) in [(&key_type, "keys", &*first_key), (&value_type, "values", &*first_value)] {
+                    if !ty.is_displayable() {
+                        return Err(format!(
+                            "line {}: map {} can be int or str, not {}",
+                            expression_span(node).unwrap_or(*span).line,
+                            what,
+                            ty.name()
+                        ));
+                    }
+                }
+                for (key, value) in rest.iter_mut() {
+                    for (node, expected, found_what, first) in
+                        [(key, &key_type, "key", &*first_key), (value, &value_type, "value", &*first_value)]
+                    {
+                        self.analyze_value(node)?;
+                        let found = self.type_of(node)?;
+                        if found != *expected {
+                            return Err(TypeMismatch {
+                                expected: expected.clone(),
+                                found,
+                                found_at: expression_span(node).unwrap_or(*span),
+                                found_what,
+                                context: expression_span(first),
+                                reason: format!(
+                                    "the first {}, as all {}s of a map have the same type",
+                                    found_what, found_what
+                                ),
+                            }
+                            .to_string());
+                        }
+                    }
+                }
+                check_duplicate_keys(entries, *span)
+            }
+            ASTNode::Index { collection, index, collection_type, span } => {
+                self.analyze_value(collection)?;
+                let ty = self.type_of(collection)?;
+                *collection_type = Some(ty.clone());
+                if let Some((key_type, _)) = ty.entry() {
+                    self.analyze_value(index)?;
+                    let found = self.type_of(index)?;
+                    if found != key_type {
+                        return Err(TypeMismatch {
+                            expected: key_type,
+                            found,
+                            found_at: expression_span(index).unwrap_or(*span),
+                            found_what: "key",
+                            context: expression_span(collection),
+                            reason: format!("the keys of this map, which is {}", ty.name()),
+                        }
+                        .to_string());
+                    }
+                    return Ok(());
+                }
+                if ty.element().is_none() {
+                    return Err(format!(
+                        "line {}: cannot index into a value of type {}; only arrays and maps can be indexed",
+                        span.line,
+                        ty.name()
+                    ));
+                }
+                self.analyze_value(index)?;
+                let found = self.type_of(index)?;
+                if found != Type::Int {
+                    return Err(TypeMismatch {
+                        expected: Type::Int,
+                        found,
+                        found_at: expression_span(index).unwrap_or(*span),
+                        found_what: "index",
+                        context: None,
+                        reason: "`[]`, which counts elements from 0".to_string(),
+                    }
+                    .to_string());
+                }
+                self.check_constant_index(collection, index, *span)
+            }
+            // Declared by `analyze`
+            ASTNode::Enum { span, .. } if self.scopes.len() > 1 => {
+                Err(format!("line {}: enums can only be declared at the top level", span.line))
+            }
+            ASTNode::Enum { .. } => Ok(()),
+            ASTNode::Match { value, arms, else_body, value_type, span } => {
+                self.analyze_value(value)?;
+                let ty = self.type_of(value)?;
+                let optional = ["some".to_string(), "none".to_string()];
+                let variants = match self.enums.get(ty.name().as_ref()) {
+                    Some((variants, _)) if matches!(ty, Type::Enum(_)) => variants.as_slice(),
+                    _ if ty.inner().is_some() => optional.as_slice(),
+                    _ => {
+                        return Err(format!(
+                            "line {}: `match` works on enum and optional values, not {}",
+                            span.line,
+                            ty.name()
+                        ))
+                    }
+                };
+                for (i, arm) in arms.iter().enumerate() {
+                    if !variants.contains(&arm.variant) {
+                        let what = if ty.inner().is_some() { "optional" } else { "enum" };
+                        return Err(format!(
+                            "line {}: {} {} has no variant `{}`{}",
+                            arm.span.line,
+                            what,
+                            ty.name(),
+                            arm.variant,
+                            did_you_mean(&arm.variant, variants.iter().map(String::as_str))
+                        ));
+                    }
+                    if let Some(first) = arms[..i].iter().find(|first| first.variant == arm.variant) {
+                        return Err(format!(
+                            "line {}: `{}` already has an arm at line {}",
+                            arm.span.line, arm.variant, first.span.line
+                        ));
+                    }
+                    let some = ty.inner().is_some() && arm.variant == "some";
+                    match &arm.binding {
+                        None if some => {
+                            return Err(format!(
+                                "line {}: the `some` arm names the value it holds: `some(value) {{ ... }}`",
+                                arm.span.line
+                            ));
+                        }
+                        Some(binding) if !some => {
+                            return Err(format!(
+                                "line {}: `{}` holds no value, so its arm cannot bind `{}`",
+                                binding.span.line, arm.variant, binding.name
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                let missing: Vec<String> = variants
+                    .iter()
+                    .filter(|variant| !arms.iter().any(|arm| &arm.variant == *variant))
+                    .map(|variant| format!("`{}`", variant))
+                    .collect();
+                match else_body {
+                    None if !missing.is_empty() => {
+                        return Err(format!(
+                            "line {}: `match` does not cover {} of {}; add an arm for each or an `else`",
+                            span.line,
+                            missing.join(", "),
+                            ty.name()
+                        ));
+                    }
+                    Some(_) if missing.is_empty() => {
+                        return Err(format!(
+                            "line {}: `else` is never reached, as every variant of {} has an arm",
+                            span.line,
+                            ty.name()
+                        ));
+                    }
+                    _ => {}
+                }
+                *value_type = Some(ty.clone());
+                // Numbered before the bodies, where the post-pass finds it too; only `some` binds.
+                let number = self.let_count;
+                self.let_count += usize::from(arms.iter().any(|arm| arm.binding.is_some()));
+                for arm in arms {
+                    let Some(binding) = &arm.binding else {
+                        self.analyze_block(&mut arm.body)?;
+                        continue;
+                    };
+                    // The bound value is in scope of the arm only, like a `let` at its start.
+                    self.scopes.push(HashMap::new());
+                    self.declare(&binding.name, SymbolInfo {
+                        kind: SymbolKind::Variable { binding: number },
+                        ty: ty.inner().expect("only `some` arms bind").clone(),
+                        mutable: false,
+                        used: false,
+                        span: binding.span,
+                    });
+                    self.record(&binding.name, binding.span, true);
+                    self.analyze_block(&mut arm.body)?;
+                    let scope = self.scopes.pop().expect("arm scope was pushed above");
+                    self.warn_unused(scope.into_iter());
+                }
+                if let Some(else_body) = else_body {
+                    self.analyze_block(else_body)?;
+                }
+                Ok(())
+            }
+            ASTNode::If { condition, then_body, else_body, span } => {
+                self.check_condition(condition, *span, "`if`")?;
+                self.analyze_block(then_body)?;
+                self.analyze_block(else_body)
+            }
+            ASTNode::While { condition, body, span } => {
+                self.check_condition(condition, *span, "`while`")?;
+                self.analyze_block(body)
+            }
+            ASTNode::For { var, start, end, body, span } => {
+                for bound in [start, end] {
+                    self.analyze_value(bound)?;
+                    let found = self.type_of(bound)?;
+                    if found != Type::Int {
+                        return Err(TypeMismatch {
+                            expected: Type::Int,
+                            found,
+                            found_at: expression_span(bound).unwrap_or(*span),
+                            found_what: "range bound",
+                            context: None,
+                            reason: "`for`, which counts through a range of ints".to_string(),
+                        }
+                        .to_string());
+                    }
+                }
+                self.scopes.push(HashMap::new());
+                self.declare(var, SymbolInfo {
+                    kind: SymbolKind::LoopVariable,
+                    ty: Type::Int,
+                    mutable: false,
+                    used: false,
+                    span: *span,
+                });
+                self.record(var, *span, true);
+                self.analyze_block(body)?;
+                self.scopes.pop();
+                Ok(())
+            }
+            ASTNode::Binary { op, left, right, operand_type, span } if op.is_comparison() => {
+                self.analyze_value(left)?;
+                let expected = self.type_of(left)?;
+                if let Type::Enum(_) = expected {
+                    self.check_overload(*op, &expected, right, *span)?;
+                    *operand_type = Some(expected);
+                    return Ok(());
+                }
+                self.infer_none(right, &expected);
+                self.analyze_value(right)?;
+                let found = self.type_of(right)?;
+                if found != expected {
+                    return Err(TypeMismatch {
+                        expected,
+                        found,
+                        found_at: expression_span(right).unwrap_or(*span),
+                        found_what: "right operand",
+                        context: expression_span(left),
+                        reason: format!("the left operand of `{}`, as both sides must have the same type", op.symbol()),
+                    }
+                    .to_string());
+                }
+                if !expected.is_displayable() {
+                    let hint = if expected.inner().is_some() { "; `match` on it to tell `some` from `none`" } else { "" };
+                    return Err(format!(
+                        "line {}: `{}` compares int or str values, not {}{}",
+                        span.line,
+                        op.symbol(),
+                        expected.name(),
+                        hint
+                    ));
+                }
+                *operand_type = Some(expected);
+                Ok(())
+            }
+            ASTNode::Binary { op, left, right, operand_type, span } => {
+                self.analyze_value(left)?;
+                let left_type = self.type_of(left)?;
+                // An enum operand uses the function that defines the operator for it.
+                if let Type::Enum(_) = left_type {
+                    self.check_overload(*op, &left_type, right, *span)?;
+                    *operand_type = Some(left_type);
+                    return Ok(());
+                }
+                // `+` joins two strings; the left operand decides which `+` it is.
+                if *op == BinaryOp::Add && left_type == Type::Str {
+                    self.analyze_value(right)?;
+                    let found = self.type_of(right)?;
+                    if found != Type::Str {
+                        return Err(TypeMismatch {
+                            expected: Type::Str,
+                            found,
+                            found_at: expression_span(right).unwrap_or(*span),
+                            found_what: "right operand",
+                            context: expression_span(left),
+                            reason: "the left operand of `+`, which joins two str values or adds two int values"
+                                .to_string(),
+                        }
+                        .to_string());
+                    }
+                    *operand_type = Some(Type::Str);
+                    return Ok(());
+                }
+
+                let reason = match op {
+                    BinaryOp::Add => "`+`, which joins two str values or adds two int values".to_string(),
+                    _ => format!("`{}`, which only works on int values", op.symbol()),
+                };
+                let mismatch = |found: Type, operand: &ASTNode| {
+                    TypeMismatch {
+                        expected: Type::Int,
+                        found,
+                        found_at: expression_span(operand).unwrap_or(*span),
+                        found_what: "operand",
+                        context: None,
+                        reason: reason.clone(),
+                    }
+                    .to_string()
+                };
+                if left_type != Type::Int {
+                    return Err(mismatch(left_type, left));
+                }
+                self.analyze_value(right)?;
+                let found = self.type_of(right)?;
+                if found != Type::Int {
+                    return Err(mismatch(found, right));
+                }
+                if *op == BinaryOp::Divide && constant_int(right) == Some(0) {
+                    return Err(format!(
+                        "line {}: division by zero; the right operand of `/` is always 0",
+                        expression_span(right).unwrap_or(*span).line
+                    ));
+                }
+                *operand_type = Some(Type::Int);
+                Ok(())
+            }
+            ASTNode::Negate { operand, span } => {
+                self.analyze_value(operand)?;
+                let found = self.type_of(operand)?;
+                if found != Type::Int {
+                    return Err(TypeMismatch {
+                        expected: Type::Int,
+                        found,
+                        found_at: expression_span(operand).unwrap_or(*span),
+                        found_what: "operand",
+                        context: None,
+                        reason: "`-`, which only works on int values".to_string(),
+                    }
+                    .to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Rejects a constant index that is negative or past the end of an array
+    /// whose length is known: a literal, or a `let` of one that is never reassigned.
+    fn check_constant_index(&mut self, array: &ASTNode, index: &ASTNode, span: Span) -> Result<(), String> {
+        let (negative, digits) = match index {
+            ASTNode::Number { value } => (false, value),
+            ASTNode::Negate { operand, .. } => match &**operand {
+                ASTNode::Number { value } => (true, value),
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        // Too large for usize is past the end of any array
+        let position = digits.parse::<usize>().unwrap_or(usize::MAX);
+        if negative && position > 0 {
+            return Err(format!("line {}: index -{} is out of bounds; arrays are indexed from 0", span.line, digits));
+        }
+        match array {
+            ASTNode::Array { elements, .. } if position >= elements.len() => Err(format!(
+                "line {}: index out of bounds: the length of the array is {} but the index is {}",
+                span.line,
+                elements.len(),
+                digits
+            )),
+            ASTNode::Identifier { name, .. } => {
+                let Some(SymbolInfo { kind: SymbolKind::Variable { binding }, .. }) = self.lookup(name) else {
+                    return Ok(());
+                };
+                let binding = *binding;
+                if let Some(&length) = self.array_lengths.get(&binding).filter(|&&length| position >= length) {
+                    let error = format!(
+                        "line {}: index out of bounds: the length of `{}` is {} but the index is {}",
+                        span.line, name, length, digits
+                    );
+                    self.out_of_bounds.push((binding, error));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Conditions are int for now: the block runs while the value is not 0.
+    fn check_condition(&mut self, condition: &mut ASTNode, span: Span, keyword: &str) -> Result<(), String> {
+        self.analyze_value(condition)?;
+        let found = self.type_of(condition)?;
+        if found != Type::Int {
+            return Err(TypeMismatch {
+                expected: Type::Int,
+                found,
+                found_at: expression_span(condition).unwrap_or(span),
+                found_what: "condition",
+                context: None,
+                reason: format!("{}, which runs its block when the int is not 0", keyword),
+            }
+            .to_string());
+        }
+        Ok(())
+    }
+
+    /// Analyzes the statements of an `if` branch or loop body in a scope of their own.
+    fn analyze_block(&mut self, body: &mut [ASTNode]) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.analyze_node(stmt)?;
+        }
+        let scope = self.scopes.pop().expect("block scope was pushed above");
+        self.warn_unused(scope.into_iter());
+        Ok(())
+    }
+
+    fn declare_enum(&mut self, name: &str, variants: &[String], span: Span) -> Result<(), String> {
+        if !matches!(Type::from_name(name), Ok(Type::Enum(_))) {
+            return Err(format!("line {}: enum {} has the name of a builtin type", span.line, name));
+        }
+        if let Some((_, existing)) = self.enums.get(name) {
+            return Err(format!("line {}: enum {} is already defined at line {}", span.line, name, existing.line));
+        }
+        if let Some((_, variant)) = variants.iter().enumerate().find(|&(i, v)| variants[..i].contains(v)) {
+            return Err(format!("line {}: duplicate variant `{}` in enum {}", span.line, variant, name));
+        }
+        self.enums.insert(name.to_string(), (variants.to_vec(), span));
+        Ok(())
+    }
+
+    /// The type an annotation names, which must be builtin or a declared enum,
+    /// or a tuple of those.
+    fn resolve_type(&self, name: &str) -> Result<Type, String> {
+        let ty = Type::from_name(name)?;
+        for element in ty.components() {
+            if matches!(element, Type::Enum(_)) && !self.enums.contains_key(element.name().as_ref()) {
+                return Err(format!("Unknown type: {}", element.name()));
+            }
+        }
+        Ok(ty)
+    }
 
-        let value = match expr {
-            ASTNode::Identifier { name } => name,
-            ASTNode::Number { value } => value,
-            _ => return Err("Invalid expression in back statement".to_string()),
+    fn declare_function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        return_type: &Option<String>,
+        span: Span,
+    ) -> Result<(), String> {
+        if BUILTIN_FUNCTIONS.contains(&name) {
+            return Err(format!("line {}: `{}` is a builtin function and cannot be redefined", span.line, name));
+        }
+        let ty = match return_type {
+            Some(ty) => self.resolve_type(ty)?,
+            None => Type::Unit,
         };
-
-        Ok(ASTNode::Back { value })
+        let params = params
+            .iter()
+            .map(|p| self.resolve_type(p.type_name()))
+            .collect::<Result<_, _>>()?;
+        self.declare(name, SymbolInfo {
+            kind: SymbolKind::Function { params },
+            ty,
+            mutable: false,
+            used: false,
+            span,
+        });
+        Ok(())
     }
 
-    fn parse_fun(&mut self) -> Result<ASTNode, String> {
-        self.eat(TokenType::Fun)?;
+    /// Checks the arguments of a call to `name` against the types of its
+    /// parameters. `skip` leading parameters take no argument: the receiver
+    /// of a method call, checked already.
+    fn check_arguments(
+        &mut self,
+        name: &str,
+        args: &mut [ASTNode],
+        params: &[Type],
+        skip: usize,
+        declared: Span,
+        span: Span,
+    ) -> Result<(), String> {
+        for (i, (arg, expected)) in args.iter_mut().zip(&params[skip..]).enumerate() {
+            self.infer_none(arg, expected);
+            self.analyze_value(arg)?;
+            let found = self.type_of(arg)?;
+            if found != *expected {
+                return Err(TypeMismatch {
+                    expected: expected.clone(),
+                    found,
+                    found_at: expression_span(arg).unwrap_or(span),
+                    found_what: "argument",
+                    // Host functions have no declaration in the source to point at
+                    context: Some(declared).filter(|_| !self.host_functions.contains(name)),
+                    reason: format!("parameter {} of `{}`", skip + i + 1, name),
+                }
+                .to_string());
+            }
+        }
+        Ok(())
+    }
 
-        let ident_token = self.current_token().ok_or("Expected function name")?;
-        if ident_token.type_ != TokenType::Identifier {
-            return Err("Expected function name".to_string());
+    /// Checks `left op right` for an enum left operand against the top-level
+    /// function that defines `op` for it, and analyzes the right operand.
+    fn check_overload(&mut self, op: BinaryOp, left_type: &Type, right: &mut ASTNode, span: Span) -> Result<(), String> {
+        let function = op.overload().and_then(|name| Some((name, self.scopes[0].get(name)?.clone())));
+        let (name, params, returns, declared) = match function {
+            Some((name, SymbolInfo { kind: SymbolKind::Function { params }, ty, span: declared, .. }))
+                if params.len() == 2 && params[0] == *left_type =>
+            {
+                (name, params, ty, declared)
+            }
+            _ => {
+                let hint = match op.overload() {
+                    Some(name) => format!("; define `fun {}(a: {}, b: ...)` to give it one", name, left_type.name()),
+                    None => String::new(),
+                };
+                return Err(format!(
+                    "line {}: `{}` is not defined for {}{}",
+                    span.line,
+                    op.symbol(),
+                    left_type.name(),
+                    hint
+                ));
+            }
+        };
+        if name == "eq" && returns != Type::Int {
+            return Err(format!(
+                "line {}: `eq` must return int to define `{}` for {}",
+                declared.line,
+                op.symbol(),
+                left_type.name()
+            ));
+        }
+        if returns == Type::Unit {
+            return Err(format!("Function {} has no return value", name));
+        }
+        self.infer_none(right, &params[1]);
+        self.analyze_value(right)?;
+        let found = self.type_of(right)?;
+        if found != params[1] {
+            return Err(TypeMismatch {
+                expected: params[1].clone(),
+                found,
+                found_at: expression_span(right).unwrap_or(span),
+                found_what: "right operand",
+                context: Some(declared),
+                reason: format!("parameter 2 of `{}`, which defines `{}` for {}", name, op.symbol(), left_type.name()),
+            }
+            .to_string());
         }
-        let name = ident_token.value.clone();
-        self.eat(TokenType::Identifier)?;
+        Ok(())
+    }
 
-        // Handle optional parentheses for parameters (even if empty)
-        if let Some(token) = self.current_token() {
-            if token.type_ == TokenType::LParen {
-                self.eat(TokenType::LParen)?;
-                self.eat(TokenType::RParen)?;
+    /// Replaces a `none` where a value of type `expected` is required with an
+    /// `ASTNode::None` of that type, unless a value named `none` hides it.
+    fn infer_none(&self, node: &mut ASTNode, expected: &Type) {
+        match node {
+            ASTNode::Identifier { name, span, .. } if name == "none" && expected.inner().is_some() && self.lookup(name).is_none() => {
+                *node = ASTNode::None { ty: expected.clone(), span: *span };
             }
+            _ => {}
         }
+    }
 
-        self.eat(TokenType::LBrace)?;
-        let mut body = Vec::new();
+    /// Analyzes a node used as a value, rejecting calls to functions without a return type.
+    fn analyze_value(&mut self, node: &mut ASTNode) -> Result<(), String> {
+        self.analyze_node(node)?;
 
-        while let Some(token) = self.current_token() {
-            if token.type_ == TokenType::RBrace {
-                break;
-            }
+        if let ASTNode::FunctionCall { name, .. } | ASTNode::MethodCall { name, method: Some(Method::Function), .. } = node
+            && self.lookup(name).is_some_and(|s| s.ty == Type::Unit)
+        {
+            return Err(format!("Function {} has no return value", name));
+        }
+        Ok(())
+    }
 
-            if token.type_ == TokenType::Semicolon {
-                self.eat(TokenType::Semicolon)?;
-                continue;
+    /// Type of an already analyzed expression.
+    pub fn type_of(&self, node: &ASTNode) -> Result<Type, String> {
+        match node {
+            ASTNode::Binary { op: BinaryOp::Add, operand_type: Some(Type::Str), .. } => Ok(Type::Str),
+            ASTNode::Binary { op, operand_type: Some(Type::Enum(_)), .. } if !op.is_comparison() => {
+                let name = op.overload().expect("checked by the analyzer");
+                Ok(self.scopes[0].get(name).map_or(Type::Int, |function| function.ty.clone()))
             }
-
-            let stmt = self.parse_statement()?;
-            body.push(stmt);
+            ASTNode::Number { .. } | ASTNode::Binary { .. } | ASTNode::Negate { .. } => Ok(Type::Int),
+            ASTNode::String { .. } | ASTNode::Interpolation { .. } => Ok(Type::Str),
+            ASTNode::Array { elements, .. } => elements
+                .first()
+                .map(|first| self.type_of(first))
+                .transpose()?
+                .and_then(|first| first.array_of())
+                .ok_or_else(|| "cannot infer the type of the array".to_string()),
+            ASTNode::Map { entries, .. } => {
+                let (key, value) = entries.first().ok_or_else(|| "cannot infer the type of the map".to_string())?;
+                Type::map_of(self.type_of(key)?, self.type_of(value)?)
+                    .ok_or_else(|| "cannot infer the type of the map".to_string())
+            }
+            ASTNode::Tuple { elements, .. } => {
+                let elements = elements.iter().map(|element| self.type_of(element)).collect::<Result<Vec<_>, _>>()?;
+                Type::tuple_of(&elements).ok_or_else(|| "cannot infer the type of the tuple".to_string())
+            }
+            ASTNode::Index { collection, .. } => {
+                let ty = self.type_of(collection)?;
+                ty.element()
+                    .or_else(|| ty.entry().map(|(_, value)| value))
+                    .ok_or_else(|| "only arrays and maps can be indexed".to_string())
+            }
+            ASTNode::MethodCall { method: Some(Method::Len), .. } => Ok(Type::Int),
+            ASTNode::Field { enum_type: Some(ty), .. } => Ok(ty.clone()),
+            ASTNode::MethodCall { name, method: None, .. } => Err(format!("method {} is not resolved", name)),
+            ASTNode::FunctionCall { name, .. } if name == "format" => Ok(Type::Str),
+            ASTNode::FunctionCall { name, args, .. } if name == "some" => {
+                let value = args.first().ok_or_else(|| "some expects 1 argument".to_string())?;
+                self.type_of(value)?.optional_of().ok_or_else(|| "optionals cannot be nested".to_string())
+            }
+            ASTNode::None { ty, .. } => Ok(ty.clone()),
+            ASTNode::Identifier { name, .. } | ASTNode::FunctionCall { name, .. } | ASTNode::MethodCall { name, .. } => self
+                .lookup(name)
+                .map(|s| s.ty.clone())
+                .ok_or_else(|| format!("Undefined identifier: {}", name)),
+            _ => Err(format!("{} is not an expression", node.kind())),
         }
+    }
+}
 
-        self.eat(TokenType::RBrace)?;
+/// Maps lines of the generated Rust back to lines of the Netflu source, so
+/// that rustc diagnostics can point at the code the user wrote.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    /// Indexed by 0-based generated line; `None` for lines with no counterpart.
+    lines: Vec<Option<u32>>,
+}
 
-        if let Some(token) = self.current_token() {
-            if token.type_ == TokenType::Semicolon {
-                self.eat(TokenType::Semicolon)?;
-            }
+impl SourceMap {
+    /// Source line for a 1-based generated line.
+    pub fn source_line(&self, generated_line: usize) -> Option<u32> {
+        self.lines.get(generated_line.checked_sub(1)?).copied().flatten()
+    }
+
+    fn record(&mut self, generated_line: usize, source_line: Option<u32>) {
+        if self.lines.len() <= generated_line {
+            self.lines.resize(generated_line + 1, None);
         }
+        self.lines[generated_line] = source_line;
+    }
+}
 
-        Ok(ASTNode::Fun { name, body })
+/// Line of the first span inside a statement; `print` carries none of its own.
+fn statement_line(node: &ASTNode) -> Option<u32> {
+    match node {
+        ASTNode::Let { span, .. }
+        | ASTNode::LetTuple { span, .. }
+        | ASTNode::Assign { span, .. }
+        | ASTNode::Function { span, .. }
+        | ASTNode::ExternFunction { span, .. }
+        | ASTNode::FunctionCall { span, .. }
+        | ASTNode::Identifier { span, .. }
+        | ASTNode::Back { span, .. }
+        | ASTNode::Binary { span, .. }
+        | ASTNode::Negate { span, .. }
+        | ASTNode::Interpolation { span, .. }
+        | ASTNode::Array { span, .. }
+        | ASTNode::Map { span, .. }
+        | ASTNode::Tuple { span, .. }
+        | ASTNode::None { span, .. }
+        | ASTNode::Index { span, .. }
+        | ASTNode::MethodCall { span, .. }
+        | ASTNode::Field { span, .. }
+        | ASTNode::If { span, .. }
+        | ASTNode::While { span, .. }
+        | ASTNode::For { span, .. }
+        | ASTNode::Enum { span, .. }
+        | ASTNode::Match { span, .. } => Some(span.line),
+        ASTNode::Print { value, .. } => statement_line(value),
+        _ => None,
     }
+}
 
-    fn parse_statement(&mut self) -> Result<ASTNode, String> {
-        let token = self.current_token().ok_or("Unexpected EOF")?;
+/// Rust for an analyzed program, whose int arithmetic handles overflow as `overflow` says.
+#[tracing::instrument(skip_all, fields(nodes = nodes.len()))]
+pub fn generate_code(nodes: &[ASTNode], overflow: Overflow) -> Result<String, String> {
+    generate_code_with_map(nodes, overflow).map(|(code, _)| code)
+}
 
-        match token.type_ {
-            TokenType::Let => self.parse_let(),
-            TokenType::Print => self.parse_print(),
-            TokenType::Method => self.parse_method(),
-            TokenType::Fun => self.parse_fun(),
-            TokenType::Back => self.parse_back(),
-            TokenType::Identifier => {
-                if self.pos + 1 < self.tokens.len() && self.tokens[self.pos + 1].type_ == TokenType::Assign {
-                    let name = token.value.clone();
-                    self.eat(TokenType::Identifier)?;
-                    self.eat(TokenType::Assign)?;
-                    let value = self.parse_expression()?;
-                    self.eat(TokenType::Semicolon)?;
+/// Like `generate_code`, also returning where each generated line came from.
+pub fn generate_code_with_map(nodes: &[ASTNode], overflow: Overflow) -> Result<(String, SourceMap), String> {
+    let mut code = String::new();
+    let mut map = SourceMap::default();
+    let mut has_main = false;
+    let mut start = 0;
 
-                    Ok(ASTNode::Assign {
-                        name,
-                        value: Box::new(value),
-                    })
-                } else {
-                    let func_name = token.value.clone();
-                    let func_call = self.parse_function_call(func_name)?;
-                    self.eat(TokenType::Semicolon)?;
-                    Ok(func_call)
-                }
+    for node in nodes {
+        if let ASTNode::Function { name, .. } = node {
+            if name == "main" {
+                has_main = true;
             }
-            _ => Err(format!("Unexpected token {:?}", token.type_)),
         }
+        let mut node_code = generate_node_code(node)?;
+        if let Some(operator) = generate_operator_impl(node)? {
+            node_code.push('\n');
+            node_code.push_str(&operator);
+        }
+        let line = statement_line(node);
+        for offset in 0..node_code.lines().count() {
+            map.record(start + offset, line);
+        }
+        if let ASTNode::Function { body, public, .. } = node {
+            let header = start + usize::from(*public);
+            record_body(&mut map, header + 1, body, line)?;
+        }
+        start += node_code.matches('\n').count() + 1;
+        code.push_str(&node_code);
+        code.push('\n');
     }
 
-    pub fn parse(&mut self) -> Result<Vec<ASTNode>, String> {
-        let mut statements = Vec::new();
-
-        while self.pos < self.tokens.len() {
-            let stmt = self.parse_statement()?;
-            statements.push(stmt);
-        }
+    if !has_main {
+        debug!("no `main` found, emitting empty main");
+        code.push_str("\nfn main() {\n}\n");
+    }
 
-        Ok(statements)
+    let mut used = Vec::new();
+    for node in nodes {
+        node.walk(&mut |node| used.extend(Arithmetic::of(node)));
+    }
+    for arithmetic in Arithmetic::ALL.into_iter().filter(|arithmetic| used.contains(arithmetic)) {
+        code.push('\n');
+        code.push_str(&arithmetic.definition(overflow));
     }
+
+    debug!(bytes = code.len(), "code generation finished");
+    Ok((code, map))
 }
 
-pub struct SemanticAnalyzer {
-    symbol_table: HashMap<String, ASTNode>,
+/// A function that generated code does int arithmetic through, emitted when a
+/// program uses it, so that a failure names its line instead of being a Rust panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Negate,
+    Divide,
 }
 
-impl SemanticAnalyzer {
-    pub fn new() -> Self {
-        SemanticAnalyzer {
-            symbol_table: HashMap::new(),
+impl Arithmetic {
+    /// In the order the definitions are emitted.
+    const ALL: [Arithmetic; 5] =
+        [Arithmetic::Add, Arithmetic::Subtract, Arithmetic::Multiply, Arithmetic::Negate, Arithmetic::Divide];
+
+    /// The function `node` is generated as a call to, if any. `+` is also
+    /// concatenation and an enum operator, and a negated literal stays a literal.
+    fn of(node: &ASTNode) -> Option<Arithmetic> {
+        match node {
+            ASTNode::Binary { op: BinaryOp::Add, operand_type: Some(Type::Int), .. } => Some(Arithmetic::Add),
+            ASTNode::Binary { op: BinaryOp::Subtract, .. } => Some(Arithmetic::Subtract),
+            ASTNode::Binary { op: BinaryOp::Multiply, .. } => Some(Arithmetic::Multiply),
+            ASTNode::Binary { op: BinaryOp::Divide, .. } => Some(Arithmetic::Divide),
+            ASTNode::Negate { operand, .. } if !matches!(**operand, ASTNode::Number { .. }) => Some(Arithmetic::Negate),
+            _ => None,
         }
     }
 
-    pub fn analyze(&mut self, nodes: &mut [ASTNode]) -> Result<(), String> {
-        for node in nodes {
-            self.analyze_node(node)?;
+    fn name(self) -> &'static str {
+        match self {
+            Arithmetic::Add => "__ntfp_add",
+            Arithmetic::Subtract => "__ntfp_subtract",
+            Arithmetic::Multiply => "__ntfp_multiply",
+            Arithmetic::Negate => "__ntfp_negate",
+            Arithmetic::Divide => "__ntfp_divide",
         }
-        Ok(())
     }
 
-    fn analyze_node(&mut self, node: &mut ASTNode) -> Result<(), String> {
-        match node {
-            ASTNode::Method { name, body, local_symbol_table, return_value } => {
-                for stmt in body {
-                    self.analyze_node(stmt)?;
+    /// The Rust function. Overflow stops the program unless `overflow` wraps
+    /// it; a division that fails always does, as it panics in Rust either way.
+    fn definition(self, overflow: Overflow) -> String {
+        let exit = |what: &str| {
+            format!("{{\n        eprintln!(\"line {{}}: {}\", line);\n        std::process::exit(101);\n    }}", what)
+        };
+        let (params, method, what) = match self {
+            Arithmetic::Add => ("a: i32, b: i32", "add(b)", "addition overflow"),
+            Arithmetic::Subtract => ("a: i32, b: i32", "sub(b)", "subtraction overflow"),
+            Arithmetic::Multiply => ("a: i32, b: i32", "mul(b)", "multiplication overflow"),
+            Arithmetic::Negate => ("a: i32", "neg()", "negation overflow"),
+            Arithmetic::Divide => {
+                return format!(
+                    "fn {}(dividend: i32, divisor: i32, line: u32) -> i32 {{\n    if divisor == 0 {}\n    dividend.checked_div(divisor).unwrap_or_else(|| {})\n}}\n",
+                    self.name(),
+                    exit("division by zero"),
+                    exit("division overflow")
+                )
+            }
+        };
+        match overflow {
+            Overflow::Checked => format!(
+                "fn {}({}, line: u32) -> i32 {{\n    a.checked_{}.unwrap_or_else(|| {})\n}}\n",
+                self.name(),
+                params,
+                method,
+                exit(what)
+            ),
+            Overflow::Wrapping => format!("fn {}({}, _line: u32) -> i32 {{\n    a.wrapping_{}\n}}\n", self.name(), params, method),
+        }
+    }
+}
 
-                    match stmt {
-                        ASTNode::Let { name, value } => {
-                            if let ASTNode::Number { value: num_val } = &**value {
-                                let num = num_val.parse().map_err(|_| format!("Invalid number: {}", num_val))?;
-                                local_symbol_table.insert(name.clone(), num);
-                            }
-                        }
-                        ASTNode::Back { value } => {
-                            *return_value = Some(value.clone());
-                        }
-                        _ => {}
-                    }
-                }
+/// Comparisons (`i32::from(...)`) and int arithmetic are emitted as calls,
+/// which need no parentheses around them.
+fn generates_call(node: &ASTNode) -> bool {
+    matches!(node, ASTNode::Binary { op, .. } if op.is_comparison()) || Arithmetic::of(node).is_some()
+}
 
-                self.symbol_table.insert(name.clone(), node.clone());
-                Ok(())
+/// The trait impl that makes `+` or `==` on an enum call the top-level `add` or
+/// `eq` defining it, which the analyzer resolved the operator to.
+fn generate_operator_impl(node: &ASTNode) -> Result<Option<String>, String> {
+    let ASTNode::Function { name, params, return_type, .. } = node else {
+        return Ok(None);
+    };
+    let [left, right] = params.as_slice() else {
+        return Ok(None);
+    };
+    let left = Type::from_name(left.type_name())?;
+    if !matches!(left, Type::Enum(_)) {
+        return Ok(None);
+    }
+    let right = Type::from_name(right.type_name())?;
+    let returns = return_type.as_deref().map(Type::from_name).transpose()?;
+    Ok(match (name.as_str(), returns) {
+        ("add", Some(returns)) => Some(format!(
+            "impl std::ops::Add<{right}> for {left} {{\n    type Output = {returns};\n    fn add(self, other: {right}) -> {returns} {{\n        add(self, other)\n    }}\n}}",
+            left = left.rust(),
+            right = right.rust(),
+            returns = returns.rust()
+        )),
+        ("eq", Some(Type::Int)) => Some(format!(
+            "impl PartialEq<{right}> for {left} {{\n    fn eq(&self, other: &{right}) -> bool {{\n        eq(*self, {other}) != 0\n    }}\n}}",
+            left = left.rust(),
+            right = right.rust(),
+            other = if right.is_copy() { "*other" } else { "other.clone()" }
+        )),
+        _ => None,
+    })
+}
+
+/// Records the lines of `body` as emitted by `generate_body_code` from generated
+/// line `at` on; returns the line after the block.
+fn record_body(map: &mut SourceMap, mut at: usize, body: &[ASTNode], fallback: Option<u32>) -> Result<usize, String> {
+    for stmt in body {
+        at = match stmt {
+            ASTNode::If { .. } => record_if(map, at, stmt)?,
+            ASTNode::While { body: inner, span, .. } | ASTNode::For { body: inner, span, .. } => {
+                map.record(at, Some(span.line));
+                let end = record_body(map, at + 1, inner, Some(span.line))?;
+                map.record(end, Some(span.line));
+                end + 1
             }
-            ASTNode::Fun { name: _, body } => {
-                for stmt in body {
-                    self.analyze_node(stmt)?;
+            // Each arm opens and closes a line of its own, like a block
+            ASTNode::Match { arms, else_body, span, .. } => {
+                map.record(at, Some(span.line));
+                let mut next = at + 1;
+                let bodies = arms.iter().map(|arm| (arm.span.line, &arm.body)).chain(else_body.iter().map(|body| (span.line, body)));
+                for (line, inner) in bodies {
+                    map.record(next, Some(line));
+                    let end = record_body(map, next + 1, inner, Some(line))?;
+                    map.record(end, Some(line));
+                    next = end + 1;
                 }
-                Ok(())
+                map.record(next, Some(span.line));
+                next + 1
             }
-            ASTNode::FunctionCall { name, args } => {
-                if !self.symbol_table.contains_key(name) {
-                    return Err(format!("Undefined function: {}", name));
+            _ => {
+                let lines = generate_statement_code(stmt)?.lines().count().max(1);
+                for offset in 0..lines {
+                    map.record(at + offset, statement_line(stmt).or(fallback));
                 }
+                at + lines
+            }
+        };
+    }
+    Ok(at)
+}
 
-                if let ASTNode::Method { return_value, .. } = &self.symbol_table[name] {
-                    if return_value.is_none() {
-                        return Err(format!("Function {} has no return value", name));
-                    }
-                } else {
-                    return Err(format!("{} is not a function", name));
-                }
+/// Like `record_body` for one `if`, whose branches map line by line. In an
+/// `else if` chain, the `} else if` line belongs to the inner condition.
+fn record_if(map: &mut SourceMap, at: usize, node: &ASTNode) -> Result<usize, String> {
+    let ASTNode::If { then_body, else_body, span, .. } = node else {
+        unreachable!("only called for `if`");
+    };
+    let line = Some(span.line);
+    map.record(at, line);
+    let end = record_body(map, at + 1, then_body, line)?;
+    map.record(end, line);
+    match else_body.as_slice() {
+        [] => Ok(end + 1),
+        [nested @ ASTNode::If { .. }] => record_if(map, end, nested),
+        _ => {
+            let end = record_body(map, end + 1, else_body, line)?;
+            map.record(end, line);
+            Ok(end + 1)
+        }
+    }
+}
 
-                for arg in args {
-                    self.analyze_node(arg)?;
-                }
+/// The statements of a block, one per line and indented one level.
+fn generate_body_code(body: &[ASTNode]) -> Result<String, String> {
+    let mut code = String::new();
+    for stmt in body {
+        let stmt_code = generate_statement_code(stmt)?;
+        if stmt_code.is_empty() {
+            code.push('\n');
+        }
+        code.push_str(&indented(&stmt_code));
+    }
+    Ok(code)
+}
 
-                Ok(())
-            }
-            ASTNode::Assign { name, value } => {
-                self.analyze_node(value)?;
+/// Each line of `code` indented one level and ended with `\n`; empty lines stay empty.
+fn indented(code: &str) -> String {
+    let mut out = String::new();
+    for line in code.lines() {
+        if !line.is_empty() {
+            out.push_str("    ");
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
 
-                if let ASTNode::Identifier { name: ident_name } = &**value {
-                    if !self.symbol_table.contains_key(ident_name) {
-                        return Err(format!("Undefined variable: {}", ident_name));
-                    }
+/// A comparison as a Rust `bool`. Its operands never need parentheses: arithmetic
+/// binds tighter, and nested comparisons are wrapped in `i32::from`.
+fn generate_comparison(node: &ASTNode) -> Result<String, String> {
+    let ASTNode::Binary { op, left, right, operand_type, .. } = node else {
+        unreachable!("only called for comparisons");
+    };
+    let is_literal = |node: &ASTNode| matches!(node, ASTNode::String { .. });
+    let (left, right) = match operand_type {
+        // The enum's `PartialEq` takes the type of `eq`'s second parameter, which a literal is not.
+        Some(Type::Enum(_)) if is_literal(right) => (borrowed_code(left)?, generate_node_code(right)?),
+        // `String` is ordered against `String` only, and a literal is a `&str`.
+        Some(Type::Str) if !matches!(op, BinaryOp::Equal | BinaryOp::NotEqual) && is_literal(left) != is_literal(right) => {
+            let as_str = |node: &ASTNode| -> Result<String, String> {
+                if is_literal(node) {
+                    borrowed_code(node)
+                } else {
+                    Ok(format!("{}.as_str()", postfix_operand_code(node)?))
                 }
+            };
+            (as_str(left)?, as_str(right)?)
+        }
+        _ => (borrowed_code(left)?, borrowed_code(right)?),
+    };
+    Ok(format!("{} {} {}", left, op.symbol(), right))
+}
 
-                self.symbol_table.insert(name.clone(), ASTNode::Identifier { name: name.clone() });
-                Ok(())
-            }
-            ASTNode::Let { name, value } => {
-                self.analyze_node(value)?;
-                self.symbol_table.insert(name.clone(), ASTNode::Identifier { name: name.clone() });
-                Ok(())
-            }
-            ASTNode::Print { value } => {
-                self.analyze_node(value)?;
-                Ok(())
-            }
-            ASTNode::Back { .. } => Ok(()),
-            ASTNode::Identifier { name } => {
-                if !self.symbol_table.contains_key(name) {
-                    return Err(format!("Undefined identifier: {}", name));
-                }
-                Ok(())
-            }
-            ASTNode::Number { .. } => Ok(()),
-            ASTNode::String { .. } => Ok(()),
+/// The `format!`/`print!` arguments for an interpolated literal: the literal with
+/// every `{name}` replaced by `{}`, then the names. `{{` and `}}` mean the same in Rust.
+fn interpolation_arguments(literal: &str, args: &[ASTNode]) -> Result<String, String> {
+    let mut code = String::from("\"");
+    for piece in string_pieces(literal)? {
+        match piece {
+            StringPiece::Text(text) => code.push_str(text),
+            StringPiece::Name(..) => code.push_str("{}"),
         }
     }
+    code.push('"');
+    let mut code = escape_line_breaks(&code);
+    for arg in args {
+        code.push_str(", ");
+        code.push_str(&borrowed_code(arg)?);
+    }
+    Ok(code)
 }
 
-pub fn generate_code(nodes: &[ASTNode]) -> Result<String, String> {
-    let mut code = String::new();
-    let mut has_main = false;
+/// The operands of a chain of str `+`, in order, so that it becomes one `format!`.
+fn concatenated<'a>(node: &'a ASTNode, parts: &mut Vec<&'a ASTNode>) {
+    match node {
+        ASTNode::Binary { op: BinaryOp::Add, operand_type: Some(Type::Str), left, right, .. } => {
+            concatenated(left, parts);
+            concatenated(right, parts);
+        }
+        _ => parts.push(node),
+    }
+}
 
-    for node in nodes {
-        if let ASTNode::Fun { name, .. } = node {
-            if name == "main" {
-                has_main = true;
+/// The condition of an `if` or `while`: a comparison directly, any other int
+/// is true when it is not 0.
+fn generate_condition(node: &ASTNode) -> Result<String, String> {
+    match node {
+        ASTNode::Binary { op, .. } if op.is_comparison() => generate_comparison(node),
+        _ => Ok(format!("{} != 0", generate_node_code(node)?)),
+    }
+}
+
+fn generate_statement_code(node: &ASTNode) -> Result<String, String> {
+    match node {
+        ASTNode::FunctionCall { .. } | ASTNode::MethodCall { .. } => Ok(format!("{};", generate_node_code(node)?)),
+        _ => generate_node_code(node),
+    }
+}
+
+/// Code for `node` as the operand before `[...]` or `.`, which bind tighter
+/// than anything but a name, a literal, a call or another index.
+fn postfix_operand_code(node: &ASTNode) -> Result<String, String> {
+    let code = borrowed_code(node)?;
+    Ok(match node {
+        ASTNode::Identifier { .. } | ASTNode::String { .. } | ASTNode::FunctionCall { .. } | ASTNode::Index { .. } => code,
+        _ => format!("({})", code),
+    })
+}
+
+/// Rust keywords, including the ones reserved by newer editions, so that the
+/// output stays valid whichever edition rustc defaults to.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "Self", "static", "struct",
+    "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while",
+    "yield",
+];
+
+/// Names that can't be written as Rust identifiers even in raw form.
+const UNRAWABLE: &[&str] = &["crate", "self", "super", "Self", "_"];
+
+/// Whether `name` matches the identifier token.
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Spells a Netflu identifier in the generated Rust. Rust keywords become raw
+/// identifiers (`match` -> `r#match`); the few that can't be raw get the
+/// `__ntfp_` prefix, which is reserved for generated code.
+pub fn rust_name(name: &str) -> Cow<'_, str> {
+    if UNRAWABLE.contains(&name) {
+        Cow::Owned(format!("__ntfp_{}", name))
+    } else if RUST_KEYWORDS.contains(&name) {
+        Cow::Owned(format!("r#{}", name))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Declares the C symbol under a private name and wraps it in a safe Rust
+/// function with the Netflu signature: `int` is passed as `c_int`, `str` as a
+/// NUL-terminated copy.
+fn generate_c_binding(name: &str, params: &[Param], return_type: &Option<String>) -> Result<String, String> {
+    let mut c_params = Vec::new();
+    let mut wrapper_params = Vec::new();
+    let mut conversions = String::new();
+    let mut args = Vec::new();
+    for param in params {
+        let param_name = rust_name(&param.name);
+        match Type::from_name(param.type_name())? {
+            Type::Int => {
+                c_params.push(format!("{}: std::os::raw::c_int", param_name));
+                wrapper_params.push(format!("{}: i32", param_name));
+                args.push(param_name.to_string());
+            }
+            Type::Str => {
+                c_params.push(format!("{}: *const std::os::raw::c_char", param_name));
+                wrapper_params.push(format!("{}: String", param_name));
+                conversions.push_str(&format!(
+                    "    let {0} = std::ffi::CString::new({0}).expect(\"string passed to extern c function {1} contains a NUL byte\");\n",
+                    param_name, name
+                ));
+                args.push(format!("{}.as_ptr()", param_name));
             }
+            ty => return Err(format!("Parameter {} of {} cannot have type {}", param.name, name, ty.name())),
         }
-        code.push_str(&generate_node_code(node)?);
-        code.push('\n');
     }
+    let ret = match return_type {
+        Some(ty) => format!(" -> {}", Type::from_name(ty)?.rust()),
+        None => String::new(),
+    };
 
-    if !has_main {
-        code.push_str("\nfn main() {\n}\n");
+    Ok(format!(
+        "extern \"C\" {{\n    #[link_name = \"{name}\"]\n    fn __ntfp_c_{name}({c_params}){ret};\n}}\nfn {wrapper}({wrapper_params}){ret} {{\n{conversions}    unsafe {{ __ntfp_c_{name}({args}) }}\n}}",
+        name = name,
+        wrapper = rust_name(name),
+        c_params = c_params.join(", "),
+        wrapper_params = wrapper_params.join(", "),
+        ret = ret,
+        conversions = conversions,
+        args = args.join(", "),
+    ))
+}
+
+/// Code for `node` where Rust only borrows the value: the operands of comparisons
+/// and the arguments of `format!`. Names and indexes are not cloned, and str
+/// literals stay `&str`; anything else is a new value, as `generate_node_code` makes it.
+fn borrowed_code(node: &ASTNode) -> Result<String, String> {
+    match node {
+        ASTNode::Identifier { name, .. } => Ok(rust_name(name).into_owned()),
+        ASTNode::String { value } => Ok(escape_line_breaks(value)),
+        ASTNode::Index { .. } => index_code(node),
+        _ => generate_node_code(node),
     }
+}
 
-    Ok(code)
+/// The element of an array or the value of a map that an `Index` names, borrowed.
+fn index_code(node: &ASTNode) -> Result<String, String> {
+    let ASTNode::Index { collection, index, collection_type, .. } = node else {
+        unreachable!("only called for indexes");
+    };
+    let collection_code = postfix_operand_code(collection)?;
+    if collection_type.as_ref().and_then(Type::entry).is_some() {
+        // Maps with `String` keys are indexed by `&str`, which a literal already is.
+        if let ASTNode::String { value } = &**index {
+            return Ok(format!("{}[{}]", collection_code, escape_line_breaks(value)));
+        }
+        let index_code = borrowed_code(index)?;
+        let index_code = match **index {
+            ASTNode::Binary { .. } if !generates_call(index) => format!("({})", index_code),
+            _ => index_code,
+        };
+        return Ok(format!("{}[&{}]", collection_code, index_code));
+    }
+    let index_code = generate_node_code(index)?;
+    // A negative index becomes a huge usize, which is out of bounds like in the interpreter.
+    let index_code = match **index {
+        ASTNode::Binary { .. } if !generates_call(index) => format!("({})", index_code),
+        _ => index_code,
+    };
+    Ok(format!("{}[{} as usize]", collection_code, index_code))
+}
+
+/// Escaped, a line break in a literal (`\r\n` included, as rustc reads it)
+/// survives the indentation added to the generated lines.
+fn escape_line_breaks(literal: &str) -> String {
+    literal.replace("\r\n", "\n").replace('\n', "\\n")
 }
 
 fn generate_node_code(node: &ASTNode) -> Result<String, String> {
     match node {
-        ASTNode::Let { name, value } => {
-            Ok(format!("let {} = {};", name, generate_node_code(value)?))
+        ASTNode::Let { name, value, mutable, .. } => {
+            let binding = if *mutable { "let mut" } else { "let" };
+            Ok(format!("{} {} = {};", binding, rust_name(name), generate_node_code(value)?))
         }
-        ASTNode::Print { value } => {
-            let expr = generate_node_code(value)?;
-            match **value {
-                ASTNode::String { .. } => Ok(format!("print!({});", expr)),
-                _ => Ok(format!("print!(\"{{}}\", {});", expr)),
-            }
+        ASTNode::LetTuple { bindings, value, .. } => {
+            let names: Vec<_> = bindings
+                .iter()
+                .map(|b| format!("{}{}", if b.mutable { "mut " } else { "" }, rust_name(&b.name)))
+                .collect();
+            Ok(format!("let ({}) = {};", names.join(", "), generate_node_code(value)?))
         }
-        ASTNode::Method { name, body, .. } => {
-            let mut method_code = format!("fn {}() -> i32 {{\n", name);
-
-            for stmt in body {
-                let stmt_code = generate_node_code(stmt)?;
-                method_code.push_str(&format!("    {}\n", stmt_code));
-            }
-
-            let has_return = body.iter().any(|n| matches!(n, ASTNode::Back { .. }));
-            if !has_return {
-                method_code.push_str("    return 0;\n");
+        ASTNode::Print { value, value_type, newline } => {
+            let expr = borrowed_code(value)?;
+            let print = if *newline { "println" } else { "print" };
+            match (&**value, value_type) {
+                (ASTNode::FunctionCall { name, args, .. }, _) if name == "format" => {
+                    let (value, spec) = format_call_args(args)?;
+                    Ok(format!("{}!(\"{{:{}}}\", {});", print, spec.as_str(), borrowed_code(value)?))
+                }
+                // A literal without braces is already a valid format string.
+                (ASTNode::String { value: literal }, _) if !literal.contains(['{', '}']) => {
+                    Ok(format!("{}!({});", print, expr))
+                }
+                (ASTNode::Interpolation { value: literal, args, .. }, _) => {
+                    Ok(format!("{}!({});", print, interpolation_arguments(literal, args)?))
+                }
+                (_, Some(ty)) if !ty.is_displayable() => Ok(format!("{}!(\"{{:?}}\", {});", print, expr)),
+                _ => Ok(format!("{}!(\"{{}}\", {});", print, expr)),
             }
-
-            method_code.push('}');
-            Ok(method_code)
         }
-        ASTNode::Fun { name, body } => {
-            let mut fun_code = format!("fn {}() {{\n", name);
+        ASTNode::Function { name, params, return_type, body, public, .. } => {
+            let params_code: Vec<String> = params
+                .iter()
+                .map(|p| Ok(format!("{}: {}", rust_name(&p.name), Type::from_name(p.type_name())?.rust())))
+                .collect::<Result<_, String>>()?;
+            let rust_name = rust_name(name);
+            let mut fun_code = match (*public, &rust_name) {
+                (false, _) => String::new(),
+                // Raw identifiers export the name without `r#`; prefixed ones need it spelled out.
+                (true, Cow::Owned(mangled)) if !mangled.starts_with("r#") => {
+                    format!("#[export_name = \"{}\"]\npub extern \"C\" ", name)
+                }
+                (true, _) => "#[no_mangle]\npub extern \"C\" ".to_string(),
+            };
+            fun_code.push_str(&match return_type {
+                Some(ty) => format!(
+                    "fn {}({}) -> {} {{\n",
+                    rust_name,
+                    params_code.join(", "),
+                    Type::from_name(ty)?.rust()
+                ),
+                None => format!("fn {}({}) {{\n", rust_name, params_code.join(", ")),
+            });
 
-            for stmt in body {
-                let stmt_code = generate_node_code(stmt)?;
-                fun_code.push_str(&format!("    {}\n", stmt_code));
+            fun_code.push_str(&generate_body_code(body)?);
+            if return_type.is_some() && !always_backs(body) {
+                fun_code.push_str("    return 0;\n");
             }
 
             fun_code.push('}');
             Ok(fun_code)
         }
-        ASTNode::Back { value } => {
-            Ok(format!("return {};", value))
+        // Only ever the last statement of a function body, so a Rust tail expression too.
+        // Returning ends the function, so a name is moved out rather than cloned.
+        ASTNode::Back { value: Some(value), implicit: true, .. } => match &**value {
+            ASTNode::Identifier { .. } => borrowed_code(value),
+            _ => generate_node_code(value),
+        },
+        ASTNode::Back { value: Some(value), .. } => match &**value {
+            ASTNode::Identifier { .. } => Ok(format!("return {};", borrowed_code(value)?)),
+            _ => Ok(format!("return {};", generate_node_code(value)?)),
+        },
+        ASTNode::Back { value: None, .. } => Ok("return;".to_string()),
+        ASTNode::ExternFunction { abi, name, params, return_type, .. } if abi == "c" => {
+            generate_c_binding(name, params, return_type)
         }
-        ASTNode::FunctionCall { name, args } => {
+        ASTNode::ExternFunction { name, .. } if UNRAWABLE.contains(&name.as_str()) => {
+            Err(format!("extern rust function {} cannot be implemented in Rust, where `{}` is a reserved word", name, name))
+        }
+        // Linked in as a module by the build; see `[rust] sources` in ntfp.toml.
+        ASTNode::ExternFunction { .. } => Ok(String::new()),
+        ASTNode::FunctionCall { name, args, .. } if name == "format" => {
+            let (value, spec) = format_call_args(args)?;
+            Ok(format!("format!(\"{{:{}}}\", {})", spec.as_str(), borrowed_code(value)?))
+        }
+        ASTNode::FunctionCall { name, args, .. } if name == "some" => match args.as_slice() {
+            [value] => Ok(format!("Some({})", generate_node_code(value)?)),
+            _ => Err(format!("some expects 1 argument, got {}", args.len())),
+        },
+        // Rust infers the type of `None` from where it is used, as the analyzer did.
+        ASTNode::None { .. } => Ok("None".to_string()),
+        ASTNode::FunctionCall { name, args, .. } => {
             let args_code: Vec<String> = args.iter()
                 .map(|arg| generate_node_code(arg))
                 .collect::<Result<_, _>>()?;
 
-            Ok(format!("{}({})", name, args_code.join(", ")))
+            Ok(format!("{}({})", rust_name(name), args_code.join(", ")))
         }
-        ASTNode::Identifier { name } => {
-            Ok(name.clone())
+        ASTNode::Identifier { name, ty: Some(ty), .. } if !ty.is_copy() => Ok(format!("{}.clone()", rust_name(name))),
+        ASTNode::Identifier { name, .. } => {
+            Ok(rust_name(name).into_owned())
         }
         ASTNode::Number { value } => {
             Ok(value.clone())
         }
-        ASTNode::String { value } => {
-            Ok(value.clone())
+        ASTNode::String { value } => Ok(format!("String::from({})", escape_line_breaks(value))),
+        ASTNode::Interpolation { value, args, .. } => Ok(format!("format!({})", interpolation_arguments(value, args)?)),
+        ASTNode::Array { elements, .. } => {
+            let elements = elements.iter().map(generate_node_code).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("vec![{}]", elements.join(", ")))
+        }
+        // A missing key panics with "no entry found for key".
+        ASTNode::Map { entries, .. } => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| Ok(format!("({}, {})", generate_node_code(key)?, generate_node_code(value)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("std::collections::HashMap::from([{}])", entries.join(", ")))
         }
-        ASTNode::Assign { name, value } => {
-            Ok(format!("{} = {};", name, generate_node_code(value)?))
+        ASTNode::Tuple { elements, .. } => {
+            let elements = elements.iter().map(generate_node_code).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("({})", elements.join(", ")))
         }
+        // Indexing names a place inside the collection; values that are not `Copy` are cloned out of it.
+        ASTNode::Index { collection_type, .. } => {
+            let element = collection_type.as_ref().and_then(|ty| ty.element().or_else(|| ty.entry().map(|(_, value)| value)));
+            let code = index_code(node)?;
+            Ok(if element.is_some_and(|ty| !ty.is_copy()) { format!("{}.clone()", code) } else { code })
+        }
+        // `usize` lengths convert like indexes, wrapping past `i32::MAX`.
+        ASTNode::MethodCall { receiver, method: Some(Method::Len), .. } => {
+            Ok(format!("({}.len() as i32)", postfix_operand_code(receiver)?))
+        }
+        ASTNode::MethodCall { receiver, name, args, method: Some(Method::Function), .. } => {
+            let args_code = std::iter::once(&**receiver)
+                .chain(args)
+                .map(generate_node_code)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("{}({})", rust_name(name), args_code.join(", ")))
+        }
+        ASTNode::MethodCall { name, method: None, .. } => Err(format!("method {} was not resolved", name)),
+        ASTNode::Field { name, enum_type: Some(ty), .. } => Ok(format!("{}::{}", ty.rust(), rust_name(name))),
+        ASTNode::Field { name, enum_type: None, .. } => Err(format!("field `{}` does not exist", name)),
+        // `Copy`, as Netflu values can be used any number of times; `Debug` prints the variant.
+        ASTNode::Enum { name, variants, .. } => {
+            let variants: String = variants.iter().map(|variant| format!("    {},\n", rust_name(variant))).collect();
+            Ok(format!("#[derive(Debug, Clone, Copy)]\nenum {} {{\n{}}}", rust_name(name), variants))
+        }
+        ASTNode::Match { value, arms, else_body, value_type, .. } => {
+            let ty = value_type.as_ref().ok_or("`match` was not analyzed")?;
+            let mut code = format!("match {} {{\n", generate_node_code(value)?);
+            for arm in arms {
+                let pattern = match (&arm.binding, ty.inner()) {
+                    (Some(b), Some(_)) => format!("Some({}{})", if b.mutable { "mut " } else { "" }, rust_name(&b.name)),
+                    (None, Some(_)) => "None".to_string(),
+                    _ => format!("{}::{}", ty.rust(), rust_name(&arm.variant)),
+                };
+                code.push_str(&indented(&format!("{} => {{\n{}}}", pattern, generate_body_code(&arm.body)?)));
+            }
+            if let Some(else_body) = else_body {
+                code.push_str(&indented(&format!("_ => {{\n{}}}", generate_body_code(else_body)?)));
+            }
+            code.push('}');
+            Ok(code)
+        }
+        ASTNode::Assign { name, value, .. } => {
+            Ok(format!("{} = {};", rust_name(name), generate_node_code(value)?))
+        }
+        ASTNode::If { condition, then_body, else_body, .. } => {
+            let mut code = format!("if {} {{\n{}}}", generate_condition(condition)?, generate_body_code(then_body)?);
+            match else_body.as_slice() {
+                [] => {}
+                [nested @ ASTNode::If { .. }] => code.push_str(&format!(" else {}", generate_node_code(nested)?)),
+                _ => code.push_str(&format!(" else {{\n{}}}", generate_body_code(else_body)?)),
+            }
+            Ok(code)
+        }
+        ASTNode::While { condition, body, .. } => {
+            Ok(format!("while {} {{\n{}}}", generate_condition(condition)?, generate_body_code(body)?))
+        }
+        ASTNode::For { var, start, end, body, .. } => Ok(format!(
+            "for {} in {}..{} {{\n{}}}",
+            rust_name(var),
+            generate_node_code(start)?,
+            generate_node_code(end)?,
+            generate_body_code(body)?
+        )),
+        ASTNode::Binary { op, .. } if op.is_comparison() => Ok(format!("i32::from({})", generate_comparison(node)?)),
+        ASTNode::Binary { op: BinaryOp::Add, operand_type: Some(Type::Str), .. } => {
+            let mut parts = Vec::new();
+            concatenated(node, &mut parts);
+            let parts = parts.into_iter().map(borrowed_code).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("format!(\"{}\", {})", "{}".repeat(parts.len()), parts.join(", ")))
+        }
+        ASTNode::Binary { op, left, right, span, .. } => match Arithmetic::of(node) {
+            // Int arithmetic that fails stops the program with a Netflu message instead of a Rust panic.
+            Some(arithmetic) => Ok(format!(
+                "{}({}, {}, {})",
+                arithmetic.name(),
+                generate_node_code(left)?,
+                generate_node_code(right)?,
+                span.line
+            )),
+            // `+` on enums, through the trait impl of `generate_operator_impl`.
+            None => {
+                let operand = |node: &ASTNode, right: bool| -> Result<String, String> {
+                    let code = generate_node_code(node)?;
+                    Ok(if needs_parens(node, *op, right) && !generates_call(node) { format!("({})", code) } else { code })
+                };
+                Ok(format!("{} {} {}", operand(left, false)?, op.symbol(), operand(right, true)?))
+            }
+        },
+        ASTNode::Negate { operand, span } => match Arithmetic::of(node) {
+            Some(arithmetic) => Ok(format!("{}({}, {})", arithmetic.name(), generate_node_code(operand)?, span.line)),
+            // `-2147483648` is a valid literal even though `2147483648` is not.
+            None => Ok(format!("-{}", generate_node_code(operand)?)),
+        },
     }
 }
-
-fn main() -> Result<(), String> {
-    let code = "fun main() { let x = \"5\"; print(x); }";
-
-    println!("Original code:\n{}", code);
-
-    let tokens = lexer(code)?;
-    println!("\nTokens:");
-    for token in &tokens {
-        println!("{:?}", token);
-    }
-
-    let mut parser = Parser::new(tokens);
-    let mut ast = parser.parse()?;
-    println!("\nAST:");
-    for node in &ast {
-        println!("{:?}", node);
-    }
-
-    let mut analyzer = SemanticAnalyzer::new();
-    analyzer.analyze(&mut ast)?;
-
-    let generated_code = generate_code(&ast)?;
-    println!("\nGenerated Code:");
-    println!("{}", generated_code);
-
-    std::fs::write("generated.rs", &generated_code)
-        .map_err(|e| format!("Failed to write generated code: {}", e))?;
-    println!("\nGenerated code written to 'generated.rs'");
-
-    let compile_output = std::process::Command::new("rustc")
-        .arg("generated.rs")
-        .arg("-o")
-        .arg(".\\generated_bin.exe")
-        .output()
-        .map_err(|e| format!("Failed to run rustc: {}", e))?;
-
-    if !compile_output.status.success() {
-        let err_msg = String::from_utf8_lossy(&compile_output.stderr);
-        return Err(format!("Compilation failed: {}", err_msg));
-    }
-    println!("\nCompilation successful. Output binary: 'generated_bin.exe'");
-
-    let run_output = std::process::Command::new(".\\generated_bin.exe")
-        .output()
-        .map_err(|e| format!("Failed to run binary: {}", e))?;
-
-    if !run_output.status.success() {
-        let err_msg = String::from_utf8_lossy(&run_output.stderr);
-        return Err(format!("Execution failed: {}", err_msg));
-    }
-
-    let output = String::from_utf8_lossy(&run_output.stdout);
-    println!("\nProgram output:\n{}", output);
-
-    Ok(())
-}
\ No newline at end of file
@@ -0,0 +1,68 @@
+use crate::compile::{Edition, Lint};
+use crate::manifest;
+use anyhow::Result;
+use clap_complete::Shell;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+/// `ntfp complete <kind>`能补全的参数值
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum CompletionKind {
+    /// `--allow` / `--warn` / `--deny`的lint名
+    Lints,
+    /// `--edition`
+    Editions,
+    /// `--example`，当前项目examples/中的示例
+    Examples,
+}
+
+/// bash脚本中按前一个参数决定补全内容，值由`ntfp complete`动态给出，
+/// 其余情况交给clap生成的`_ntfp`
+const BASH_DYNAMIC: &str = r#"
+_ntfp_values() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" kind=""
+    case "${prev}" in
+        --allow|--warn|--deny) kind=lints ;;
+        --edition) kind=editions ;;
+        --example) kind=examples ;;
+    esac
+    if [[ -n "${kind}" ]]; then
+        COMPREPLY=($(compgen -W "$("${COMP_WORDS[0]}" complete "${kind}" 2>/dev/null)" -- "${cur}"))
+        return 0
+    fi
+    _ntfp "$@"
+}
+
+complete -F _ntfp_values -o bashdefault -o default ntfp
+"#;
+
+/// 输出`shell`的补全脚本
+pub fn print_script(shell: Shell, command: &mut clap::Command) -> Result<()> {
+    let mut stdout = io::stdout();
+    clap_complete::generate(shell, command, "ntfp", &mut stdout);
+    if shell == Shell::Bash {
+        stdout.write_all(BASH_DYNAMIC.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 补全脚本调用的隐藏命令，每行输出一个候选值
+pub fn complete(kind: CompletionKind) -> Result<()> {
+    let candidates: Vec<String> = match kind {
+        CompletionKind::Lints => {
+            std::iter::once("warnings").chain(Lint::ALL.iter().map(Lint::name)).map(String::from).collect()
+        }
+        CompletionKind::Editions => Edition::ALL.iter().map(|e| e.as_str().to_string()).collect(),
+        // 不在项目中时没有候选值，而不是报错打断补全
+        CompletionKind::Examples => match manifest::find_project_root(Path::new(".")) {
+            Some(root) => manifest::Manifest::load(&root)?.example_names(&root)?,
+            None => Vec::new(),
+        },
+    };
+    for candidate in candidates {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
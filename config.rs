@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// 全局配置: `~/.ntfp/config.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalConfig {
+    /// 使用指定的rustc代替PATH中的rustc
+    pub rustc: Option<PathBuf>,
+    /// 在本地记录构建次数、耗时和错误类别，用`ntfp stats`查看
+    #[serde(rename = "usage-stats", default)]
+    pub usage_stats: bool,
+}
+
+/// ntfp的数据目录，可通过`NTFP_HOME`覆盖
+pub fn ntfp_home() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("NTFP_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| Path::new(&home).join(".ntfp"))
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    ntfp_home().map(|home| home.join("cache"))
+}
+
+/// 工具目录，`ntfp shell`把它加入PATH
+pub fn bin_dir() -> Option<PathBuf> {
+    ntfp_home().map(|home| home.join("bin"))
+}
+
+/// 优先于PATH查找工具的目录: `~/.ntfp/bin`和当前ntfp所在目录
+pub fn tool_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    dirs.extend(bin_dir());
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        dirs.push(dir);
+    }
+    dirs
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    ntfp_home().map(|home| home.join("config.toml"))
+}
+
+impl GlobalConfig {
+    /// 读取全局配置，文件不存在时返回默认配置
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path() else {
+            return Ok(GlobalConfig::default());
+        };
+        if !path.exists() {
+            return Ok(GlobalConfig::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("无法读取文件: {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("无法解析全局配置: {:?}", path))
+    }
+
+    pub fn rustc(&self) -> PathBuf {
+        self.rustc.clone().unwrap_or_else(|| PathBuf::from("rustc"))
+    }
+}
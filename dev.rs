@@ -0,0 +1,1030 @@
+use crate::compile::{self, ASTNode, BinaryOp, Binding, MatchArm, Param, Span};
+use crate::config;
+use crate::formatter;
+use crate::interp::{self, Engine};
+use crate::langdoc;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// 设置后`dev codegen`还会用rustc检查生成的代码 (CI中使用)
+const CODEGEN_RUSTC_ENV: &str = "NTFP_CODEGEN_RUSTC";
+
+#[derive(Subcommand)]
+pub enum DevCommands {
+    /// 随机生成AST，格式化后重新解析并比较结构；同时检查AST经过JSON (`--emit ast-json`) 后不变
+    Roundtrip {
+        /// 生成的用例数量
+        #[arg(long, default_value_t = 256)]
+        cases: u32,
+        /// 随机种子 (默认: 当前时间)
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// 比较两种解释器引擎运行同一程序的耗时
+    BenchInterp {
+        /// 调用树的深度，共执行约2^depth次函数调用
+        #[arg(long, default_value_t = 16)]
+        depth: u32,
+        /// 每个引擎运行的次数
+        #[arg(long, default_value_t = 5)]
+        runs: u32,
+    },
+    /// 生成一个大文件，测量词法分析、解析、语义分析、代码生成和格式化各自的耗时；
+    /// 同时测量一半大小的文件，耗时增长明显超过2倍说明该阶段不是线性的
+    BenchPhases {
+        /// 生成的源码行数
+        #[arg(long, default_value_t = 100_000)]
+        lines: usize,
+        /// 每个阶段运行的次数，取最快的一次
+        #[arg(long, default_value_t = 3)]
+        runs: u32,
+        /// 输出一行JSON，便于记录到文件中跟踪变化
+        #[arg(long)]
+        json: bool,
+    },
+    /// 运行`ntfp help-lang`中的每个示例 (extern示例只做语义分析)
+    LangExamples,
+    /// 把目录中的每个.ntf片段生成Rust代码，与同名.rs文件比较；
+    /// 设置NTFP_CODEGEN_RUSTC=1时还用rustc --edition 2021检查生成的代码
+    Codegen {
+        /// 片段所在目录
+        #[arg(long, default_value = "codegen-tests")]
+        dir: PathBuf,
+        /// 用当前的生成结果覆盖.rs文件
+        #[arg(long)]
+        bless: bool,
+    },
+    /// 用曾经导致panic的输入检查词法分析、解析、语义分析和格式化只报告错误，
+    /// 不会panic。修复崩溃时把输入加入目录，防止再次出现
+    Crashes {
+        /// 输入所在目录，其中每个文件都是一个输入
+        #[arg(long, default_value = "tests/crashes")]
+        dir: PathBuf,
+    },
+    /// 解析两个.ntf文件并输出AST的结构差异 (新增、删除和修改的节点)，
+    /// 忽略格式和注释。有差异时退出码为1
+    DiffAst {
+        old: PathBuf,
+        new: PathBuf,
+        /// 解析使用的edition (默认: 最新)
+        #[arg(long, value_parser = crate::parse_edition)]
+        edition: Option<compile::Edition>,
+    },
+}
+
+pub fn run(command: DevCommands) -> Result<()> {
+    match command {
+        DevCommands::Roundtrip { cases, seed } => roundtrip(cases, seed),
+        DevCommands::BenchInterp { depth, runs } => bench_interp(depth, runs),
+        DevCommands::BenchPhases { lines, runs, json } => bench_phases(lines, runs, json),
+        DevCommands::Codegen { dir, bless } => codegen(&dir, bless),
+        DevCommands::LangExamples => lang_examples(),
+        DevCommands::Crashes { dir } => crashes(&dir),
+        DevCommands::DiffAst { old, new, edition } => diff_ast(&old, &new, edition.unwrap_or(compile::Edition::LATEST)),
+    }
+}
+
+/// xorshift64*, enough to make failing cases reproducible from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+// `letter`, `iffy` and `index` start with a keyword but must still lex as identifiers.
+const IDENTS: &[&str] = &["a", "x", "y", "count", "total", "item", "name_2", "_tmp", "letter", "iffy", "index", "type"];
+const STRINGS: &[&str] = &["\"\"", "\"hello\"", "\"Hello, Netflu!\"", "\"a b c\"", "\"123\""];
+
+const BINARY_OPS: &[BinaryOp] = &[
+    BinaryOp::Add,
+    BinaryOp::Subtract,
+    BinaryOp::Multiply,
+    BinaryOp::Divide,
+    BinaryOp::Equal,
+    BinaryOp::NotEqual,
+    BinaryOp::Less,
+    BinaryOp::Greater,
+    BinaryOp::LessEqual,
+    BinaryOp::GreaterEqual,
+];
+
+fn gen_ident(rng: &mut Rng) -> String {
+    rng.pick(IDENTS).to_string()
+}
+
+fn gen_expression(rng: &mut Rng, depth: usize) -> ASTNode {
+    let kinds = if depth < 3 { 13 } else { 4 };
+    match rng.below(kinds) {
+        0 => ASTNode::Identifier { name: gen_ident(rng), span: Span::default(), ty: None },
+        1 => ASTNode::Number { value: (rng.next() % 100_000).to_string() },
+        2 => ASTNode::String { value: rng.pick(STRINGS).to_string() },
+        3 => gen_interpolation(rng),
+        4 => gen_call(rng, depth + 1),
+        5 => ASTNode::Negate { operand: Box::new(gen_expression(rng, depth + 1)), span: Span::default() },
+        6 => ASTNode::Array {
+            elements: (0..rng.below(4)).map(|_| gen_expression(rng, depth + 1)).collect(),
+            span: Span::default(),
+        },
+        7 => ASTNode::Map {
+            entries: (0..rng.below(4))
+                .map(|_| (gen_expression(rng, depth + 1), gen_expression(rng, depth + 1)))
+                .collect(),
+            span: Span::default(),
+        },
+        8 => ASTNode::Index {
+            collection: Box::new(gen_expression(rng, depth + 1)),
+            index: Box::new(gen_expression(rng, depth + 1)),
+            collection_type: None,
+            span: Span::default(),
+        },
+        9 => ASTNode::MethodCall {
+            receiver: Box::new(gen_expression(rng, depth + 1)),
+            name: gen_ident(rng),
+            args: (0..rng.below(3)).map(|_| gen_expression(rng, depth + 1)).collect(),
+            method: None,
+            span: Span::default(),
+        },
+        10 => ASTNode::Field {
+            object: Box::new(gen_expression(rng, depth + 1)),
+            name: gen_ident(rng),
+            enum_type: None,
+            span: Span::default(),
+        },
+        11 => ASTNode::Tuple {
+            elements: (0..2 + rng.below(2)).map(|_| gen_expression(rng, depth + 1)).collect(),
+            span: Span::default(),
+        },
+        _ => ASTNode::Binary {
+            op: BINARY_OPS[rng.below(BINARY_OPS.len())],
+            left: Box::new(gen_expression(rng, depth + 1)),
+            right: Box::new(gen_expression(rng, depth + 1)),
+            operand_type: None,
+            span: Span::default(),
+        },
+    }
+}
+
+fn gen_interpolation(rng: &mut Rng) -> ASTNode {
+    let names: Vec<String> = (0..1 + rng.below(3)).map(|_| gen_ident(rng)).collect();
+    let mut value = String::from("\"");
+    for name in &names {
+        value.push_str(rng.pick(STRINGS).trim_matches('"'));
+        if rng.below(4) == 0 {
+            value.push_str("{{}}");
+        }
+        value.push_str(&format!("{{{}}}", name));
+    }
+    value.push('"');
+    let args = names.into_iter().map(|name| ASTNode::Identifier { name, span: Span::default(), ty: None }).collect();
+    ASTNode::Interpolation { value, args, span: Span::default() }
+}
+
+fn gen_call(rng: &mut Rng, depth: usize) -> ASTNode {
+    let argc = rng.below(4);
+    ASTNode::FunctionCall {
+        name: gen_ident(rng),
+        args: (0..argc).map(|_| gen_expression(rng, depth)).collect(),
+        span: Span::default(),
+    }
+}
+
+fn gen_body(rng: &mut Rng, depth: usize) -> Vec<ASTNode> {
+    let len = rng.below(5);
+    (0..len).map(|_| gen_statement(rng, depth + 1)).collect()
+}
+
+fn gen_params(rng: &mut Rng) -> Vec<Param> {
+    let mut params = Vec::new();
+    for name in IDENTS {
+        if rng.below(4) == 0 {
+            params.push(Param { name: name.to_string(), ty: gen_type(rng), span: Span::default() });
+        }
+    }
+    params
+}
+
+fn gen_type(rng: &mut Rng) -> Option<String> {
+    match rng.below(9) {
+        0 => Some("int".to_string()),
+        1 => Some("str".to_string()),
+        2 => Some("[int]".to_string()),
+        3 => Some("[str]".to_string()),
+        4 => Some(format!("{{{}: {}}}", rng.pick(&["int", "str"]), rng.pick(&["int", "str"]))),
+        5 => Some(format!("({}, {})", rng.pick(&["int", "str"]), rng.pick(&["int", "str"]))),
+        6 => Some(format!("{}?", rng.pick(&["int", "str", "[int]"]))),
+        _ => None,
+    }
+}
+
+fn gen_if(rng: &mut Rng, depth: usize) -> ASTNode {
+    ASTNode::If {
+        condition: Box::new(gen_expression(rng, depth)),
+        then_body: gen_body(rng, depth),
+        else_body: match rng.below(3) {
+            0 => Vec::new(),
+            1 => vec![gen_if(rng, depth + 1)],
+            _ => gen_body(rng, depth),
+        },
+        span: Span::default(),
+    }
+}
+
+fn gen_match(rng: &mut Rng, depth: usize) -> ASTNode {
+    ASTNode::Match {
+        value: Box::new(gen_expression(rng, depth)),
+        arms: (0..rng.below(4))
+            .map(|_| MatchArm {
+                variant: gen_ident(rng),
+                binding: (rng.below(3) == 0).then(|| Binding { name: gen_ident(rng), mutable: false, span: Span::default() }),
+                body: gen_body(rng, depth),
+                span: Span::default(),
+            })
+            .collect(),
+        else_body: match rng.below(2) {
+            0 => None,
+            _ => Some(gen_body(rng, depth)),
+        },
+        value_type: None,
+        span: Span::default(),
+    }
+}
+
+fn gen_statement(rng: &mut Rng, depth: usize) -> ASTNode {
+    let kinds = if depth < 2 { 12 } else { 6 };
+    match rng.below(kinds) {
+        0 => ASTNode::Let {
+            name: gen_ident(rng),
+            value: Box::new(gen_expression(rng, depth)),
+            mutable: false,
+            span: Span::default(),
+        },
+        1 => ASTNode::Print {
+            value: Box::new(gen_expression(rng, depth)),
+            value_type: None,
+            newline: rng.below(2) == 0,
+        },
+        2 => ASTNode::Assign {
+            name: gen_ident(rng),
+            value: Box::new(gen_expression(rng, depth)),
+            span: Span::default(),
+        },
+        3 => gen_call(rng, depth),
+        4 => ASTNode::Back {
+            value: match rng.below(3) {
+                0 => None,
+                _ => Some(Box::new(gen_expression(rng, depth))),
+            },
+            span: Span::default(),
+            implicit: false,
+        },
+        5 => ASTNode::ExternFunction {
+            abi: ["rust", "c"][rng.below(2)].to_string(),
+            name: gen_ident(rng),
+            params: gen_params(rng),
+            return_type: gen_type(rng),
+            span: Span::default(),
+        },
+        6 => gen_if(rng, depth),
+        7 => ASTNode::While {
+            condition: Box::new(gen_expression(rng, depth)),
+            body: gen_body(rng, depth),
+            span: Span::default(),
+        },
+        8 => ASTNode::For {
+            var: gen_ident(rng),
+            start: Box::new(gen_expression(rng, depth)),
+            end: Box::new(gen_expression(rng, depth)),
+            body: gen_body(rng, depth),
+            span: Span::default(),
+        },
+        9 => gen_match(rng, depth),
+        // Enums are only declared at the top level
+        10 if depth == 0 => ASTNode::Enum {
+            name: gen_ident(rng),
+            variants: (0..1 + rng.below(3)).map(|_| gen_ident(rng)).collect(),
+            span: Span::default(),
+        },
+        11 => ASTNode::LetTuple {
+            bindings: (0..2 + rng.below(2))
+                .map(|_| Binding { name: gen_ident(rng), mutable: false, span: Span::default() })
+                .collect(),
+            value: Box::new(gen_expression(rng, depth)),
+            span: Span::default(),
+        },
+        _ => ASTNode::Function {
+            name: gen_ident(rng),
+            params: gen_params(rng),
+            return_type: gen_type(rng),
+            body: gen_function_body(rng, depth),
+            public: rng.below(4) == 0,
+            span: Span::default(),
+        },
+    }
+}
+
+/// Like `gen_body`, sometimes ending in an expression that is the return value.
+fn gen_function_body(rng: &mut Rng, depth: usize) -> Vec<ASTNode> {
+    let mut body = gen_body(rng, depth);
+    if rng.below(3) == 0 {
+        body.push(ASTNode::Back {
+            value: Some(Box::new(gen_expression(rng, depth + 1))),
+            span: Span::default(),
+            implicit: true,
+        });
+    }
+    body
+}
+
+fn gen_program(rng: &mut Rng) -> Vec<ASTNode> {
+    let len = 1 + rng.below(6);
+    (0..len).map(|_| gen_statement(rng, 0)).collect()
+}
+
+fn reparse(source: &str) -> Result<Vec<ASTNode>, String> {
+    let tokens = compile::lexer(source)?;
+    compile::Parser::new(tokens).parse()
+}
+
+fn roundtrip(cases: u32, seed: Option<u64>) -> Result<()> {
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1)
+    });
+    println!("roundtrip: {} 个用例, 种子 {}", cases, seed);
+
+    let mut rng = Rng::new(seed);
+    for case in 0..cases {
+        let ast = gen_program(&mut rng);
+        let source = formatter::format_program(&ast);
+
+        let parsed = reparse(&source).map_err(|e| {
+            anyhow::anyhow!("用例 {} 重新解析失败 (种子 {}): {}\n源码:\n{}", case, seed, e, source)
+        })?;
+
+        if parsed != ast {
+            anyhow::bail!(
+                "用例 {} AST不一致 (种子 {})\n源码:\n{}\n生成:\n{:#?}\n解析:\n{:#?}",
+                case, seed, source, ast, parsed
+            );
+        }
+
+        let json = serde_json::to_string(&parsed).context("无法序列化AST")?;
+        let decoded: Vec<ASTNode> = serde_json::from_str(&json)
+            .with_context(|| format!("用例 {} 的JSON无法解析 (种子 {})\n{}", case, seed, json))?;
+        if decoded != parsed {
+            anyhow::bail!("用例 {} 经过JSON后AST不一致 (种子 {})\n源码:\n{}\nJSON:\n{}", case, seed, source, json);
+        }
+
+        let reformatted = formatter::format_program(&parsed);
+        if reformatted != source {
+            anyhow::bail!(
+                "用例 {} 格式化结果不稳定 (种子 {})\n第一次:\n{}\n第二次:\n{}",
+                case, seed, source, reformatted
+            );
+        }
+    }
+
+    println!("roundtrip: 全部通过");
+    Ok(())
+}
+
+/// `f<n>` calls `f<n-1>` twice, so `main` makes 2^depth calls without printing.
+fn bench_program(depth: u32) -> String {
+    let mut source = String::from("fun f0(a) -> int {\n    let b = a;\n    back b;\n}\n");
+    for n in 1..=depth {
+        source.push_str(&format!(
+            "\nfun f{n}(a) -> int {{\n    let x = f{prev}(a);\n    x = f{prev}(x);\n    back x;\n}}\n",
+            n = n,
+            prev = n - 1
+        ));
+    }
+    source.push_str(&format!("\nfun main() {{\n    let _r = f{}(1);\n}}\n", depth));
+    source
+}
+
+fn bench_interp(depth: u32, runs: u32) -> Result<()> {
+    let program = interp::load_program(&bench_program(depth), compile::Edition::LATEST, &Default::default(), false)?;
+    println!("bench-interp: 深度 {} (约 {} 次调用), 每个引擎 {} 次", depth, 1u64 << depth, runs);
+
+    for engine in [Engine::Ast, Engine::Bytecode] {
+        let mut times = Vec::new();
+        for _ in 0..runs.max(1) {
+            let start = Instant::now();
+            interp::execute(&program, engine, compile::Overflow::Checked, None).map_err(|e| anyhow::anyhow!("运行错误: {}", e))?;
+            times.push(start.elapsed());
+        }
+        times.sort();
+        println!(
+            "  {:<9} 最快 {:>8.2} ms, 中位数 {:>8.2} ms",
+            engine.name(),
+            times[0].as_secs_f64() * 1000.0,
+            times[times.len() / 2].as_secs_f64() * 1000.0
+        );
+    }
+    Ok(())
+}
+
+/// Average line length of `large_program`, which grows by whole functions.
+const BYTES_PER_LINE: usize = 17;
+
+/// About `lines` lines of functions using most of the language, each calling the previous one.
+fn large_program(lines: usize) -> String {
+    let mut source = String::from("fun f0(a, s: str) -> int {\n    back a;\n}\n");
+    let mut n = 0;
+    while source.len() < lines * BYTES_PER_LINE {
+        n += 1;
+        source.push_str(&format!(
+            "\n// f{n} 调用 f{prev}
+fun f{n}(a, s: str) -> int {{
+    let x = a * 2 + {n};
+    let t = s + \"-{n}\";
+    if x > 100 {{
+        x = x - 100;
+    }} else {{
+        print(\"small {{x}} in {{t}}\\n\");
+    }}
+    for i in 0..3 {{
+        x = x + f{prev}(i, t);
+    }}
+    while x > 10 {{
+        x = x / 2;
+    }}
+    back -x;
+}}
+",
+            n = n,
+            prev = n - 1
+        ));
+    }
+    source.push_str(&format!("\nfun main() {{\n    print(f{}(1, \"\"));\n}}\n", n));
+    source
+}
+
+/// Fastest of `runs` runs of each front-end phase over `source`, in order.
+fn time_phases(source: &str, runs: u32) -> Result<Vec<(&'static str, f64)>, String> {
+    fn fastest<T>(runs: u32, mut phase: impl FnMut() -> Result<T, String>) -> Result<(T, f64), String> {
+        let mut best = None;
+        for _ in 0..runs {
+            let start = Instant::now();
+            let value = phase()?;
+            let ms = start.elapsed().as_secs_f64() * 1000.0;
+            if best.as_ref().is_none_or(|(_, best_ms)| ms < *best_ms) {
+                best = Some((value, ms));
+            }
+        }
+        Ok(best.expect("at least one run"))
+    }
+
+    let edition = compile::Edition::LATEST;
+    let runs = runs.max(1);
+    // Parsing and analysis consume their input; the copies are made before timing
+    let (tokens, lex) = fastest(runs, || compile::lexer(source))?;
+    let mut inputs = vec![tokens; runs as usize];
+    let (ast, parse) = fastest(runs, || compile::Parser::with_edition(inputs.pop().unwrap_or_default(), edition).parse())?;
+    let mut inputs = vec![ast; runs as usize];
+    let (ast, analyze) = fastest(runs, || {
+        let mut ast = inputs.pop().unwrap_or_default();
+        compile::SemanticAnalyzer::new().analyze(&mut ast)?;
+        Ok(ast)
+    })?;
+    let (_, codegen) = fastest(runs, || compile::generate_code(&ast, compile::Overflow::Checked))?;
+    let style = formatter::Style::default();
+    let (_, format) = fastest(runs, || formatter::format_source(source, edition, &style))?;
+    // What editors do on every keystroke with format-on-type: one line in the middle of the file
+    let middle = source.lines().count() as u32 / 2;
+    let (_, format_line) = fastest(runs, || formatter::format_lines(source, edition, &style, middle..=middle))?;
+    Ok(vec![
+        ("lex", lex),
+        ("parse", parse),
+        ("analyze", analyze),
+        ("codegen", codegen),
+        ("format", format),
+        ("format_line", format_line),
+    ])
+}
+
+fn bench_phases(lines: usize, runs: u32, json: bool) -> Result<()> {
+    let source = large_program(lines);
+    let half = large_program(lines / 2);
+    let count = source.lines().count();
+    let full = time_phases(&source, runs).map_err(|e| anyhow::anyhow!("生成的程序无法编译: {}", e))?;
+    let half = time_phases(&half, runs).map_err(|e| anyhow::anyhow!("生成的程序无法编译: {}", e))?;
+
+    if json {
+        let phases: Vec<String> = full.iter().map(|(phase, ms)| format!("\"{}\": {:.3}", phase, ms)).collect();
+        println!("{{\"lines\": {}, \"bytes\": {}, \"ms\": {{{}}}}}", count, source.len(), phases.join(", "));
+        return Ok(());
+    }
+    println!("bench-phases: {} 行 ({} 字节), 每个阶段 {} 次取最快", count, source.len(), runs.max(1));
+    for ((phase, ms), (_, half_ms)) in full.iter().zip(&half) {
+        println!("  {:<11} {:>9.2} ms, {:>6.3} ms/千行, 一半大小时的 {:.2} 倍", phase, ms, ms * 1000.0 / count as f64, ms / half_ms);
+    }
+    Ok(())
+}
+
+fn generate_rust(source: &str) -> Result<String, String> {
+    let tokens = compile::lexer(source)?;
+    let mut ast = compile::Parser::with_edition(tokens, compile::Edition::LATEST).parse()?;
+    compile::SemanticAnalyzer::new().analyze(&mut ast)?;
+    compile::generate_code(&ast, compile::Overflow::Checked)
+}
+
+/// Converted to the other kind of line endings, a snippet must still lex to
+/// tokens on the same lines, generate the same code, and format the same.
+fn check_line_endings(source: &str, code: &str) -> Result<(), String> {
+    let lf = source.replace("\r\n", "\n");
+    let crlf = lf.replace('\n', "\r\n");
+    let lines = |source: &str| compile::lexer(source).map(|tokens| tokens.iter().map(|t| t.line).collect::<Vec<_>>());
+    if lines(&lf)? != lines(&crlf)? {
+        return Err("CRLF换行时token的行号不同".to_string());
+    }
+    if generate_rust(&lf)? != code || generate_rust(&crlf)? != code {
+        return Err("LF和CRLF换行生成的代码不同".to_string());
+    }
+    let style = formatter::Style::default();
+    let formatted = formatter::format_source(&lf, compile::Edition::LATEST, &style)?;
+    if formatter::format_source(&crlf, compile::Edition::LATEST, &style)? != formatted.replace('\n', "\r\n") {
+        return Err("CRLF换行的格式化结果与LF不一致".to_string());
+    }
+    Ok(())
+}
+
+/// Type-checks without linking, so `extern c` snippets need no C library.
+fn check_with_rustc(rustc: &Path, code: &str, scratch: &Path, name: &str) -> Result<(), String> {
+    let path = scratch.join(format!("{}.rs", name));
+    fs::write(&path, code).map_err(|e| e.to_string())?;
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "--crate-type", "bin", "--emit", "metadata", "-A", "warnings"])
+        .arg("--out-dir")
+        .arg(scratch)
+        .arg(&path)
+        .output()
+        .map_err(|e| format!("无法运行rustc {:?}: {}", rustc, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+/// First line where `expected` and `actual` differ, 1-based.
+fn first_difference(expected: &str, actual: &str) -> (usize, String, String) {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (e, a) => {
+                let show = |l: Option<&str>| l.map_or("<文件结束>".to_string(), str::to_string);
+                return (line, show(e), show(a));
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn codegen(dir: &Path, bless: bool) -> Result<()> {
+    let mut snippets: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("无法读取目录: {:?}", dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ntf"))
+        .collect();
+    snippets.sort();
+
+    let rustc = match std::env::var_os(CODEGEN_RUSTC_ENV) {
+        Some(_) => Some(config::GlobalConfig::load()?.rustc()),
+        None => None,
+    };
+    let scratch = std::env::temp_dir().join(format!("ntfp-codegen-{}", std::process::id()));
+    if rustc.is_some() {
+        fs::create_dir_all(&scratch).with_context(|| format!("无法创建目录: {:?}", scratch))?;
+    }
+    println!(
+        "codegen: {} 个片段{}",
+        snippets.len(),
+        if rustc.is_some() { ", 使用rustc检查" } else { "" }
+    );
+
+    let mut failures = 0;
+    for snippet in &snippets {
+        let name = snippet.file_stem().unwrap().to_string_lossy().into_owned();
+        let golden = snippet.with_extension("rs");
+        let source = fs::read_to_string(snippet).with_context(|| format!("无法读取文件: {:?}", snippet))?;
+
+        let code = match generate_rust(&source) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("  失败 {}: 代码生成错误: {}", name, e);
+                failures += 1;
+                continue;
+            }
+        };
+
+        if bless {
+            fs::write(&golden, &code).with_context(|| format!("无法写入文件: {:?}", golden))?;
+        } else {
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == code => {}
+                Ok(expected) => {
+                    let (line, e, a) = first_difference(&expected, &code);
+                    println!("  失败 {}: 与{:?}第{}行不同\n    期望: {}\n    实际: {}", name, golden, line, e, a);
+                    failures += 1;
+                    continue;
+                }
+                Err(_) => {
+                    println!("  失败 {}: 缺少{:?}，用--bless生成", name, golden);
+                    failures += 1;
+                    continue;
+                }
+            }
+        }
+
+        if let Err(e) = check_line_endings(&source, &code) {
+            println!("  失败 {}: {}", name, e);
+            failures += 1;
+            continue;
+        }
+
+        let checked = match &rustc {
+            Some(rustc) => check_with_rustc(rustc, &code, &scratch, &name),
+            None => Ok(()),
+        };
+        if let Err(e) = checked {
+            println!("  失败 {}: rustc拒绝了生成的代码:\n{}", name, e);
+            failures += 1;
+            continue;
+        }
+        println!("  通过 {}", name);
+    }
+    if rustc.is_some() {
+        let _ = fs::remove_dir_all(&scratch);
+    }
+
+    if failures > 0 {
+        anyhow::bail!("codegen: {} 个片段失败", failures);
+    }
+    println!("codegen: 全部通过");
+    Ok(())
+}
+
+fn lang_examples() -> Result<()> {
+    for topic in langdoc::TOPICS {
+        println!("== {} ==", topic.name);
+        let program = interp::load_program(topic.example, topic.edition, &Default::default(), false)
+            .with_context(|| format!("help-lang {} 的示例无法编译", topic.name))?;
+        // 示例即默认排版风格 (formatter::Style::default) 的参照；旧edition的示例展示的正是fmt会改写的旧写法
+        let formatted = formatter::format_source(topic.example, topic.edition, &formatter::Style::default())
+            .map_err(|e| anyhow::anyhow!("help-lang {} 的示例无法格式化: {}", topic.name, e))?;
+        if topic.edition == compile::Edition::LATEST && formatted != topic.example {
+            anyhow::bail!("help-lang {} 的示例不符合默认的ntfp fmt风格，应为:\n{}", topic.name, formatted);
+        }
+        if program.iter().any(|node| matches!(node, ASTNode::ExternFunction { .. })) {
+            println!("(extern示例，跳过运行)");
+            continue;
+        }
+        interp::execute(&program, Engine::Bytecode, compile::Overflow::Checked, None)
+            .map_err(|e| anyhow::anyhow!("help-lang {} 的示例运行失败: {}", topic.name, e))?;
+        println!();
+    }
+    println!("lang-examples: 全部通过");
+    Ok(())
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("(无法显示的panic)".to_string(), |s| s.to_string()),
+    }
+}
+
+/// 每个edition下依次运行编译器前端和格式化，返回第一个panic
+fn front_end_panic(source: &str) -> Option<String> {
+    for &edition in compile::Edition::ALL {
+        let result = std::panic::catch_unwind(|| {
+            // 这里只关心是否panic，错误是预期的结果
+            let _ = interp::load_program(source, edition, &Default::default(), true);
+            let _ = formatter::format_source(source, edition, &formatter::Style::default());
+        });
+        if let Err(payload) = result {
+            return Some(format!("edition {}: {}", edition.as_str(), panic_message(payload)));
+        }
+    }
+    None
+}
+
+/// 刚好在嵌套和链长度限制以内和以外的输入，由程序生成，不放进目录
+fn generated_crash_inputs() -> Vec<(String, String)> {
+    let nest = |open: &str, inner: &str, close: &str, depth: usize| {
+        format!("{}{}{}", open.repeat(depth), inner, close.repeat(depth))
+    };
+    let mut inputs = Vec::new();
+    for depth in [compile::MAX_NESTING, compile::MAX_NESTING + 1] {
+        inputs.push((
+            format!("{}层代码块", depth),
+            format!("fun main() {{\n{}}}\n", nest("if 1 {\n", "print(1);\n", "}\n", depth)),
+        ));
+        inputs.push((
+            format!("{}层括号", depth),
+            format!("fun main() {{\n    print({});\n}}\n", nest("(", "1", ")", depth)),
+        ));
+        inputs.push((
+            format!("{}层函数调用", depth),
+            format!("fun f(x) -> int {{\n    back x;\n}}\n\nfun main() {{\n    print({});\n}}\n", nest("f(", "1", ")", depth)),
+        ));
+        inputs.push((
+            format!("{}层取负", depth),
+            format!("fun main() {{\n    print({}1);\n}}\n", "-".repeat(depth)),
+        ));
+    }
+    for links in [compile::MAX_CHAIN_LINKS, compile::MAX_CHAIN_LINKS + 1] {
+        inputs.push((
+            format!("{}个运算符的链", links),
+            format!("fun main() {{\n    print({});\n}}\n", vec!["1"; links + 1].join(" + ")),
+        ));
+        let branches: Vec<String> = (0..=links).map(|i| format!("if x == {} {{\n        print({});\n    }}", i, i)).collect();
+        inputs.push((
+            format!("{}个else if的链", links),
+            format!("fun main() {{\n    let x = 1;\n    {}\n}}\n", branches.join(" else ")),
+        ));
+        inputs.push((
+            format!("{}次下标的链", links),
+            format!("fun main() {{\n    let a = [1];\n    print(a{});\n}}\n", "[0]".repeat(links)),
+        ));
+    }
+    // 每层括号里的链都算在总长度内
+    inputs.push((
+        "括号中嵌套的链".to_string(),
+        format!("fun main() {{\n    print({});\n}}\n", nest("(1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + ", "1", ")", compile::MAX_NESTING)),
+    ));
+    inputs
+}
+
+fn crashes(dir: &Path) -> Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("无法读取目录: {:?}", dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    let mut inputs = Vec::new();
+    for path in paths {
+        let bytes = fs::read(&path).with_context(|| format!("无法读取文件: {:?}", path))?;
+        // 无效的UTF-8在读取源文件时就会报错，到不了编译器
+        inputs.push((path.display().to_string(), String::from_utf8_lossy(&bytes).into_owned()));
+    }
+    inputs.extend(generated_crash_inputs());
+    println!("crashes: {} 个输入", inputs.len());
+
+    // panic信息由下面统一输出，不需要默认钩子打印的内容
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut failures = 0;
+    for (name, source) in &inputs {
+        if let Some(message) = front_end_panic(source) {
+            println!("  失败 {}: {}", name, message);
+            failures += 1;
+        }
+    }
+    std::panic::set_hook(hook);
+
+    if failures > 0 {
+        anyhow::bail!("crashes: {} 个输入导致panic", failures);
+    }
+    println!("crashes: 全部通过");
+    Ok(())
+}
+
+fn parse_for_diff(path: &Path, edition: compile::Edition) -> Result<Vec<Value>> {
+    let source = fs::read_to_string(path).with_context(|| format!("无法读取文件: {:?}", path))?;
+    let tokens = compile::lexer(&source).map_err(|e| anyhow::anyhow!("{}: 词法分析错误: {}", path.display(), e))?;
+    let ast = compile::Parser::with_edition(tokens, edition)
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}: 语法分析错误: {}", path.display(), e))?;
+    ast.iter().map(|node| serde_json::to_value(node).context("无法序列化AST")).collect()
+}
+
+fn diff_ast(old: &Path, new: &Path, edition: compile::Edition) -> Result<()> {
+    let mut diff = AstDiff::default();
+    diff.nodes("", "", &parse_for_diff(old, edition)?, &parse_for_diff(new, edition)?, (0, 0));
+    if diff.added + diff.removed + diff.changed == 0 {
+        println!("diff-ast: AST相同");
+        return Ok(());
+    }
+    println!("diff-ast: 新增 {}, 删除 {}, 修改 {}", diff.added, diff.removed, diff.changed);
+    std::process::exit(1);
+}
+
+/// 在节点的JSON形式 (`--emit ast-json`) 上比较，因此新增的节点种类不需要在这里处理
+#[derive(Default)]
+struct AstDiff {
+    added: usize,
+    removed: usize,
+    changed: usize,
+}
+
+impl AstDiff {
+    /// 按最长公共子序列对齐两组语句 (或参数)，相邻的删除和新增中种类相同的节点视为修改
+    fn nodes(&mut self, path: &str, field: &str, old: &[Value], new: &[Value], lines: (u32, u32)) {
+        let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if same(&old[i], &new[j]) {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && same(&old[i], &new[j]) {
+                self.unmatched(path, field, &removed, &added, lines);
+                removed.clear();
+                added.clear();
+                i += 1;
+                j += 1;
+            } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+                removed.push((i, &old[i]));
+                i += 1;
+            } else {
+                added.push((j, &new[j]));
+                j += 1;
+            }
+        }
+        self.unmatched(path, field, &removed, &added, lines);
+    }
+
+    /// 两个相同节点之间删除的`removed`和新增的`added`，都带有在原列表中的下标
+    fn unmatched(&mut self, path: &str, field: &str, removed: &[(usize, &Value)], added: &[(usize, &Value)], lines: (u32, u32)) {
+        for k in 0..removed.len().max(added.len()) {
+            match (removed.get(k), added.get(k)) {
+                (Some(&(_, old)), Some(&(j, new))) if kind(old) == kind(new) => {
+                    self.node(&element_path(path, field, new, j), old, new, lines);
+                }
+                (removed, added) => {
+                    if let Some(&(i, old)) = removed {
+                        self.removed += 1;
+                        let at = element_path(path, field, old, i);
+                        println!("- {} (第{}行) {}: {}", at, line_of(old, lines.0), kind(old), render(old));
+                    }
+                    if let Some(&(j, new)) = added {
+                        self.added += 1;
+                        let at = element_path(path, field, new, j);
+                        println!("+ {} (第{}行) {}: {}", at, line_of(new, lines.1), kind(new), render(new));
+                    }
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, path: &str, old: &Value, new: &Value, lines: (u32, u32)) {
+        if same(old, new) {
+            return;
+        }
+        let lines = (line_of(old, lines.0), line_of(new, lines.1));
+        let (Value::Object(old_fields), Value::Object(new_fields)) = (old, new) else {
+            return;
+        };
+        if kind(old) != kind(new) {
+            self.report_changed(path, lines, &format!("{} → {}", kind(old), kind(new)), old, new);
+            return;
+        }
+
+        // 名称、运算符、字面量等字段的差异算作这个节点的修改，子节点中的差异在其后分别报告
+        let fields: Vec<&str> = old_fields
+            .iter()
+            .filter(|(name, value)| !matches!(name.as_str(), "kind" | "span") && !same(value, &new_fields[name.as_str()]))
+            .filter(|(name, value)| child_nodes(value, &new_fields[name.as_str()]).is_none())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !fields.is_empty() {
+            self.report_changed(path, lines, &format!("{}.{}", kind(old), fields.join(", ")), old, new);
+        }
+        for (name, old_value) in old_fields {
+            match child_nodes(old_value, &new_fields[name.as_str()]) {
+                Some(Children::List(old_items, new_items)) => self.nodes(path, name, old_items, new_items, lines),
+                Some(Children::Node(old_value, new_value)) => {
+                    self.node(&format!("{}.{}", path, name), old_value, new_value, lines)
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn report_changed(&mut self, path: &str, lines: (u32, u32), what: &str, old: &Value, new: &Value) {
+        self.changed += 1;
+        println!("~ {} (第{}行 → 第{}行) {}: {} → {}", path, lines.0, lines.1, what, render(old), render(new));
+    }
+}
+
+enum Children<'a> {
+    List(&'a [Value], &'a [Value]),
+    Node(&'a Value, &'a Value),
+}
+
+/// 字段的两个值都是节点 (或节点列表) 时，逐个比较其中的节点
+fn child_nodes<'a>(old: &'a Value, new: &'a Value) -> Option<Children<'a>> {
+    match (old, new) {
+        // 参数列表等不是节点的列表作为一个整体比较
+        (Value::Array(old), Value::Array(new)) if old.iter().chain(new).all(|item| !kind(item).is_empty()) => {
+            Some(Children::List(old, new))
+        }
+        _ if !kind(old).is_empty() && !kind(new).is_empty() => Some(Children::Node(old, new)),
+        _ => None,
+    }
+}
+
+/// 忽略`span`，与`ASTNode`的相等比较一致
+fn same(old: &Value, new: &Value) -> bool {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            old.len() == new.len()
+                && old.iter().all(|(name, value)| name == "span" || new.get(name).is_some_and(|other| same(value, other)))
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            old.len() == new.len() && old.iter().zip(new).all(|(old, new)| same(old, new))
+        }
+        _ => old == new,
+    }
+}
+
+/// 节点的种类，不是节点时为空
+fn kind(node: &Value) -> &str {
+    node.get("kind").and_then(Value::as_str).unwrap_or("")
+}
+
+/// 节点所在的行; 字面量等没有位置的节点使用外层节点的行
+fn line_of(node: &Value, outer: u32) -> u32 {
+    node.pointer("/span/line").and_then(Value::as_u64).map_or(outer, |line| line as u32)
+}
+
+/// 顶层函数用名称表示，其他节点用所在字段和下标，例如`main.body[2]`
+fn element_path(path: &str, field: &str, node: &Value, index: usize) -> String {
+    match node.get("name").and_then(Value::as_str) {
+        Some(name) if path.is_empty() && matches!(kind(node), "Function" | "ExternFunction") => name.to_string(),
+        _ if path.is_empty() => format!("[{}]", index),
+        _ => format!("{}.{}[{}]", path, field, index),
+    }
+}
+
+/// 节点的源码形式，多行时只保留第一行
+fn render(node: &Value) -> String {
+    let Ok(node) = serde_json::from_value::<ASTNode>(node.clone()) else {
+        return node.to_string();
+    };
+    let text = formatter::format_expression(&node);
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default().to_string();
+    if lines.next().is_some() { format!("{} …", first) } else { first }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ntfp dev crashes`, on a thread with the stack that `ntfp` runs the compiler on
+    #[test]
+    fn crash_corpus() {
+        let corpus = std::thread::Builder::new()
+            .stack_size(compile::STACK_SIZE)
+            .spawn(|| crashes(Path::new("tests/crashes")))
+            .unwrap();
+        corpus.join().unwrap().unwrap();
+    }
+
+    /// `ntfp dev roundtrip` with fixed seeds, so failures reproduce
+    #[test]
+    fn roundtrip_fixed_seeds() {
+        for seed in [1, 42, 20260101] {
+            roundtrip(128, Some(seed)).unwrap();
+        }
+    }
+}
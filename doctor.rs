@@ -0,0 +1,127 @@
+use crate::config::{self, GlobalConfig};
+use crate::manifest;
+use anyhow::Result;
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+};
+
+const MIN_RUSTC: (u32, u32, u32) = (1, 60, 0);
+
+/// 逐项输出检查结果，`ntfp verify`也使用
+pub struct Report {
+    pub problems: usize,
+}
+
+impl Report {
+    pub fn ok(&self, message: &str) {
+        println!("  [正常] {}", message);
+    }
+
+    pub fn warn(&self, message: &str, fix: &str) {
+        println!("  [警告] {}", message);
+        println!("         建议: {}", fix);
+    }
+
+    pub fn fail(&mut self, message: &str, fix: &str) {
+        self.problems += 1;
+        println!("  [错误] {}", message);
+        println!("         修复: {}", fix);
+    }
+}
+
+/// 解析`rustc 1.80.1 (3f5fd8dd4 2024-08-06)`形式的版本号
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output.split_whitespace().nth(1)?;
+    let mut parts = version.split(['.', '-']).map(|p| p.parse::<u32>().ok());
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+fn tool_version(program: &Path) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn check_writable(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let probe = dir.join(".ntfp-doctor-probe");
+    fs::write(&probe, b"ok").map_err(|e| e.to_string())?;
+    fs::remove_file(&probe).map_err(|e| e.to_string())
+}
+
+pub fn run_doctor(path: &str) -> Result<()> {
+    let mut report = Report { problems: 0 };
+
+    println!("全局配置:");
+    let global = match GlobalConfig::load() {
+        Ok(global) => {
+            match config::config_path() {
+                Some(p) if p.exists() => report.ok(&format!("{:?} 有效", p)),
+                _ => report.ok("未找到全局配置，使用默认值"),
+            }
+            global
+        }
+        Err(e) => {
+            report.fail(
+                &format!("{:#}", e),
+                "修正或删除该文件，目前支持的键: rustc = \"<rustc路径>\", usage-stats = true",
+            );
+            GlobalConfig::default()
+        }
+    };
+
+    println!("工具链:");
+    let rustc = global.rustc();
+    match tool_version(&rustc) {
+        Some(version) => match parse_version(&version) {
+            Some(found) if found >= MIN_RUSTC => report.ok(&version),
+            Some(_) => report.fail(
+                &format!("{} 低于最低要求 {}.{}.{}", version, MIN_RUSTC.0, MIN_RUSTC.1, MIN_RUSTC.2),
+                "运行`rustup update stable`升级Rust工具链",
+            ),
+            None => report.warn(&format!("无法识别rustc版本: {}", version), "确认rustc可以正常运行"),
+        },
+        None => report.fail(
+            &format!("找不到rustc: {:?}", rustc),
+            "从 https://rustup.rs 安装Rust，或在全局配置中设置 rustc = \"<rustc路径>\"",
+        ),
+    }
+    match tool_version(Path::new("cargo")) {
+        Some(version) => report.ok(&version),
+        None => report.warn("找不到cargo (构建不需要，但建议安装)", "通过rustup安装完整的Rust工具链"),
+    }
+
+    println!("目录权限:");
+    if let Some(project_path) = manifest::find_project_root(Path::new(path)) {
+        let target = project_path.join("target");
+        match check_writable(&target) {
+            Ok(()) => report.ok(&format!("{:?} 可写", target)),
+            Err(e) => report.fail(
+                &format!("{:?} 不可写: {}", target, e),
+                "检查目录权限，或删除target目录后重新构建",
+            ),
+        }
+    } else {
+        report.ok("当前目录不是Netflu项目，跳过target检查");
+    }
+    match config::cache_dir() {
+        Some(cache) => match check_writable(&cache) {
+            Ok(()) => report.ok(&format!("{:?} 可写", cache)),
+            Err(e) => report.fail(
+                &format!("{:?} 不可写: {}", cache, e),
+                "检查目录权限，或设置NTFP_HOME指向可写目录",
+            ),
+        },
+        None => report.fail("无法确定用户主目录", "设置HOME (或USERPROFILE) 或NTFP_HOME环境变量"),
+    }
+
+    if report.problems > 0 {
+        anyhow::bail!("发现 {} 个问题", report.problems);
+    }
+    println!("一切正常!");
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Netflu as a scripting language inside a Rust program. The host registers
+//! native functions, then runs scripts or evaluates expressions that call them:
+//!
+//! ```
+//! use ntfp::embed::{Runtime, Type, Value};
+//!
+//! let mut runtime = Runtime::new();
+//! runtime
+//!     .register("clamp", &[Type::Int, Type::Int], Type::Int, |args| match args {
+//!         [Value::Int(n), Value::Int(max)] => Ok(Value::Int(*n.min(max))),
+//!         _ => unreachable!("arguments are type checked"),
+//!     })
+//!     .unwrap();
+//! assert_eq!(runtime.eval("clamp(7 * 6, 40) + 1").unwrap(), Value::Int(41));
+//! ```
+//!
+//! Scripts go through the same front end as `ntfp script`: calls to host
+//! functions are type checked against their registered signatures before
+//! anything runs.
+
+use crate::compile::{self, ASTNode, Span};
+use crate::interp::{self, Native};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub use crate::compile::{Edition, Overflow, Type};
+pub use crate::interp::{Engine, Key, Value};
+
+/// Name of the function `eval` wraps the expression into; not a valid identifier,
+/// so it cannot collide with anything a host or script defines.
+const EVAL_FUNCTION: &str = "<eval>";
+
+struct HostFunction {
+    params: Vec<Type>,
+    returns: Type,
+    native: Native,
+}
+
+/// Host functions plus the settings used to run code against them.
+pub struct Runtime {
+    edition: Edition,
+    engine: Engine,
+    overflow: Overflow,
+    functions: HashMap<String, HostFunction>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime::new()
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Runtime { edition: Edition::LATEST, engine: Engine::default(), overflow: Overflow::default(), functions: HashMap::new() }
+    }
+
+    pub fn edition(mut self, edition: Edition) -> Self {
+        self.edition = edition;
+        self
+    }
+
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Makes `function` callable from Netflu as `name`. Calls are checked
+    /// against `params` when the code is analyzed, and the value `function`
+    /// returns is checked against `returns` when it runs; an `Err` it returns
+    /// stops the program like any runtime error.
+    pub fn register<F>(&mut self, name: &str, params: &[Type], returns: Type, function: F) -> Result<&mut Self>
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        let is_identifier = matches!(
+            compile::lexer(name).as_deref(),
+            Ok([token]) if token.type_ == compile::TokenType::Identifier
+        );
+        if !is_identifier {
+            bail!("宿主函数名必须是标识符: {:?}", name);
+        }
+        if params.contains(&Type::Unit) {
+            bail!("宿主函数 {} 的参数只能是int或str", name);
+        }
+        if self.functions.contains_key(name) || compile::BUILTIN_FUNCTIONS.contains(&name) {
+            bail!("函数 {} 已经定义过了", name);
+        }
+
+        let function_name = name.to_string();
+        let expected = returns.clone();
+        let native: Native = Rc::new(move |args: &[Value]| {
+            let value = function(args)?;
+            if value.ty() != expected {
+                return Err(format!(
+                    "host function {} returned {}, expected {}",
+                    function_name,
+                    value.ty().name(),
+                    expected.name()
+                ));
+            }
+            Ok(value)
+        });
+        self.functions.insert(name.to_string(), HostFunction { params: params.to_vec(), returns, native });
+        Ok(self)
+    }
+
+    fn analyzer(&self) -> compile::SemanticAnalyzer {
+        let mut analyzer = compile::SemanticAnalyzer::new();
+        for (name, function) in &self.functions {
+            analyzer
+                .declare_host_function(name, function.params.clone(), function.returns.clone())
+                .expect("register rejects duplicate names");
+        }
+        analyzer
+    }
+
+    fn natives(&self) -> HashMap<String, Native> {
+        self.functions.iter().map(|(name, function)| (name.clone(), Rc::clone(&function.native))).collect()
+    }
+
+    fn load(&self, source: &str, script: bool) -> Result<Vec<ASTNode>> {
+        interp::load_program_with(source, self.edition, &Default::default(), script, self.analyzer())
+    }
+
+    /// Runs a script: its top-level statements, or `main` if it defines one.
+    pub fn run(&self, source: &str) -> Result<()> {
+        let program = self.load(source, true)?;
+        // Like the generated code, a program without `main` does nothing.
+        if program.iter().any(|node| matches!(node, ASTNode::Function { name, .. } if name == "main")) {
+            self.call_in(&program, "main", Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// Calls `function` of a program made of function definitions.
+    pub fn call(&self, source: &str, function: &str, args: &[Value]) -> Result<Value> {
+        let program = self.load(source, false)?;
+        let params = program.iter().find_map(|node| match node {
+            ASTNode::Function { name, params, .. } if name == function => Some(params),
+            _ => None,
+        });
+        let Some(params) = params else {
+            bail!("程序中没有函数 {}", function);
+        };
+        if params.len() != args.len() {
+            bail!("函数 {} 需要 {} 个参数，传入了 {} 个", function, params.len(), args.len());
+        }
+        for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+            if param.type_name() != arg.ty().name() {
+                bail!("函数 {} 的第 {} 个参数应为{}，传入了{}", function, i + 1, param.type_name(), arg.ty().name());
+            }
+        }
+        self.call_in(&program, function, args.to_vec())
+    }
+
+    /// Evaluates a single expression, which can call the host functions.
+    pub fn eval(&self, expression: &str) -> Result<Value> {
+        let tokens = compile::lexer(expression).map_err(|e| anyhow!("词法分析错误: {}", e))?;
+        let mut parser = compile::Parser::with_edition(tokens, self.edition);
+        let mut expr = parser.parse_lone_expression().map_err(|e| anyhow!("语法分析错误: {}", e))?;
+        let ty = self.analyzer().analyze_expression(&mut expr).map_err(|e| anyhow!("语义分析错误: {}", e))?;
+        let function = ASTNode::Function {
+            name: EVAL_FUNCTION.to_string(),
+            params: Vec::new(),
+            return_type: (ty != Type::Unit).then(|| ty.name().to_string()),
+            body: vec![ASTNode::Back { value: Some(Box::new(expr)), span: Span::default(), implicit: false }],
+            public: false,
+            span: Span::default(),
+        };
+        self.call_in(&[function], EVAL_FUNCTION, Vec::new())
+    }
+
+    fn call_in(&self, program: &[ASTNode], function: &str, args: Vec<Value>) -> Result<Value> {
+        interp::call(program, self.engine, self.overflow, self.natives(), function, args).map_err(|e| anyhow!("运行错误: {}", e))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{compile, config, manifest, platform, signals};
+use anyhow::{Context, Result};
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// 外部命令的可执行文件前缀: `ntfp foo`运行`ntfp-foo`
+const PREFIX: &str = "ntfp-";
+
+/// 依次在`~/.ntfp/bin`、当前ntfp所在目录和PATH中查找`ntfp-<name>`
+pub fn find(name: &str) -> Option<PathBuf> {
+    let file_name = platform::executable_name(&format!("{}{}", PREFIX, name));
+    let path = env::var_os("PATH").unwrap_or_default();
+    config::tool_dirs()
+        .into_iter()
+        .chain(env::split_paths(&path))
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// 运行`ntfp <name> <args>...`对应的外部命令，退出码原样返回给调用者。
+/// 找不到时从`builtin`中提示拼写相近的内置命令
+///
+/// 项目信息通过环境变量传递，标准输入输出留给外部命令自己使用:
+/// `NTFP` (当前ntfp的路径)、`NTFP_VERSION`，在项目中时还有`NTFP_PROJECT`、
+/// `NTFP_MANIFEST_PATH`、`NTFP_PACKAGE_NAME`、`NTFP_PACKAGE_VERSION`和`NTFP_EDITION`
+pub fn run(args: Vec<OsString>, builtin: &[String]) -> Result<()> {
+    let mut args = args.into_iter();
+    let name = args.next().unwrap_or_default();
+    let name = name.to_str().ok_or_else(|| anyhow::anyhow!("无效的命令名: {:?}", name))?;
+    let Some(program) = find(name) else {
+        let hint = match compile::suggest(name, builtin.iter().map(String::as_str)) {
+            Some(similar) => format!("你是想用`ntfp {}`吗?", similar),
+            _ => "运行`ntfp --help`查看可用的命令".to_string(),
+        };
+        anyhow::bail!("未知的命令: {} (也没有找到外部命令{}{})\n{}", name, PREFIX, name, hint);
+    };
+
+    let mut command = Command::new(&program);
+    command.args(args).env("NTFP_VERSION", env!("CARGO_PKG_VERSION"));
+    if let Ok(ntfp) = env::current_exe() {
+        command.env("NTFP", ntfp);
+    }
+    if let Some(root) = manifest::find_project_root(Path::new(".")) {
+        command.env("NTFP_PROJECT", &root).env("NTFP_MANIFEST_PATH", root.join(manifest::FILE_NAME));
+        // 配置有误时仍然运行外部命令，它可能正是用来修复配置的
+        if let Ok(manifest) = manifest::Manifest::load(&root) {
+            command
+                .env("NTFP_PACKAGE_NAME", &manifest.package.name)
+                .env("NTFP_PACKAGE_VERSION", &manifest.package.version)
+                .env("NTFP_EDITION", &manifest.package.edition);
+        }
+    }
+
+    tracing::debug!(?program, "running external command");
+    let status = signals::status_interactive(&mut command)
+        .with_context(|| format!("无法运行外部命令: {:?}", program))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
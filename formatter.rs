@@ -0,0 +1,634 @@
+use crate::compile::{self, ASTNode, Binding, Comment, Edition, Param};
+use std::ops::{Range, RangeInclusive};
+
+/// How `ntfp fmt` lays code out, from `[fmt]` in ntfp.toml. The defaults are
+/// the style of the examples in `ntfp help-lang`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    /// Spaces per level of indentation.
+    pub indent_width: usize,
+    pub brace_style: BraceStyle,
+    /// Statements and signatures longer than this get one argument or
+    /// parameter per line; lines that cannot be broken stay long.
+    pub max_width: usize,
+    /// Write `;` after the `}` that ends a statement. The parser accepts it
+    /// either way.
+    pub semicolon_after_block: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BraceStyle {
+    /// `{` ends the line that opens the block.
+    SameLine,
+    /// `{` goes on a line of its own, and so does `else`.
+    NextLine,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style { indent_width: 4, brace_style: BraceStyle::SameLine, max_width: 100, semicolon_after_block: false }
+    }
+}
+
+impl Style {
+    fn indent(&self, depth: usize) -> String {
+        " ".repeat(self.indent_width * depth)
+    }
+
+    fn fits(&self, line: &str) -> bool {
+        line.chars().count() <= self.max_width
+    }
+}
+
+/// A parsed file, with what the AST leaves out: its comments and where its
+/// statements and blocks are.
+struct Parsed {
+    nodes: Vec<ASTNode>,
+    /// Byte range of each top-level statement.
+    items: Vec<Range<usize>>,
+    comments: Vec<Comment>,
+    statements: Vec<Range<usize>>,
+    block_ends: Vec<usize>,
+}
+
+impl Parsed {
+    fn layout<'a>(&'a self, source: &'a str) -> Layout<'a> {
+        Layout {
+            source,
+            comments: &self.comments,
+            statements: &self.statements,
+            block_ends: &self.block_ends,
+            limit: usize::MAX,
+        }
+    }
+}
+
+fn parse(source: &str, edition: Edition) -> Result<Parsed, String> {
+    let (tokens, comments) = compile::lex(source)?;
+    let mut parser = compile::Parser::with_edition(tokens, edition);
+    let nodes = parser.parse()?;
+    let items = parser
+        .statement_tokens
+        .iter()
+        .map(|range| {
+            let last = &parser.tokens[range.end - 1];
+            parser.tokens[range.start].position..last.position + last.value.len()
+        })
+        .collect();
+    Ok(Parsed {
+        nodes,
+        items,
+        comments,
+        statements: parser.statement_bytes,
+        block_ends: parser.block_ends,
+    })
+}
+
+/// Where the comments of a file go while its AST is printed. Each comment is
+/// printed on its own line before the statement or `}` that follows it, or at
+/// the end of its statement's line if it was there already. Comments inside an
+/// expression move up above their statement.
+#[derive(Default)]
+struct Layout<'a> {
+    source: &'a str,
+    /// The comments, statements and block ends not printed yet, in source order.
+    comments: &'a [Comment],
+    statements: &'a [Range<usize>],
+    block_ends: &'a [usize],
+    /// Comments from here on belong to code that is not being printed.
+    limit: usize,
+}
+
+impl Layout<'_> {
+    fn next_statement(&mut self) -> Option<Range<usize>> {
+        let (first, rest) = self.statements.split_first()?;
+        self.statements = rest;
+        Some(first.clone())
+    }
+
+    fn next_block_end(&mut self) -> usize {
+        let Some((first, rest)) = self.block_ends.split_first() else {
+            return 0;
+        };
+        self.block_ends = rest;
+        *first
+    }
+
+    fn has_comment_before(&self, byte: usize) -> bool {
+        self.comments.first().is_some_and(|c| c.position < byte.min(self.limit))
+    }
+
+    /// Prints the comments before `byte`, each on a line of its own.
+    fn leading(&mut self, byte: usize, pad: &str, out: &mut String) {
+        while self.has_comment_before(byte) {
+            out.push_str(&format!("{}{}\n", pad, self.comments[0].text));
+            self.comments = &self.comments[1..];
+        }
+    }
+
+    /// Moves a comment right after `byte`, on the same line, to the end of the line just printed.
+    fn trailing(&mut self, byte: usize, out: &mut String) {
+        let Some(comment) = self.comments.first() else {
+            return;
+        };
+        if comment.position >= self.limit || comment.position < byte {
+            return;
+        }
+        let gap = &self.source[byte..comment.position];
+        if gap.contains('\n') || !gap.trim().is_empty() {
+            return;
+        }
+        out.pop();
+        out.push_str(&format!(" {}\n", comment.text));
+        self.comments = &self.comments[1..];
+    }
+
+    /// Drops what comes before `byte`, for printing only part of a file.
+    fn skip_to(&mut self, byte: usize) {
+        self.comments = &self.comments[self.comments.partition_point(|c| c.position < byte)..];
+        self.statements = &self.statements[self.statements.partition_point(|s| s.start < byte)..];
+        self.block_ends = &self.block_ends[self.block_ends.partition_point(|&end| end < byte)..];
+    }
+}
+
+/// Formatting may only change layout: the result must parse back to the same
+/// program, with the same comments in the same order. Returns that parse.
+fn check_unchanged(parsed: &Parsed, formatted: &str, edition: Edition) -> Result<Parsed, String> {
+    let reparsed = parse(formatted, edition)
+        .ok()
+        .filter(|reparsed| reparsed.nodes == parsed.nodes)
+        .ok_or("formatting would change the program; this is a formatter bug")?;
+    let texts = |comments: &[Comment]| comments.iter().map(|c| c.text.clone()).collect::<Vec<_>>();
+    if texts(&reparsed.comments) != texts(&parsed.comments) {
+        return Err("formatting would move a comment out of order; this is a formatter bug".to_string());
+    }
+    Ok(reparsed)
+}
+
+/// Formats a whole file. The output is guaranteed to parse back to the same
+/// program and to be a fixed point, so formatting twice changes nothing.
+pub fn format_source(source: &str, edition: Edition, style: &Style) -> Result<String, String> {
+    with_line_endings(source, |source| format_all(source, edition, style))
+}
+
+/// Formats with `\n` line endings, then gives the result those of `source`
+/// (judged by its first line), so files with Windows line endings keep them.
+fn with_line_endings(source: &str, format: impl FnOnce(&str) -> Result<String, String>) -> Result<String, String> {
+    let crlf = source.find('\n').is_some_and(|i| source[..i].ends_with('\r'));
+    if !crlf {
+        return format(source);
+    }
+    Ok(format(&source.replace("\r\n", "\n"))?.replace('\n', "\r\n"))
+}
+
+fn format_all(source: &str, edition: Edition, style: &Style) -> Result<String, String> {
+    let parsed = parse(source, edition)?;
+    let formatted = print_program(&parsed.nodes, style, &mut parsed.layout(source));
+    let reparsed = check_unchanged(&parsed, &formatted, edition)?;
+    if print_program(&reparsed.nodes, style, &mut reparsed.layout(&formatted)) != formatted {
+        return Err("formatting is not stable for this file; this is a formatter bug".to_string());
+    }
+    Ok(formatted)
+}
+
+/// 1-based line of a byte offset.
+fn line_at(source: &str, byte: usize) -> u32 {
+    source[..byte].matches('\n').count() as u32 + 1
+}
+
+/// Lines (1-based, inclusive) covered by a byte range, for `--byte-range`.
+pub fn byte_range_lines(source: &str, bytes: Range<usize>) -> RangeInclusive<u32> {
+    let end = bytes.end.min(source.len());
+    let start = bytes.start.min(end);
+    line_at(source, start)..=line_at(source, end.saturating_sub(1).max(start))
+}
+
+/// Reformats only the top-level items (functions, declarations and top-level
+/// statements) that touch `lines`; every other byte, including the whitespace
+/// between items, is kept as is. Editors use this for format-on-type.
+pub fn format_lines(
+    source: &str,
+    edition: Edition,
+    style: &Style,
+    lines: RangeInclusive<u32>,
+) -> Result<String, String> {
+    with_line_endings(source, |source| format_items(source, edition, style, lines))
+}
+
+fn format_items(source: &str, edition: Edition, style: &Style, lines: RangeInclusive<u32>) -> Result<String, String> {
+    let parsed = parse(source, edition)?;
+    let mut layout = parsed.layout(source);
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    // Looked up for every item, so not counted from the start of the file each time
+    let line_starts: Vec<usize> =
+        std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let line_at = |byte: usize| line_starts.partition_point(|&start| start <= byte) as u32;
+    for (node, bytes) in parsed.nodes.iter().zip(parsed.items.iter().cloned()) {
+        let touched = line_at(bytes.start) <= *lines.end() && line_at(bytes.end) >= *lines.start();
+        if !touched {
+            continue;
+        }
+        // Top-level items start at column 0, so indentation before the item is replaced too.
+        let line_start = source[..bytes.start].rfind('\n').map_or(0, |i| i + 1);
+        let start = if source[line_start..bytes.start].trim().is_empty() { line_start } else { bytes.start };
+        // Comments around the item are copied with the source; only those inside it are printed.
+        layout.skip_to(bytes.start);
+        layout.limit = bytes.end;
+        let mut item = String::new();
+        format_statement(node, 0, style, &mut item, &mut layout);
+        out.push_str(&source[copied..start]);
+        out.push_str(item.trim_end());
+        copied = bytes.end;
+    }
+    out.push_str(&source[copied..]);
+    check_unchanged(&parsed, &out, edition)?;
+    Ok(out)
+}
+
+/// Pretty-prints a parsed program back to canonical Netflu source.
+pub fn format_program(nodes: &[ASTNode]) -> String {
+    print_program(nodes, &Style::default(), &mut Layout::default())
+}
+
+fn print_program(nodes: &[ASTNode], style: &Style, layout: &mut Layout) -> String {
+    let mut out = String::new();
+
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 && (is_definition(node) || is_definition(&nodes[i - 1])) {
+            out.push('\n');
+        }
+        format_statement(node, 0, style, &mut out, layout);
+    }
+    if layout.has_comment_before(usize::MAX) && nodes.last().is_some_and(is_definition) {
+        out.push('\n');
+    }
+    layout.leading(usize::MAX, "", &mut out);
+
+    out
+}
+
+fn is_definition(node: &ASTNode) -> bool {
+    matches!(node, ASTNode::Function { .. } | ASTNode::Enum { .. })
+}
+
+fn format_statement(node: &ASTNode, depth: usize, style: &Style, out: &mut String, layout: &mut Layout) {
+    let pad = style.indent(depth);
+    let has_block = matches!(
+        node,
+        ASTNode::Function { .. }
+            | ASTNode::If { .. }
+            | ASTNode::While { .. }
+            | ASTNode::For { .. }
+            | ASTNode::Enum { .. }
+            | ASTNode::Match { .. }
+    );
+    let bytes = layout.next_statement();
+    if let Some(bytes) = &bytes {
+        // Statements with a block print the comments inside it themselves.
+        layout.leading(if has_block { bytes.start } else { bytes.end }, &pad, out);
+    }
+
+    match node {
+        ASTNode::Function { name, params, return_type, body, public, .. } => {
+            out.push_str(&pad);
+            if *public {
+                out.push_str("pub ");
+            }
+            let mut header = String::new();
+            format_signature(name, params, return_type, &mut header);
+            if !style.fits(&format!("{}{} {{", pad, header)) && !params.is_empty() {
+                header = broken_signature(name, params, return_type, depth, style);
+            }
+            out.push_str(&header);
+            format_block(body, depth, style, out, layout);
+        }
+        ASTNode::If { .. } => {
+            out.push_str(&pad);
+            format_if(node, depth, style, out, layout);
+        }
+        ASTNode::While { condition, body, .. } => {
+            out.push_str(&format!("{}while {}", pad, format_expression(condition)));
+            format_block(body, depth, style, out, layout);
+        }
+        ASTNode::For { var, start, end, body, .. } => {
+            out.push_str(&format!("{}for {} in {}..{}", pad, var, format_expression(start), format_expression(end)));
+            format_block(body, depth, style, out, layout);
+        }
+        ASTNode::Enum { name, variants, .. } => {
+            out.push_str(&format!("{}enum {}", pad, name));
+            open_brace(depth, style, out);
+            let inner = style.indent(depth + 1);
+            for variant in variants {
+                format_item(&inner, out, layout, |out, _| out.push_str(&format!("{}{};\n", inner, variant)));
+            }
+            close_brace(depth, style, out, layout);
+        }
+        ASTNode::Match { value, arms, else_body, .. } => {
+            out.push_str(&format!("{}match {}", pad, format_expression(value)));
+            open_brace(depth, style, out);
+            let inner = style.indent(depth + 1);
+            for arm in arms {
+                format_item(&inner, out, layout, |out, layout| {
+                    out.push_str(&format!("{}{}", inner, arm.variant));
+                    if let Some(binding) = &arm.binding {
+                        out.push_str(&format!("({})", binding.name));
+                    }
+                    format_block(&arm.body, depth + 1, style, out, layout);
+                });
+            }
+            if let Some(else_body) = else_body {
+                format_item(&inner, out, layout, |out, layout| {
+                    out.push_str(&format!("{}else", inner));
+                    format_block(else_body, depth + 1, style, out, layout);
+                });
+            }
+            close_brace(depth, style, out, layout);
+        }
+        ASTNode::ExternFunction { abi, name, params, return_type, .. } => {
+            let mut header = String::new();
+            format_signature(name, params, return_type, &mut header);
+            let line = format!("{}extern {} {};", pad, abi, header);
+            if !style.fits(&line) && !params.is_empty() {
+                header = broken_signature(name, params, return_type, depth, style);
+                out.push_str(&format!("{}extern {} {};\n", pad, abi, header));
+            } else {
+                out.push_str(&format!("{}\n", line));
+            }
+        }
+        ASTNode::Let { .. }
+        | ASTNode::LetTuple { .. }
+        | ASTNode::Print { .. }
+        | ASTNode::Back { .. }
+        | ASTNode::Assign { .. }
+        | ASTNode::FunctionCall { .. }
+        | ASTNode::Identifier { .. }
+        | ASTNode::Number { .. }
+        | ASTNode::String { .. }
+        | ASTNode::Interpolation { .. }
+        | ASTNode::Array { .. }
+        | ASTNode::Map { .. }
+        | ASTNode::Tuple { .. }
+        | ASTNode::None { .. }
+        | ASTNode::Index { .. }
+        | ASTNode::MethodCall { .. }
+        | ASTNode::Field { .. }
+        | ASTNode::Binary { .. }
+        | ASTNode::Negate { .. } => {
+            let line = format!("{}{}", pad, simple_statement(node));
+            match wrap_point(node).filter(|_| !style.fits(&line)) {
+                Some((before, callee, args)) => {
+                    let end = if matches!(node, ASTNode::Back { implicit: true, .. }) { "" } else { ";" };
+                    out.push_str(&format!("{}{}{}{}\n", pad, before, broken_list(callee, &args, depth, style), end));
+                }
+                None => out.push_str(&format!("{}\n", line)),
+            }
+        }
+    }
+    if has_block && style.semicolon_after_block {
+        out.pop();
+        out.push_str(";\n");
+    }
+    if let Some(bytes) = bytes {
+        layout.trailing(bytes.end, out);
+    }
+}
+
+/// A statement without a block, on one line.
+fn simple_statement(node: &ASTNode) -> String {
+    match node {
+        ASTNode::Let { name, value, .. } => format!("let {} = {};", name, format_expression(value)),
+        ASTNode::LetTuple { bindings, value, .. } => format!("let {} = {};", tuple_pattern(bindings), format_expression(value)),
+        ASTNode::Print { value, newline, .. } => {
+            format!("{}({});", if *newline { "println" } else { "print" }, format_expression(value))
+        }
+        ASTNode::Back { value: Some(value), implicit: true, .. } => format_expression(value),
+        ASTNode::Back { value: Some(value), .. } => format!("back {};", format_expression(value)),
+        ASTNode::Back { value: None, .. } => "back;".to_string(),
+        ASTNode::Assign { name, value, .. } => format!("{} = {};", name, format_expression(value)),
+        _ => format!("{};", format_expression(node)),
+    }
+}
+
+/// Where a statement too long for `max-width` is broken: the call it is built
+/// around, as the text before the call, the callee and the arguments.
+fn wrap_point(node: &ASTNode) -> Option<(String, &str, Vec<String>)> {
+    fn call(before: String, value: &ASTNode) -> Option<(String, &str, Vec<String>)> {
+        match value {
+            ASTNode::FunctionCall { name, args, .. } if !args.is_empty() => {
+                Some((before, name.as_str(), args.iter().map(format_expression).collect()))
+            }
+            _ => None,
+        }
+    }
+    match node {
+        ASTNode::Let { name, value, .. } => call(format!("let {} = ", name), value),
+        ASTNode::LetTuple { bindings, value, .. } => call(format!("let {} = ", tuple_pattern(bindings)), value),
+        ASTNode::Assign { name, value, .. } => call(format!("{} = ", name), value),
+        ASTNode::Back { value: Some(value), implicit: true, .. } => call(String::new(), value),
+        ASTNode::Back { value: Some(value), .. } => call("back ".to_string(), value),
+        ASTNode::Print { value, newline, .. } => {
+            Some((String::new(), if *newline { "println" } else { "print" }, vec![format_expression(value)]))
+        }
+        ASTNode::FunctionCall { .. } => call(String::new(), node),
+        _ => None,
+    }
+}
+
+/// `(a, b)` of `let (a, b) = ...`.
+fn tuple_pattern(bindings: &[Binding]) -> String {
+    let names: Vec<&str> = bindings.iter().map(|b| b.name.as_str()).collect();
+    format!("({})", names.join(", "))
+}
+
+/// `name(` followed by one item per line, for calls and parameter lists that
+/// do not fit in `max-width`.
+fn broken_list(name: &str, items: &[String], depth: usize, style: &Style) -> String {
+    let inner = style.indent(depth + 1);
+    let items: Vec<String> = items.iter().map(|item| format!("{}{}", inner, item)).collect();
+    format!("{}(\n{}\n{})", name, items.join(",\n"), style.indent(depth))
+}
+
+fn broken_signature(name: &str, params: &[Param], return_type: &Option<String>, depth: usize, style: &Style) -> String {
+    let params: Vec<String> = params.iter().map(format_param).collect();
+    let mut out = broken_list(&format!("fun {}", name), &params, depth, style);
+    if let Some(ty) = return_type {
+        out.push_str(&format!(" -> {}", ty));
+    }
+    out
+}
+
+/// `if cond { ... } else ...` from the current position, chaining `else if`.
+fn format_if(node: &ASTNode, depth: usize, style: &Style, out: &mut String, layout: &mut Layout) {
+    let ASTNode::If { condition, then_body, else_body, .. } = node else {
+        unreachable!("only called for `if`");
+    };
+    out.push_str(&format!("if {}", format_expression(condition)));
+    format_block(then_body, depth, style, out, layout);
+    if else_body.is_empty() {
+        return;
+    }
+    match style.brace_style {
+        // `else` continues the line that closed the `if` block.
+        BraceStyle::SameLine => {
+            out.pop();
+            out.push_str(" else");
+        }
+        BraceStyle::NextLine => out.push_str(&format!("{}else", style.indent(depth))),
+    }
+    match else_body.as_slice() {
+        [nested @ ASTNode::If { .. }] => {
+            out.push(' ');
+            format_if(nested, depth, style, out, layout);
+        }
+        _ => format_block(else_body, depth, style, out, layout),
+    }
+}
+
+pub fn format_signature(name: &str, params: &[Param], return_type: &Option<String>, out: &mut String) {
+    let params: Vec<String> = params.iter().map(format_param).collect();
+    out.push_str(&format!("fun {}({})", name, params.join(", ")));
+    if let Some(ty) = return_type {
+        out.push_str(&format!(" -> {}", ty));
+    }
+}
+
+fn format_param(param: &Param) -> String {
+    match &param.ty {
+        Some(ty) => format!("{}: {}", param.name, ty),
+        None => param.name.clone(),
+    }
+}
+
+/// Prints an enum variant or match arm with the comments before it and at
+/// the end of its line, the way `format_statement` does for statements.
+fn format_item(pad: &str, out: &mut String, layout: &mut Layout, print: impl FnOnce(&mut String, &mut Layout)) {
+    let bytes = layout.next_statement();
+    if let Some(bytes) = &bytes {
+        layout.leading(bytes.start, pad, out);
+    }
+    print(out, layout);
+    if let Some(bytes) = bytes {
+        layout.trailing(bytes.end, out);
+    }
+}
+
+fn format_block(body: &[ASTNode], depth: usize, style: &Style, out: &mut String, layout: &mut Layout) {
+    if body.is_empty() && !layout.has_comment_before(layout.block_ends.first().copied().unwrap_or(0)) {
+        layout.next_block_end();
+        out.push_str(" {}\n");
+        return;
+    }
+
+    open_brace(depth, style, out);
+    for stmt in body {
+        format_statement(stmt, depth + 1, style, out, layout);
+    }
+    close_brace(depth, style, out, layout);
+}
+
+/// The `{` that opens a block at `depth`, ending its line.
+fn open_brace(depth: usize, style: &Style, out: &mut String) {
+    match style.brace_style {
+        BraceStyle::SameLine => out.push_str(" {\n"),
+        BraceStyle::NextLine => out.push_str(&format!("\n{}{{\n", style.indent(depth))),
+    }
+}
+
+/// The `}` of a block at `depth`, after the comments left inside it.
+fn close_brace(depth: usize, style: &Style, out: &mut String, layout: &mut Layout) {
+    let end = layout.next_block_end();
+    layout.leading(end, &style.indent(depth + 1), out);
+    out.push_str(&format!("{}}}\n", style.indent(depth)));
+}
+
+/// The operand of `[...]` or `.`, which bind tighter than any operator, `-` included.
+fn postfix_operand(node: &ASTNode) -> String {
+    let text = format_expression(node);
+    match node {
+        ASTNode::Binary { .. } | ASTNode::Negate { .. } => format!("({})", text),
+        _ => text,
+    }
+}
+
+pub fn format_expression(node: &ASTNode) -> String {
+    match node {
+        ASTNode::FunctionCall { name, args, .. } => {
+            let args: Vec<String> = args.iter().map(format_expression).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        ASTNode::Identifier { name, .. } => name.clone(),
+        ASTNode::None { .. } => "none".to_string(),
+        ASTNode::Number { value } | ASTNode::String { value } | ASTNode::Interpolation { value, .. } => value.clone(),
+        ASTNode::Binary { op, left, right, .. } => {
+            let operand = |node: &ASTNode, right: bool| {
+                let text = format_expression(node);
+                if compile::needs_parens(node, *op, right) { format!("({})", text) } else { text }
+            };
+            format!("{} {} {}", operand(left, false), op.symbol(), operand(right, true))
+        }
+        ASTNode::Negate { operand, .. } => {
+            let text = format_expression(operand);
+            if compile::negate_needs_parens(operand) { format!("-({})", text) } else { format!("-{}", text) }
+        }
+        ASTNode::Array { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(format_expression).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        ASTNode::Tuple { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(format_expression).collect();
+            format!("({})", elements.join(", "))
+        }
+        ASTNode::Map { entries, .. } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| format!("{}: {}", format_expression(key), format_expression(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        ASTNode::Index { collection, index, .. } => {
+            format!("{}[{}]", postfix_operand(collection), format_expression(index))
+        }
+        ASTNode::MethodCall { receiver, name, args, .. } => {
+            let args: Vec<String> = args.iter().map(format_expression).collect();
+            format!("{}.{}({})", postfix_operand(receiver), name, args.join(", "))
+        }
+        ASTNode::Field { object, name, .. } => format!("{}.{}", postfix_operand(object), name),
+        _ => {
+            let mut stmt = String::new();
+            format_statement(node, 0, &Style::default(), &mut stmt, &mut Layout::default());
+            stmt.trim_end().to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source, Edition::LATEST, &Style::default()).unwrap()
+    }
+
+    #[test]
+    fn comment_on_its_own_line_stays_there() {
+        let source = "fun a() {\n    print(1); // one\n}\n\n// note about b\nfun b() {\n    print(2);\n}\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn comment_at_end_of_file_stays_on_its_own_line() {
+        assert_eq!(format("fun a() {\n    print(1);\n}\n// eof comment\n"), "fun a() {\n    print(1);\n}\n\n// eof comment\n");
+    }
+
+    #[test]
+    fn comments_stay_with_enum_variants() {
+        assert_eq!(format("enum C { R; /* r */ G; // g\n}\n"), "enum C {\n    R; /* r */\n    G; // g\n}\n");
+    }
+
+    #[test]
+    fn comments_stay_with_match_arms() {
+        let source = "enum C {\n    R;\n    G;\n}\n\nfun f(c: C) {\n    match c {\n        // red\n        R {\n            print(1);\n        } // arm\n        // the rest\n        else {\n            print(2);\n        } // else\n    }\n}\n";
+        assert_eq!(format(source), source);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::compile;
+use regex::Regex;
+use std::path::Path;
+
+/// 可以用`extern rust`声明的Rust函数
+#[derive(Debug)]
+pub struct Binding {
+    pub name: String,
+    pub params: Vec<String>,
+    pub return_type: Option<&'static str>,
+}
+
+impl Binding {
+    pub fn declaration(&self) -> String {
+        let mut decl = format!("extern rust fun {}({})", self.name, self.params.join(", "));
+        if let Some(ty) = self.return_type {
+            decl.push_str(&format!(" -> {}", ty));
+        }
+        decl.push(';');
+        decl
+    }
+}
+
+// Netflu keywords can't name a function; Rust keywords can, since codegen writes them as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "let", "print", "println", "method", "fun", "back", "extern", "pub", "if", "else", "while", "for", "in", "enum", "match",
+];
+
+fn netflu_return_type(rust: &str) -> Result<Option<&'static str>, String> {
+    match rust.split_whitespace().collect::<String>().as_str() {
+        "" | "()" => Ok(None),
+        "i32" => Ok(Some("int")),
+        "String" => Ok(Some("str")),
+        other => Err(format!("不支持的返回类型 `{}`", other)),
+    }
+}
+
+fn parse_params(params: &str) -> Result<Vec<String>, String> {
+    let param = Regex::new(r"^(?:mut\s+)?(?:r#)?([A-Za-z_][A-Za-z0-9_]*)\s*:\s*(.+)$").unwrap();
+    params
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match param.captures(p) {
+            Some(caps) => match caps[2].split_whitespace().collect::<String>().as_str() {
+                "i32" => Ok(caps[1].to_string()),
+                "String" => Ok(format!("{}: str", &caps[1])),
+                other => Err(format!("参数 `{}` 的类型 `{}` 不支持 (只支持i32和String)", &caps[1], other)),
+            },
+            None => Err(format!("无法识别的参数 `{}`", p)),
+        })
+        .collect()
+}
+
+/// 提取`pub fn`签名，返回可以绑定的函数和跳过的函数 (附原因)
+pub fn extract(source: &str) -> (Vec<Binding>, Vec<(String, String)>) {
+    let signature = Regex::new(
+        r"(?m)^[ \t]*pub[ \t]+((?:const|async|unsafe|extern[ \t]+\S+)[ \t]+)*fn[ \t]+(?:r#)?([A-Za-z_][A-Za-z0-9_]*)[ \t]*(<[^>]*>)?[ \t]*\(([^)]*)\)[ \t]*(?:->([^{;]*?))?[ \t]*(where[^{]*)?\{",
+    )
+    .unwrap();
+
+    let mut bindings = Vec::new();
+    let mut skipped = Vec::new();
+    for caps in signature.captures_iter(source) {
+        let name = caps[2].to_string();
+        let checked = if caps.get(1).is_some() {
+            Err("不支持带修饰符的函数".to_string())
+        } else if caps.get(3).is_some() || caps.get(6).is_some() {
+            Err("不支持泛型函数".to_string())
+        } else if KEYWORDS.contains(&name.as_str()) {
+            Err("名称是Netflu关键字".to_string())
+        } else if compile::RESERVED_WORDS.iter().any(|(word, _)| *word == name) {
+            Err("名称是Netflu保留字".to_string())
+        } else {
+            parse_params(&caps[4]).and_then(|params| {
+                let return_type = netflu_return_type(caps.get(5).map_or("", |m| m.as_str()))?;
+                Ok(Binding { name: name.clone(), params, return_type })
+            })
+        };
+
+        match checked {
+            Ok(binding) => bindings.push(binding),
+            Err(reason) => skipped.push((name, reason)),
+        }
+    }
+    (bindings, skipped)
+}
+
+/// 由文件名得到合法的Rust模块名
+pub fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("ffi")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
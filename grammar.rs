@@ -0,0 +1,446 @@
+use crate::compile::{token_type_to_name, TokenType, TOKEN_SPECS};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum GrammarFormat {
+    /// 扩展巴科斯范式文本
+    Ebnf,
+    /// 可在浏览器中查看的铁路图 (HTML)
+    Railroad,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Token(TokenType),
+    Rule(&'static str),
+    Seq(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    Repeat(Box<Expr>),
+}
+
+pub struct Rule {
+    pub name: &'static str,
+    pub expr: Expr,
+}
+
+fn tok(t: TokenType) -> Expr {
+    Expr::Token(t)
+}
+
+fn rule(name: &'static str) -> Expr {
+    Expr::Rule(name)
+}
+
+fn seq(items: Vec<Expr>) -> Expr {
+    Expr::Seq(items)
+}
+
+fn choice(items: Vec<Expr>) -> Expr {
+    Expr::Choice(items)
+}
+
+fn opt(item: Expr) -> Expr {
+    Expr::Optional(Box::new(item))
+}
+
+fn many(item: Expr) -> Expr {
+    Expr::Repeat(Box::new(item))
+}
+
+/// The grammar accepted by `compile::Parser`, one entry per parse function.
+pub fn rules() -> Vec<Rule> {
+    use TokenType::*;
+
+    let block = || {
+        seq(vec![
+            tok(LBrace),
+            many(choice(vec![rule("statement"), tok(Semicolon)])),
+            tok(RBrace),
+            opt(tok(Semicolon)),
+        ])
+    };
+
+    // From edition 2026, an expression before the `}` is the return value.
+    let function_body = || {
+        seq(vec![
+            tok(LBrace),
+            many(choice(vec![rule("statement"), tok(Semicolon)])),
+            opt(rule("expression")),
+            tok(RBrace),
+            opt(tok(Semicolon)),
+        ])
+    };
+
+    let param = || seq(vec![tok(Identifier), opt(seq(vec![tok(Colon), rule("type")]))]);
+    let signature = || {
+        vec![
+            tok(Fun),
+            tok(Identifier),
+            opt(seq(vec![
+                tok(LParen),
+                opt(seq(vec![param(), many(seq(vec![tok(Comma), param()])), opt(tok(Comma))])),
+                tok(RParen),
+            ])),
+            opt(seq(vec![tok(Arrow), rule("type")])),
+        ]
+    };
+
+    vec![
+        Rule {
+            name: "program",
+            expr: many(rule("statement")),
+        },
+        Rule {
+            name: "statement",
+            expr: choice(vec![
+                rule("let_stmt"),
+                rule("print_stmt"),
+                rule("method_def"),
+                rule("fun_def"),
+                rule("back_stmt"),
+                rule("extern_decl"),
+                rule("if_stmt"),
+                rule("while_stmt"),
+                rule("for_stmt"),
+                rule("enum_def"),
+                rule("match_stmt"),
+                rule("assign_stmt"),
+                rule("call_stmt"),
+            ]),
+        },
+        Rule {
+            name: "let_stmt",
+            expr: seq(vec![
+                tok(Let),
+                choice(vec![
+                    tok(Identifier),
+                    seq(vec![
+                        tok(LParen),
+                        tok(Identifier),
+                        many(seq(vec![tok(Comma), tok(Identifier)])),
+                        opt(tok(Comma)),
+                        tok(RParen),
+                    ]),
+                ]),
+                tok(Assign),
+                rule("expression"),
+                tok(Semicolon),
+            ]),
+        },
+        Rule {
+            name: "print_stmt",
+            expr: seq(vec![
+                choice(vec![tok(Print), tok(Println)]),
+                tok(LParen),
+                rule("expression"),
+                tok(RParen),
+                tok(Semicolon),
+            ]),
+        },
+        Rule {
+            name: "type",
+            // A trailing `?` makes the type optional; optionals cannot be nested.
+            expr: seq(vec![
+                choice(vec![
+                    tok(Identifier),
+                    seq(vec![tok(LBracket), tok(Identifier), tok(RBracket)]),
+                    seq(vec![tok(LBrace), tok(Identifier), tok(Colon), tok(Identifier), tok(RBrace)]),
+                    seq(vec![
+                        tok(LParen),
+                        rule("type"),
+                        many(seq(vec![tok(Comma), rule("type")])),
+                        opt(tok(Comma)),
+                        tok(RParen),
+                    ]),
+                ]),
+                opt(tok(Question)),
+            ]),
+        },
+        Rule {
+            name: "method_def",
+            expr: seq(vec![tok(Method), tok(Identifier), block()]),
+        },
+        Rule {
+            name: "fun_def",
+            expr: seq([vec![opt(tok(Pub))], signature(), vec![function_body()]].concat()),
+        },
+        Rule {
+            name: "back_stmt",
+            expr: seq(vec![tok(Back), opt(rule("expression")), tok(Semicolon)]),
+        },
+        Rule {
+            name: "extern_decl",
+            expr: seq([vec![tok(Extern), tok(Identifier)], signature(), vec![tok(Semicolon)]].concat()),
+        },
+        Rule {
+            name: "if_stmt",
+            expr: seq(vec![
+                tok(If),
+                rule("expression"),
+                block(),
+                opt(seq(vec![tok(Else), choice(vec![rule("if_stmt"), block()])])),
+            ]),
+        },
+        Rule {
+            name: "while_stmt",
+            expr: seq(vec![tok(While), rule("expression"), block()]),
+        },
+        Rule {
+            name: "for_stmt",
+            expr: seq(vec![tok(For), tok(Identifier), tok(In), rule("expression"), tok(DotDot), rule("expression"), block()]),
+        },
+        Rule {
+            name: "enum_def",
+            // Only at the top level.
+            expr: seq(vec![
+                tok(Enum),
+                tok(Identifier),
+                tok(LBrace),
+                tok(Identifier),
+                many(seq(vec![tok(Semicolon), tok(Identifier)])),
+                opt(tok(Semicolon)),
+                tok(RBrace),
+                opt(tok(Semicolon)),
+            ]),
+        },
+        Rule {
+            name: "match_stmt",
+            expr: seq(vec![
+                tok(Match),
+                rule("expression"),
+                tok(LBrace),
+                many(seq(vec![tok(Identifier), opt(seq(vec![tok(LParen), tok(Identifier), tok(RParen)])), block()])),
+                opt(seq(vec![tok(Else), block()])),
+                tok(RBrace),
+                opt(tok(Semicolon)),
+            ]),
+        },
+        Rule {
+            name: "assign_stmt",
+            expr: seq(vec![tok(Identifier), tok(Assign), rule("expression"), tok(Semicolon)]),
+        },
+        Rule {
+            name: "call_stmt",
+            // The last postfix, if any, must be a method call.
+            expr: seq(vec![choice(vec![rule("call"), tok(Identifier)]), many(rule("postfix")), tok(Semicolon)]),
+        },
+        Rule {
+            name: "call",
+            expr: seq(vec![tok(Identifier), rule("arguments")]),
+        },
+        Rule {
+            name: "arguments",
+            expr: seq(vec![
+                tok(LParen),
+                opt(seq(vec![
+                    rule("expression"),
+                    // Edition 2025 also accepts `;` and `+` here, with a deprecation warning.
+                    many(seq(vec![tok(Comma), rule("expression")])),
+                    opt(tok(Comma)),
+                ])),
+                tok(RParen),
+            ]),
+        },
+        Rule {
+            name: "expression",
+            // Comparisons don't chain: `a < b < c` is an error.
+            expr: seq(vec![
+                rule("sum"),
+                opt(seq(vec![
+                    choice(vec![tok(Equal), tok(NotEqual), tok(Less), tok(Greater), tok(LessEqual), tok(GreaterEqual)]),
+                    rule("sum"),
+                ])),
+            ]),
+        },
+        Rule {
+            name: "sum",
+            // Edition 2025 call arguments keep `+` as a separator; `( a + b )` still adds.
+            expr: seq(vec![rule("term"), many(seq(vec![choice(vec![tok(Plus), tok(Minus)]), rule("term")]))]),
+        },
+        Rule {
+            name: "term",
+            expr: seq(vec![rule("factor"), many(seq(vec![choice(vec![tok(Multiply), tok(Divide)]), rule("factor")]))]),
+        },
+        Rule {
+            name: "factor",
+            expr: choice(vec![
+                seq(vec![rule("operand"), many(rule("postfix"))]),
+                seq(vec![tok(Minus), rule("factor")]),
+            ]),
+        },
+        Rule {
+            name: "postfix",
+            expr: choice(vec![
+                seq(vec![tok(LBracket), rule("expression"), tok(RBracket)]),
+                seq(vec![tok(Dot), tok(Identifier), opt(rule("arguments"))]),
+            ]),
+        },
+        Rule {
+            name: "operand",
+            expr: choice(vec![
+                rule("call"),
+                tok(Identifier),
+                tok(Number),
+                tok(String),
+                rule("group"),
+                rule("array"),
+                rule("map"),
+            ]),
+        },
+        Rule {
+            name: "group",
+            // One expression only groups; more make a tuple.
+            expr: seq(vec![
+                tok(LParen),
+                rule("expression"),
+                many(seq(vec![tok(Comma), rule("expression")])),
+                opt(tok(Comma)),
+                tok(RParen),
+            ]),
+        },
+        Rule {
+            name: "array",
+            expr: seq(vec![
+                tok(LBracket),
+                opt(seq(vec![
+                    rule("expression"),
+                    many(seq(vec![tok(Comma), rule("expression")])),
+                    opt(tok(Comma)),
+                ])),
+                tok(RBracket),
+            ]),
+        },
+        Rule {
+            name: "map",
+            expr: seq(vec![
+                tok(LBrace),
+                opt(seq(vec![
+                    rule("entry"),
+                    many(seq(vec![tok(Comma), rule("entry")])),
+                    opt(tok(Comma)),
+                ])),
+                tok(RBrace),
+            ]),
+        },
+        Rule {
+            name: "entry",
+            expr: seq(vec![rule("expression"), tok(Colon), rule("expression")]),
+        },
+    ]
+}
+
+fn token_pattern(t: &TokenType) -> &'static str {
+    TOKEN_SPECS
+        .iter()
+        .find(|(spec, _)| spec == t)
+        .map(|(_, pattern)| *pattern)
+        .unwrap_or("")
+}
+
+/// Fixed tokens are rendered as quoted literals, pattern tokens by name.
+fn token_literal(t: &TokenType) -> Option<String> {
+    let pattern = token_pattern(t);
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // `\b` only marks the end of a keyword
+            '\\' => match chars.next()? {
+                'b' => {}
+                c => literal.push(c),
+            },
+            '[' | ']' | '.' | '*' | '+' | '?' | '(' | ')' | '{' | '}' | '|' | '^' | '$' => return None,
+            _ => literal.push(c),
+        }
+    }
+    Some(literal)
+}
+
+fn ebnf_expr(expr: &Expr, nested: bool) -> String {
+    match expr {
+        Expr::Token(t) => match token_literal(t) {
+            Some(literal) => format!("\"{}\"", literal),
+            None => token_type_to_name(t).to_string(),
+        },
+        Expr::Rule(name) => name.to_string(),
+        Expr::Seq(items) => items.iter().map(|e| ebnf_expr(e, true)).collect::<Vec<_>>().join(" "),
+        Expr::Choice(items) => {
+            let body = items.iter().map(|e| ebnf_expr(e, false)).collect::<Vec<_>>().join(" | ");
+            if nested { format!("( {} )", body) } else { body }
+        }
+        Expr::Optional(item) => format!("[ {} ]", ebnf_expr(item, false)),
+        Expr::Repeat(item) => format!("{{ {} }}", ebnf_expr(item, false)),
+    }
+}
+
+/// EBNF of a single rule, e.g. for the language reference.
+pub fn rule_ebnf(name: &str) -> Option<String> {
+    rules()
+        .iter()
+        .find(|r| r.name == name)
+        .map(|r| format!("{} = {} ;", r.name, ebnf_expr(&r.expr, false)))
+}
+
+pub fn to_ebnf() -> String {
+    let rules = rules();
+    let width = rules.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let mut out = String::from("(* Netflu grammar, generated by `ntfp grammar` *)\n\n");
+
+    for r in &rules {
+        out.push_str(&format!("{:<width$} = {} ;\n", r.name, ebnf_expr(&r.expr, false), width = width));
+    }
+
+    out.push_str("\n(* Terminals *)\n");
+    for (t, pattern) in TOKEN_SPECS {
+        if *t != TokenType::Mismatch && token_literal(t).is_none() {
+            out.push_str(&format!("{:<width$} = ? /{}/ ? ;\n", token_type_to_name(t), pattern, width = width));
+        }
+    }
+    out
+}
+
+fn js_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn railroad_expr(expr: &Expr) -> String {
+    let list = |items: &[Expr]| items.iter().map(railroad_expr).collect::<Vec<_>>().join(", ");
+    match expr {
+        Expr::Token(t) => match token_literal(t) {
+            Some(literal) => format!("Terminal({})", js_string(&literal)),
+            None => format!("Terminal({})", js_string(token_type_to_name(t))),
+        },
+        Expr::Rule(name) => format!("NonTerminal({})", js_string(name)),
+        Expr::Seq(items) => format!("Sequence({})", list(items)),
+        Expr::Choice(items) => format!("Choice(0, {})", list(items)),
+        Expr::Optional(item) => format!("Optional({})", railroad_expr(item)),
+        Expr::Repeat(item) => format!("ZeroOrMore({})", railroad_expr(item)),
+    }
+}
+
+pub fn to_railroad_html() -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Netflu grammar</title>\n\
+         <link rel=\"stylesheet\" href=\"https://cdn.jsdelivr.net/npm/railroad-diagrams@1.0.0/railroad-diagrams.css\">\n\
+         <script src=\"https://cdn.jsdelivr.net/npm/railroad-diagrams@1.0.0/railroad-diagrams.js\"></script>\n\
+         </head>\n<body>\n",
+    );
+
+    for r in rules() {
+        out.push_str(&format!(
+            "<h2 id=\"{name}\">{name}</h2>\n<div><script>Diagram({expr}).addTo();</script></div>\n",
+            name = r.name,
+            expr = railroad_expr(&r.expr)
+        ));
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub fn render(format: GrammarFormat) -> String {
+    match format {
+        GrammarFormat::Ebnf => to_ebnf(),
+        GrammarFormat::Railroad => to_railroad_html(),
+    }
+}
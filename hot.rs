@@ -0,0 +1,90 @@
+use crate::compile::{ASTNode, Edition, Feature};
+use crate::interp::{self, Engine};
+use crate::manifest::Manifest;
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::debug;
+
+/// 两次检查源文件之间的最短间隔，避免每次函数调用都访问文件系统
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct Watcher {
+    source: PathBuf,
+    edition: Edition,
+    disabled: HashSet<Feature>,
+    top_level_statements: bool,
+    /// 每次重新加载都要声明其中的`[config]`常量
+    manifest: Manifest,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    fn poll(&mut self) -> Option<Vec<ASTNode>> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = fs::metadata(&self.source).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        debug!(source = ?self.source, "source changed");
+
+        let loaded = fs::read_to_string(&self.source)
+            .with_context(|| format!("无法读取文件: {:?}", self.source))
+            .and_then(|source| {
+                let analyzer = self.manifest.analyzer()?;
+                interp::load_program_with(&source, self.edition, &self.disabled, self.top_level_statements, analyzer)
+            });
+        match loaded {
+            Ok(program) => {
+                eprintln!("已重新加载: {:?}", self.source);
+                Some(program)
+            }
+            Err(e) => {
+                eprintln!("警告: 重新加载失败，继续使用旧代码: {:#}", e);
+                None
+            }
+        }
+    }
+}
+
+/// 用解释器运行项目，源文件修改后替换函数定义而不重启程序
+pub fn run_hot(project_path: &Path, engine: Engine) -> Result<()> {
+    let manifest = Manifest::load(project_path)?;
+    let edition = manifest.edition()?;
+    let disabled = manifest.disabled_features()?;
+    let source = project_path.join("src").join("main.ntf");
+
+    let modified = fs::metadata(&source).and_then(|m| m.modified()).ok();
+    let content = fs::read_to_string(&source)
+        .with_context(|| format!("无法读取文件: {:?}", source))?;
+    let top_level_statements = manifest.language.top_level_statements;
+    let overflow = manifest.profile.overflow(false);
+    let program = interp::load_program_with(&content, edition, &disabled, top_level_statements, manifest.analyzer()?)?;
+
+    eprintln!(
+        "正在以热重载模式运行项目: {} (解释执行, 引擎: {})",
+        manifest.package.name,
+        engine.name()
+    );
+    let mut watcher = Watcher {
+        source,
+        edition,
+        disabled,
+        top_level_statements,
+        manifest,
+        modified,
+        last_poll: Instant::now(),
+    };
+    interp::execute(&program, engine, overflow, Some(Box::new(move || watcher.poll())))
+        .map_err(|e| anyhow::anyhow!("运行错误: {}", e))
+}
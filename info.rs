@@ -0,0 +1,66 @@
+use crate::manifest::Manifest;
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+fn print_list(label: &str, items: &[String]) {
+    if items.is_empty() {
+        println!("{}: (无)", label);
+    } else {
+        println!("{}: {}", label, items.join(", "));
+    }
+}
+
+/// 输出项目的概要: 包信息、构建目标、外部依赖和源文件统计
+pub fn show(project_path: &Path) -> Result<()> {
+    let manifest = Manifest::load(project_path)?;
+    let package = &manifest.package;
+
+    println!("包: {} v{}", package.name, package.version);
+    println!("路径: {}", project_path.display());
+    println!("edition: {}", manifest.edition()?.as_str());
+    if let Some(version) = &package.min_ntfp_version {
+        println!("ntfp版本: >= {}", version);
+    }
+
+    match manifest.library_name()? {
+        Some(name) => println!("目标: 库 (cdylib) target/<profile>/{}", name),
+        None => println!("目标: 可执行文件 target/<profile>/{}", manifest.binary_name()?),
+    }
+
+    // Netflu没有包依赖，项目依赖的外部代码都在ntfp.toml中列出
+    print_list("Rust源文件", &manifest.rust.sources);
+    print_list("C库", &manifest.c.link);
+    if let Some(allow) = &manifest.language.allow {
+        print_list("允许的特性", allow);
+    }
+    if !manifest.language.deny.is_empty() {
+        print_list("禁用的特性", &manifest.language.deny);
+    }
+    let plugins: Vec<String> = manifest.plugins.iter().map(|(name, path)| format!("{} ({})", name, path)).collect();
+    if !plugins.is_empty() {
+        print_list("编译器插件", &plugins);
+    }
+    let lints: Vec<String> = manifest.lints.iter().map(|(name, level)| format!("{} = {}", name, level)).collect();
+    if !lints.is_empty() {
+        print_list("lint", &lints);
+    }
+
+    if let Some(include) = &package.include {
+        print_list("include", include);
+    }
+    if !package.exclude.is_empty() {
+        print_list("exclude", &package.exclude);
+    }
+
+    let files = manifest.sources()?.files(project_path)?;
+    let mut lines = 0;
+    for file in &files {
+        let file = project_path.join(file);
+        lines += fs::read_to_string(&file)
+            .with_context(|| format!("无法读取文件: {:?}", file))?
+            .lines()
+            .count();
+    }
+    println!("源文件: {} 个文件, {} 行", files.len(), lines);
+    Ok(())
+}
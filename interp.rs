@@ -0,0 +1,816 @@
+use crate::compile::{self, ASTNode, BinaryOp, Edition, Feature, Method, Overflow, SemanticAnalyzer, StringPiece, Type, Warning};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use crate::vm::Vm;
+use tracing::debug;
+
+/// Runtime value; mirrors `compile::Type` so interpreted programs print what
+/// the generated Rust would.
+///
+/// Every value has copy semantics, and cloning never copies text or allocates:
+/// - `int` is copied.
+/// - `str` is immutable and shared through `Rc<str>`. Generated code gives each
+///   binding its own `String`, cloned where a name is used again; nothing can
+///   mutate a string in place, so sharing one here is unobservable.
+/// - Arrays are immutable too, and shared through `Rc<[_]>` where generated
+///   code clones a `Vec`.
+/// - Maps likewise share an immutable `Rc<HashMap<_, _>>` where generated code
+///   clones a `HashMap`.
+/// - Enum values are `Copy` in generated code; here they share the names of
+///   their enum and variant.
+/// - Tuples are cloned in generated code unless all their elements are `Copy`;
+///   here they share an immutable `Rc<[_]>` of them.
+/// - Optionals are cloned like the value inside; here they share it through an
+///   `Rc`, and keep their type so that `none` has one.
+///
+/// Mutable collections must follow their Rust lowering: a collection that is
+/// shared by reference in generated code is an `Rc<RefCell<_>>` here, one that
+/// is moved or cloned there is cloned here. Mixing the two is how interpreted
+/// and compiled output drift apart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Str(Rc<str>),
+    Unit,
+    IntArray(Rc<[i32]>),
+    StrArray(Rc<[Rc<str>]>),
+    /// The map type, which an entry alone cannot tell apart from the others.
+    Map(Type, Rc<HashMap<Key, Value>>),
+    /// A variant of the enum with this name.
+    Enum(Rc<str>, Rc<str>),
+    Tuple(Rc<[Value]>),
+    /// A value of this optional type: `some` of the value inside, or `none`.
+    Optional(Type, Option<Rc<Value>>),
+}
+
+/// A map key; maps are keyed by int or str.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Key {
+    Int(i32),
+    Str(Rc<str>),
+}
+
+impl Key {
+    fn from_value(value: &Value) -> Result<Key, String> {
+        match value {
+            Value::Int(n) => Ok(Key::Int(*n)),
+            Value::Str(s) => Ok(Key::Str(Rc::clone(s))),
+            other => Err(format!("map keys must be int or str, got {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Int(n) => write!(f, "{}", n),
+            Key::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Int(_) => Type::Int,
+            Value::Str(_) => Type::Str,
+            Value::Unit => Type::Unit,
+            Value::IntArray(_) => Type::IntArray,
+            Value::StrArray(_) => Type::StrArray,
+            Value::Map(ty, _) => ty.clone(),
+            Value::Enum(name, _) => Type::Enum(name.clone()),
+            Value::Tuple(elements) => {
+                let types: Vec<Type> = elements.iter().map(Value::ty).collect();
+                Type::tuple_of(&types).expect("tuples have at least two elements, none of them a tuple")
+            }
+            Value::Optional(ty, _) => ty.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Unit => write!(f, "()"),
+            // Like Rust's `{:?}` of a slice, which is how generated code prints arrays.
+            Value::IntArray(elements) => write!(f, "{:?}", elements),
+            Value::StrArray(elements) => write!(f, "{:?}", elements),
+            // Generated code cannot print maps; sorted here so that errors and
+            // debugging output are stable.
+            Value::Map(_, entries) => {
+                let mut keys: Vec<&Key> = entries.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    match &entries[key] {
+                        Value::Str(s) => write!(f, "{}{}: {:?}", separator, key, s)?,
+                        value => write!(f, "{}{}: {}", separator, key, value)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            // The variant name, like the derived `Debug` of generated code.
+            Value::Enum(_, variant) => write!(f, "{}", variant),
+            // Like Rust's `{:?}` of a tuple, which quotes the strings in it.
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    match element {
+                        Value::Str(s) => write!(f, "{}{:?}", separator, s)?,
+                        element => write!(f, "{}{}", separator, element)?,
+                    }
+                }
+                write!(f, ")")
+            }
+            // Like Rust's `{:?}` of an `Option`.
+            Value::Optional(_, None) => write!(f, "None"),
+            Value::Optional(_, Some(value)) => match &**value {
+                Value::Str(s) => write!(f, "Some({:?})", s),
+                value => write!(f, "Some({})", value),
+            },
+        }
+    }
+}
+
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+/// Which engine runs interpreted programs. The AST walker is the reference
+/// implementation; the bytecode VM is the default because it is faster.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Engine {
+    Ast,
+    #[default]
+    Bytecode,
+}
+
+impl Engine {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Ast => "ast",
+            Engine::Bytecode => "bytecode",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Engine> {
+        [Engine::Ast, Engine::Bytecode].into_iter().find(|e| e.name() == s)
+    }
+}
+
+/// Runs `main` of an analyzed program with the chosen engine, handling int
+/// overflow like code generated for the same `overflow`.
+pub fn execute(nodes: &[ASTNode], engine: Engine, overflow: Overflow, reload: Option<ReloadHook>) -> Result<(), String> {
+    match (engine, reload) {
+        (Engine::Ast, None) => Interpreter::new(nodes).with_overflow(overflow).run(),
+        (Engine::Ast, Some(hook)) => Interpreter::new(nodes).with_overflow(overflow).with_reload(hook).run(),
+        (Engine::Bytecode, None) => Vm::new(nodes).with_overflow(overflow).run(),
+        (Engine::Bytecode, Some(hook)) => Vm::new(nodes).with_overflow(overflow).with_reload(hook).run(),
+    }
+}
+
+/// Calls `name` of an analyzed program with the chosen engine and returns its
+/// result. Calls to functions the program does not define go to `natives`.
+pub fn call(
+    nodes: &[ASTNode],
+    engine: Engine,
+    overflow: Overflow,
+    natives: HashMap<String, Native>,
+    name: &str,
+    args: Vec<Value>,
+) -> Result<Value, String> {
+    match engine {
+        Engine::Ast => Interpreter::new(nodes).with_overflow(overflow).with_natives(natives).call(name, args),
+        Engine::Bytecode => Vm::new(nodes).with_overflow(overflow).with_natives(natives).call(name, args),
+    }
+}
+
+/// Polled before every call; returning a program swaps in its functions.
+pub type ReloadHook = Box<dyn FnMut() -> Option<Vec<ASTNode>>>;
+
+/// A function implemented in Rust by the program embedding the interpreter,
+/// see `embed::Runtime::register`. Arguments have already been type checked.
+pub type Native = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+/// Tree-walking interpreter for analyzed programs.
+pub struct Interpreter {
+    functions: HashMap<String, Rc<ASTNode>>,
+    /// Block scopes of every active call, innermost last.
+    scopes: Vec<HashMap<String, Value>>,
+    /// Index of the running function's outermost scope; names below it belong to callers.
+    base: usize,
+    reload: Option<ReloadHook>,
+    natives: HashMap<String, Native>,
+    overflow: Overflow,
+}
+
+/// Runs the front end over `source` for programs executed by the interpreter.
+/// `script` wraps top-level statements into `main`, see `compile::lower_top_level`.
+pub fn load_program(
+    source: &str,
+    edition: Edition,
+    disabled: &HashSet<Feature>,
+    script: bool,
+) -> anyhow::Result<Vec<ASTNode>> {
+    load_program_with(source, edition, disabled, script, SemanticAnalyzer::new())
+}
+
+/// `load_program` with an analyzer that may already know functions provided
+/// by the host, see `SemanticAnalyzer::declare_host_function`.
+pub fn load_program_with(
+    source: &str,
+    edition: Edition,
+    disabled: &HashSet<Feature>,
+    script: bool,
+    analyzer: SemanticAnalyzer,
+) -> anyhow::Result<Vec<ASTNode>> {
+    load_program_with_warnings(source, edition, disabled, script, analyzer).map(|(ast, _)| ast)
+}
+
+/// `load_program_with`, also returning the warnings of the parser and the
+/// analyzer so the caller can report them.
+pub fn load_program_with_warnings(
+    source: &str,
+    edition: Edition,
+    disabled: &HashSet<Feature>,
+    script: bool,
+    mut analyzer: SemanticAnalyzer,
+) -> anyhow::Result<(Vec<ASTNode>, Vec<Warning>)> {
+    let tokens = compile::lexer(source).map_err(|e| anyhow::anyhow!("词法分析错误: {}", e))?;
+    let mut parser = compile::Parser::with_edition(tokens, edition);
+    parser.disabled = disabled.clone();
+    let ast = parser.parse().map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))?;
+    let mut ast = compile::lower_top_level(ast, script).map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))?;
+    analyzer
+        .analyze(&mut ast)
+        .map_err(|e| anyhow::anyhow!("语义分析错误: {}", e))?;
+    let mut warnings = parser.warnings;
+    warnings.append(&mut analyzer.warnings);
+    Ok((ast, warnings))
+}
+
+/// The decoded texts around the placeholders of an interpolated literal; one
+/// more than there are placeholders.
+pub fn interpolation_texts(literal: &str) -> Result<Vec<String>, String> {
+    compile::string_pieces(literal)?
+        .into_iter()
+        .filter_map(|piece| match piece {
+            StringPiece::Text(text) => Some(text),
+            StringPiece::Name(..) => None,
+        })
+        .map(|text| Ok(unquote(&format!("\"{}\"", text))?.replace("{{", "{").replace("}}", "}")))
+        .collect()
+}
+
+/// Joins the texts of an interpolated literal with the placeholder values.
+pub fn interpolate(texts: &[String], values: &[Value]) -> Value {
+    let mut out = texts[0].clone();
+    for (value, text) in values.iter().zip(&texts[1..]) {
+        out.push_str(&value.to_string());
+        out.push_str(text);
+    }
+    Value::Str(out.into())
+}
+
+/// Decodes a string literal token (quotes included) the way rustc would.
+pub fn unquote(literal: &str) -> Result<String, String> {
+    let inner = &literal[1..literal.len() - 1];
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        // A line break inside the literal is `\n`, also in files with Windows line endings
+        if c == '\r' && chars.clone().next() == Some('\n') {
+            continue;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            Some('\'') => out.push('\''),
+            Some(other) => return Err(format!("Unsupported escape sequence: \\{}", other)),
+            None => return Err("Unterminated escape sequence".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+/// Applies a binary operator to evaluated operands; shared with the VM.
+pub fn binary(op: BinaryOp, left: Value, right: Value, overflow: Overflow) -> Result<Value, String> {
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) => op.apply(a, b, overflow).map(Value::Int),
+        (Value::Str(a), Value::Str(b)) if op == BinaryOp::Add => Ok(Value::Str(format!("{}{}", a, b).into())),
+        (Value::Str(a), Value::Str(b)) if op.is_comparison() => Ok(Value::Int(i32::from(op.holds(a.cmp(&b))))),
+        (a, b) if op.is_comparison() => Err(format!("`{}` cannot compare {} and {}", op.symbol(), a, b)),
+        (a, b) if op == BinaryOp::Add => Err(format!("`+` joins two str values or adds two int values, got {} and {}", a, b)),
+        (a, b) => Err(format!("`{}` only works on int values, got {} and {}", op.symbol(), a, b)),
+    }
+}
+
+/// Builds an array from its evaluated elements, which the analyzer checked
+/// are all int or all str; shared with the VM.
+pub fn array(elements: Vec<Value>) -> Result<Value, String> {
+    let ints: Option<Rc<[i32]>> = elements
+        .iter()
+        .map(|element| match element {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        })
+        .collect();
+    let strs: Option<Rc<[Rc<str>]>> = elements
+        .iter()
+        .map(|element| match element {
+            Value::Str(s) => Some(Rc::clone(s)),
+            _ => None,
+        })
+        .collect();
+    match (ints, strs) {
+        (Some(ints), _) if !ints.is_empty() => Ok(Value::IntArray(ints)),
+        (_, Some(strs)) if !strs.is_empty() => Ok(Value::StrArray(strs)),
+        _ => Err("array elements must all be int or all be str".to_string()),
+    }
+}
+
+/// The elements of a tuple for `let (...)` with `count` names, which the
+/// analyzer checked match; shared with the VM.
+pub fn untuple(value: Value, count: usize) -> Result<Vec<Value>, String> {
+    match value {
+        Value::Tuple(elements) if elements.len() == count => Ok(elements.to_vec()),
+        other => Err(format!("`let (...)` with {} names cannot take apart {}", count, other)),
+    }
+}
+
+/// `some(value)`; the analyzer checked that `value` is not optional itself.
+/// Shared with the VM.
+pub fn some(value: Value) -> Result<Value, String> {
+    let ty = value.ty().optional_of().ok_or_else(|| format!("`some` cannot hold {}", value))?;
+    Ok(Value::Optional(ty, Some(Rc::new(value))))
+}
+
+/// The variant a `match` arm names for `value`: that of an enum value, or
+/// `some` or `none` for an optional one. Shared with the VM.
+pub fn variant(value: &Value) -> Result<&str, String> {
+    match value {
+        Value::Enum(_, variant) => Ok(variant),
+        Value::Optional(_, Some(_)) => Ok("some"),
+        Value::Optional(_, None) => Ok("none"),
+        other => Err(format!("`match` only works on enum and optional values, got {}", other)),
+    }
+}
+
+/// The value inside an optional that `variant` found to be `some`; shared with the VM.
+pub fn unwrap(value: Value) -> Result<Value, String> {
+    match value {
+        Value::Optional(_, Some(value)) => Ok(Rc::unwrap_or_clone(value)),
+        other => Err(format!("`some(...)` cannot bind the value inside {}", other)),
+    }
+}
+
+/// Builds a map from its evaluated entries, which the analyzer checked have
+/// keys of one type and values of one type; a later entry replaces an earlier
+/// one with the same key, as in `HashMap::from`. Shared with the VM.
+pub fn map(entries: Vec<(Value, Value)>) -> Result<Value, String> {
+    let (key, value) = entries.first().ok_or("maps have at least one entry")?;
+    let ty = Type::map_of(key.ty(), value.ty()).ok_or("map keys and values must be int or str")?;
+    let entries = entries
+        .into_iter()
+        .map(|(key, value)| Ok((Key::from_value(&key)?, value)))
+        .collect::<Result<HashMap<_, _>, String>>()?;
+    Ok(Value::Map(ty, Rc::new(entries)))
+}
+
+/// `array[index]`, failing like the generated Rust when the index is out of
+/// bounds; a negative index wraps to a huge one there as well. `map[key]`
+/// fails like `HashMap`'s `Index` when the key is missing.
+pub fn index(collection: &Value, index: Value) -> Result<Value, String> {
+    if let Value::Map(_, entries) = collection {
+        return entries.get(&Key::from_value(&index)?).cloned().ok_or_else(|| "no entry found for key".to_string());
+    }
+    let Value::Int(index) = index else {
+        return Err(format!("array index must be int, got {}", index));
+    };
+    let (len, element) = match collection {
+        Value::IntArray(elements) => (elements.len(), elements.get(index as usize).map(|&n| Value::Int(n))),
+        Value::StrArray(elements) => (elements.len(), elements.get(index as usize).map(|s| Value::Str(Rc::clone(s)))),
+        other => return Err(format!("only arrays and maps can be indexed, got {}", other)),
+    };
+    element.ok_or_else(|| format!("index out of bounds: the len is {} but the index is {}", len, index as usize))
+}
+
+/// `value.len()`: bytes of a str, elements of an array or entries of a map,
+/// converted with `as i32` like the generated Rust. Shared with the VM.
+pub fn len(value: &Value) -> Result<Value, String> {
+    let len = match value {
+        Value::Str(s) => s.len(),
+        Value::IntArray(elements) => elements.len(),
+        Value::StrArray(elements) => elements.len(),
+        Value::Map(_, entries) => entries.len(),
+        other => return Err(format!("{} has no len()", other)),
+    };
+    Ok(Value::Int(len as i32))
+}
+
+pub fn negate(value: Value, overflow: Overflow) -> Result<Value, String> {
+    match value {
+        Value::Int(n) => overflow.negate(n).map(Value::Int),
+        other => Err(format!("`-` only works on int values, got {}", other)),
+    }
+}
+
+/// `-2147483648` is a valid literal even though `2147483648` is not, so a
+/// negated number literal is parsed as a whole.
+pub fn negative_literal(value: &str) -> Result<i32, String> {
+    format!("-{}", value)
+        .parse()
+        .map_err(|_| format!("Integer literal out of range: -{}", value))
+}
+
+fn function_table(nodes: &[ASTNode]) -> HashMap<String, Rc<ASTNode>> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Function { name, .. } | ASTNode::ExternFunction { name, .. } => {
+                Some((name.clone(), Rc::new(node.clone())))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+impl Interpreter {
+    pub fn new(nodes: &[ASTNode]) -> Self {
+        Interpreter {
+            functions: function_table(nodes),
+            scopes: Vec::new(),
+            base: 0,
+            reload: None,
+            natives: HashMap::new(),
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Enables hot reloading: calls already running finish with the old
+    /// definition, every call made afterwards uses the new one.
+    pub fn with_reload(mut self, hook: ReloadHook) -> Self {
+        self.reload = Some(hook);
+        self
+    }
+
+    pub fn with_natives(mut self, natives: HashMap<String, Native>) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Runs `main`; like the generated code, a program without one does nothing.
+    #[tracing::instrument(skip_all)]
+    pub fn run(&mut self) -> Result<(), String> {
+        if !self.functions.contains_key("main") {
+            debug!("no `main` found, nothing to run");
+            return Ok(());
+        }
+        self.call("main", Vec::new())?;
+        Ok(())
+    }
+
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        if let Some(program) = self.reload.as_mut().and_then(|hook| hook()) {
+            debug!("reloading function definitions");
+            self.functions = function_table(&program);
+        }
+
+        let Some(function) = self.functions.get(name).cloned() else {
+            return match self.natives.get(name) {
+                Some(native) => native(&args),
+                None => Err(format!("Undefined function: {}", name)),
+            };
+        };
+        let (params, return_type, body) = match &*function {
+            ASTNode::Function { params, return_type, body, .. } => (params, return_type, body),
+            ASTNode::ExternFunction { abi, .. } => {
+                return Err(format!(
+                    "extern {} function {} cannot be called by the interpreter; build with rustc instead",
+                    abi, name
+                ))
+            }
+            _ => unreachable!("function table only holds functions"),
+        };
+        if params.len() != args.len() {
+            return Err(format!(
+                "Function {} expects {} arguments, got {}",
+                name, params.len(), args.len()
+            ));
+        }
+
+        let base = self.scopes.len();
+        let outer = std::mem::replace(&mut self.base, base);
+        self.scopes.push(params.iter().map(|p| p.name.clone()).zip(args).collect());
+        let flow = self.execute_body(body);
+        self.scopes.truncate(base);
+        self.base = outer;
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            Flow::Next if return_type.is_some() => Ok(Value::Int(0)),
+            Flow::Next => Ok(Value::Unit),
+        }
+    }
+
+    fn execute_body(&mut self, body: &[ASTNode]) -> Result<Flow, String> {
+        for stmt in body {
+            if let Flow::Return(value) = self.execute(stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Runs an `if` branch or one loop iteration; its `let` bindings end with it, as in the generated Rust.
+    fn execute_block(&mut self, body: &[ASTNode]) -> Result<Flow, String> {
+        self.scopes.push(HashMap::new());
+        let flow = self.execute_body(body);
+        self.scopes.pop();
+        flow
+    }
+
+    fn execute(&mut self, node: &ASTNode) -> Result<Flow, String> {
+        match node {
+            ASTNode::Let { name, value, .. } => {
+                let value = self.evaluate(value)?;
+                self.scope().insert(name.clone(), value);
+            }
+            ASTNode::LetTuple { bindings, value, .. } => {
+                let elements = untuple(self.evaluate(value)?, bindings.len())?;
+                for (binding, element) in bindings.iter().zip(elements) {
+                    self.scope().insert(binding.name.clone(), element);
+                }
+            }
+            ASTNode::Assign { name, value, .. } => {
+                let value = self.evaluate(value)?;
+                let scope = self.scopes[self.base..].iter_mut().rev().find(|scope| scope.contains_key(name));
+                match scope {
+                    Some(scope) => scope.insert(name.clone(), value),
+                    None => return Err(format!("Undefined identifier: {}", name)),
+                };
+            }
+            ASTNode::If { condition, then_body, else_body, .. } => {
+                let branch = if self.condition(condition)? { then_body } else { else_body };
+                return self.execute_block(branch);
+            }
+            ASTNode::While { condition, body, .. } => {
+                while self.condition(condition)? {
+                    if let Flow::Return(value) = self.execute_block(body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+            }
+            ASTNode::For { var, start, end, body, .. } => {
+                // Both bounds are evaluated once, before the first iteration.
+                let (start, end) = (self.bound(start)?, self.bound(end)?);
+                for i in start..end {
+                    self.scopes.push(HashMap::from([(var.clone(), Value::Int(i))]));
+                    let flow = self.execute_block(body);
+                    self.scopes.pop();
+                    if let Flow::Return(value) = flow? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+            }
+            ASTNode::Print { value, newline: false, .. } => print!("{}", self.evaluate(value)?),
+            ASTNode::Print { value, newline: true, .. } => println!("{}", self.evaluate(value)?),
+            ASTNode::Back { value: Some(value), .. } => return Ok(Flow::Return(self.evaluate(value)?)),
+            ASTNode::Back { value: None, .. } => return Ok(Flow::Return(Value::Unit)),
+            ASTNode::FunctionCall { .. } | ASTNode::MethodCall { .. } => {
+                self.evaluate(node)?;
+            }
+            ASTNode::Match { value, arms, else_body, .. } => {
+                let value = self.evaluate(value)?;
+                let variant = variant(&value)?;
+                let Some(arm) = arms.iter().find(|arm| arm.variant == variant) else {
+                    // The analyzer checked that the arms and `else` cover every variant
+                    let body = else_body.as_ref().ok_or_else(|| format!("no arm for variant {}", variant))?;
+                    return self.execute_block(body);
+                };
+                let Some(binding) = &arm.binding else {
+                    return self.execute_block(&arm.body);
+                };
+                self.scopes.push(HashMap::from([(binding.name.clone(), unwrap(value)?)]));
+                let flow = self.execute_block(&arm.body);
+                self.scopes.pop();
+                return flow;
+            }
+            ASTNode::Function { name, .. } => {
+                return Err(format!("Nested function {} is not supported by the interpreter", name))
+            }
+            ASTNode::ExternFunction { .. } | ASTNode::Enum { .. } => {}
+            _ => return Err(format!("{} is not a statement", node.kind())),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn condition(&mut self, node: &ASTNode) -> Result<bool, String> {
+        match self.evaluate(node)? {
+            Value::Int(n) => Ok(n != 0),
+            other => Err(format!("condition must be int, got {}", other)),
+        }
+    }
+
+    fn bound(&mut self, node: &ASTNode) -> Result<i32, String> {
+        match self.evaluate(node)? {
+            Value::Int(n) => Ok(n),
+            other => Err(format!("range bound must be int, got {}", other)),
+        }
+    }
+
+    fn evaluate(&mut self, node: &ASTNode) -> Result<Value, String> {
+        match node {
+            ASTNode::Number { value } => value
+                .parse()
+                .map(Value::Int)
+                .map_err(|_| format!("Integer literal out of range: {}", value)),
+            ASTNode::String { value } => unquote(value).map(|s| Value::Str(s.into())),
+            ASTNode::Interpolation { value, args, .. } => {
+                let texts = interpolation_texts(value)?;
+                let values = args.iter().map(|arg| self.evaluate(arg)).collect::<Result<Vec<_>, _>>()?;
+                Ok(interpolate(&texts, &values))
+            }
+            ASTNode::Identifier { name, .. } => self.scopes[self.base..]
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(name))
+                .cloned()
+                .ok_or_else(|| format!("Undefined identifier: {}", name)),
+            ASTNode::FunctionCall { name, args, .. } if name == "format" => {
+                let (value, spec) = compile::format_call_args(args)?;
+                match self.evaluate(value)? {
+                    Value::Int(n) => Ok(Value::Str(spec.apply(n).into())),
+                    other => Err(format!("format only supports int values, got {}", other)),
+                }
+            }
+            ASTNode::FunctionCall { name, args, .. } if name == "some" => match args.as_slice() {
+                [value] => some(self.evaluate(value)?),
+                _ => Err(format!("some expects 1 argument, got {}", args.len())),
+            },
+            ASTNode::None { ty, .. } => Ok(Value::Optional(ty.clone(), None)),
+            ASTNode::FunctionCall { name, args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<_, _>>()?;
+                self.call(name, args)
+            }
+            ASTNode::Binary { op, left, right, operand_type: Some(Type::Enum(_)), .. } => {
+                let name = op.overload().ok_or_else(|| format!("`{}` is not defined for enums", op.symbol()))?;
+                let args = vec![self.evaluate(left)?, self.evaluate(right)?];
+                let result = self.call(name, args)?;
+                // `eq` returns int; `!=` is true where it is 0.
+                match op {
+                    BinaryOp::Equal => binary(BinaryOp::NotEqual, result, Value::Int(0), self.overflow),
+                    BinaryOp::NotEqual => binary(BinaryOp::Equal, result, Value::Int(0), self.overflow),
+                    _ => Ok(result),
+                }
+            }
+            // Errors name the line, like the checks of generated code.
+            ASTNode::Binary { op, left, right, span, .. } => {
+                let left = self.evaluate(left)?;
+                binary(*op, left, self.evaluate(right)?, self.overflow).map_err(|e| format!("line {}: {}", span.line, e))
+            }
+            ASTNode::Negate { operand, span } => match &**operand {
+                ASTNode::Number { value } => negative_literal(value).map(Value::Int),
+                operand => negate(self.evaluate(operand)?, self.overflow).map_err(|e| format!("line {}: {}", span.line, e)),
+            },
+            ASTNode::Array { elements, .. } => {
+                array(elements.iter().map(|element| self.evaluate(element)).collect::<Result<_, _>>()?)
+            }
+            ASTNode::Tuple { elements, .. } => Ok(Value::Tuple(
+                elements.iter().map(|element| self.evaluate(element)).collect::<Result<_, _>>()?,
+            )),
+            ASTNode::Map { entries, .. } => map(entries
+                .iter()
+                .map(|(key, value)| Ok((self.evaluate(key)?, self.evaluate(value)?)))
+                .collect::<Result<_, String>>()?),
+            ASTNode::Index { collection, index: position, .. } => {
+                let collection = self.evaluate(collection)?;
+                index(&collection, self.evaluate(position)?)
+            }
+            ASTNode::MethodCall { receiver, method: Some(Method::Len), .. } => len(&self.evaluate(receiver)?),
+            ASTNode::MethodCall { receiver, name, args, method: Some(Method::Function), .. } => {
+                let args = std::iter::once(&**receiver)
+                    .chain(args)
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<_, _>>()?;
+                self.call(name, args)
+            }
+            ASTNode::Field { name, enum_type: Some(Type::Enum(enum_name)), .. } => {
+                Ok(Value::Enum(enum_name.clone(), name.as_str().into()))
+            }
+            _ => Err(format!("{} is not an expression", node.kind())),
+        }
+    }
+
+    fn scope(&mut self) -> &mut HashMap<String, Value> {
+        self.scopes.last_mut().expect("statements only run inside a function")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls `function` of a program with both engines, which must agree.
+    fn call_both(source: &str, function: &str) -> Value {
+        let program = load_program(source, Edition::LATEST, &HashSet::new(), false).unwrap();
+        let ast = call(&program, Engine::Ast, Overflow::Checked, HashMap::new(), function, Vec::new()).unwrap();
+        let bytecode = call(&program, Engine::Bytecode, Overflow::Checked, HashMap::new(), function, Vec::new()).unwrap();
+        assert_eq!(ast, bytecode);
+        ast
+    }
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    fn tuple(elements: Vec<Value>) -> Value {
+        Value::Tuple(elements.into())
+    }
+
+    #[test]
+    fn assigning_a_str_copies_it() {
+        let source = "fun f() -> (str, str) { let a = \"one\"; let b = a; a = a + \"!\"; back (a, b); }";
+        assert_eq!(call_both(source, "f"), tuple(vec![str("one!"), str("one")]));
+    }
+
+    #[test]
+    fn assigning_a_collection_copies_it() {
+        let source = "fun f() -> ([int], int) { let a = [1, 2]; let m = {\"x\": 1}; let b = a; let n = m; \
+                      a = [3]; m = {\"x\": 2}; back (b, n[\"x\"]); }";
+        assert_eq!(call_both(source, "f"), tuple(vec![Value::IntArray(Rc::from([1, 2])), Value::Int(1)]));
+    }
+
+    #[test]
+    fn assigning_a_tuple_or_optional_copies_it() {
+        let source = "fun t() -> (int, str) { let t = (1, \"a\"); let u = t; t = (2, \"b\"); back u; } \
+                      fun o() -> str? { let o = some(\"c\"); let p = o; o = none; back p; }";
+        assert_eq!(call_both(source, "t"), tuple(vec![Value::Int(1), str("a")]));
+        let optional = Type::Str.optional_of().unwrap();
+        assert_eq!(call_both(source, "o"), Value::Optional(optional, Some(Rc::new(str("c")))));
+    }
+
+    #[test]
+    fn arguments_are_copies() {
+        let source = "fun g(xs: [str]) -> int { let ys = xs; ys = [\"z\"]; back ys.len(); } \
+                      fun f() -> [str] { let xs = [\"a\", \"b\"]; let n = g(xs); back xs; }";
+        assert_eq!(call_both(source, "f"), Value::StrArray(Rc::from([Rc::from("a"), Rc::from("b")])));
+    }
+
+    #[test]
+    fn cloning_shares_the_text() {
+        let value = str("shared");
+        let (Value::Str(original), Value::Str(copy)) = (&value, &value.clone()) else {
+            unreachable!("both are strings");
+        };
+        assert!(Rc::ptr_eq(original, copy));
+    }
+
+    /// A failed division names its line in both engines, like `__ntfp_divide` in generated code
+    #[test]
+    fn division_errors() {
+        let program = load_program("fun divide(a, b) -> int {\n    back a / b;\n}\n", Edition::LATEST, &HashSet::new(), false).unwrap();
+        for engine in [Engine::Ast, Engine::Bytecode] {
+            let divide = |a, b| call(&program, engine, Overflow::Checked, HashMap::new(), "divide", vec![Value::Int(a), Value::Int(b)]);
+            assert_eq!(divide(-7, 2), Ok(Value::Int(-3)));
+            assert_eq!(divide(7, 0), Err("line 2: division by zero".to_string()));
+            assert_eq!(divide(i32::MIN, -1), Err("line 2: division overflow".to_string()));
+        }
+    }
+
+    /// `+ - *` and negation stop with their line when checked and wrap otherwise, in both engines
+    #[test]
+    fn overflow() {
+        let source = "fun sum(a, b) -> int {\n    back a + b;\n}\n\nfun flip(a) -> int {\n    back -a;\n}\n";
+        let program = load_program(source, Edition::LATEST, &HashSet::new(), false).unwrap();
+        for engine in [Engine::Ast, Engine::Bytecode] {
+            let run = |overflow, function, args| call(&program, engine, overflow, HashMap::new(), function, args);
+            let max = vec![Value::Int(i32::MAX), Value::Int(1)];
+            assert_eq!(run(Overflow::Checked, "sum", max.clone()), Err("line 2: addition overflow".to_string()));
+            assert_eq!(run(Overflow::Wrapping, "sum", max), Ok(Value::Int(i32::MIN)));
+            let min = vec![Value::Int(i32::MIN)];
+            assert_eq!(run(Overflow::Checked, "flip", min.clone()), Err("line 6: negation overflow".to_string()));
+            assert_eq!(run(Overflow::Wrapping, "flip", min), Ok(Value::Int(i32::MIN)));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::compile::Edition;
+use crate::grammar;
+
+/// 语言参考中的一个主题。语法由`grammar::rules()`生成，`ntfp help-lang`和
+/// `ntfp help-lang --markdown`共用这张表，`ntfp dev lang-examples`检查每个示例都能运行
+pub struct Topic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// 说明语法的语法规则名，词法层面的主题 (例如注释) 没有
+    pub rules: &'static [&'static str],
+    pub example: &'static str,
+    /// 示例使用的edition
+    pub edition: Edition,
+}
+
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "let",
+        summary: "声明变量。变量的类型由初始值决定，之后可以重新赋值",
+        rules: &["let_stmt"],
+        example: "fun main() {\n    let greeting = \"Hello\\n\";\n    print(greeting);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "assign",
+        summary: "给已声明的变量赋新值，新值的类型必须与变量相同",
+        rules: &["assign_stmt"],
+        example: "fun main() {\n    let n = 1;\n    n = 2;\n    print(n);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "print",
+        summary: "输出一个值。print不会自动换行，println (2026) 在值之后换行",
+        rules: &["print_stmt"],
+        example: "fun main() {\n    print(\"Hello, \");\n    println(\"Netflu!\");\n    println(42);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "fun",
+        summary: "定义函数。参数默认为int，可以写`name: str`；`-> int`声明返回类型。程序从main开始运行",
+        rules: &["fun_def", "call", "arguments"],
+        example: "fun echo(n) -> int {\n    back n;\n}\n\nfun shout(text: str) {\n    print(text);\n}\n\nfun main() {\n    print(echo(7));\n    shout(\"!\\n\");\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "back",
+        summary: "从函数返回，可以带一个返回值。2026中函数体最后一个不带`;`的表达式就是返回值，不必写back。声明了返回类型的函数没有执行到back时返回0",
+        rules: &["back_stmt", "fun_def"],
+        example: "fun pick(a, b) -> int {\n    if a > b {\n        back a;\n    }\n    b\n}\n\nfun main() {\n    print(pick(1, 2));\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "pub",
+        summary: "把顶层函数以C ABI导出，用于`[lib]`项目；参数和返回值只能是int",
+        rules: &["fun_def"],
+        example: "pub fun answer() -> int {\n    back 42;\n}\n\nfun main() {\n    print(answer());\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "extern",
+        summary: "声明在Rust (`extern rust`) 或C (`extern c`) 中实现的函数，需要用rustc构建",
+        rules: &["extern_decl"],
+        example: "extern c fun abs(n) -> int;\n\nfun main() {\n    print(abs(5));\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "format",
+        summary: "按格式说明把int格式化为str，例如`\"08\"`、`\"#x\"`、`\"*^10\"`、`\"+\"`、`\"b\"`",
+        rules: &["call", "arguments"],
+        example: "fun main() {\n    print(format(255, \"#x\"));\n    print(\"\\n\");\n    print(format(7, \"03\"));\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "types",
+        summary: "内置类型: int (32位整数) 和 str (字符串)。`+`连接两个str，两边必须同为int或同为str",
+        rules: &["expression"],
+        example: "fun main() {\n    let n = 42;\n    let s = \"text\";\n    print(n);\n    print(s + \"\\n\");\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "arrays",
+        summary: "数组`[a, b, c]`，元素都是int或都是str，类型写作`[int]`和`[str]`；`xs[i]`取第i个元素 (从0开始)，越界是运行错误，常量下标越界在编译时报错。数组不能为空，也不能修改",
+        rules: &["array", "factor", "postfix", "type"],
+        example: "fun sum3(xs: [int]) -> int {\n    back xs[0] + xs[1] + xs[2];\n}\n\nfun main() {\n    let primes = [2, 3, 5];\n    let names = [\"ann\", \"bob\"];\n    println(primes);\n    println(sum3(primes));\n    println(names[1]);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "tuples",
+        summary: "元组`(1, \"x\")`至少有两个元素，类型写作`(int, str)`，元素不能是元组；`let (a, b) = pair();`把元素分别绑定到变量，名称的个数必须和元素个数相同。元组输出为`(1, \"x\")`，不能比较或插值",
+        rules: &["group", "let_stmt", "type"],
+        example: "fun divide(a: int, b: int) -> (int, int) {\n    (a / b, a - a / b * b)\n}\n\nfun main() {\n    let (q, r) = divide(17, 5);\n    println(q);\n    println(r);\n    println((q, \"rest\"));\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "optionals",
+        summary: "可选类型`int?`的值是`some(x)`或`none`，生成Rust的`Option`；`none`只能写在知道类型的地方，如`back`、参数和赋值。可选值要用`match`分别处理`some(x)`和`none`才能取出其中的值，把`int?`当作int使用是类型错误。输出为`Some(1)`或`None`",
+        rules: &["type", "match_stmt"],
+        example: "fun find(names: [str], wanted: str) -> int? {\n    for i in 0..names.len() {\n        if names[i] == wanted {\n            back some(i);\n        }\n    }\n    none\n}\n\nfun main() {\n    match find([\"ann\", \"bob\"], \"bob\") {\n        some(i) {\n            println(i);\n        }\n        none {\n            println(\"not found\");\n        }\n    }\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "maps",
+        summary: "映射`{\"a\": 1, \"b\": 2}`，键都是int或都是str，值也一样，类型写作`{str: int}`等；`m[key]`取键对应的值，键不存在是运行错误，常量键重复在编译时报错。映射不能为空，也不能修改；条目没有顺序，所以不能整个输出",
+        rules: &["map", "entry", "factor", "postfix", "type"],
+        example: "fun price(prices: {str: int}, item: str) -> int {\n    prices[item]\n}\n\nfun main() {\n    let prices = {\"apple\": 3, \"pear\": 5};\n    let names = {1: \"one\", 2: \"two\"};\n    println(price(prices, \"pear\"));\n    println(names[1]);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "methods",
+        summary: "方法调用`x.f(a)`就是`f(x, a)`，f的第一个参数必须与x类型相同；另有内置方法`len()`，返回str的字节数、数组的元素数或映射的条目数。`.`、调用和下标从左到右连写，如`xs.first().len()`",
+        rules: &["postfix", "call_stmt"],
+        example: "fun twice(n: int) -> int {\n    n * 2\n}\n\nfun add(a: int, b: int) -> int {\n    a + b\n}\n\nfun main() {\n    let names = [\"ann\", \"bob\"];\n    println(names.len());\n    println(names[1].len().twice());\n    println(3.add(4).twice());\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "interpolation",
+        summary: "字符串插值。字面量中的`{名称}`替换为该变量的值 (int或str)，`{{`和`}}`表示花括号本身；花括号中只能写变量名。2025中花括号是普通字符",
+        rules: &[],
+        example: "fun main() {\n    let name = \"Netflu\";\n    let n = 3;\n    print(\"{name} has {n} {{braces}}\\n\");\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "arithmetic",
+        summary: "int的四则运算`+ - * /`和取负`-x`。取负优先于乘除，先乘除后加减，同级从左到右，可以用括号改变顺序；除法向零取整。`+ - *`和取负溢出时默认debug构建报告所在行并结束程序、release构建回绕，可以用`[profile.*]`的`overflow-checks`设置。除数总是0 (如`x / (1 - 1)`) 时编译报错，运行时除以0或除法溢出 (`i32::MIN / -1`) 会报告所在行并结束程序",
+        rules: &["sum", "term", "factor"],
+        example: "fun main() {\n    let x = 1 + 2 * 3;\n    print((x - 1) / 2);\n    print(-x * 2);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "compare",
+        summary: "比较运算`== != < > <= >=`，成立时得到1，否则得到0。两边的类型必须相同，int按大小、str按字典序比较；比大小的优先级低于四则运算，不能连写 (`a < b < c`)",
+        rules: &["expression"],
+        example: "fun main() {\n    let x = 3;\n    if x * 2 >= 6 {\n        print(\"big\\n\");\n    }\n    print(\"apple\" < \"banana\");\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "if",
+        summary: "条件语句。条件是int，不为0时执行第一个代码块，否则执行else后的代码块；可以用`else if`连续判断。代码块中声明的变量只在块内可见",
+        rules: &["if_stmt"],
+        example: "fun describe(count) {\n    if count {\n        print(\"some\\n\");\n    } else {\n        print(\"none\\n\");\n    }\n}\n\nfun main() {\n    describe(0);\n    describe(3);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "while",
+        summary: "循环。每次执行代码块前计算条件 (int)，为0时结束；在函数中可以用back提前退出",
+        rules: &["while_stmt"],
+        example: "fun main() {\n    let n = 3;\n    while n > 0 {\n        print(n);\n        n = n - 1;\n    }\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "for",
+        summary: "计数循环。`for i in start..end`让i从start依次取到end - 1 (不含end)，两端是int，只在第一次循环前计算；循环变量只在代码块内可见，不能赋值",
+        rules: &["for_stmt"],
+        example: "fun main() {\n    let total = 0;\n    for i in 1..5 {\n        total = total + i * i;\n    }\n    print(total);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "enums",
+        summary: "枚举。`enum Color { Red; Green; Blue; }`只能在顶层声明，值写作`Color.Red`，类型名可以用作参数和返回类型。`match`按枚举值选择代码块，每个变体最多一个分支，其余变体由最后的else处理；漏掉变体或else永远不会执行都是编译错误。枚举值输出为变体名，不能插值或放进数组和映射；比较和`+`见overloading",
+        rules: &["enum_def", "match_stmt"],
+        example: "enum Color {\n    Red;\n    Green;\n    Blue;\n}\n\nfun warm(c: Color) -> int {\n    match c {\n        Red {\n            back 1;\n        }\n        else {\n            back 0;\n        }\n    }\n}\n\nfun main() {\n    let c = Color.Green;\n    println(c);\n    println(warm(c));\n    println(warm(Color.Red));\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "overloading",
+        summary: "顶层函数`fun add(a: T, b: U)`为枚举T定义`+`，`fun eq(a: T, b: U) -> int`定义`==`和`!=`；左操作数是T时，类型检查把运算符解析为对这个函数的调用，右操作数必须是U。生成的Rust代码实现Add和PartialEq。没有定义的运算符用在枚举上是编译错误",
+        rules: &[],
+        example: "enum Level {\n    Low;\n    High;\n}\n\nfun rank(l: Level) -> int {\n    match l {\n        Low {\n            back 1;\n        }\n        High {\n            back 2;\n        }\n    }\n}\n\nfun eq(a: Level, b: Level) -> int {\n    back rank(a) == rank(b);\n}\n\nfun add(a: Level, b: Level) -> int {\n    back rank(a) + rank(b);\n}\n\nfun main() {\n    let a = Level.Low;\n    println(a + Level.High);\n    println(a != Level.High);\n}\n",
+        edition: Edition::LATEST,
+    },
+    Topic {
+        name: "method",
+        summary: "edition 2025的旧写法，等价于`fun name() -> int`，新代码请使用fun",
+        rules: &["method_def"],
+        example: "method answer {\n    back 42;\n}\n\nfun main() {\n    print(answer());\n}\n",
+        edition: Edition::E2025,
+    },
+    Topic {
+        name: "comment",
+        summary: "`//`到行尾是注释，`/* */`是可以跨行、可以嵌套的块注释，都可以写在任何空白处；ntfp fmt会保留注释，表达式中间的注释移到所在语句之前",
+        rules: &[],
+        example: "/* 打印前几个平方数\n   /* 块注释可以嵌套 */ */\nfun main() {\n    for i in 1..4 {\n        print(i * i); // 1, 4, 9\n    }\n}\n",
+        edition: Edition::LATEST,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|t| t.name == name)
+}
+
+fn syntax(topic: &Topic) -> Vec<String> {
+    topic.rules.iter().filter_map(|rule| grammar::rule_ebnf(rule)).collect()
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { "\n".to_string() } else { format!("    {}\n", line) })
+        .collect()
+}
+
+/// 终端中显示的单个主题
+pub fn render_topic(topic: &Topic) -> String {
+    let mut out = format!("{} - {}\n", topic.name, topic.summary);
+    if !topic.rules.is_empty() {
+        out.push_str("\n语法:\n");
+    }
+    for rule in syntax(topic) {
+        out.push_str(&format!("    {}\n", rule));
+    }
+    out.push_str(&format!("\n示例 (edition {}):\n", topic.edition.as_str()));
+    out.push_str(&indent(topic.example));
+    out
+}
+
+/// 主题列表
+pub fn render_index() -> String {
+    let width = TOPICS.iter().map(|t| t.name.len()).max().unwrap_or(0);
+    let mut out = String::from("Netflu语言参考，使用`ntfp help-lang <主题>`查看详情:\n\n");
+    for topic in TOPICS {
+        out.push_str(&format!("    {:<width$}  {}\n", topic.name, topic.summary, width = width));
+    }
+    out
+}
+
+/// 全部主题的Markdown文档
+pub fn render_markdown() -> String {
+    let mut out = String::from("# Netflu 语言参考\n\n<!-- 由`ntfp help-lang --markdown`生成 -->\n");
+    for topic in TOPICS {
+        out.push_str(&format!("\n## `{}`\n\n{}\n\n", topic.name, topic.summary));
+        if !topic.rules.is_empty() {
+            out.push_str("```ebnf\n");
+            for rule in syntax(topic) {
+                out.push_str(&format!("{}\n", rule));
+            }
+            out.push_str("```\n\n");
+        }
+        if topic.edition != Edition::LATEST {
+            out.push_str(&format!("示例需要 `edition = \"{}\"`：\n\n", topic.edition.as_str()));
+        }
+        out.push_str(&format!("```netflu\n{}```\n", topic.example));
+    }
+    out
+}
@@ -0,0 +1,8 @@
+//! The Netflu front end and interpreters as a library. Rust programs embed
+//! Netflu through `embed`; the `ntfp` command line tool uses the same modules.
+
+pub mod compile;
+pub mod embed;
+pub mod interp;
+pub mod platform;
+mod vm;
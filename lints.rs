@@ -0,0 +1,115 @@
+use crate::compile::{Lint, Warning};
+use crate::manifest::Manifest;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// 代表所有lint的组名，`warnings = "deny"`即把所有警告视为错误
+const WARNINGS_GROUP: &str = "warnings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    fn parse(s: &str) -> Result<LintLevel> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => anyhow::bail!("无效的lint级别: \"{}\" (可用: allow, warn, deny)", s),
+        }
+    }
+}
+
+/// 命令行上的`--allow`/`--warn`/`--deny`，优先于ntfp.toml中的`[lints]`
+#[derive(Debug, Default, Clone, clap::Args)]
+pub struct LintOverrides {
+    /// 忽略指定的lint (可重复，`warnings`表示全部)
+    #[arg(long = "allow", value_name = "LINT")]
+    pub allow: Vec<String>,
+    /// 把指定的lint报告为警告
+    #[arg(long = "warn", value_name = "LINT")]
+    pub warn: Vec<String>,
+    /// 把指定的lint视为错误
+    #[arg(long = "deny", value_name = "LINT")]
+    pub deny: Vec<String>,
+}
+
+impl LintOverrides {
+    fn entries(&self) -> impl Iterator<Item = (&str, LintLevel)> {
+        self.allow
+            .iter()
+            .map(|n| (n.as_str(), LintLevel::Allow))
+            .chain(self.warn.iter().map(|n| (n.as_str(), LintLevel::Warn)))
+            .chain(self.deny.iter().map(|n| (n.as_str(), LintLevel::Deny)))
+    }
+}
+
+/// 每个lint最终的级别
+pub struct LintLevels {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintLevels {
+    /// 依次应用ntfp.toml和命令行；同一来源中具体的lint优先于`warnings`组
+    pub fn resolve(manifest: &Manifest, overrides: &LintOverrides) -> Result<Self> {
+        let mut levels = Self::warn_all().levels;
+
+        let from_manifest = manifest
+            .lints
+            .iter()
+            .map(|(name, level)| Ok((name.as_str(), LintLevel::parse(level)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let from_cli: Vec<_> = overrides.entries().collect();
+
+        for entries in [from_manifest, from_cli] {
+            let (groups, lints): (Vec<_>, Vec<_>) =
+                entries.into_iter().partition(|(name, _)| *name == WARNINGS_GROUP);
+            for (_, level) in groups {
+                levels.values_mut().for_each(|l| *l = level);
+            }
+            for (name, level) in lints {
+                levels.insert(parse_lint(name)?, level);
+            }
+        }
+        Ok(LintLevels { levels })
+    }
+
+    /// 没有ntfp.toml时 (例如`ntfp script`) 使用的级别: 所有lint都是warn
+    pub fn warn_all() -> Self {
+        LintLevels { levels: Lint::ALL.iter().map(|&lint| (lint, LintLevel::Warn)).collect() }
+    }
+
+    /// 按级别输出警告并返回以warn级别报告的警告，有被deny的警告时返回错误
+    pub fn report(&self, warnings: Vec<Warning>) -> Result<Vec<Warning>> {
+        let mut reported = Vec::new();
+        let mut denied = 0;
+        for warning in warnings {
+            match self.levels[&warning.lint] {
+                LintLevel::Allow => {}
+                LintLevel::Warn => {
+                    eprintln!("警告: {} [{}]", warning.message, warning.lint.name());
+                    reported.push(warning);
+                }
+                LintLevel::Deny => {
+                    eprintln!("错误: {} [{}]", warning.message, warning.lint.name());
+                    denied += 1;
+                }
+            }
+        }
+        if denied > 0 {
+            anyhow::bail!("{} 个警告被设置为deny (见ntfp.toml中的[lints]或--deny)", denied);
+        }
+        Ok(reported)
+    }
+}
+
+fn parse_lint(name: &str) -> Result<Lint> {
+    Lint::parse(name).ok_or_else(|| {
+        let known: Vec<_> = Lint::ALL.iter().map(Lint::name).collect();
+        anyhow::anyhow!("未知的lint: \"{}\" (可用: {}, {})", name, WARNINGS_GROUP, known.join(", "))
+    })
+}
@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, File, TryLockError},
+    path::Path,
+};
+use tracing::debug;
+
+/// target目录中的锁文件名
+const LOCK_FILE: &str = ".ntfp-lock";
+
+/// target目录的独占锁，释放 (drop) 前其他ntfp进程不能写入target
+///
+/// 构建和转译在写入target前获取此锁；`ntfp run --hot`只读取源文件，
+/// 不写入target，因此不获取锁，可以与构建同时运行。
+/// 进程退出 (包括Ctrl-C) 时由操作系统释放。
+#[derive(Debug)]
+pub struct TargetLock {
+    _file: File,
+}
+
+impl TargetLock {
+    /// 获取`project_path/target`的锁，被占用时提示并等待
+    pub fn acquire(project_path: &Path) -> Result<TargetLock> {
+        let target = project_path.join("target");
+        fs::create_dir_all(&target).with_context(|| format!("无法创建目录: {:?}", target))?;
+        let path = target.join(LOCK_FILE);
+        let file = File::create(&path).with_context(|| format!("无法创建锁文件: {:?}", path))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                eprintln!("正在等待文件锁: {:?} (另一个ntfp进程正在使用target目录)", target);
+                file.lock().with_context(|| format!("无法锁定: {:?}", path))?;
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("无法锁定: {:?}", path));
+            }
+        }
+        debug!(lock = ?path, "acquired target lock");
+        Ok(TargetLock { _file: file })
+    }
+}
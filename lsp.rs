@@ -0,0 +1,975 @@
+//! `ntfp lsp`: 通过标准输入输出与编辑器通信的语言服务器 (Language Server Protocol)
+use crate::compile::{self, ASTNode, Comment, ConfigValue, Edition, Feature, NameRef, StringPiece, SymbolKind, Token, TokenType};
+use crate::formatter::{self, Style};
+use crate::manifest;
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// 语义高亮的token类型，下标就是协议中的编号
+const TOKEN_TYPES: &[&str] = &[
+    "keyword", "function", "variable", "parameter", "type", "number", "string", "operator", "comment", "method", "property",
+    "enum", "enumMember",
+];
+/// 语义高亮的修饰符，第i个对应第i位
+const TOKEN_MODIFIERS: &[&str] = &["declaration", "defaultLibrary"];
+const DECLARATION: u32 = 1;
+const DEFAULT_LIBRARY: u32 = 2;
+
+/// 内置函数的签名，补全时显示
+const BUILTIN_SIGNATURES: &[(&str, &str)] =
+    &[("format", "fun format(value: int, spec: str) -> str"), ("some", "fun some(value: T) -> T?")];
+
+/// LSP的CompletionItemKind
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_KEYWORD: u32 = 14;
+
+/// LSP的InlayHintKind
+const INLAY_TYPE: u32 = 1;
+
+/// LSP的DocumentHighlightKind
+const HIGHLIGHT_TEXT: u32 = 1;
+const HIGHLIGHT_READ: u32 = 2;
+const HIGHLIGHT_WRITE: u32 = 3;
+
+/// JSON-RPC: 未知方法
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC: 参数无效
+const INVALID_PARAMS: i64 = -32602;
+
+/// 读取一条`Content-Length`分帧的消息，输入结束时返回`None`
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).context("无法读取消息头")? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let value = header.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("Content-Length"));
+        if let Some((_, value)) = value {
+            length = Some(value.trim().parse::<usize>().with_context(|| format!("无效的Content-Length: {:?}", value))?);
+        }
+    }
+    let length = length.context("消息缺少Content-Length")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).context("无法读取消息内容")?;
+    serde_json::from_slice(&body).context("消息不是有效的JSON").map(Some)
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush().context("无法写入消息")
+}
+
+/// 文档所在项目的语言设置，不在项目中时按`ntfp script`处理
+struct Settings {
+    edition: Edition,
+    disabled: HashSet<Feature>,
+    script: bool,
+    /// `[fmt]`，与`ntfp fmt`一致，不使用客户端传来的缩进设置
+    style: Style,
+    /// `[config]`中的常量，无效的值被忽略
+    config: Vec<(String, ConfigValue)>,
+}
+
+impl Settings {
+    fn for_uri(uri: &str) -> Settings {
+        let project = uri_to_path(uri)
+            .and_then(|path| manifest::find_project_root(path.parent()?))
+            .and_then(|root| manifest::Manifest::load(&root).ok());
+        let Some(manifest) = project else {
+            return Settings {
+                edition: Edition::LATEST,
+                disabled: HashSet::new(),
+                script: true,
+                style: Style::default(),
+                config: Vec::new(),
+            };
+        };
+        Settings {
+            edition: manifest.edition().unwrap_or(Edition::LATEST),
+            disabled: manifest.disabled_features().unwrap_or_default(),
+            script: manifest.language.top_level_statements,
+            style: manifest.style().unwrap_or_default(),
+            config: manifest.config_values().unwrap_or_default(),
+        }
+    }
+}
+
+/// `file://`URI对应的本地路径，只解码百分号转义
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = encoded
+            .get(i + 1..i + 3)
+            .filter(|_| encoded[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+struct Document {
+    text: String,
+    settings: Settings,
+    /// 符号索引，第一次使用时分析，文本改变后丢弃
+    analysis: OnceCell<Analysis>,
+}
+
+impl Document {
+    fn new(text: String, settings: Settings) -> Document {
+        Document { text, settings, analysis: OnceCell::new() }
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.text = text;
+        self.analysis = OnceCell::new();
+    }
+
+    fn analysis(&self) -> &Analysis {
+        self.analysis.get_or_init(|| Analysis::new(&self.text, &self.settings))
+    }
+}
+
+/// 一次分析的结果。语义错误时保留出错之前解析到的名称
+struct Analysis {
+    tokens: Vec<Token>,
+    comments: Vec<Comment>,
+    /// 语法错误时为`None`
+    ast: Option<Vec<ASTNode>>,
+    /// 按名称第一个字节的位置索引，不含自动生成的`main`等源码中没有的名称
+    names: HashMap<usize, NameRef>,
+}
+
+impl Analysis {
+    fn new(text: &str, settings: &Settings) -> Analysis {
+        let Ok((tokens, comments)) = compile::lex(text) else {
+            return Analysis { tokens: Vec::new(), comments: Vec::new(), ast: None, names: HashMap::new() };
+        };
+        let mut parser = compile::Parser::with_edition(tokens.clone(), settings.edition);
+        parser.disabled = settings.disabled.clone();
+        let mut analyzer = compile::SemanticAnalyzer::new();
+        for (key, value) in &settings.config {
+            let _ = analyzer.declare_config(key, value.clone());
+        }
+        let ast = parser.parse().ok().map(|ast| {
+            // 函数外的语句在项目中是错误，但其中的名称仍然可以分析
+            let mut ast = compile::lower_top_level(ast.clone(), settings.script).unwrap_or(ast);
+            if let Err(e) = analyzer.analyze(&mut ast) {
+                debug!(error = %e, "analysis stopped early");
+            }
+            ast
+        });
+        let mut written: HashMap<usize, &str> = tokens
+            .iter()
+            .filter(|t| t.type_ == TokenType::Identifier)
+            .map(|t| (t.position, t.value.as_str()))
+            .collect();
+        // 字符串插值中的`{名称}`
+        for token in tokens.iter().filter(|t| t.type_ == TokenType::String) {
+            for piece in compile::string_pieces(&token.value).unwrap_or_default() {
+                if let StringPiece::Name(name, offset) = piece {
+                    written.insert(token.position + offset, name);
+                }
+            }
+        }
+        let names = analyzer
+            .names
+            .into_iter()
+            .filter(|name| written.get(&name.span.start) == Some(&name.name.as_str()))
+            .map(|name| (name.span.start, name))
+            .collect();
+        Analysis { tokens, comments, ast, names }
+    }
+
+    /// 第`i`个token是标识符时，它解析到的符号
+    fn name_at(&self, i: usize) -> Option<&NameRef> {
+        self.names.get(&self.tokens[i].position)
+    }
+
+    /// 程序是否声明了名为`name`的枚举
+    fn is_enum(&self, name: &str) -> bool {
+        self.ast.iter().flatten().any(|node| matches!(node, ASTNode::Enum { name: declared, .. } if declared == name))
+    }
+
+    /// 第`i`个token是否是枚举声明`enum Name { A; B; }`中的变体
+    fn in_enum_body(&self, i: usize) -> bool {
+        let mut j = i;
+        while j > 0 && matches!(self.tokens[j - 1].type_, TokenType::Identifier | TokenType::Semicolon) {
+            j -= 1;
+        }
+        j >= 3 && self.tokens[j - 1].type_ == TokenType::LBrace && self.tokens[j - 3].type_ == TokenType::Enum
+    }
+
+    /// 光标所在 (包括紧跟其后) 的名称
+    fn name_at_offset(&self, offset: usize) -> Option<&NameRef> {
+        let i = self.tokens.partition_point(|t| t.position <= offset).checked_sub(1)?;
+        if self.tokens[i].type_ == TokenType::String {
+            return self
+                .names
+                .values()
+                .find(|name| name.span.start <= offset && offset <= name.span.start + name.name.len());
+        }
+        self.name_at(i).filter(|name| offset <= name.span.start + name.name.len())
+    }
+
+    /// 与`target`解析到同一个符号的所有名称，按源码顺序
+    fn occurrences(&self, target: &NameRef) -> Vec<&NameRef> {
+        let symbol = |name: &NameRef| (name.name.clone(), name.declared.map(|span| span.start));
+        let mut found: Vec<_> = self.names.values().filter(|name| symbol(name) == symbol(target)).collect();
+        found.sort_by_key(|name| name.span.start);
+        found
+    }
+
+    /// 赋值和带初始值的声明是写入，函数和参数的声明只是文本
+    fn highlight_kind(&self, name: &NameRef) -> u32 {
+        let next = self.tokens.partition_point(|t| t.position <= name.span.start);
+        if self.tokens.get(next).is_some_and(|t| t.type_ == TokenType::Assign) {
+            HIGHLIGHT_WRITE
+        } else if name.declaration {
+            HIGHLIGHT_TEXT
+        } else {
+            HIGHLIGHT_READ
+        }
+    }
+
+    /// 第`i`个token`{`是否开始一个块，而不是映射字面量或映射类型`{str: int}`:
+    /// 块前面是条件、签名或`else`的结尾
+    fn opens_block(&self, i: usize) -> bool {
+        let previous = i.checked_sub(1).map(|i| &self.tokens[i].type_);
+        matches!(
+            previous,
+            Some(
+                TokenType::RParen
+                    | TokenType::Identifier
+                    | TokenType::Number
+                    | TokenType::String
+                    | TokenType::RBracket
+                    | TokenType::RBrace
+                    | TokenType::Else
+            )
+        )
+    }
+
+    fn blocks(&self) -> Blocks {
+        // 映射的花括号也要配对，但不算作块
+        let mut open = Vec::new();
+        let mut blocks = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.type_ {
+                TokenType::LBrace => open.push(Some(token.position).filter(|_| self.opens_block(i))),
+                TokenType::RBrace => blocks.extend(open.pop().flatten().map(|start| (start, token.position, None))),
+                _ => {}
+            }
+        }
+        blocks.extend(open.into_iter().flatten().map(|start| (start, usize::MAX, None)));
+        blocks.sort_by_key(|&(start, _, _)| start);
+        let mut enclosing: Vec<usize> = Vec::new();
+        for i in 0..blocks.len() {
+            while enclosing.last().is_some_and(|&outer| blocks[outer].1 < blocks[i].0) {
+                enclosing.pop();
+            }
+            blocks[i].2 = enclosing.last().copied();
+            enclosing.push(i);
+        }
+        Blocks(blocks)
+    }
+
+    /// 声明的作用域: 参数是函数体，循环变量是循环体，其他名称是包含声明的最内层块，顶层为整个文件
+    fn scope(&self, declaration: &NameRef, blocks: &Blocks) -> Option<(usize, usize)> {
+        let at = declaration.span.start;
+        if matches!(declaration.kind, SymbolKind::Parameter | SymbolKind::LoopVariable) {
+            // extern声明以`;`结束，它的参数没有作用域
+            let after = self.tokens.partition_point(|t| t.position <= at);
+            let body = (after..self.tokens.len()).map(|i| (i, &self.tokens[i])).find(|&(i, t)| {
+                t.type_ == TokenType::Semicolon || (t.type_ == TokenType::LBrace && self.opens_block(i))
+            })?.1;
+            return blocks.starting_at(body.position).filter(|_| body.type_ == TokenType::LBrace);
+        }
+        Some(blocks.innermost(at).unwrap_or((0, usize::MAX)))
+    }
+
+    /// 在`offset`处可见的声明，同名时只保留最内层 (最后) 的一个
+    fn visible(&self, offset: usize) -> Vec<&NameRef> {
+        let blocks = self.blocks();
+        let mut visible: HashMap<&str, &NameRef> = HashMap::new();
+        for name in self.names.values().filter(|name| name.declaration) {
+            let Some((start, end)) = self.scope(name, &blocks) else {
+                continue;
+            };
+            // 函数在整个作用域中可见 (可以先调用后定义)，变量从声明之后开始
+            let declared = matches!(name.kind, SymbolKind::Function { .. }) || name.span.start < offset;
+            if !(start < offset && offset <= end && declared) {
+                continue;
+            }
+            let entry = visible.entry(&name.name).or_insert(name);
+            if entry.span.start < name.span.start {
+                *entry = name;
+            }
+        }
+        let mut visible: Vec<_> = visible.into_values().collect();
+        visible.sort_by_key(|name| name.span.start);
+        visible
+    }
+
+    /// 每个函数的签名，按声明的位置索引
+    fn signatures(&self) -> HashMap<usize, String> {
+        let mut signatures = HashMap::new();
+        for node in self.ast.iter().flatten() {
+            node.walk(&mut |node| match node {
+                ASTNode::Function { name, params, return_type, span, .. }
+                | ASTNode::ExternFunction { name, params, return_type, span, .. } => {
+                    let mut out = String::new();
+                    formatter::format_signature(name, params, return_type, &mut out);
+                    signatures.insert(span.start, out);
+                }
+                _ => {}
+            });
+        }
+        signatures
+    }
+}
+
+/// 每对花括号的字节范围和外层块的下标，按开始位置排序; 没有闭合的块 (正在输入) 延伸到文件末尾
+struct Blocks(Vec<(usize, usize, Option<usize>)>);
+
+impl Blocks {
+    fn starting_at(&self, start: usize) -> Option<(usize, usize)> {
+        let i = self.0.binary_search_by_key(&start, |&(start, _, _)| start).ok()?;
+        Some((self.0[i].0, self.0[i].1))
+    }
+
+    /// 包含`at`的最内层块: 从`at`之前最后开始的块向外找
+    fn innermost(&self, at: usize) -> Option<(usize, usize)> {
+        let mut i = self.0.partition_point(|&(start, _, _)| start < at).checked_sub(1)?;
+        loop {
+            let (start, end, outer) = self.0[i];
+            if at < end {
+                return Some((start, end));
+            }
+            i = outer?;
+        }
+    }
+}
+
+/// 把字节位置换算成LSP使用的行号和UTF-16列号 (都从0开始)
+struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> LineIndex<'a> {
+        let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { text, starts }
+    }
+
+    /// LSP位置对应的字节位置，超出行尾时取行尾 (CRLF换行时在`\r`之前)
+    fn offset(&self, line: u32, column: u32) -> usize {
+        let Some(&start) = self.starts.get(line as usize) else {
+            return self.text.len();
+        };
+        let end = self.starts.get(line as usize + 1).map_or(self.text.len(), |next| next - 1);
+        let end = if self.text[start..end].ends_with('\r') { end - 1 } else { end };
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= column as usize {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+
+    fn position(&self, offset: usize) -> (u32, u32) {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.text[self.starts[line]..offset].encode_utf16().count();
+        (line as u32, column as u32)
+    }
+
+    /// 名称在LSP中的范围
+    fn range(&self, name: &NameRef) -> Value {
+        let position = |offset| {
+            let (line, character) = self.position(offset);
+            json!({ "line": line, "character": character })
+        };
+        json!({ "start": position(name.span.start), "end": position(name.span.start + name.name.len()) })
+    }
+}
+
+fn semantic_type(analysis: &Analysis, i: usize) -> Option<(&'static str, u32)> {
+    let token = &analysis.tokens[i];
+    let kind = match token.type_ {
+        TokenType::Let
+        | TokenType::Print
+        | TokenType::Println
+        | TokenType::Method
+        | TokenType::Fun
+        | TokenType::Back
+        | TokenType::Extern
+        | TokenType::Pub
+        | TokenType::If
+        | TokenType::Else
+        | TokenType::While
+        | TokenType::For
+        | TokenType::In
+        | TokenType::Enum
+        | TokenType::Match => "keyword",
+        TokenType::Number => "number",
+        TokenType::String => "string",
+        TokenType::Plus
+        | TokenType::Minus
+        | TokenType::Multiply
+        | TokenType::Divide
+        | TokenType::Assign
+        | TokenType::Equal
+        | TokenType::NotEqual
+        | TokenType::Less
+        | TokenType::Greater
+        | TokenType::LessEqual
+        | TokenType::GreaterEqual
+        | TokenType::DotDot
+        | TokenType::Dot
+        | TokenType::Question => "operator",
+        TokenType::Identifier => return Some(identifier_type(analysis, i)),
+        _ => return None,
+    };
+    Some((kind, 0))
+}
+
+/// 优先使用符号表; 分析没有到达的名称按上下文猜测
+fn identifier_type(analysis: &Analysis, i: usize) -> (&'static str, u32) {
+    // `.`之后是方法 (`x.f()`，符号表里是函数f，没有记录的是内置的`len()`) 或字段
+    if i.checked_sub(1).is_some_and(|j| analysis.tokens[j].type_ == TokenType::Dot) {
+        // `Color.Red`，除非`Color`是遮住了枚举的变量
+        let object = i.checked_sub(2).filter(|&j| analysis.tokens[j].type_ == TokenType::Identifier);
+        if object.is_some_and(|j| analysis.name_at(j).is_none() && analysis.is_enum(&analysis.tokens[j].value)) {
+            return ("enumMember", 0);
+        }
+        return match analysis.tokens.get(i + 1).map(|t| &t.type_) {
+            Some(TokenType::LParen) if analysis.name_at(i).is_some() => ("method", 0),
+            Some(TokenType::LParen) => ("method", DEFAULT_LIBRARY),
+            _ => ("property", 0),
+        };
+    }
+    if let Some(name) = analysis.name_at(i) {
+        let kind = match name.kind {
+            SymbolKind::Function { .. } => "function",
+            SymbolKind::Variable { .. } | SymbolKind::LoopVariable => "variable",
+            SymbolKind::Parameter => "parameter",
+        };
+        let mut modifiers = 0;
+        if name.declaration {
+            modifiers |= DECLARATION;
+        }
+        if name.declared.is_none() {
+            modifiers |= DEFAULT_LIBRARY;
+        }
+        return (kind, modifiers);
+    }
+    let before = |n: usize| i.checked_sub(n).map(|i| &analysis.tokens[i].type_);
+    let (previous, next) = (before(1), analysis.tokens.get(i + 1).map(|t| &t.type_));
+    if previous == Some(&TokenType::Enum) {
+        return ("enum", DECLARATION);
+    }
+    if analysis.in_enum_body(i) {
+        return ("enumMember", DECLARATION);
+    }
+    // match的分支`Red { ... }`和`some(x) { ... }`: 语句开头的名称后面不会是`{`
+    let arm = next == Some(&TokenType::LBrace)
+        || (next == Some(&TokenType::LParen) && analysis.tokens.get(i + 3).is_some_and(|t| t.type_ == TokenType::LBrace));
+    if arm && matches!(previous, Some(TokenType::LBrace | TokenType::RBrace | TokenType::Semicolon)) {
+        return ("enumMember", 0);
+    }
+    // 分析时替换成了`ASTNode::None`的`none`不在符号表里
+    if analysis.tokens[i].value == "none" {
+        return ("enumMember", DEFAULT_LIBRARY);
+    }
+    if analysis.is_enum(&analysis.tokens[i].value) {
+        return ("enum", 0);
+    }
+    // `: int`、`-> str`，以及数组类型`: [int]`中的元素类型和映射类型`: {str: int}`中的键类型
+    let annotation = |previous: Option<&TokenType>| matches!(previous, Some(TokenType::Colon | TokenType::Arrow));
+    // 元组类型`-> (int, [str])`中的元素类型: 往前越过类型中的token能到达注解后的`(`
+    let in_tuple_type = || {
+        let start = analysis.tokens[..i].iter().rposition(|t| {
+            !matches!(
+                t.type_,
+                TokenType::Identifier
+                    | TokenType::Comma
+                    | TokenType::Colon
+                    | TokenType::LBracket
+                    | TokenType::RBracket
+                    | TokenType::LBrace
+                    | TokenType::RBrace
+                    | TokenType::Question
+            )
+        });
+        start.is_some_and(|j| analysis.tokens[j].type_ == TokenType::LParen && annotation(before(i - j + 1)))
+    };
+    if annotation(previous)
+        || (matches!(previous, Some(TokenType::LBracket | TokenType::LBrace)) && annotation(before(2)))
+        || in_tuple_type()
+    {
+        ("type", 0)
+    } else if matches!(previous, Some(TokenType::Fun)) || next == Some(&TokenType::LParen) {
+        ("function", 0)
+    } else {
+        ("variable", 0)
+    }
+}
+
+/// `textDocument/semanticTokens/full`的数据: 每个token五个数，位置相对于上一个token
+fn semantic_tokens(document: &Document) -> Vec<u32> {
+    let analysis = document.analysis();
+    let index = LineIndex::new(&document.text);
+    let mut spans: Vec<(usize, &str, &str, u32)> = analysis
+        .tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(i, token)| {
+            let (kind, modifiers) = semantic_type(analysis, i)?;
+            Some((token.position, token.value.as_str(), kind, modifiers))
+        })
+        .chain(analysis.comments.iter().map(|c| (c.position, c.text.as_str(), "comment", 0)))
+        .collect();
+    spans.sort_by_key(|span| span.0);
+
+    let mut data = Vec::new();
+    let mut last = (0, 0);
+    for (position, value, kind, modifiers) in spans {
+        let kind = TOKEN_TYPES.iter().position(|t| *t == kind).expect("listed in TOKEN_TYPES") as u32;
+        // 多数客户端不支持跨行的token，字符串按行拆开
+        let mut offset = position;
+        for piece in value.split('\n') {
+            let (line, column) = index.position(offset);
+            let delta_column = if line == last.0 { column - last.1 } else { column };
+            let length = piece.trim_end_matches('\r').encode_utf16().count() as u32;
+            data.extend([line - last.0, delta_column, length, kind, modifiers]);
+            last = (line, column);
+            offset += piece.len() + 1;
+        }
+    }
+    data
+}
+
+/// `textDocument/completion`: 光标处可见的名称、关键字和内置函数
+fn completion(document: &Document, offset: usize) -> Vec<Value> {
+    let fallback;
+    let mut analysis = document.analysis();
+    let in_comment = |c: &Comment| (c.position..=c.position + c.text.len()).contains(&offset);
+    if analysis.comments.iter().any(in_comment) {
+        return Vec::new();
+    }
+    if analysis.ast.is_none() {
+        // 正在输入的那一行通常无法解析，去掉它再分析一次，其他位置保持不变
+        let line_start = document.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = document.text[offset..].find('\n').map_or(document.text.len(), |i| offset + i);
+        let blank = " ".repeat(line_end - line_start);
+        let text = format!("{}{}{}", &document.text[..line_start], blank, &document.text[line_end..]);
+        fallback = Analysis::new(&text, &document.settings);
+        analysis = &fallback;
+    }
+
+    let signatures = analysis.signatures();
+    let mut items: Vec<Value> = analysis
+        .visible(offset)
+        .into_iter()
+        .map(|name| match name.kind {
+            SymbolKind::Function { .. } => json!({
+                "label": name.name,
+                "kind": COMPLETION_FUNCTION,
+                "detail": signatures.get(&name.span.start).cloned().unwrap_or_default(),
+            }),
+            SymbolKind::Variable { .. } | SymbolKind::Parameter | SymbolKind::LoopVariable => json!({
+                "label": name.name,
+                "kind": COMPLETION_VARIABLE,
+                "detail": name.ty.name(),
+            }),
+        })
+        .collect();
+    items.extend(BUILTIN_SIGNATURES.iter().map(|(name, signature)| {
+        json!({ "label": name, "kind": COMPLETION_FUNCTION, "detail": signature })
+    }));
+    items.extend(compile::STATEMENT_KEYWORDS.iter().chain(&["else"]).map(|keyword| {
+        json!({ "label": keyword, "kind": COMPLETION_KEYWORD })
+    }));
+    items
+}
+
+/// `textDocument/references`: 只在当前文档中查找，每个.ntf文件都是独立的程序
+fn references(uri: &str, document: &Document, offset: usize, include_declaration: bool) -> Vec<Value> {
+    let analysis = document.analysis();
+    let Some(target) = analysis.name_at_offset(offset) else {
+        return Vec::new();
+    };
+    let index = LineIndex::new(&document.text);
+    analysis
+        .occurrences(target)
+        .into_iter()
+        .filter(|name| include_declaration || !name.declaration)
+        .map(|name| json!({ "uri": uri, "range": index.range(name) }))
+        .collect()
+}
+
+fn document_highlight(document: &Document, offset: usize) -> Vec<Value> {
+    let analysis = document.analysis();
+    let Some(target) = analysis.name_at_offset(offset) else {
+        return Vec::new();
+    };
+    let index = LineIndex::new(&document.text);
+    analysis
+        .occurrences(target)
+        .into_iter()
+        .map(|name| json!({ "range": index.range(name), "kind": analysis.highlight_kind(name) }))
+        .collect()
+}
+
+/// 把`old`改成`new`的一处编辑，只替换两者不同的部分，让编辑器尽量保持光标和折叠的位置
+fn text_edit(old: &str, new: &str) -> Option<Value> {
+    if old == new {
+        return None;
+    }
+    let boundary = |text: &str, mut i: usize, forward: bool| {
+        while !text.is_char_boundary(i) {
+            i = if forward { i + 1 } else { i - 1 };
+        }
+        i
+    };
+    let prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    let prefix = boundary(old, prefix, false);
+    let suffix = old[prefix..].bytes().rev().zip(new[prefix..].bytes().rev()).take_while(|(a, b)| a == b).count();
+    let (old_end, new_end) = (boundary(old, old.len() - suffix, true), boundary(new, new.len() - suffix, true));
+    let index = LineIndex::new(old);
+    let position = |offset| {
+        let (line, character) = index.position(offset);
+        json!({ "line": line, "character": character })
+    };
+    Some(json!({
+        "range": { "start": position(prefix), "end": position(old_end) },
+        "newText": &new[prefix..new_end],
+    }))
+}
+
+/// `textDocument/formatting`和`rangeFormatting`，与`ntfp fmt`相同。
+/// 有语法错误时不做修改，保存时不会弹出错误
+fn formatting(document: &Document, lines: Option<RangeInclusive<u32>>) -> Value {
+    let settings = &document.settings;
+    let formatted = match lines {
+        None => formatter::format_source(&document.text, settings.edition, &settings.style),
+        Some(lines) => formatter::format_lines(&document.text, settings.edition, &settings.style, lines),
+    };
+    match formatted {
+        Ok(formatted) => json!(text_edit(&document.text, &formatted).into_iter().collect::<Vec<_>>()),
+        Err(e) => {
+            debug!(error = %e, "not formatting");
+            Value::Null
+        }
+    }
+}
+
+/// 显示哪些类型提示，来自`initializationOptions`或`ntfp`配置下的`inlayHints`，
+/// 例如`{ "inlayHints": { "parameterTypes": false } }`
+struct HintConfig {
+    /// `let`和`for`变量推断出的类型
+    variable_types: bool,
+    /// 没有标注类型的参数 (int)
+    parameter_types: bool,
+    /// `method`隐含的`-> int`
+    return_types: bool,
+}
+
+impl Default for HintConfig {
+    fn default() -> Self {
+        HintConfig { variable_types: true, parameter_types: true, return_types: true }
+    }
+}
+
+impl HintConfig {
+    /// 只修改配置中出现的开关
+    fn update(&mut self, options: &Value) {
+        let hints = &options["inlayHints"];
+        for (key, flag) in [
+            ("variableTypes", &mut self.variable_types),
+            ("parameterTypes", &mut self.parameter_types),
+            ("returnTypes", &mut self.return_types),
+        ] {
+            if let Some(value) = hints[key].as_bool() {
+                *flag = value;
+            }
+        }
+    }
+}
+
+/// `textDocument/inlayHint`: 在`start..end`中的名称后显示没有写出的类型
+fn inlay_hints(document: &Document, config: &HintConfig, start: usize, end: usize) -> Vec<Value> {
+    let analysis = document.analysis();
+    let index = LineIndex::new(&document.text);
+    let mut hints = Vec::new();
+    for (i, token) in analysis.tokens.iter().enumerate() {
+        let Some(name) = analysis.name_at(i).filter(|name| name.declaration) else {
+            continue;
+        };
+        if token.position < start || token.position > end {
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| &analysis.tokens[i].type_);
+        let annotated = analysis.tokens.get(i + 1).is_some_and(|t| t.type_ == TokenType::Colon);
+        let label = match name.kind {
+            SymbolKind::Variable { .. } | SymbolKind::LoopVariable if config.variable_types => {
+                format!(": {}", name.ty.name())
+            }
+            SymbolKind::Parameter if config.parameter_types && !annotated => format!(": {}", name.ty.name()),
+            SymbolKind::Function { .. } if config.return_types && previous == Some(&TokenType::Method) => {
+                format!(" -> {}", name.ty.name())
+            }
+            _ => continue,
+        };
+        let (line, character) = index.position(token.position + token.value.len());
+        hints.push(json!({
+            "position": { "line": line, "character": character },
+            "label": label,
+            "kind": INLAY_TYPE,
+        }));
+    }
+    hints
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    hints: HintConfig,
+    shutdown: bool,
+}
+
+impl Server {
+    fn open(&mut self, uri: &str, text: String) {
+        let settings = Settings::for_uri(uri);
+        self.documents.insert(uri.to_string(), Document::new(text, settings));
+    }
+
+    /// 请求中的文档和光标位置 (字节位置)
+    fn document_offset(&self, params: &Value) -> Result<(&Document, usize), (i64, String)> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        match (position["line"].as_u64(), position["character"].as_u64()) {
+            (Some(line), Some(column)) => Ok((document, LineIndex::new(&document.text).offset(line as u32, column as u32))),
+            _ => Err((INVALID_PARAMS, "缺少position".to_string())),
+        }
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or((INVALID_PARAMS, "缺少textDocument.uri".to_string()))?;
+        self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("文档没有打开: {}", uri)))
+    }
+
+    fn capabilities() -> Value {
+        json!({
+            "capabilities": {
+                // 每次修改发送完整文本
+                "textDocumentSync": 1,
+                "semanticTokensProvider": {
+                    "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": TOKEN_MODIFIERS },
+                    "full": true,
+                },
+                "completionProvider": {},
+                "referencesProvider": true,
+                "documentHighlightProvider": true,
+                "documentFormattingProvider": true,
+                "documentRangeFormattingProvider": true,
+                "inlayHintProvider": true,
+            },
+            "serverInfo": { "name": "ntfp", "version": env!("CARGO_PKG_VERSION") },
+        })
+    }
+
+    /// 处理一个请求，返回`result`或`(错误码, 消息)`
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                self.hints.update(&params["initializationOptions"]);
+                Ok(Self::capabilities())
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/semanticTokens/full" => Ok(json!({ "data": semantic_tokens(self.document(params)?) })),
+            "textDocument/completion" => {
+                let (document, offset) = self.document_offset(params)?;
+                Ok(json!(completion(document, offset)))
+            }
+            "textDocument/references" => {
+                let (document, offset) = self.document_offset(params)?;
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+                Ok(json!(references(uri, document, offset, include_declaration)))
+            }
+            "textDocument/documentHighlight" => {
+                let (document, offset) = self.document_offset(params)?;
+                Ok(json!(document_highlight(document, offset)))
+            }
+            "textDocument/formatting" => Ok(formatting(self.document(params)?, None)),
+            "textDocument/inlayHint" => {
+                let document = self.document(params)?;
+                let index = LineIndex::new(&document.text);
+                let offset = |position: &Value| {
+                    let (line, column) = (position["line"].as_u64()?, position["character"].as_u64()?);
+                    Some(index.offset(line as u32, column as u32))
+                };
+                match (offset(&params["range"]["start"]), offset(&params["range"]["end"])) {
+                    (Some(start), Some(end)) => Ok(json!(inlay_hints(document, &self.hints, start, end))),
+                    _ => Err((INVALID_PARAMS, "缺少range".to_string())),
+                }
+            }
+            "textDocument/rangeFormatting" => {
+                let document = self.document(params)?;
+                let (start, end) = (&params["range"]["start"], &params["range"]["end"]);
+                let (Some(first), Some(last)) = (start["line"].as_u64(), end["line"].as_u64()) else {
+                    return Err((INVALID_PARAMS, "缺少range".to_string()));
+                };
+                // 结束于行首的范围 (选中了整行) 不包含那一行
+                let last = if last > first && end["character"].as_u64() == Some(0) { last - 1 } else { last };
+                Ok(formatting(document, Some(first as u32 + 1..=last as u32 + 1)))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("不支持的请求: {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                self.open(uri, params["textDocument"]["text"].as_str().unwrap_or_default().to_string());
+            }
+            "textDocument/didChange" => {
+                // 完整同步: 最后一个变更就是新的全文
+                let text = params["contentChanges"].as_array().and_then(|changes| changes.last());
+                match (self.documents.get_mut(uri), text.and_then(|change| change["text"].as_str())) {
+                    (Some(document), Some(text)) => document.set_text(text.to_string()),
+                    _ => debug!(uri, "ignoring change to unknown document"),
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+            }
+            "workspace/didChangeConfiguration" => self.hints.update(&params["settings"]["ntfp"]),
+            _ => debug!(method, "ignoring notification"),
+        }
+    }
+}
+
+pub fn run() -> Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            if method == "exit" {
+                // 协议规定: 收到shutdown之后退出码为0，否则为1
+                std::process::exit(if server.shutdown { 0 } else { 1 });
+            }
+            server.notification(method, params);
+            continue;
+        };
+        let response = match server.request(method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///nonexistent/main.ntf";
+
+    fn server(text: &str) -> Server {
+        let mut server = Server::default();
+        server.open(URI, text.to_string());
+        server
+    }
+
+    #[test]
+    fn line_index_counts_utf16_columns_and_stops_before_crlf() {
+        let index = LineIndex::new("a😀b\r\nxy\n");
+        assert_eq!(index.offset(0, 3), 5);
+        assert_eq!(index.offset(0, 99), 6);
+        assert_eq!(index.offset(1, 1), 9);
+        assert_eq!(index.offset(9, 0), 11);
+        assert_eq!(index.position(5), (0, 3));
+        assert_eq!(index.position(9), (1, 1));
+    }
+
+    #[test]
+    fn text_edit_trims_to_char_boundaries() {
+        // é和è只有最后一个字节不同
+        let edit = text_edit("let s = \"é\";\n", "let s = \"è\";\n").unwrap();
+        assert_eq!(edit["range"], json!({ "start": { "line": 0, "character": 9 }, "end": { "line": 0, "character": 10 } }));
+        assert_eq!(edit["newText"], "è");
+        assert_eq!(text_edit("print(1);\n", "print(1);\n"), None);
+    }
+
+    #[test]
+    fn references_use_utf16_positions() {
+        let mut server = server("fun square(n) -> int {\n    back n * n;\n}\n\nlet s = \"😀\"; print(square(2));\n");
+        // 😀占两个UTF-16单元，第二个square从第20列开始
+        let params = |include_declaration: bool| {
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": 4, "character": 21 },
+                "context": { "includeDeclaration": include_declaration },
+            })
+        };
+        let range = |line, character| {
+            json!({ "start": { "line": line, "character": character }, "end": { "line": line, "character": character + 6 } })
+        };
+        assert_eq!(server.request("textDocument/references", &params(false)), Ok(json!([{ "uri": URI, "range": range(4, 20) }])));
+        assert_eq!(
+            server.request("textDocument/references", &params(true)),
+            Ok(json!([{ "uri": URI, "range": range(0, 4) }, { "uri": URI, "range": range(4, 20) }]))
+        );
+    }
+
+    #[test]
+    fn inlay_hints_show_inferred_types() {
+        let mut server = server("fun square(n) -> int {\n    back n * n;\n}\n\nlet total = square(2);\n");
+        let params = json!({
+            "textDocument": { "uri": URI },
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 5, "character": 0 } },
+        });
+        let hint = |line, character| {
+            json!({ "position": { "line": line, "character": character }, "label": ": int", "kind": INLAY_TYPE })
+        };
+        assert_eq!(server.request("textDocument/inlayHint", &params), Ok(json!([hint(0, 12), hint(4, 9)])));
+    }
+}
@@ -1,13 +1,44 @@
 use clap::CommandFactory;
 use clap::{Parser, Subcommand};
 use std::{
+    ffi::OsString,
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     process::Command,
+    time::{Duration, Instant},
 };
 use anyhow::{Context, Result};
-mod compile;
+use tracing::{debug, info};
+use tracing_subscriber::EnvFilter;
+use ntfp::{compile, interp, platform};
+mod bundle;
+mod cache;
+mod completions;
+mod config;
+mod dev;
+mod doctor;
+mod external;
+mod formatter;
+mod from_rust;
+mod grammar;
+mod hot;
+mod info;
+mod langdoc;
+mod lints;
+mod lock;
+mod lsp;
+mod manifest;
+mod metadata;
+mod plugins;
+mod report;
+mod shell;
+mod signals;
+mod size;
+mod sources;
+mod stats;
+mod usage;
+mod verify;
 
 #[derive(Parser)]
 #[command(
@@ -20,14 +51,42 @@ mod compile;
   ntfp new <name>      创建新项目
   ntfp build [path]    编译项目
   ntfp run [path]      构建并运行项目
+  ntfp run-all-examples
+                       构建并运行examples/中的全部示例
+  ntfp script <file>   直接运行单个.ntf文件
+  ntfp shell           进入设置好项目环境的子shell
   ntfp init [path]     初始化现有目录为Netflu项目
+  ntfp grammar         导出语言语法
+  ntfp help-lang [topic]
+                       查看语言参考
+  ntfp transpile       生成独立的Cargo项目
+  ntfp doctor          检查开发环境
+  ntfp verify          不构建，快速检查项目配置和语法
+  ntfp size            比较debug和release产物的大小
+  ntfp inspect <file>  查看ntfp构建的二进制文件信息
+  ntfp info            查看项目概要
+  ntfp stats           查看本地使用统计
+  ntfp completions <shell>
+                       生成shell补全脚本
 
 示例:
   ntfp new hello_world
   ntfp build
-  ntfp run"
+  ntfp run
+
+外部命令:
+  ntfp <name> [args]   运行PATH或~/.ntfp/bin中的ntfp-<name>
+
+日志:
+  -v                   输出更详细的状态 (ntfp run显示完整的运行报告)
+  -vv / -vvv           输出debug / trace级别的编译器日志
+  NTFP_LOG=<filter>    按模块过滤日志 (例如: NTFP_LOG=ntfp::compile=trace)"
 )]
 struct Cli {
+    /// 输出更详细的状态和日志 (-v: 详细状态, -vv: debug, -vvv: trace)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -38,82 +97,621 @@ enum Commands {
     #[command(about = "创建新的Netflu项目", long_about = "创建一个新的Netflu项目目录并初始化基本文件结构
 
 参数:
-  <name>      项目名称
-  --template  使用的模板名称 (默认: 'default')
+  <name>       项目名称
+  --template   使用的模板名称 (默认: 'default')
+  --from-rust  从Rust源文件导入pub fn，生成extern rust声明并加入[rust] sources
+  --edition    语言版本 (默认: 最新版本)
 
 示例:
   ntfp new hello_world
-  ntfp new my_project --template default")]
+  ntfp new legacy_project --edition 2025
+  ntfp new my_project --template default
+  ntfp new my_project --from-rust ../mylib/src/lib.rs")]
     New {
         /// 项目名称
         name: String,
         /// 使用的模板名称
         #[arg(short, long, default_value = "default")]
         template: String,
+        /// 导入的Rust源文件
+        #[arg(long)]
+        from_rust: Option<PathBuf>,
+        /// 语言版本
+        #[arg(long, value_parser = parse_edition)]
+        edition: Option<compile::Edition>,
     },
     
     /// 构建并运行项目
     #[command(about = "构建并运行项目", long_about = "编译项目并运行生成的可执行文件
 
 参数:
-  --path  项目路径 (默认: 当前目录)
+  --path  项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+          直接指定ntfp.toml的路径
+  --release
+          以release配置构建，使用ntfp.toml中的[profile.release]
+  --example <NAME>
+          运行examples/<NAME>.ntf而不是src/main.ntf
+  --hot   用解释器运行，src/main.ntf修改后替换函数定义而不重启程序
+  --engine
+          热重载模式使用的解释器: bytecode (默认) 或 ast
 
 示例:
   ntfp run
-  ntfp run --path ./my_project")]
+  ntfp run --path ./my_project
+  ntfp run --manifest-path ../other/ntfp.toml
+  ntfp run --release
+  ntfp run --example hello
+  ntfp run --hot
+  ntfp run --hot --engine ast")]
     Run {
-        #[arg(short, long, default_value = ".")]
-        path: String,
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 以release配置构建
+        #[arg(long, conflicts_with = "hot")]
+        release: bool,
+        /// 运行examples/中的示例
+        #[arg(long, value_name = "NAME", conflicts_with = "hot")]
+        example: Option<String>,
+        /// 热重载模式
+        #[arg(long)]
+        hot: bool,
+        /// 解释器引擎
+        #[arg(long, value_enum, default_value = "bytecode", requires = "hot")]
+        engine: interp::Engine,
     },
     
+    /// 构建并运行全部示例
+    #[command(about = "构建并运行全部示例", long_about = "依次构建并运行examples/中的每个.ntf文件，报告哪些示例成功、哪些失败；有失败时返回非零退出码，便于在语言更新后检查教学材料
+
+参数:
+  --path     项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+             直接指定ntfp.toml的路径
+  --release  以release配置构建
+  --format <FORMAT>
+             结果格式: human (默认), json 或 junit (JUnit XML，供CI系统显示)
+
+示例:
+  ntfp run-all-examples
+  ntfp run-all-examples --path ./course
+  ntfp run-all-examples --format junit > examples.xml")]
+    RunAllExamples {
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 以release配置构建
+        #[arg(long)]
+        release: bool,
+        /// 结果格式
+        #[arg(long, value_enum, default_value = "human")]
+        format: report::ReportFormat,
+    },
+
+    /// 直接运行单个.ntf文件
+    #[command(about = "直接运行单个.ntf文件", long_about = "不需要ntfp.toml，用解释器直接运行一个.ntf文件。\
+脚本可以把语句直接写在文件顶层，这些语句按顺序组成自动生成的main函数；\
+写了顶层语句的脚本不能再定义fun main
+
+参数:
+  <FILE>      要运行的.ntf文件
+  --edition   脚本使用的语言版本 (默认: 最新)
+  --engine    解释器引擎: bytecode (默认) 或 ast
+
+  --native    用rustc编译后运行；生成的代码和程序缓存在~/.ntfp/cache/scripts中，
+              以内容哈希为键，不会写入当前目录，源码不变时直接复用
+
+示例:
+  ntfp script hello.ntf
+  ntfp script old.ntf --edition 2025
+  ntfp script bench.ntf --native")]
+    Script {
+        /// 要运行的.ntf文件
+        file: PathBuf,
+        /// 语言版本
+        #[arg(long, value_parser = parse_edition)]
+        edition: Option<compile::Edition>,
+        /// 解释器引擎
+        #[arg(long, value_enum, default_value = "bytecode")]
+        engine: interp::Engine,
+        /// 用rustc编译后运行
+        #[arg(long, conflicts_with = "engine")]
+        native: bool,
+    },
+
+    /// 进入设置好项目环境的子shell
+    #[command(about = "进入设置好项目环境的子shell", long_about = "启动一个子shell，方便课堂和演示环境:
+  - NTFP_PROJECT设置为项目路径
+  - PATH最前面加入~/.ntfp/bin和当前ntfp所在的目录
+  - bash、zsh、fish和cmd中可以使用别名nb (build)、nr (run)、nh (run --hot)、ni (info)，
+    在任何目录下都作用于这个项目
+
+shell取自$SHELL (Windows上为%COMSPEC%)，输入exit返回
+
+参数:
+  --path     项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+             直接指定ntfp.toml的路径
+
+示例:
+  ntfp shell
+  ntfp shell --path ./lesson3")]
+    Shell {
+        #[command(flatten)]
+        project: ProjectLocation,
+    },
+
     /// 初始化现有目录为Netflu项目
     #[command(about = "初始化现有目录为Netflu项目", long_about = "将现有目录初始化为Netflu项目，创建必要的配置文件
 
 参数:
-  --path  要初始化的目录路径 (默认: 当前目录)
+  --path     要初始化的目录路径 (默认: 当前目录)
+  --edition  语言版本 (默认: 最新版本)
 
 示例:
   ntfp init
-  ntfp init --path ./existing_project")]
+  ntfp init --path ./existing_project
+  ntfp init --edition 2025")]
     Init {
         #[arg(short, long, default_value = ".")]
         path: String,
+        /// 语言版本
+        #[arg(long, value_parser = parse_edition)]
+        edition: Option<compile::Edition>,
     },
     
     /// 编译项目但不运行
     #[command(about = "编译项目但不运行", long_about = "将.ntf源文件编译为Rust代码并生成可执行文件
 
 参数:
-  --path  项目路径 (默认: 当前目录)
+  --path   项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+           直接指定ntfp.toml的路径
+  --release
+           优化构建，输出到target/release，并应用[profile.release]中的
+           strip / lto / panic / static / overflow-checks选项
+  --example <NAME>
+           构建examples/<NAME>.ntf，输出到target/<profile>/examples
+  --stats  输出词法单元、AST节点、符号表和峰值内存统计
+  --out    构建成功后将二进制文件复制到指定路径
+  --bundle-interpreter
+           不调用rustc，生成内嵌源码、由解释器执行的启动器 (适用于未安装Rust的环境)
+  --embed-source
+           在二进制文件中嵌入生成的Rust代码，可用`ntfp inspect --source`查看
+  --engine 启动器使用的解释器: bytecode (默认) 或 ast，需要--bundle-interpreter
+  --show-backend-warnings
+           显示rustc对生成代码的警告，位置尽量换算为src/main.ntf中的行
+  --allow / --warn / --deny <LINT>
+           设置lint级别，覆盖ntfp.toml中的[lints] (`warnings`表示全部)
+  --emit ast-json
+           不生成二进制文件，把语义分析后的词法单元和AST写到
+           target/<profile>/<name>.ast.json，供可视化、分析等外部工具使用
 
 示例:
   ntfp build
-  ntfp build --path ./my_project")]
+  ntfp build --path ./my_project
+  ntfp build --release
+  ntfp build --emit ast-json
+  ntfp build --stats
+  ntfp build --out ./dist/myapp
+  ntfp build --bundle-interpreter
+  ntfp build --deny warnings")]
     Build {
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 以release配置构建
+        #[arg(long)]
+        release: bool,
+        /// 构建examples/中的示例
+        #[arg(long, value_name = "NAME")]
+        example: Option<String>,
+        /// 输出编译统计信息
+        #[arg(long)]
+        stats: bool,
+        /// 二进制文件的复制目标 (已存在的目录则复制到该目录下)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// 生成由解释器执行的启动器，不需要rustc
+        #[arg(long)]
+        bundle_interpreter: bool,
+        /// 启动器使用的解释器引擎
+        #[arg(long, value_enum, default_value = "bytecode", requires = "bundle_interpreter")]
+        engine: interp::Engine,
+        /// 在二进制文件中嵌入生成的Rust代码
+        #[arg(long)]
+        embed_source: bool,
+        /// 显示rustc的警告
+        #[arg(long, conflicts_with = "bundle_interpreter")]
+        show_backend_warnings: bool,
+        #[command(flatten)]
+        lints: lints::LintOverrides,
+        /// 只输出中间结果，不生成二进制文件
+        #[arg(long, value_enum, conflicts_with_all = ["out", "bundle_interpreter", "embed_source", "stats"])]
+        emit: Option<Emit>,
+    },
+
+    /// 生成独立的Cargo项目
+    #[command(about = "生成独立的Cargo项目", long_about = "将项目转译为完整的Cargo项目 (Cargo.toml + src)，但不进行编译，便于审查生成的Rust代码或交给其他构建系统
+
+参数:
+  --path  项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+          直接指定ntfp.toml的路径
+  --out   输出目录 (默认: target/transpiled/<name>)
+
+示例:
+  ntfp transpile
+  ntfp transpile --out ../my_project_rs")]
+    Transpile {
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 输出目录
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// 格式化源文件
+    #[command(about = "格式化源文件", long_about = "按统一的风格重新排版.ntf文件。格式化只改变排版: 结果保证能解析回同一个程序，\
+并且再次格式化不会有任何变化，适合在保存时自动运行
+
+参数:
+  [FILES]...  要格式化的文件 (默认: 项目中的全部源文件，受[package] include/exclude影响)
+  --path      项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --check     只检查，列出需要格式化的文件，有则返回非零退出码
+  --stdin     从标准输入读取，把结果写到标准输出
+  --range <START:END>
+              只格式化与这些行 (从1开始，包含两端) 有重叠的顶层定义和语句，其余内容原样保留；
+              只写一个数字表示单独一行。需要--stdin或者正好一个文件
+  --byte-range <START:END>
+              同--range，但用字节偏移 (不含END) 指定，方便编辑器传入选区
+  --edition   解析使用的语言版本 (默认: 文件所在项目的edition，不在项目中时为最新)
+
+示例:
+  ntfp fmt
+  ntfp fmt --check
+  ntfp fmt src/main.ntf --range 10:20
+  cat hello.ntf | ntfp fmt --stdin --range 3")]
+    Fmt {
+        /// 要格式化的文件
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 只检查，不写入
+        #[arg(long)]
+        check: bool,
+        /// 从标准输入读取，写到标准输出
+        #[arg(long, conflicts_with = "files")]
+        stdin: bool,
+        /// 只格式化这些行
+        #[arg(long, value_name = "START:END", value_parser = parse_line_range)]
+        range: Option<std::ops::RangeInclusive<u32>>,
+        /// 只格式化这些字节
+        #[arg(long, value_name = "START:END", value_parser = parse_byte_range, conflicts_with = "range")]
+        byte_range: Option<std::ops::Range<usize>>,
+        /// 语言版本
+        #[arg(long, value_parser = parse_edition)]
+        edition: Option<compile::Edition>,
+    },
+
+    /// 导出语言语法
+    #[command(about = "导出语言语法", long_about = "导出编译器当前使用的语法规则，语法表与解析器同源维护
+
+参数:
+  --format  输出格式: ebnf 或 railroad (默认: ebnf)
+
+示例:
+  ntfp grammar
+  ntfp grammar --format railroad > grammar.html")]
+    Grammar {
+        /// 输出格式
+        #[arg(short, long, value_enum, default_value = "ebnf")]
+        format: grammar::GrammarFormat,
+    },
+
+    /// 查看语言参考
+    #[command(about = "查看语言参考", long_about = "输出各个语言结构的说明、语法 (与`ntfp grammar`同源) 和可运行的示例
+
+参数:
+  [topic]     主题名，省略时列出全部主题
+  --markdown  以Markdown格式输出全部主题，用于生成文档
+
+示例:
+  ntfp help-lang
+  ntfp help-lang fun
+  ntfp help-lang --markdown > docs/language.md")]
+    HelpLang {
+        /// 主题名
+        topic: Option<String>,
+        /// 输出Markdown文档
+        #[arg(long, conflicts_with = "topic")]
+        markdown: bool,
+    },
+
+    /// 检查开发环境
+    #[command(about = "检查开发环境", long_about = "检查rustc/cargo是否可用及其版本、target和缓存目录的写入权限以及全局配置，并给出修复建议
+
+参数:
+  --path  项目路径 (默认: 当前目录)
+
+示例:
+  ntfp doctor
+  ntfp doctor --path ./my_project")]
+    Doctor {
         #[arg(short, long, default_value = ".")]
         path: String,
     },
+
+    /// 不构建，快速检查项目
+    #[command(about = "不构建，快速检查项目配置和语法", long_about = "不运行语义分析和rustc，检查:
+  - ntfp.toml: 格式、edition、[language]、[lints]、include/exclude和[profile.release]
+  - 名称: 包名、版本号和输出文件名
+  - 文件: src/main.ntf以及[rust] sources、[c] link-search和[plugins]中的路径
+  - 源文件: 项目中的每个.ntf文件都能通过语法分析
+
+有问题时返回非零退出码，适合作为git pre-commit钩子
+
+参数:
+  --path     项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+             直接指定ntfp.toml的路径
+
+示例:
+  ntfp verify
+  ntfp verify --path ./my_project")]
+    Verify {
+        #[command(flatten)]
+        project: ProjectLocation,
+    },
+
+    /// 比较debug和release产物的大小
+    #[command(about = "比较debug和release产物的大小", long_about = "分别以debug和release配置构建项目，输出产物的大小和两者的差别
+
+参数:
+  --path     项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+             直接指定ntfp.toml的路径
+  --symbols  用nm读取符号表，列出项目中每个函数的代码大小 (release构建被strip时使用debug构建)
+
+示例:
+  ntfp size
+  ntfp size --symbols")]
+    Size {
+        #[command(flatten)]
+        project: ProjectLocation,
+        /// 列出每个函数的大小
+        #[arg(long)]
+        symbols: bool,
+    },
+
+    /// 查看ntfp构建的二进制文件信息
+    #[command(about = "查看ntfp构建的二进制文件信息", long_about = "读取ntfp构建的二进制文件中嵌入的元数据: 包名、版本、edition、编译器版本、构建配置和后端
+
+参数:
+  <binary>  二进制文件路径
+  --source  同时输出嵌入的源码 (rustc后端需要以`--embed-source`构建)
+
+示例:
+  ntfp inspect target/debug/main
+  ntfp inspect dist/myapp --source")]
+    Inspect {
+        /// 二进制文件路径
+        binary: PathBuf,
+        /// 输出嵌入的源码
+        #[arg(long)]
+        source: bool,
+    },
+
+    /// 查看项目概要
+    #[command(about = "查看项目概要", long_about = "输出项目的名称、版本、edition、构建目标、ntfp.toml中列出的外部代码 (Rust源文件和C库) 以及源文件数量和行数
+
+参数:
+  --path  项目路径，可以是项目中的子目录 (默认: 当前目录)
+  --manifest-path <FILE>
+          直接指定ntfp.toml的路径
+
+示例:
+  ntfp info
+  ntfp info --path ./my_project")]
+    Info {
+        #[command(flatten)]
+        project: ProjectLocation,
+    },
+
+    /// 查看本地使用统计
+    #[command(about = "查看本地使用统计", long_about = "显示本地记录的构建次数、耗时和最常见的错误类别，统计数据不会上传
+
+需要先在全局配置 ~/.ntfp/config.toml 中开启:
+  usage-stats = true
+
+参数:
+  --reset  清空已记录的统计
+
+示例:
+  ntfp stats
+  ntfp stats --reset")]
+    Stats {
+        /// 清空已记录的统计
+        #[arg(long)]
+        reset: bool,
+    },
+
+    /// 生成shell补全脚本
+    #[command(about = "生成shell补全脚本", long_about = "输出指定shell的补全脚本。bash脚本还会调用ntfp补全lint名和edition等参数值
+
+参数:
+  <shell>  bash, zsh, fish, powershell 或 elvish
+
+示例:
+  ntfp completions bash > ~/.local/share/bash-completion/completions/ntfp
+  ntfp completions zsh > ~/.zfunc/_ntfp
+  ntfp completions fish > ~/.config/fish/completions/ntfp.fish")]
+    Completions {
+        /// 目标shell
+        shell: clap_complete::Shell,
+    },
+
+    /// 补全脚本使用的辅助命令，输出参数的候选值
+    #[command(hide = true)]
+    Complete {
+        kind: completions::CompletionKind,
+    },
+
+    /// 管理~/.ntfp/cache
+    #[command(about = "管理ntfp的缓存", long_about = "管理~/.ntfp/cache (可通过NTFP_HOME改变位置)。\
+`ntfp script --native`把编译产物缓存在这里，每次写入后自动清理到512M以内
+
+子命令:
+  gc          删除最久未使用的缓存条目，直到总大小不超过--max-size (默认: 512M)
+
+示例:
+  ntfp cache gc
+  ntfp cache gc --max-size 100M
+  ntfp cache gc --max-size 0")]
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommands,
+    },
+
+    /// 启动语言服务器
+    #[command(about = "启动语言服务器 (LSP)", long_about = "通过标准输入输出与编辑器通信的语言服务器 (Language Server Protocol)，\
+由编辑器启动，不需要手动运行。文档所在目录向上找到ntfp.toml时使用项目的edition和[language]设置，否则按`ntfp script`处理
+
+支持的功能:
+  语义高亮    根据符号表区分函数、变量、参数、类型和关键字
+  补全        光标处可见的名称、关键字和内置函数，附带函数签名
+  查找引用    当前文件中同一个符号的所有位置，以及读写高亮
+  格式化      整个文件或选中的范围，与`ntfp fmt`相同
+  类型提示    推断出的变量类型、参数的默认类型int和method隐含的返回类型
+
+示例 (Neovim):
+  vim.lsp.start({ name = \"ntfp\", cmd = { \"ntfp\", \"lsp\" } })")]
+    Lsp,
+
+    /// 编译器开发者工具
+    #[command(hide = true)]
+    Dev {
+        #[command(subcommand)]
+        command: dev::DevCommands,
+    },
+
+    /// 其他命令交给外部的`ntfp-<name>`
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
 }
 
+/// 定位项目: 从`--path`开始向上查找ntfp.toml (与cargo相同)，或用`--manifest-path`直接指定
+#[derive(Debug, Clone, clap::Args)]
+struct ProjectLocation {
+    /// 项目路径，可以是项目中的任意子目录
+    #[arg(short, long, default_value = ".")]
+    path: PathBuf,
+    /// ntfp.toml的路径
+    #[arg(long, value_name = "FILE", conflicts_with = "path")]
+    manifest_path: Option<PathBuf>,
+}
+
+impl ProjectLocation {
+    /// 项目根目录的绝对路径，项目中的其他路径都相对于它解析
+    fn root(&self) -> Result<PathBuf> {
+        if let Some(manifest_path) = &self.manifest_path {
+            if manifest_path.file_name() != Some(manifest::FILE_NAME.as_ref()) {
+                anyhow::bail!("--manifest-path必须指向`{}`: {:?}", manifest::FILE_NAME, manifest_path);
+            }
+            if !manifest_path.is_file() {
+                anyhow::bail!("配置文件不存在: {:?}", manifest_path);
+            }
+            let dir = manifest_path.parent().filter(|p| !p.as_os_str().is_empty());
+            let dir = dir.unwrap_or(Path::new("."));
+            return dir.canonicalize().with_context(|| format!("无法访问目录: {:?}", dir));
+        }
+        if !self.path.exists() {
+            anyhow::bail!("项目路径不存在: {}", self.path.display());
+        }
+        manifest::find_project_root(&self.path).ok_or_else(|| {
+            anyhow::anyhow!(
+                "不是有效的`Netflu`项目: {:?} 及其上级目录中都没有`{}`",
+                self.path,
+                manifest::FILE_NAME
+            )
+        })
+    }
+}
+
+/// `ntfp build --emit`的输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Emit {
+    /// 词法单元和语义分析后的AST，格式见`compile::AstJson`
+    AstJson,
+}
+
+#[derive(Debug, Default)]
+struct BuildOptions {
+    release: bool,
+    /// 构建`examples/<name>.ntf`而不是`src/main.ntf`
+    example: Option<String>,
+    stats: bool,
+    out: Option<PathBuf>,
+    bundle_interpreter: bool,
+    engine: interp::Engine,
+    embed_source: bool,
+    show_backend_warnings: bool,
+    lints: lints::LintOverrides,
+}
+
+impl BuildOptions {
+    fn profile(&self) -> &'static str {
+        if self.release { "release" } else { "debug" }
+    }
+}
+
+/// 项目的主程序，相对于项目目录
+const MAIN_SOURCE: &str = "src/main.ntf";
+
 struct ProjectTemplate {
     name: String,
     files: Vec<(String, String)>,
 }
 
-fn default_template(project_name: &str) -> ProjectTemplate {
+fn parse_edition(s: &str) -> Result<compile::Edition, String> {
+    compile::Edition::parse(s).ok_or_else(|| {
+        let known: Vec<_> = compile::Edition::ALL.iter().map(|e| e.as_str()).collect();
+        format!("不支持的edition: \"{}\" (可用: {})", s, known.join(", "))
+    })
+}
+
+/// `10:20`或`10`，从1开始，包含两端
+fn parse_line_range(s: &str) -> Result<std::ops::RangeInclusive<u32>, String> {
+    let (start, end) = s.split_once(':').unwrap_or((s, s));
+    match (start.trim().parse::<u32>(), end.trim().parse::<u32>()) {
+        (Ok(start), Ok(end)) if start >= 1 && start <= end => Ok(start..=end),
+        _ => Err(format!("无效的行范围: \"{}\" (格式: 开始行:结束行，从1开始)", s)),
+    }
+}
+
+/// `120:340`，不含结束位置
+fn parse_byte_range(s: &str) -> Result<std::ops::Range<usize>, String> {
+    match s.split_once(':').map(|(a, b)| (a.trim().parse::<usize>(), b.trim().parse::<usize>())) {
+        Some((Ok(start), Ok(end))) if start <= end => Ok(start..end),
+        _ => Err(format!("无效的字节范围: \"{}\" (格式: 开始:结束)", s)),
+    }
+}
+
+fn default_template(project_name: &str, edition: compile::Edition) -> ProjectTemplate {
     ProjectTemplate {
         name: "default".to_string(),
         files: vec![
             (
                 "ntfp.toml".to_string(),
                 format!(
-                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2025\"\n\n[dependencies]\n", project_name
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{}\"\n\n[dependencies]\n",
+                    project_name,
+                    edition.as_str()
                 ),
             ),
             (
                 "src/main.ntf".to_string(),
                 format!(
-                    "fun main() {{\n    print(\"Welcome to Netflu!\");\n}}\n"
+                    "fun main() {{\n    {}(\"Welcome to Netflu!\");\n}}\n",
+                    if edition >= compile::Edition::E2026 { "println" } else { "print" }
                 ),
             ),
             (
@@ -124,9 +722,15 @@ fn default_template(project_name: &str) -> ProjectTemplate {
     }
 }
 
-fn create_project(name: &str, template_name: &str) -> Result<()> {
+#[tracing::instrument]
+fn create_project(
+    name: &str,
+    template_name: &str,
+    from_rust: Option<&Path>,
+    edition: compile::Edition,
+) -> Result<()> {
     let template = match template_name {
-        "default" => default_template(name),
+        "default" => default_template(name, edition),
         _ => anyhow::bail!("未知模板: {}", template_name),
     };
 
@@ -147,38 +751,99 @@ fn create_project(name: &str, template_name: &str) -> Result<()> {
             .with_context(|| format!("无法写入文件: {:?}", full_path))?;
     }
 
-    println!("项目 '{}' 已创建成功! 使用模板: {}", name, template.name);
+    if let Some(rust_file) = from_rust {
+        import_rust_file(project_dir, rust_file)?;
+    }
+
+    eprintln!("项目 '{}' 已创建成功! 使用模板: {}", name, template.name);
     Ok(())
 }
 
-fn run_project(path: &str) -> Result<()> {
-    let project_path = Path::new(path);
+/// 把Rust源文件复制到项目中，并为其中兼容Netflu类型的`pub fn`生成`extern rust`声明
+fn import_rust_file(project_dir: &Path, rust_file: &Path) -> Result<()> {
+    let source = fs::read_to_string(rust_file)
+        .with_context(|| format!("无法读取文件: {:?}", rust_file))?;
+    let (bindings, skipped) = from_rust::extract(&source);
 
-    if !project_path.exists() {
-        anyhow::bail!("项目路径不存在: {}", path);
+    let relative = format!("src/{}.rs", from_rust::module_name(rust_file));
+    fs::copy(rust_file, project_dir.join(&relative))
+        .with_context(|| format!("无法复制文件: {:?}", rust_file))?;
+
+    let manifest_path = project_dir.join("ntfp.toml");
+    let mut manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("无法读取文件: {:?}", manifest_path))?;
+    manifest.push_str(&format!("\n[rust]\nsources = [\"{}\"]\n", relative));
+    fs::write(&manifest_path, manifest)
+        .with_context(|| format!("无法写入文件: {:?}", manifest_path))?;
+
+    let main_ntf = project_dir.join("src").join("main.ntf");
+    let mut program: String = bindings.iter().map(|b| b.declaration() + "\n").collect();
+    program.push('\n');
+    program.push_str(&fs::read_to_string(&main_ntf)
+        .with_context(|| format!("无法读取文件: {:?}", main_ntf))?);
+    fs::write(&main_ntf, program)
+        .with_context(|| format!("无法写入文件: {:?}", main_ntf))?;
+
+    eprintln!("已从 {:?} 导入 {} 个函数:", rust_file, bindings.len());
+    for binding in &bindings {
+        eprintln!("  {}", binding.declaration());
     }
+    for (name, reason) in &skipped {
+        eprintln!("警告: 跳过 {}: {}", name, reason);
+    }
+    Ok(())
+}
 
-    let cargo_toml = project_path.join("ntfp.toml");
-    if !cargo_toml.exists() {
-        anyhow::bail!("不是有效的`Netflu`项目: 未找到`ntfp.toml`。");
+#[tracing::instrument]
+fn run_project(
+    project_path: &Path,
+    release: bool,
+    example: Option<String>,
+    hot: bool,
+    engine: interp::Engine,
+    verbose: bool,
+) -> Result<()> {
+    if example.is_none() && manifest::Manifest::load(project_path)?.lib.is_some() {
+        anyhow::bail!("库项目 ([lib]) 不能直接运行，请使用`ntfp build`生成库文件");
     }
 
-    build_project(path)?;
+    if hot {
+        return hot::run_hot(project_path, engine);
+    }
 
-    let binary_path = project_path.join("target").join("debug").join("main.exe");
+    let build = build_project(project_path, &BuildOptions { release, example, ..Default::default() })?;
+    let binary_path = build.binary_path;
     if !binary_path.exists() {
         anyhow::bail!("未找到编译后的二进制文件，请先运行`ntfp build`。");
     }
 
-    println!("正在运行项目: {}", path);
-    let output = Command::new(binary_path)
-        .current_dir(project_path)
-        .output()
-        .with_context(|| "无法运行项目")?;
+    if !verbose {
+        // 程序直接使用终端的标准输入输出，ntfp的退出码与程序相同，便于在管道和脚本中使用
+        debug!(binary = ?binary_path, "running project binary");
+        let status = signals::status(Command::new(&binary_path).current_dir(project_path))
+            .with_context(|| "无法运行项目")?;
+        if signals::interrupted() {
+            std::process::exit(130);
+        }
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        return Ok(());
+    }
 
+    eprintln!("正在运行项目: {}", project_path.display());
+    let output = run_binary(&binary_path, project_path)?;
+
+    if signals::interrupted() {
+        anyhow::bail!(
+            "项目已被中断\n标准输出:\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
     if output.status.success() {
-        println!("项目运行成功!");
-        println!("标准输出:\n{}", String::from_utf8_lossy(&output.stdout));
+        eprintln!("项目运行成功!");
+        eprintln!("标准输出:");
+        print!("{}", String::from_utf8_lossy(&output.stdout));
     } else {
         anyhow::bail!(
             "项目运行失败!\n错误输出:\n{}",
@@ -189,70 +854,719 @@ fn run_project(path: &str) -> Result<()> {
     Ok(())
 }
 
-fn build_project(path: &str) -> Result<()> {
-    let project_path = Path::new(path);
+/// 在项目目录中运行构建出的程序并收集输出
+fn run_binary(binary_path: &Path, project_path: &Path) -> Result<std::process::Output> {
+    debug!(binary = ?binary_path, "spawning project binary");
+    signals::output(Command::new(binary_path).current_dir(project_path)).with_context(|| "无法运行项目")
+}
 
-    if !project_path.exists() {
-        anyhow::bail!("项目路径不存在: {}", path);
+/// 不经过项目，运行单个文件；顶层语句组成自动生成的main。默认用解释器，
+/// `native`时用rustc编译，中间文件都放在缓存目录中
+fn run_script(file: &Path, edition: compile::Edition, engine: interp::Engine, native: bool) -> Result<()> {
+    let source = fs::read_to_string(file).with_context(|| format!("无法读取文件: {:?}", file))?;
+    let (program, warnings) =
+        interp::load_program_with_warnings(&source, edition, &Default::default(), true, compile::SemanticAnalyzer::new())?;
+    lints::LintLevels::warn_all().report(warnings)?;
+    if !native {
+        return interp::execute(&program, engine, compile::Overflow::Checked, None).map_err(|e| anyhow::anyhow!("运行错误: {}", e));
+    }
+
+    let code = compile::generate_code(&program, compile::Overflow::Checked).map_err(|e| anyhow::anyhow!("代码生成错误: {}", e))?;
+    let rustc = config::GlobalConfig::load()?.rustc();
+    let key = cache::key(&[env!("CARGO_PKG_VERSION"), &rustc.to_string_lossy(), &code]);
+    let dir = cache::entry("scripts", &key)?;
+    let binary_path = dir.join(platform::executable_name("script"));
+    if binary_path.exists() {
+        debug!(binary = ?binary_path, "reusing cached script binary");
+    } else {
+        // 先输出到临时文件，rustc被中断时缓存中不会留下不完整的程序
+        let partial = dir.join(platform::executable_name("script.partial"));
+        compile_with_rustc(&dir, &dir.join("main.rs"), &code, &partial, &[])?;
+        fs::rename(&partial, &binary_path).with_context(|| format!("无法写入缓存: {:?}", binary_path))?;
+        if let Err(e) = cache::gc(cache::DEFAULT_MAX_SIZE) {
+            eprintln!("警告: 清理缓存失败: {:#}", e);
+        }
     }
 
-    let ntfp_toml = project_path.join("ntfp.toml");
-    if !ntfp_toml.exists() {
-        anyhow::bail!("不是有效的`Netflu`项目: 未找到`ntfp.toml`");
+    let status = signals::status(&mut Command::new(&binary_path)).with_context(|| "无法运行脚本")?;
+    if signals::interrupted() {
+        std::process::exit(130);
     }
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// 依次构建并运行每个示例，全部完成后汇总结果
+fn run_all_examples(project_path: &Path, release: bool, format: report::ReportFormat) -> Result<()> {
+    let manifest = manifest::Manifest::load(project_path)?;
+    let names = manifest.example_names(project_path)?;
+    if names.is_empty() {
+        anyhow::bail!("项目没有示例: 在`{}/`中添加.ntf文件", manifest::EXAMPLES_DIR);
+    }
+
+    let mut cases = Vec::new();
+    for name in &names {
+        let started = Instant::now();
+        let options = BuildOptions { release, example: Some(name.clone()), ..Default::default() };
+        let result = build_project(project_path, &options)
+            .and_then(|build| run_binary(&build.binary_path, project_path));
+        if signals::interrupted() {
+            anyhow::bail!("已中断");
+        }
+        let failure = match result {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some((format!("运行失败 ({})", output.status), String::from_utf8_lossy(&output.stderr).into_owned())),
+            Err(e) => Some(("构建失败".to_string(), format!("{:#}", e))),
+        };
+        if format == report::ReportFormat::Human {
+            match &failure {
+                None => println!("示例 {}: 通过", name),
+                Some((reason, output)) => println!("示例 {}: {}\n{}", name, reason, output),
+            }
+        }
+        cases.push(report::CaseResult { name: name.clone(), duration: started.elapsed(), failure });
+    }
+
+    let failed: Vec<&str> = cases.iter().filter(|c| c.failure.is_some()).map(|c| c.name.as_str()).collect();
+    match format {
+        report::ReportFormat::Human => {
+            println!("\n{} 个示例: {} 个通过, {} 个失败", names.len(), names.len() - failed.len(), failed.len())
+        }
+        report::ReportFormat::Json => print!("{}", report::render_json(&manifest.package.name, &cases)),
+        report::ReportFormat::Junit => print!("{}", report::render_junit(&manifest.package.name, &cases)),
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("失败的示例: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+#[tracing::instrument]
+fn build_project(project_path: &Path, options: &BuildOptions) -> Result<BuildResult> {
+    let started = Instant::now();
+    let result = compile_project(project_path, options);
+    usage::record_build(started.elapsed(), &result);
+    if let Ok(build) = &result {
+        info!(
+            binary = ?build.binary_path,
+            rust = ?build.generated_rust_path,
+            warnings = build.warnings.len(),
+            "build finished"
+        );
+    }
+    result
+}
+
+/// 一次构建的产物；调用方从这里取路径，而不是自己推算target下的文件名
+#[derive(Debug)]
+struct BuildResult {
+    /// 生成的二进制文件，库项目为动态库
+    binary_path: PathBuf,
+    /// 交给rustc的Rust源文件，`--bundle-interpreter`时为`None`
+    generated_rust_path: Option<PathBuf>,
+    /// 以warn级别报告过的警告
+    warnings: Vec<compile::Warning>,
+    duration: Duration,
+}
+
+/// 前端各阶段的结果，构建和转译共用
+struct AnalyzedProject {
+    manifest: manifest::Manifest,
+    edition: compile::Edition,
+    source: String,
+    ast: Vec<compile::ASTNode>,
+    token_count: usize,
+    symbol_count: usize,
+    warnings: Vec<compile::Warning>,
+}
 
-    let src_dir = project_path.join("src");
-    let main_ntf = src_dir.join("main.ntf");
+/// 读取项目并完成词法、语法和语义分析，`action`用于进度提示 (例如"构建")
+/// `source_name`是相对于项目目录的入口文件，例如`src/main.ntf`
+fn analyze_project(
+    project_path: &Path,
+    source_name: &str,
+    action: &str,
+    lints: &lints::LintOverrides,
+) -> Result<AnalyzedProject> {
+    let main_ntf = project_path.join(source_name);
     if !main_ntf.exists() {
-        anyhow::bail!("未找到主程序文件: `src/main.ntf`");
+        anyhow::bail!("未找到主程序文件: `{}`", source_name);
+    }
+
+    let manifest = manifest::Manifest::load(project_path)?;
+    let edition = manifest.edition()?;
+    if !manifest.sources()?.contains(Path::new(source_name)) {
+        anyhow::bail!("`{}`被ntfp.toml中的[package] include/exclude排除", source_name);
     }
 
-    println!("正在构建项目: {}", path);
+    eprintln!(
+        "正在{}项目: {} v{} ({})",
+        action,
+        manifest.package.name,
+        manifest.package.version,
+        project_path.display()
+    );
 
     let ntf_content = fs::read_to_string(&main_ntf)
         .with_context(|| format!("无法读取文件: {:?}", main_ntf))?;
 
+    stats::alloc::reset_peak();
     let tokens = compile::lexer(&ntf_content)
         .map_err(|e| anyhow::anyhow!("词法分析错误: {}", e))?;
+    let token_count = tokens.len();
     
-    let mut parser = compile::Parser::new(tokens);
-    let mut ast = parser.parse()
+    let mut parser = compile::Parser::with_edition(tokens, edition);
+    parser.disabled = manifest.disabled_features()?;
+    let ast = parser.parse()
         .map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))?;
+    let ast = compile::lower_top_level(ast, manifest.language.top_level_statements)
+        .map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))?;
+    let (mut ast, mut plugin_warnings) = plugins::run(project_path, &manifest, ast, edition)?;
 
-    let mut analyzer = compile::SemanticAnalyzer::new();
+    let mut analyzer = manifest.analyzer()?;
     analyzer.analyze(&mut ast)
         .map_err(|e| anyhow::anyhow!("语义分析错误: {}", e))?;
+    let lint_levels = lints::LintLevels::resolve(&manifest, lints)?;
+    let mut warnings = parser.warnings;
+    warnings.append(&mut plugin_warnings);
+    warnings.append(&mut analyzer.warnings);
+    let warnings = lint_levels.report(warnings)?;
+
+    Ok(AnalyzedProject {
+        manifest,
+        edition,
+        source: ntf_content,
+        ast,
+        token_count,
+        symbol_count: analyzer.symbol_count(),
+        warnings,
+    })
+}
 
-    let generated_code = compile::generate_code(&ast)
-        .map_err(|e| anyhow::anyhow!("代码生成错误: {}", e))?;
+/// 要构建的入口文件，相对于项目目录
+fn build_source_name(project_path: &Path, options: &BuildOptions) -> Result<String> {
+    match &options.example {
+        Some(name) => {
+            let examples = manifest::Manifest::load(project_path)?.example_names(project_path)?;
+            if !examples.contains(name) {
+                anyhow::bail!("没有示例 \"{}\" (可用: {})", name, examples.join(", "));
+            }
+            Ok(format!("{}/{}.ntf", manifest::EXAMPLES_DIR, name))
+        }
+        None => Ok(MAIN_SOURCE.to_string()),
+    }
+}
 
-    let target_dir = project_path.join("target").join("debug");
+/// `target/<profile>`，示例为`target/<profile>/examples`；不存在时创建
+fn build_target_dir(project_path: &Path, options: &BuildOptions) -> Result<PathBuf> {
+    let mut target_dir = project_path.join("target").join(options.profile());
+    if options.example.is_some() {
+        target_dir.push(manifest::EXAMPLES_DIR);
+    }
     fs::create_dir_all(&target_dir)
         .with_context(|| format!("无法创建目录: {:?}", target_dir))?;
+    Ok(target_dir)
+}
+
+/// `ntfp build --emit ast-json`: 只运行前端，把结果写成JSON
+fn emit_ast_json(project_path: &Path, options: &BuildOptions) -> Result<()> {
+    let source_name = build_source_name(project_path, options)?;
+    let project = analyze_project(project_path, &source_name, "分析", &options.lints)?;
+    let _lock = lock::TargetLock::acquire(project_path)?;
+    let target_dir = build_target_dir(project_path, options)?;
+
+    let tokens = compile::lexer(&project.source).map_err(|e| anyhow::anyhow!("词法分析错误: {}", e))?;
+    let document = compile::AstJson {
+        schema: compile::AST_JSON_SCHEMA,
+        edition: project.edition,
+        tokens,
+        ast: project.ast,
+    };
+    let stem = options.example.as_deref().unwrap_or("main");
+    let path = target_dir.join(format!("{}.ast.json", stem));
+    let json = serde_json::to_string_pretty(&document).context("无法序列化AST")?;
+    fs::write(&path, json + "\n").with_context(|| format!("无法写入文件: {:?}", path))?;
+    eprintln!("AST已写入 {}", path.display());
+    Ok(())
+}
+
+fn compile_project(project_path: &Path, options: &BuildOptions) -> Result<BuildResult> {
+    let started = Instant::now();
+    let source_name = build_source_name(project_path, options)?;
+    let AnalyzedProject { manifest, edition, source, ast, token_count, symbol_count, warnings } =
+        analyze_project(project_path, &source_name, "构建", &options.lints)?;
+    let _lock = lock::TargetLock::acquire(project_path)?;
+    let profile = options.profile();
+    let target_dir = build_target_dir(project_path, options)?;
+    // 示例总是构建为可执行文件，即使项目本身是库
+    let library = match &options.example {
+        Some(_) => None,
+        None => manifest.library_name()?,
+    };
+    let binary_path = match (&library, &options.example) {
+        (Some(name), _) => target_dir.join(name),
+        (None, Some(example)) => target_dir.join(platform::executable_name(example)),
+        (None, None) => target_dir.join(manifest.binary_name()?),
+    };
+
+    if options.bundle_interpreter && library.is_some() {
+        anyhow::bail!("库项目 ([lib]) 需要rustc编译，不能使用--bundle-interpreter");
+    }
+    let overflow = manifest.profile.overflow(options.release);
+    let generated_rust_path = if options.bundle_interpreter {
+        if let Some(compile::ASTNode::ExternFunction { name, .. }) = ast
+            .iter()
+            .find(|n| matches!(n, compile::ASTNode::ExternFunction { .. }))
+        {
+            anyhow::bail!("extern函数 {} 需要rustc编译，不能使用--bundle-interpreter", name);
+        }
+        info!(output = ?binary_path, "bundling interpreter launcher");
+        let mut metadata = metadata::Metadata::new(&manifest, edition, profile, "interpreter");
+        metadata.fields.push(("engine".to_string(), options.engine.name().to_string()));
+        let overflow_checks = overflow == compile::Overflow::Checked;
+        metadata.fields.push(("overflow-checks".to_string(), overflow_checks.to_string()));
+        if manifest.language.top_level_statements {
+            metadata.fields.push(("top-level-statements".to_string(), "true".to_string()));
+        }
+        metadata.fields.extend(bundle::config_fields(&manifest)?);
+        bundle::write_launcher(&binary_path, metadata, &source)?;
+        None
+    } else {
+        let (mut generated_code, source_map) = compile::generate_code_with_map(&ast, overflow)
+            .map_err(|e| anyhow::anyhow!("代码生成错误: {}", e))?;
+        let mut metadata = metadata::Metadata::new(&manifest, edition, profile, "rustc");
+        if options.embed_source {
+            metadata.source = Some(generated_code.clone());
+        }
+        generated_code.push_str(&rust_modules(project_path, &manifest)?);
+        generated_code.push_str(&metadata.rust_static());
+        let mut rustc_args = Vec::new();
+        if library.is_some() {
+            rustc_args.extend(["--crate-type".to_string(), "cdylib".to_string()]);
+            rustc_args.extend(["--crate-name".to_string(), manifest.crate_name()]);
+        }
+        if options.release {
+            rustc_args.extend(release_rustc_args(&manifest.profile.release, library.is_some())?);
+        }
+        for dir in c_link_search(project_path, &manifest)? {
+            rustc_args.push("-L".to_string());
+            rustc_args.push(format!("native={}", dir.display()));
+        }
+        for lib in &manifest.c.link {
+            rustc_args.push("-l".to_string());
+            rustc_args.push(lib.clone());
+        }
+        let rust_path = target_dir.join(match &options.example {
+            Some(name) => format!("{}.rs", name),
+            None => "main.rs".to_string(),
+        });
+        let translate = |output: &str| translate_backend_output(output, &rust_path, &source_name, &source_map);
+        let backend_output =
+            compile_with_rustc(project_path, &rust_path, &generated_code, &binary_path, &rustc_args)
+                .map_err(|e| anyhow::anyhow!(translate(&format!("{:#}", e))))?;
+        let backend_warnings = count_backend_warnings(&backend_output);
+        if options.show_backend_warnings {
+            eprint!("{}", translate(&backend_output));
+        } else if backend_warnings > 0 {
+            eprintln!("rustc对生成的代码报告了 {} 个警告，使用--show-backend-warnings查看", backend_warnings);
+        }
+        Some(rust_path)
+    };
+    let build_stats = options
+        .stats
+        .then(|| stats::BuildStats::collect(token_count, &ast, symbol_count));
+
+    match library {
+        Some(_) => eprintln!("构建成功! 库文件: {:?}", binary_path),
+        None => eprintln!("构建成功! 二进制文件: {:?}", binary_path),
+    }
+    if let Some(out) = &options.out {
+        let dest = if out.is_dir() {
+            out.join(binary_path.file_name().unwrap())
+        } else {
+            out.to_path_buf()
+        };
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建目录: {:?}", parent))?;
+        }
+        fs::copy(&binary_path, &dest)
+            .with_context(|| format!("无法复制二进制文件到: {:?}", dest))?;
+        eprintln!("已复制到: {:?}", dest);
+    }
+    if let Some(build_stats) = build_stats {
+        build_stats.print();
+    }
+    Ok(BuildResult {
+        binary_path,
+        generated_rust_path,
+        warnings,
+        duration: started.elapsed(),
+    })
+}
+
+/// `[rust] sources`中的文件作为模块引入，`extern rust`声明的函数通过glob导入解析
+fn rust_modules(project_path: &Path, manifest: &manifest::Manifest) -> Result<String> {
+    let mut code = String::new();
+    for source in &manifest.rust.sources {
+        let path = project_path.join(source);
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("找不到Rust源文件: {:?}", path))?;
+        let module = from_rust::module_name(&path);
+        code.push_str(&format!("\n#[path = {:?}]", path));
+        code.push_str(&rust_module_decl(&module));
+    }
+    Ok(code)
+}
+
+/// `--release`时传给rustc的优化选项和`[profile.release]`中的选项
+fn release_rustc_args(profile: &manifest::ReleaseProfile, library: bool) -> Result<Vec<String>> {
+    let mut codegen = vec!["opt-level=3".to_string()];
+    if profile.strip {
+        codegen.push("strip=symbols".to_string());
+    }
+    if profile.lto {
+        codegen.push("lto=fat".to_string());
+    }
+    match profile.panic.as_deref() {
+        None | Some("unwind") => {}
+        Some("abort") => codegen.push("panic=abort".to_string()),
+        Some(other) => anyhow::bail!("无效的[profile.release] panic: \"{}\" (可用: unwind, abort)", other),
+    }
+    if profile.static_crt {
+        if library {
+            anyhow::bail!("库项目 ([lib]) 不能使用[profile.release] static = true");
+        }
+        codegen.push("target-feature=+crt-static".to_string());
+    }
+    Ok(codegen.into_iter().flat_map(|option| ["-C".to_string(), option]).collect())
+}
+
+/// `[c] link-search`中的目录，转换为绝对路径
+fn c_link_search(project_path: &Path, manifest: &manifest::Manifest) -> Result<Vec<PathBuf>> {
+    manifest
+        .c
+        .link_search
+        .iter()
+        .map(|dir| {
+            let path = project_path.join(dir);
+            path.canonicalize()
+                .with_context(|| format!("找不到库搜索路径: {:?}", path))
+        })
+        .collect()
+}
+
+fn rust_module_decl(module: &str) -> String {
+    format!(
+        "\n#[allow(dead_code)]\nmod {};\n#[allow(unused_imports)]\nuse {}::*;\n",
+        module, module
+    )
+}
+
+/// `ntfp fmt`要格式化的部分
+enum FormatRange {
+    All,
+    Lines(std::ops::RangeInclusive<u32>),
+    Bytes(std::ops::Range<usize>),
+}
 
-    let main_rs_path = target_dir.join("main.rs");
-    fs::write(&main_rs_path, &generated_code)
+fn format_text(
+    source: &str,
+    edition: compile::Edition,
+    style: &formatter::Style,
+    range: &FormatRange,
+) -> Result<String, String> {
+    match range {
+        FormatRange::All => formatter::format_source(source, edition, style),
+        FormatRange::Lines(lines) => formatter::format_lines(source, edition, style, lines.clone()),
+        FormatRange::Bytes(bytes) => {
+            formatter::format_lines(source, edition, style, formatter::byte_range_lines(source, bytes.clone()))
+        }
+    }
+}
+
+/// `dir`所在项目的edition和`[fmt]`排版选项，不在项目中时为最新edition和默认排版
+fn format_settings_in(dir: &Path) -> Result<(compile::Edition, formatter::Style)> {
+    match manifest::find_project_root(dir) {
+        Some(root) => {
+            let manifest = manifest::Manifest::load(&root)?;
+            Ok((manifest.edition()?, manifest.style()?))
+        }
+        None => Ok((compile::Edition::LATEST, formatter::Style::default())),
+    }
+}
+
+fn format_command(
+    files: Vec<PathBuf>,
+    project: &ProjectLocation,
+    check: bool,
+    stdin: bool,
+    range: FormatRange,
+    edition: Option<compile::Edition>,
+) -> Result<()> {
+    if stdin {
+        let mut source = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut source).context("无法读取标准输入")?;
+        let (project_edition, style) = format_settings_in(Path::new("."))?;
+        let formatted = format_text(&source, edition.unwrap_or(project_edition), &style, &range).map_err(|e| anyhow::anyhow!("<stdin>: {}", e))?;
+        if check {
+            if formatted != source {
+                anyhow::bail!("<stdin> 需要格式化");
+            }
+            return Ok(());
+        }
+        print!("{}", formatted);
+        return Ok(());
+    }
+
+    let files = if files.is_empty() {
+        let root = project.root()?;
+        let manifest = manifest::Manifest::load(&root)?;
+        manifest.sources()?.files(&root)?.into_iter().map(|file| root.join(file)).collect()
+    } else {
+        files
+    };
+    if !matches!(range, FormatRange::All) && files.len() != 1 {
+        anyhow::bail!("--range和--byte-range需要--stdin或者正好一个文件 (现在是{}个)", files.len());
+    }
+
+    let mut unformatted = 0;
+    for file in &files {
+        let source = fs::read_to_string(file).with_context(|| format!("无法读取文件: {:?}", file))?;
+        let dir = file.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let (project_edition, style) = format_settings_in(dir)?;
+        let formatted = format_text(&source, edition.unwrap_or(project_edition), &style, &range).map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+        if formatted == source {
+            continue;
+        }
+        unformatted += 1;
+        if check {
+            println!("{}", file.display());
+        } else {
+            fs::write(file, &formatted).with_context(|| format!("无法写入文件: {:?}", file))?;
+            eprintln!("已格式化 {}", file.display());
+        }
+    }
+    if check && unformatted > 0 {
+        anyhow::bail!("{} 个文件需要格式化", unformatted);
+    }
+    Ok(())
+}
+
+/// 生成独立的Cargo项目 (Cargo.toml + src/main.rs)，不进行编译
+#[tracing::instrument]
+fn transpile_project(project_path: &Path, out: Option<&Path>) -> Result<()> {
+    let project = analyze_project(project_path, MAIN_SOURCE, "转译", &lints::LintOverrides::default())?;
+    let _lock = lock::TargetLock::acquire(project_path)?;
+    let package = &project.manifest.package;
+
+    let out_dir = match out {
+        Some(out) => out.to_path_buf(),
+        None => project_path.join("target").join("transpiled").join(&package.name),
+    };
+    let src_dir = out_dir.join("src");
+    fs::create_dir_all(&src_dir)
+        .with_context(|| format!("无法创建目录: {:?}", src_dir))?;
+
+    let mut code = format!(
+        "// Generated by ntfp {} from {} v{}; do not edit.\n\n",
+        env!("CARGO_PKG_VERSION"),
+        package.name,
+        package.version
+    );
+    let is_library = project.manifest.library_name()?.is_some();
+    if is_library {
+        // Only the `pub fun` exports are reachable from outside the library.
+        code.push_str("#![allow(dead_code)]\n\n");
+    }
+    // Cargo的profile不影响生成的代码，溢出按[profile.dev]处理
+    code.push_str(&compile::generate_code(&project.ast, project.manifest.profile.overflow(false))
+        .map_err(|e| anyhow::anyhow!("代码生成错误: {}", e))?);
+    for source in &project.manifest.rust.sources {
+        let from = project_path.join(source);
+        let module = from_rust::module_name(&from);
+        let to = src_dir.join(format!("{}.rs", module));
+        fs::copy(&from, &to)
+            .with_context(|| format!("无法复制文件: {:?}", from))?;
+        code.push_str(&rust_module_decl(&module));
+    }
+
+    let main_rs = src_dir.join(if is_library { "lib.rs" } else { "main.rs" });
+    fs::write(&main_rs, code)
+        .with_context(|| format!("无法写入文件: {:?}", main_rs))?;
+
+    // An empty [workspace] keeps the output buildable when it lands inside another workspace.
+    let cargo_toml = out_dir.join("Cargo.toml");
+    let mut cargo_manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"{}\"\nedition = \"2021\"\n\n[workspace]\n",
+        package.name, package.version
+    );
+    if is_library {
+        cargo_manifest.push_str("\n[lib]\ncrate-type = [\"cdylib\"]\n");
+    }
+    fs::write(&cargo_toml, cargo_manifest)
+    .with_context(|| format!("无法写入文件: {:?}", cargo_toml))?;
+
+    let link_search = c_link_search(project_path, &project.manifest)?;
+    if !link_search.is_empty() || !project.manifest.c.link.is_empty() {
+        let mut build_rs = String::from("fn main() {\n");
+        for dir in &link_search {
+            build_rs.push_str(&format!(
+                "    println!(\"cargo:rustc-link-search=native={}\");\n",
+                dir.display().to_string().escape_default()
+            ));
+        }
+        for lib in &project.manifest.c.link {
+            build_rs.push_str(&format!("    println!(\"cargo:rustc-link-lib={}\");\n", lib));
+        }
+        build_rs.push_str("}\n");
+        let build_rs_path = out_dir.join("build.rs");
+        fs::write(&build_rs_path, build_rs)
+            .with_context(|| format!("无法写入文件: {:?}", build_rs_path))?;
+    }
+
+    eprintln!("转译完成! Cargo项目: {:?}", out_dir);
+    Ok(())
+}
+
+fn inspect_binary(binary: &Path, show_source: bool) -> Result<()> {
+    let metadata = match bundle::read_bundle(binary)? {
+        Some(bundle) => bundle.metadata,
+        None => {
+            let bytes = fs::read(binary)
+                .with_context(|| format!("无法读取文件: {:?}", binary))?;
+            metadata::Metadata::find_in_binary(&bytes)
+                .ok_or_else(|| anyhow::anyhow!("{:?} 不是ntfp构建的二进制文件", binary))?
+        }
+    };
+
+    for (key, value) in &metadata.fields {
+        println!("{:<10}{}", key, value);
+    }
+    if show_source {
+        match &metadata.source {
+            Some(source) => println!("\n{}", source),
+            None => println!("\n未嵌入源码 (使用`ntfp build --embed-source`重新构建)"),
+        }
+    }
+    Ok(())
+}
+
+fn show_language_help(topic: Option<&str>, markdown: bool) -> Result<()> {
+    if markdown {
+        print!("{}", langdoc::render_markdown());
+        return Ok(());
+    }
+    match topic {
+        None => print!("{}", langdoc::render_index()),
+        Some(name) => match langdoc::find(name) {
+            Some(topic) => print!("{}", langdoc::render_topic(topic)),
+            None => {
+                let known: Vec<_> = langdoc::TOPICS.iter().map(|t| t.name).collect();
+                anyhow::bail!("没有主题 \"{}\" (可用: {})", name, known.join(", "));
+            }
+        },
+    }
+    Ok(())
+}
+
+fn show_usage_stats(reset: bool) -> Result<()> {
+    if reset {
+        usage::UsageStats::reset()?;
+        println!("使用统计已清空");
+        return Ok(());
+    }
+    if !config::GlobalConfig::load()?.usage_stats {
+        println!("使用统计未开启，可在全局配置 ~/.ntfp/config.toml 中设置 usage-stats = true");
+    }
+    usage::UsageStats::load()?.print();
+    Ok(())
+}
+
+fn compile_with_rustc(
+    project_path: &Path,
+    main_rs_path: &Path,
+    generated_code: &str,
+    binary_path: &Path,
+    rustc_args: &[String],
+) -> Result<String> {
+    fs::write(main_rs_path, generated_code)
         .with_context(|| format!("无法写入文件: {:?}", main_rs_path))?;
 
-    let binary_path = target_dir.join("main.exe");
-    let compile_output = Command::new("rustc")
-        .arg(&main_rs_path)
-        .arg("-o")
-        .arg(&binary_path)
-        .current_dir(project_path)
-        .output()
-        .with_context(|| "调用rustc编译失败")?;
+    let rustc = config::GlobalConfig::load()?.rustc();
+    info!(source = ?main_rs_path, output = ?binary_path, rustc = ?rustc, "invoking rustc");
+    let compile_output = match signals::output(
+        Command::new(&rustc)
+            .arg(main_rs_path)
+            .arg("-o")
+            .arg(binary_path)
+            .args(rustc_args)
+            .current_dir(project_path),
+    ) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => anyhow::bail!(
+            "找不到rustc: {:?}\n可以安装Rust (https://rustup.rs)，或使用`ntfp build --bundle-interpreter`生成不需要rustc的启动器",
+            rustc
+        ),
+        result => result.with_context(|| "调用rustc编译失败")?,
+    };
 
+    debug!(status = ?compile_output.status, "rustc finished");
+    if signals::interrupted() {
+        // rustc may have been stopped halfway through writing the binary.
+        let _ = fs::remove_file(binary_path);
+        anyhow::bail!("构建已中断");
+    }
+    let stderr = String::from_utf8_lossy(&compile_output.stderr).into_owned();
     if !compile_output.status.success() {
-        let err_msg = String::from_utf8_lossy(&compile_output.stderr);
-        anyhow::bail!("编译失败: {}", err_msg);
+        anyhow::bail!("编译失败: {}", stderr);
     }
+    Ok(stderr)
+}
 
-    println!("构建成功! 二进制文件: {:?}", binary_path);
-    Ok(())
+/// 把rustc诊断中指向生成代码的位置 (`--> main.rs:L:C`) 换算为`source_name`中的行，
+/// 无法换算的位置保持不变
+fn translate_backend_output(
+    output: &str,
+    rust_path: &Path,
+    source_name: &str,
+    source_map: &compile::SourceMap,
+) -> String {
+    let location = regex::Regex::new(&format!(
+        r"--> {}:(\d+):(\d+)",
+        regex::escape(&rust_path.display().to_string())
+    ))
+    .unwrap();
+    location
+        .replace_all(output, |caps: &regex::Captures| {
+            let line: usize = caps[1].parse().unwrap_or(0);
+            match source_map.source_line(line) {
+                Some(source_line) => format!(
+                    "--> {}:{} (生成的代码 {}:{}:{})",
+                    source_name,
+                    source_line,
+                    rust_path.display(),
+                    &caps[1],
+                    &caps[2]
+                ),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// rustc输出中的警告数，不含末尾的"N warnings emitted"汇总
+fn count_backend_warnings(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.starts_with("warning") && !line.ends_with("emitted"))
+        .count()
 }
 
-fn init_project(path: &str) -> Result<()> {
+#[tracing::instrument]
+fn init_project(path: &str, edition: compile::Edition) -> Result<()> {
     let project_path = Path::new(path);
 
     if !project_path.exists() {
@@ -269,7 +1583,7 @@ fn init_project(path: &str) -> Result<()> {
         .and_then(|n| n.to_str())
         .unwrap_or("my_project");
 
-    let template = default_template(project_name);
+    let template = default_template(project_name, edition);
 
     for (file_path, content) in template.files {
         let full_path = project_path.join(file_path);
@@ -284,18 +1598,134 @@ fn init_project(path: &str) -> Result<()> {
             .with_context(|| format!("无法写入文件: {:?}", full_path))?;
     }
 
-    println!("项目已初始化成功! 项目名称: {}", project_name);
+    eprintln!("项目已初始化成功! 项目名称: {}", project_name);
     Ok(())
 }
 
+fn init_logging(verbose: u8) {
+    let default_level = match verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_env("NTFP_LOG")
+        .unwrap_or_else(|_| EnvFilter::new(default_level));
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(verbose > 2)
+        .init();
+}
+
 fn main() -> Result<()> {
+    // Deep programs need more stack than the main thread has, see compile::STACK_SIZE
+    let cli = std::thread::Builder::new()
+        .stack_size(compile::STACK_SIZE)
+        .spawn(run_cli)
+        .context("无法创建线程")?;
+    cli.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+}
+
+fn run_cli() -> Result<()> {
+    if let Some(bundle) = bundle::load_embedded()? {
+        return bundle.run();
+    }
+
     let cli = Cli::parse();
+    init_logging(cli.verbose);
+    signals::install();
 
     match cli.command {
-        Some(Commands::New { name, template }) => create_project(&name, &template),
-        Some(Commands::Run { path }) => run_project(&path),
-        Some(Commands::Init { path }) => init_project(&path),
-        Some(Commands::Build { path }) => build_project(&path),
+        Some(Commands::New { name, template, from_rust, edition }) => create_project(
+            &name,
+            &template,
+            from_rust.as_deref(),
+            edition.unwrap_or(compile::Edition::LATEST),
+        ),
+        Some(Commands::Run { project, release, example, hot, engine }) => {
+            run_project(&project.root()?, release, example, hot, engine, cli.verbose > 0)
+        }
+        Some(Commands::Shell { project }) => shell::spawn(&project.root()?),
+        Some(Commands::Script { file, edition, engine, native }) => {
+            run_script(&file, edition.unwrap_or(compile::Edition::LATEST), engine, native)
+        }
+        Some(Commands::RunAllExamples { project, release, format }) => {
+            run_all_examples(&project.root()?, release, format)
+        }
+        Some(Commands::Init { path, edition }) => {
+            init_project(&path, edition.unwrap_or(compile::Edition::LATEST))
+        }
+        Some(Commands::Build {
+            project,
+            release,
+            example,
+            stats,
+            out,
+            bundle_interpreter,
+            engine,
+            embed_source,
+            show_backend_warnings,
+            lints,
+            emit,
+        }) => {
+            let options = BuildOptions {
+                release,
+                example,
+                stats,
+                out,
+                bundle_interpreter,
+                engine,
+                embed_source,
+                show_backend_warnings,
+                lints,
+            };
+            if let Some(Emit::AstJson) = emit {
+                return emit_ast_json(&project.root()?, &options);
+            }
+            let build = build_project(&project.root()?, &options)?;
+            match build.warnings.len() {
+                0 => eprintln!("完成，用时 {:.2}s", build.duration.as_secs_f64()),
+                n => eprintln!("完成，用时 {:.2}s ({} 个警告)", build.duration.as_secs_f64(), n),
+            }
+            Ok(())
+        }
+        Some(Commands::Transpile { project, out }) => {
+            transpile_project(&project.root()?, out.as_deref())
+        }
+        Some(Commands::Fmt { files, project, check, stdin, range, byte_range, edition }) => {
+            let range = match (range, byte_range) {
+                (Some(lines), _) => FormatRange::Lines(lines),
+                (None, Some(bytes)) => FormatRange::Bytes(bytes),
+                (None, None) => FormatRange::All,
+            };
+            format_command(files, &project, check, stdin, range, edition)
+        }
+        Some(Commands::Grammar { format }) => {
+            print!("{}", grammar::render(format));
+            Ok(())
+        }
+        Some(Commands::HelpLang { topic, markdown }) => show_language_help(topic.as_deref(), markdown),
+        Some(Commands::Doctor { path }) => doctor::run_doctor(&path),
+        Some(Commands::Inspect { binary, source }) => inspect_binary(&binary, source),
+        Some(Commands::Info { project }) => info::show(&project.root()?),
+        Some(Commands::Verify { project }) => verify::verify(&project.root()?),
+        Some(Commands::Size { project, symbols }) => size::report(&project.root()?, symbols),
+        Some(Commands::Stats { reset }) => show_usage_stats(reset),
+        Some(Commands::Completions { shell }) => completions::print_script(shell, &mut Cli::command()),
+        Some(Commands::Complete { kind }) => completions::complete(kind),
+        Some(Commands::Cache { command }) => cache::run(command),
+        Some(Commands::Lsp) => lsp::run(),
+        Some(Commands::Dev { command }) => dev::run(command),
+        Some(Commands::External(args)) => {
+            let builtin: Vec<String> = Cli::command()
+                .get_subcommands()
+                .filter(|c| !c.is_hide_set())
+                .map(|c| c.get_name().to_string())
+                .collect();
+            external::run(args, &builtin)
+        }
         None => {
             Cli::command().print_help()?;
             Ok(())
@@ -0,0 +1,367 @@
+use crate::compile::{ConfigValue, Edition, Feature, Overflow, SemanticAnalyzer};
+use crate::formatter::{BraceStyle, Style};
+use crate::platform;
+use crate::sources::SourceFilter;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default)]
+    pub rust: RustSources,
+    #[serde(default)]
+    pub c: CLinking,
+    pub lib: Option<Library>,
+    /// lint名 (或`warnings`) -> allow / warn / deny
+    #[serde(default)]
+    pub lints: BTreeMap<String, String>,
+    #[serde(default)]
+    pub profile: Profiles,
+    #[serde(default)]
+    pub fmt: Fmt,
+    /// 编译器插件名 -> 动态库路径 (相对于项目目录)，需要启用plugins feature
+    #[serde(default)]
+    pub plugins: BTreeMap<String, String>,
+    /// 程序中以`config.key`读取的常量，编译时嵌入生成的代码
+    #[serde(default)]
+    pub config: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// 缺省时视为最早的edition，保证旧项目的行为不变
+    #[serde(default = "default_edition")]
+    pub edition: String,
+    /// 生成的二进制文件名 (默认: main，Windows上为main.exe)
+    #[serde(rename = "out-name")]
+    pub out_name: Option<String>,
+    /// 构建项目所需的最低ntfp版本，例如`"0.26"`
+    #[serde(rename = "min-ntfp-version")]
+    pub min_ntfp_version: Option<String>,
+    /// 属于项目的.ntf文件 (glob，相对于项目目录)，缺省时为全部
+    pub include: Option<Vec<String>>,
+    /// 从include中排除的.ntf文件
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// 限制项目可以使用的语言特性，例如教学时只开放已经讲过的内容
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Language {
+    /// 只允许列出的特性，缺省时允许全部
+    pub allow: Option<Vec<String>>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// 允许函数外的语句，像`ntfp script`一样把它们包装进自动生成的main
+    #[serde(default, rename = "top-level-statements")]
+    pub top_level_statements: bool,
+}
+
+/// 与项目一起编译的Rust源文件，其中的`pub fn`可以用`extern rust`声明后调用
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RustSources {
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// `extern c`函数所需的本地库
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CLinking {
+    /// 链接的库名，例如`["m", "sqlite3"]`
+    #[serde(default)]
+    pub link: Vec<String>,
+    /// 额外的库搜索路径 (相对于项目目录)
+    #[serde(default, rename = "link-search")]
+    pub link_search: Vec<String>,
+}
+
+/// 构建为供其他语言调用的库，`pub fun`以C ABI导出
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Library {
+    /// 目前只支持`["cdylib"]`
+    #[serde(rename = "crate-type")]
+    pub crate_type: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profiles {
+    #[serde(default)]
+    pub dev: DevProfile,
+    #[serde(default)]
+    pub release: ReleaseProfile,
+}
+
+impl Profiles {
+    /// 所选配置下int运算溢出的处理方式，默认与Rust一样debug构建检查、release构建回绕
+    pub fn overflow(&self, release: bool) -> Overflow {
+        let checks = match release {
+            false => self.dev.overflow_checks.unwrap_or(true),
+            true => self.release.overflow_checks.unwrap_or(false),
+        };
+        if checks { Overflow::Checked } else { Overflow::Wrapping }
+    }
+}
+
+/// 不加`--release`的构建和解释器运行的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DevProfile {
+    /// int的`+ - *`和取负溢出时报告所在行并结束程序，为false时回绕 (默认: true)
+    #[serde(rename = "overflow-checks")]
+    pub overflow_checks: Option<bool>,
+}
+
+/// `ntfp build --release`的选项，用于发布体积小、不依赖系统库的二进制文件
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReleaseProfile {
+    /// 去掉符号表和调试信息
+    #[serde(default)]
+    pub strip: bool,
+    /// 链接时优化
+    #[serde(default)]
+    pub lto: bool,
+    /// `"unwind"` (默认) 或 `"abort"`
+    pub panic: Option<String>,
+    /// 静态链接C运行时 (crt-static)，Linux上需要安装静态glibc或使用musl工具链
+    #[serde(default, rename = "static")]
+    pub static_crt: bool,
+    /// 同`[profile.dev]`，默认为false
+    #[serde(rename = "overflow-checks")]
+    pub overflow_checks: Option<bool>,
+}
+
+/// `ntfp fmt`和编辑器格式化的排版选项，缺省值与`ntfp help-lang`中示例的风格一致
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fmt {
+    /// 每级缩进的空格数 (默认: 4)
+    #[serde(rename = "indent-width")]
+    pub indent_width: Option<usize>,
+    /// `"same-line"` (默认) 或 `"next-line"`: `{`写在行尾还是单独一行
+    #[serde(rename = "brace-style")]
+    pub brace_style: Option<String>,
+    /// 超过这个宽度的语句和函数签名按每行一个参数拆开 (默认: 100)
+    #[serde(rename = "max-width")]
+    pub max_width: Option<usize>,
+    /// 语句末尾`}`之后的分号: `"omit"` (默认) 去掉，`"always"`补上
+    #[serde(rename = "trailing-semicolon")]
+    pub trailing_semicolon: Option<String>,
+}
+
+/// `1.2` -> `[1, 2, 0]`；预发布后缀 (`-beta`) 忽略
+pub fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let core = version.trim().split(['-', '+']).next()?;
+    let mut parts = [0; 3];
+    let mut count = 0;
+    for (i, part) in core.split('.').enumerate() {
+        *parts.get_mut(i)? = part.parse().ok()?;
+        count += 1;
+    }
+    (count >= 2).then_some(parts)
+}
+
+fn default_edition() -> String {
+    Edition::E2025.as_str().to_string()
+}
+
+/// 项目配置文件名
+pub const FILE_NAME: &str = "ntfp.toml";
+
+/// 示例程序所在的目录，每个`.ntf`文件是一个独立的程序
+pub const EXAMPLES_DIR: &str = "examples";
+
+/// 从`start`开始向上查找包含ntfp.toml的目录，返回其绝对路径
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    let start = start.canonicalize().ok()?;
+    start.ancestors().find(|dir| dir.join(FILE_NAME).is_file()).map(Path::to_path_buf)
+}
+
+impl Manifest {
+    pub fn load(project_path: &Path) -> Result<Self> {
+        let path = project_path.join(FILE_NAME);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("无法读取文件: {:?}", path))?;
+        let manifest: Manifest =
+            toml::from_str(&content).with_context(|| format!("无法解析配置文件: {:?}", path))?;
+        manifest.check_ntfp_version()?;
+        Ok(manifest)
+    }
+
+    /// 当前ntfp低于`min-ntfp-version`时拒绝继续，避免用旧编译器得到难以理解的错误
+    fn check_ntfp_version(&self) -> Result<()> {
+        let Some(required) = &self.package.min_ntfp_version else {
+            return Ok(());
+        };
+        let current = env!("CARGO_PKG_VERSION");
+        let required_parts = parse_version(required)
+            .ok_or_else(|| anyhow::anyhow!("无效的min-ntfp-version: \"{}\" (格式: 主版本.次版本[.修订号])", required))?;
+        let current_parts = parse_version(current).expect("CARGO_PKG_VERSION is x.y.z");
+        if current_parts < required_parts {
+            anyhow::bail!(
+                "项目 {} 需要ntfp {} 或更高版本，当前为 {}\n请升级ntfp后重新构建 (在ntfp源码目录运行`cargo install --path .`)",
+                self.package.name,
+                required,
+                current
+            );
+        }
+        Ok(())
+    }
+
+    pub fn edition(&self) -> Result<Edition> {
+        Edition::parse(&self.package.edition).ok_or_else(|| {
+            let known: Vec<_> = Edition::ALL.iter().map(|e| e.as_str()).collect();
+            anyhow::anyhow!(
+                "不支持的edition: \"{}\" (当前ntfp支持: {})，项目可能需要更新版本的ntfp",
+                self.package.edition,
+                known.join(", ")
+            )
+        })
+    }
+
+    pub fn disabled_features(&self) -> Result<HashSet<Feature>> {
+        let parse = |name: &String| {
+            Feature::parse(name).ok_or_else(|| {
+                let known: Vec<_> = Feature::ALL.iter().map(Feature::name).collect();
+                anyhow::anyhow!("未知的语言特性: \"{}\" (可用: {})", name, known.join(", "))
+            })
+        };
+
+        let mut disabled = HashSet::new();
+        if let Some(allow) = &self.language.allow {
+            let allowed = allow.iter().map(parse).collect::<Result<HashSet<_>>>()?;
+            disabled.extend(Feature::ALL.iter().filter(|f| !allowed.contains(f)));
+        }
+        for name in &self.language.deny {
+            disabled.insert(parse(name)?);
+        }
+        Ok(disabled)
+    }
+
+    pub fn style(&self) -> Result<Style> {
+        let fmt = &self.fmt;
+        let mut style = Style::default();
+        match fmt.indent_width {
+            Some(0) => anyhow::bail!("[fmt] indent-width必须大于0"),
+            Some(width) => style.indent_width = width,
+            None => {}
+        }
+        match fmt.max_width {
+            Some(0) => anyhow::bail!("[fmt] max-width必须大于0"),
+            Some(width) => style.max_width = width,
+            None => {}
+        }
+        style.brace_style = match fmt.brace_style.as_deref() {
+            None | Some("same-line") => BraceStyle::SameLine,
+            Some("next-line") => BraceStyle::NextLine,
+            Some(other) => anyhow::bail!("无效的[fmt] brace-style: \"{}\" (可用: same-line、next-line)", other),
+        };
+        style.semicolon_after_block = match fmt.trailing_semicolon.as_deref() {
+            None | Some("omit") => false,
+            Some("always") => true,
+            Some(other) => anyhow::bail!("无效的[fmt] trailing-semicolon: \"{}\" (可用: omit、always)", other),
+        };
+        Ok(style)
+    }
+
+    /// `[config]`中的常量；整数必须在int范围内，布尔值为1或0
+    pub fn config_values(&self) -> Result<Vec<(String, ConfigValue)>> {
+        self.config
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    toml::Value::Integer(n) => ConfigValue::Int(
+                        i32::try_from(*n).map_err(|_| anyhow::anyhow!("[config] {}超出int范围: {}", key, n))?,
+                    ),
+                    toml::Value::Boolean(b) => ConfigValue::Int(i32::from(*b)),
+                    toml::Value::String(text) => ConfigValue::Str(text.clone()),
+                    other => anyhow::bail!("[config] {}的类型不受支持: {} (只支持整数、字符串和布尔值)", key, other.type_str()),
+                };
+                Ok((key.clone(), value))
+            })
+            .collect()
+    }
+
+    /// 已声明`[config]`常量的语义分析器
+    pub fn analyzer(&self) -> Result<SemanticAnalyzer> {
+        let mut analyzer = SemanticAnalyzer::new();
+        for (key, value) in self.config_values()? {
+            analyzer.declare_config(&key, value).map_err(|e| anyhow::anyhow!("[config] {}", e))?;
+        }
+        Ok(analyzer)
+    }
+
+    /// `[package] include/exclude`选出的源文件集合
+    pub fn sources(&self) -> Result<SourceFilter> {
+        SourceFilter::new(self.package.include.as_deref(), &self.package.exclude)
+    }
+
+    /// 项目中的示例名 (`examples/<name>.ntf`)，按名称排序；没有examples目录时为空。
+    /// 被`[package] exclude`排除的文件不算示例
+    pub fn example_names(&self, project_path: &Path) -> Result<Vec<String>> {
+        let dir = project_path.join(EXAMPLES_DIR);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let sources = self.sources()?;
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&dir).with_context(|| format!("无法读取目录: {:?}", dir))? {
+            let path = entry?.path();
+            let relative = Path::new(EXAMPLES_DIR).join(path.file_name().unwrap_or_default());
+            let is_ntf = path.extension().is_some_and(|ext| ext == "ntf") && sources.contains(&relative);
+            match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) if is_ntf => names.push(stem.to_string()),
+                _ => {}
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// 传给rustc的`--crate-name`，包名中的非法字符替换为`_`
+    pub fn crate_name(&self) -> String {
+        self.package
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    /// `[lib]`项目生成的库文件名，例如Linux上的`libmy_lib.so`；不是库项目时返回`None`
+    pub fn library_name(&self) -> Result<Option<String>> {
+        let Some(lib) = &self.lib else {
+            return Ok(None);
+        };
+        match lib.crate_type.as_slice() {
+            [ty] if ty == "cdylib" => Ok(Some(platform::dynamic_library_name(&self.crate_name()))),
+            [] => anyhow::bail!("[lib] crate-type不能为空 (目前只支持\"cdylib\")"),
+            types => anyhow::bail!("不支持的crate-type: {:?} (目前只支持[\"cdylib\"])", types),
+        }
+    }
+
+    /// 生成的可执行文件名，Windows上自动补全`.exe`
+    pub fn binary_name(&self) -> Result<String> {
+        match self.package.out_name.as_deref() {
+            None => Ok(platform::executable_name(platform::DEFAULT_BINARY_STEM)),
+            Some(name) if !platform::is_portable_file_name(name) || name == "main.rs" => {
+                anyhow::bail!("无效的out-name: \"{}\" (只能是在所有平台上都合法的文件名)", name)
+            }
+            Some(name) => Ok(platform::executable_name(name)),
+        }
+    }
+}
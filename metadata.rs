@@ -0,0 +1,93 @@
+use crate::compile::Edition;
+use crate::manifest::Manifest;
+use anyhow::Result;
+
+const BEGIN: &str = "NTFP-METADATA\n";
+const END: &str = "\nNTFP-METADATA-END";
+
+/// 嵌入到构建产物中的元数据: `key=value`行，空行之后是可选的源码
+#[derive(Debug)]
+pub struct Metadata {
+    pub fields: Vec<(String, String)>,
+    pub source: Option<String>,
+}
+
+impl Metadata {
+    pub fn new(manifest: &Manifest, edition: Edition, profile: &str, backend: &str) -> Self {
+        let fields = [
+            ("package", manifest.package.name.as_str()),
+            ("version", manifest.package.version.as_str()),
+            ("edition", edition.as_str()),
+            ("compiler", concat!("ntfp ", env!("CARGO_PKG_VERSION"))),
+            ("profile", profile),
+            ("backend", backend),
+        ];
+        Metadata {
+            fields: fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            source: None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in &self.fields {
+            out.push_str(&format!("{}={}\n", key, value));
+        }
+        if let Some(source) = &self.source {
+            out.push('\n');
+            out.push_str(source);
+        }
+        out
+    }
+
+    pub fn decode(text: &str) -> Result<Self> {
+        let (header, source) = match text.split_once("\n\n") {
+            Some((header, source)) => (header, Some(source.to_string())),
+            None => (text.trim_end_matches('\n'), None),
+        };
+        let fields = header
+            .lines()
+            .map(|line| {
+                line.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .ok_or_else(|| anyhow::anyhow!("元数据格式错误: {:?}", line))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Metadata { fields, source })
+    }
+
+    /// 附加到生成代码末尾的静态变量，`#[used]`保证它留在二进制文件中
+    pub fn rust_static(&self) -> String {
+        format!(
+            "\n#[used]\nstatic __NTFP_METADATA: &str = {:?};\n",
+            format!("{}{}{}", BEGIN, self.encode(), END)
+        )
+    }
+
+    /// 在rustc生成的二进制文件中查找元数据
+    pub fn find_in_binary(bytes: &[u8]) -> Option<Self> {
+        // ntfp itself contains the bare markers, so skip anything that doesn't decode.
+        let mut offset = 0;
+        while let Some(start) = find(&bytes[offset..], BEGIN.as_bytes()) {
+            let rest = &bytes[offset + start + BEGIN.len()..];
+            offset += start + BEGIN.len();
+            let end = find(rest, END.as_bytes())?;
+            let decoded = std::str::from_utf8(&rest[..end])
+                .ok()
+                .and_then(|text| Metadata::decode(text).ok())
+                .filter(|metadata| metadata.get("package").is_some());
+            if decoded.is_some() {
+                return decoded;
+            }
+        }
+        None
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,99 @@
+use std::{env::consts, path::Path};
+
+/// 默认的可执行文件名 (不含扩展名)
+pub const DEFAULT_BINARY_STEM: &str = "main";
+
+/// 可执行文件名: Windows上补全`.exe`，已经带扩展名时保持不变
+pub fn executable_name(stem: &str) -> String {
+    executable_name_with_suffix(stem, consts::EXE_SUFFIX)
+}
+
+fn executable_name_with_suffix(stem: &str, suffix: &str) -> String {
+    if suffix.is_empty() || Path::new(stem).extension().is_some() {
+        stem.to_string()
+    } else {
+        format!("{}{}", stem, suffix)
+    }
+}
+
+/// 动态库文件名，例如Linux上的`libfoo.so`、macOS上的`libfoo.dylib`、Windows上的`foo.dll`
+pub fn dynamic_library_name(crate_name: &str) -> String {
+    format!("{}{}{}", consts::DLL_PREFIX, crate_name, consts::DLL_SUFFIX)
+}
+
+/// 在所有平台上都能使用的文件名: 不含路径分隔符和Windows禁止的字符，也不是Windows的保留设备名
+pub fn is_portable_file_name(name: &str) -> bool {
+    const FORBIDDEN: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(FORBIDDEN)
+        && !name.chars().any(char::is_control)
+        && !is_reserved_windows_name(name)
+}
+
+/// `CON`、`NUL`、`COM1`等名字 (带任意扩展名) 在Windows上指向设备而不是文件
+fn is_reserved_windows_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" => true,
+        _ => {
+            (stem.starts_with("COM") || stem.starts_with("LPT"))
+                && stem.len() == 4
+                && matches!(stem.as_bytes()[3], b'1'..=b'9')
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn executable_name_on_unix_keeps_stem() {
+        assert_eq!(executable_name_with_suffix("main", ""), "main");
+        assert_eq!(executable_name_with_suffix("main.bin", ""), "main.bin");
+    }
+
+    #[test]
+    fn executable_name_on_windows_adds_exe() {
+        assert_eq!(executable_name_with_suffix("main", ".exe"), "main.exe");
+        assert_eq!(executable_name_with_suffix("main.exe", ".exe"), "main.exe");
+        assert_eq!(executable_name_with_suffix("tool.v2", ".exe"), "tool.v2");
+    }
+
+    #[test]
+    fn executable_name_on_host() {
+        #[cfg(windows)]
+        assert_eq!(executable_name(DEFAULT_BINARY_STEM), "main.exe");
+        #[cfg(unix)]
+        assert_eq!(executable_name(DEFAULT_BINARY_STEM), "main");
+    }
+
+    #[test]
+    fn dynamic_library_name_on_host() {
+        #[cfg(windows)]
+        assert_eq!(dynamic_library_name("foo"), "foo.dll");
+        #[cfg(target_os = "macos")]
+        assert_eq!(dynamic_library_name("foo"), "libfoo.dylib");
+        #[cfg(all(unix, not(target_os = "macos")))]
+        assert_eq!(dynamic_library_name("foo"), "libfoo.so");
+    }
+
+    #[test]
+    fn portable_file_names() {
+        for name in ["main", "hello.ntf", "my-project_2", "com10", "lpt0.txt", "console", "名字"] {
+            assert!(is_portable_file_name(name), "{name}");
+        }
+        for name in ["", ".", "..", "a/b", "a\\b", "c:", "what?", "a*b", "tab\tname", "a|b"] {
+            assert!(!is_portable_file_name(name), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn reserved_windows_names_are_not_portable() {
+        for name in ["CON", "con", "Prn", "aux.txt", "NUL.tar.gz", "COM1", "com9.log", "LPT1", "lpt9"] {
+            assert!(!is_portable_file_name(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::compile::{self, ASTNode, Edition, Lint, Param, Warning};
+use crate::manifest::Manifest;
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::debug;
+
+/// 传给插件的文本第一行，格式变化时增加版本号
+const PROTOCOL: &str = "ntfp-plugin-ast 15";
+
+/// 依次运行`[plugins]`中的编译器插件，返回 (可能被插件替换的) AST 和插件报告的警告。
+/// 插件在语义分析之前运行，替换后的程序同样要通过分析
+pub fn run(
+    project_path: &Path,
+    manifest: &Manifest,
+    mut ast: Vec<ASTNode>,
+    edition: Edition,
+) -> Result<(Vec<ASTNode>, Vec<Warning>)> {
+    let mut warnings = Vec::new();
+    for (name, library) in &manifest.plugins {
+        let path = project_path.join(library);
+        debug!(plugin = %name, ?path, "running compiler plugin");
+        let output = native::call(&path, &dump(&ast)).with_context(|| format!("插件 {} 运行失败", name))?;
+        let mut errors = Vec::new();
+        let mut lines = output.lines();
+        while let Some(line) = lines.next() {
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "" => {}
+                "warning" => warnings.push(Warning { lint: Lint::Plugin, message: diagnostic(name, rest) }),
+                "error" => errors.push(diagnostic(name, rest)),
+                // 之后的所有行是替换原程序的Netflu源码
+                "source" => {
+                    let source: String = lines.by_ref().flat_map(|l| [l, "\n"]).collect();
+                    ast = reparse(&source, edition, manifest)
+                        .with_context(|| format!("插件 {} 返回的源码无效:\n{}", name, source))?;
+                }
+                _ => anyhow::bail!("插件 {} 的输出无法识别: {:?}", name, line),
+            }
+        }
+        if !errors.is_empty() {
+            anyhow::bail!("{}", errors.join("\n"));
+        }
+    }
+    Ok((ast, warnings))
+}
+
+/// `<line> <message>` -> `line N: message (插件 name)`
+fn diagnostic(plugin: &str, rest: &str) -> String {
+    match rest.split_once(' ') {
+        Some((line, message)) if line.parse::<u32>().is_ok() => format!("line {}: {} (插件 {})", line, message, plugin),
+        _ => format!("{} (插件 {})", rest, plugin),
+    }
+}
+
+fn reparse(source: &str, edition: Edition, manifest: &Manifest) -> Result<Vec<ASTNode>> {
+    let tokens = compile::lexer(source).map_err(|e| anyhow::anyhow!("词法分析错误: {}", e))?;
+    let mut parser = compile::Parser::with_edition(tokens, edition);
+    parser.disabled = manifest.disabled_features()?;
+    let ast = parser.parse().map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))?;
+    compile::lower_top_level(ast, manifest.language.top_level_statements)
+        .map_err(|e| anyhow::anyhow!("语法分析错误: {}", e))
+}
+
+/// 插件看到的AST: 第一行是协议版本，之后每个顶层节点一行S表达式，`@N`是源码行号
+///
+/// ```text
+/// (fun @1 main priv (params) - (let @2 n (op @2 + (num 1) (num 2))) (print (id @3 n)))
+/// (extern @5 c abs (params (n int)) int)
+/// ```
+pub fn dump(nodes: &[ASTNode]) -> String {
+    let mut out = format!("{}\n", PROTOCOL);
+    for node in nodes {
+        out.push_str(&sexpr(node));
+        out.push('\n');
+    }
+    out
+}
+
+fn sexpr(node: &ASTNode) -> String {
+    let list = |items: Vec<String>| format!("({})", items.join(" "));
+    let params = |params: &[Param]| {
+        let mut items = vec!["params".to_string()];
+        items.extend(params.iter().map(|p| format!("({} {})", p.name, p.type_name())));
+        list(items)
+    };
+    match node {
+        ASTNode::Let { name, value, span, .. } => {
+            list(vec!["let".into(), format!("@{}", span.line), name.clone(), sexpr(value)])
+        }
+        ASTNode::LetTuple { bindings, value, span } => {
+            let names = list(bindings.iter().map(|b| b.name.clone()).collect());
+            list(vec!["let-tuple".into(), format!("@{}", span.line), names, sexpr(value)])
+        }
+        ASTNode::Assign { name, value, span } => {
+            list(vec!["assign".into(), format!("@{}", span.line), name.clone(), sexpr(value)])
+        }
+        ASTNode::Print { value, newline: false, .. } => list(vec!["print".into(), sexpr(value)]),
+        ASTNode::Print { value, newline: true, .. } => list(vec!["println".into(), sexpr(value)]),
+        ASTNode::Back { value, .. } => list(std::iter::once("back".to_string()).chain(value.iter().map(|v| sexpr(v))).collect()),
+        ASTNode::Function { name, params: ps, return_type, body, public, span } => {
+            let mut items = vec![
+                "fun".into(),
+                format!("@{}", span.line),
+                name.clone(),
+                if *public { "pub" } else { "priv" }.into(),
+                params(ps),
+                return_type.clone().unwrap_or_else(|| "-".into()),
+            ];
+            items.extend(body.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::ExternFunction { abi, name, params: ps, return_type, span } => list(vec![
+            "extern".into(),
+            format!("@{}", span.line),
+            abi.clone(),
+            name.clone(),
+            params(ps),
+            return_type.clone().unwrap_or_else(|| "-".into()),
+        ]),
+        ASTNode::FunctionCall { name, args, span } => {
+            let mut items = vec!["call".into(), format!("@{}", span.line), name.clone()];
+            items.extend(args.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::Identifier { name, span, .. } => list(vec!["id".into(), format!("@{}", span.line), name.clone()]),
+        ASTNode::Number { value } => list(vec!["num".into(), value.clone()]),
+        // 插件在语义分析之前运行，只会看到名称 `none`
+        ASTNode::None { ty, span } => list(vec!["none".into(), format!("@{}", span.line), ty.name().into()]),
+        // 字符串保持源码中的字面量形式 (带引号和转义)
+        ASTNode::String { value } => list(vec!["str".into(), value.clone()]),
+        ASTNode::Interpolation { value, args, span } => {
+            let mut items = vec!["interp".into(), format!("@{}", span.line), value.clone()];
+            items.extend(args.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::Array { elements, span } => {
+            let mut items = vec!["array".into(), format!("@{}", span.line)];
+            items.extend(elements.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::Tuple { elements, span } => {
+            let mut items = vec!["tuple".into(), format!("@{}", span.line)];
+            items.extend(elements.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::Map { entries, span } => {
+            let mut items = vec!["map".into(), format!("@{}", span.line)];
+            items.extend(entries.iter().map(|(key, value)| list(vec![sexpr(key), sexpr(value)])));
+            list(items)
+        }
+        ASTNode::Index { collection, index, span, .. } => {
+            list(vec!["index".into(), format!("@{}", span.line), sexpr(collection), sexpr(index)])
+        }
+        ASTNode::MethodCall { receiver, name, args, span, .. } => {
+            let mut items = vec!["method".into(), format!("@{}", span.line), name.clone(), sexpr(receiver)];
+            items.extend(args.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::Field { object, name, span, .. } => {
+            list(vec!["field".into(), format!("@{}", span.line), name.clone(), sexpr(object)])
+        }
+        ASTNode::Enum { name, variants, span } => {
+            let mut items = vec!["enum".into(), format!("@{}", span.line), name.clone()];
+            items.extend(variants.iter().cloned());
+            list(items)
+        }
+        ASTNode::Match { value, arms, else_body, span, .. } => {
+            let mut items = vec!["match".into(), format!("@{}", span.line), sexpr(value)];
+            for arm in arms {
+                let mut head = vec!["arm".into(), format!("@{}", arm.span.line), arm.variant.clone()];
+                // `some(x)` 绑定的名称是原子，而语句都是列表
+                head.extend(arm.binding.iter().map(|binding| binding.name.clone()));
+                items.push(list(head.into_iter().chain(arm.body.iter().map(sexpr)).collect()));
+            }
+            if let Some(else_body) = else_body {
+                items.push(list(std::iter::once("else".to_string()).chain(else_body.iter().map(sexpr)).collect()));
+            }
+            list(items)
+        }
+        ASTNode::Binary { op, left, right, span, .. } => {
+            list(vec!["op".into(), format!("@{}", span.line), op.symbol().into(), sexpr(left), sexpr(right)])
+        }
+        ASTNode::Negate { operand, span } => list(vec!["neg".into(), format!("@{}", span.line), sexpr(operand)]),
+        ASTNode::If { condition, then_body, else_body, span } => {
+            let block = |head: &str, body: &[ASTNode]| {
+                list(std::iter::once(head.to_string()).chain(body.iter().map(sexpr)).collect())
+            };
+            list(vec!["if".into(), format!("@{}", span.line), sexpr(condition), block("then", then_body), block("else", else_body)])
+        }
+        ASTNode::While { condition, body, span } => {
+            let mut items = vec!["while".into(), format!("@{}", span.line), sexpr(condition)];
+            items.extend(body.iter().map(sexpr));
+            list(items)
+        }
+        ASTNode::For { var, start, end, body, span } => {
+            let mut items = vec!["for".into(), format!("@{}", span.line), var.clone(), sexpr(start), sexpr(end)];
+            items.extend(body.iter().map(sexpr));
+            list(items)
+        }
+    }
+}
+
+/// 插件是导出以下C函数的动态库:
+///
+/// ```c
+/// char *ntfp_plugin_run(const char *ast);   // 返回NULL表示没有输出
+/// void ntfp_plugin_free(char *output);
+/// ```
+#[cfg(all(feature = "plugins", unix))]
+mod native {
+    use anyhow::Result;
+    use std::{
+        ffi::{CStr, CString, c_char, c_void},
+        os::unix::ffi::OsStrExt,
+        path::Path,
+    };
+
+    type RunFn = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+    type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+    fn last_error() -> String {
+        let error = unsafe { libc::dlerror() };
+        if error.is_null() {
+            "未知错误".to_string()
+        } else {
+            unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+        }
+    }
+
+    fn symbol(handle: *mut c_void, name: &CStr) -> Result<*mut c_void> {
+        let symbol = unsafe { libc::dlsym(handle, name.as_ptr()) };
+        if symbol.is_null() {
+            anyhow::bail!("插件没有导出{}: {}", name.to_string_lossy(), last_error());
+        }
+        Ok(symbol)
+    }
+
+    /// 加载插件并调用一次。库在进程结束前不卸载，插件可以安全地保留全局状态
+    pub fn call(path: &Path, input: &str) -> Result<String> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            anyhow::bail!("无法加载插件 {:?}: {}", path, last_error());
+        }
+        let run: RunFn = unsafe { std::mem::transmute(symbol(handle, c"ntfp_plugin_run")?) };
+        let free: FreeFn = unsafe { std::mem::transmute(symbol(handle, c"ntfp_plugin_free")?) };
+
+        let input = CString::new(input)?;
+        let output = unsafe { run(input.as_ptr()) };
+        if output.is_null() {
+            return Ok(String::new());
+        }
+        let text = unsafe { CStr::from_ptr(output) }.to_string_lossy().into_owned();
+        unsafe { free(output) };
+        Ok(text)
+    }
+}
+
+#[cfg(not(all(feature = "plugins", unix)))]
+mod native {
+    use anyhow::Result;
+    use std::path::Path;
+
+    pub fn call(_path: &Path, _input: &str) -> Result<String> {
+        if cfg!(unix) {
+            anyhow::bail!("项目使用了编译器插件，需要启用plugins feature的ntfp (cargo install --path . --features plugins)");
+        }
+        anyhow::bail!("编译器插件目前只支持Unix系统");
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+/// `ntfp run-all-examples`的结果格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// 逐行输出，供人阅读
+    #[default]
+    Human,
+    Json,
+    /// JUnit XML，大多数CI系统可以直接显示
+    Junit,
+}
+
+/// 一个用例 (示例) 的结果
+pub struct CaseResult {
+    pub name: String,
+    pub duration: Duration,
+    /// 失败时为`(原因, 详细输出)`
+    pub failure: Option<(String, String)>,
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            // XML 1.0不允许除换行和制表符以外的控制字符
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn total_seconds(cases: &[CaseResult]) -> f64 {
+    cases.iter().map(|c| c.duration.as_secs_f64()).sum()
+}
+
+pub fn render_json(suite: &str, cases: &[CaseResult]) -> String {
+    let failed = cases.iter().filter(|c| c.failure.is_some()).count();
+    let entries: Vec<String> = cases
+        .iter()
+        .map(|case| {
+            let (status, reason, output) = match &case.failure {
+                None => ("passed", "null".to_string(), "null".to_string()),
+                Some((reason, output)) => ("failed", json_string(reason), json_string(output)),
+            };
+            format!(
+                "    {{\"name\": {}, \"status\": \"{}\", \"duration\": {:.3}, \"reason\": {}, \"output\": {}}}",
+                json_string(&case.name),
+                status,
+                case.duration.as_secs_f64(),
+                reason,
+                output
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"suite\": {},\n  \"tests\": {},\n  \"passed\": {},\n  \"failed\": {},\n  \"duration\": {:.3},\n  \"cases\": [\n{}\n  ]\n}}\n",
+        json_string(suite),
+        cases.len(),
+        cases.len() - failed,
+        failed,
+        total_seconds(cases),
+        entries.join(",\n")
+    )
+}
+
+pub fn render_junit(suite: &str, cases: &[CaseResult]) -> String {
+    let failed = cases.iter().filter(|c| c.failure.is_some()).count();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    out.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">\n",
+        xml_escape(suite),
+        cases.len(),
+        failed,
+        total_seconds(cases)
+    ));
+    for case in cases {
+        let open = format!(
+            "    <testcase classname=\"{}.examples\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(suite),
+            xml_escape(&case.name),
+            case.duration.as_secs_f64()
+        );
+        match &case.failure {
+            None => out.push_str(&format!("{}/>\n", open)),
+            Some((reason, output)) => out.push_str(&format!(
+                "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                open,
+                xml_escape(reason),
+                xml_escape(output)
+            )),
+        }
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
@@ -0,0 +1,126 @@
+use crate::{config, manifest::Manifest, signals};
+use anyhow::{Context, Result};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// 子shell中可用的别名，都作用于`$NTFP_PROJECT`，在任何目录下都可以使用
+const ALIASES: &[(&str, &str)] = &[
+    ("nb", "build"),
+    ("nr", "run"),
+    ("nh", "run --hot"),
+    ("ni", "info"),
+];
+
+/// 子shell设置的环境变量，值为项目路径；也用于检测嵌套的`ntfp shell`
+const PROJECT_VAR: &str = "NTFP_PROJECT";
+
+/// 启动设置好项目环境的子shell，退出子shell后返回
+pub fn spawn(project_path: &Path) -> Result<()> {
+    let manifest = Manifest::load(project_path)?;
+    if let Some(outer) = env::var_os(PROJECT_VAR) {
+        eprintln!("警告: 已经在ntfp shell中 (NTFP_PROJECT={})", Path::new(&outer).display());
+    }
+
+    let shell = user_shell();
+    let mut command = Command::new(&shell);
+    // 放在PATH最前面，子shell中的`ntfp`就是启动它的这个
+    let tool_dirs = config::tool_dirs();
+    let mut path = tool_dirs.clone();
+    if let Some(inherited) = env::var_os("PATH") {
+        path.extend(env::split_paths(&inherited));
+    }
+    let path = env::join_paths(path).context("PATH中包含无效的目录")?;
+    command.env(PROJECT_VAR, project_path).env("PATH", path).current_dir(project_path);
+    let rc_dir = project_path.join("target").join("shell");
+    let ntfp = env::current_exe().context("无法确定ntfp的路径")?;
+    configure(&mut command, &shell, &rc_dir, &ntfp, &tool_dirs, &manifest.package.name)?;
+
+    eprintln!(
+        "已进入项目 {} 的ntfp shell (别名: {})，输入exit退出",
+        manifest.package.name,
+        ALIASES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+    );
+    // shell的退出码只是最后一条命令的结果，不视为ntfp的错误
+    signals::status_interactive(&mut command).with_context(|| format!("无法启动shell: {:?}", shell))?;
+    eprintln!("已退出ntfp shell");
+    Ok(())
+}
+
+#[cfg(unix)]
+fn user_shell() -> PathBuf {
+    env::var_os("SHELL").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/bin/sh"))
+}
+
+#[cfg(windows)]
+fn user_shell() -> PathBuf {
+    env::var_os("COMSPEC").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("cmd.exe"))
+}
+
+fn alias_command(ntfp: &Path, args: &str) -> String {
+    format!("\"{}\" {} --path \"${}\"", ntfp.display(), args, PROJECT_VAR)
+}
+
+/// 按shell的种类设置别名和提示符。bash和zsh通过在target/shell中生成的启动文件
+/// (先加载用户自己的配置，再把`tool_dirs`重新放到PATH最前面)，fish通过`-C`，
+/// 其他shell只设置环境变量
+fn configure(
+    command: &mut Command,
+    shell: &Path,
+    rc_dir: &Path,
+    ntfp: &Path,
+    tool_dirs: &[PathBuf],
+    name: &str,
+) -> Result<()> {
+    let shell_name = shell.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let mut aliases: String = ALIASES
+        .iter()
+        .map(|(alias, args)| format!("alias {}='{}'\n", alias, alias_command(ntfp, args)))
+        .collect();
+    for dir in tool_dirs.iter().rev() {
+        aliases.push_str(&format!("PATH=\"{}:$PATH\"\n", dir.display()));
+    }
+    let write_rc = |file: &str, content: String| -> Result<PathBuf> {
+        fs::create_dir_all(rc_dir).with_context(|| format!("无法创建目录: {:?}", rc_dir))?;
+        let path = rc_dir.join(file);
+        fs::write(&path, content).with_context(|| format!("无法写入文件: {:?}", path))?;
+        Ok(path)
+    };
+
+    match shell_name {
+        "bash" => {
+            let rc = write_rc(
+                "bashrc",
+                format!("[ -f ~/.bashrc ] && . ~/.bashrc\n{}PS1=\"(ntfp:{}) $PS1\"\n", aliases, name),
+            )?;
+            command.arg("--rcfile").arg(rc).arg("-i");
+        }
+        "zsh" => {
+            write_rc(
+                ".zshrc",
+                format!("[ -f ~/.zshrc ] && . ~/.zshrc\n{}PROMPT=\"(ntfp:{}) $PROMPT\"\n", aliases, name),
+            )?;
+            command.env("ZDOTDIR", rc_dir);
+        }
+        "fish" => {
+            let fish_aliases: Vec<String> = ALIASES
+                .iter()
+                .map(|(alias, args)| format!("alias {} '{}'", alias, alias_command(ntfp, args)))
+                .collect();
+            command.arg("-C").arg(fish_aliases.join("; "));
+        }
+        "cmd" => {
+            let macros: Vec<String> = ALIASES
+                .iter()
+                .map(|(alias, args)| {
+                    format!("doskey {}=\"{}\" {} --path \"%{}%\" $*", alias, ntfp.display(), args, PROJECT_VAR)
+                })
+                .collect();
+            command.arg("/K").arg(macros.join(" & "));
+        }
+        _ => eprintln!("提示: 不支持为 {} 设置别名，只设置了环境变量", shell.display()),
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::{
+    io,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+use tracing::debug;
+
+/// 当前正在等待的子进程 (rustc或项目的二进制文件)，0表示没有
+static CHILD: AtomicU32 = AtomicU32::new(0);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// 正在运行交互式子进程 (`ntfp shell`)，Ctrl-C由它自己处理
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// 处理Ctrl-C/SIGTERM: 有子进程时转发给它并等待其退出，否则直接退出
+pub fn install() {
+    let result = ctrlc::set_handler(|| {
+        if INTERACTIVE.load(Ordering::SeqCst) {
+            return;
+        }
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        match CHILD.load(Ordering::SeqCst) {
+            0 => std::process::exit(130),
+            pid => forward(pid),
+        }
+    });
+    if let Err(e) = result {
+        debug!(error = %e, "failed to install signal handler");
+    }
+}
+
+#[cfg(unix)]
+fn forward(pid: u32) {
+    // Ctrl-C already reaches the whole foreground process group, but a SIGTERM
+    // sent to ntfp alone would otherwise orphan the child.
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn forward(_pid: u32) {
+    // Windows delivers Ctrl-C to every process attached to the console.
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// 与`Command::output`相同，但在等待期间把收到的信号转发给子进程
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    CHILD.store(child.id(), Ordering::SeqCst);
+    let output = child.wait_with_output();
+    CHILD.store(0, Ordering::SeqCst);
+    output
+}
+
+/// 与`Command::status`相同 (继承标准输入输出)，但在等待期间把收到的信号转发给子进程
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    CHILD.store(child.id(), Ordering::SeqCst);
+    let status = child.wait();
+    CHILD.store(0, Ordering::SeqCst);
+    status
+}
+
+/// 运行继承终端的交互式子进程并等待其退出。期间ntfp忽略Ctrl-C，
+/// 否则在子shell中按Ctrl-C中断命令时ntfp会先退出
+pub fn status_interactive(command: &mut Command) -> io::Result<ExitStatus> {
+    INTERACTIVE.store(true, Ordering::SeqCst);
+    let status = command.status();
+    INTERACTIVE.store(false, Ordering::SeqCst);
+    status
+}
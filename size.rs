@@ -0,0 +1,98 @@
+use crate::manifest::Manifest;
+use crate::{BuildOptions, build_project};
+use anyhow::{Context, Result};
+use std::{fs, path::Path, process::Command};
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} 字节", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB ({} 字节)", bytes as f64 / 1024.0, bytes)
+    } else {
+        format!("{:.1} MiB ({} 字节)", bytes as f64 / (1024.0 * 1024.0), bytes)
+    }
+}
+
+/// 分别以debug和release配置构建项目，比较产物大小；`symbols`时列出项目中各函数的大小
+pub fn report(project_path: &Path, symbols: bool) -> Result<()> {
+    let manifest = Manifest::load(project_path)?;
+    let mut sizes = Vec::new();
+    let mut failed = 0;
+    for release in [false, true] {
+        let profile = if release { "release" } else { "debug" };
+        let build = build_project(project_path, &BuildOptions { release, ..Default::default() });
+        match build.and_then(|b| Ok((fs::metadata(&b.binary_path)?.len(), b.binary_path))) {
+            Ok((size, path)) => sizes.push((profile, size, path)),
+            Err(e) => {
+                eprintln!("{} 构建失败: {:#}", profile, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    for (profile, size, path) in &sizes {
+        println!("{:<8} {}  {}", profile, format_size(*size), path.display());
+    }
+    if let [(_, debug, _), (_, release, _)] = sizes.as_slice() {
+        let change = (*release as f64 - *debug as f64) / *debug as f64 * 100.0;
+        println!("release比debug {} {:.1}%", if change <= 0.0 { "小" } else { "大" }, change.abs());
+    }
+
+    if symbols {
+        // strip后的release产物没有符号表，此时退回到debug构建
+        let with_symbols = sizes.iter().rev().find_map(|(profile, _, path)| {
+            function_sizes(path, &crate_prefix(&manifest)).ok().filter(|f| !f.is_empty()).map(|f| (profile, f))
+        });
+        match with_symbols {
+            Some((profile, functions)) => {
+                println!("\n函数大小 ({}构建，来自符号表):", profile);
+                let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+                for (name, size) in functions {
+                    println!("  {:<width$}  {}", name, format_size(size), width = width);
+                }
+            }
+            None => println!("\n没有找到项目函数的符号 (需要GNU nm，且产物没有被strip)"),
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} 个配置构建失败", failed);
+    }
+    Ok(())
+}
+
+/// 生成的Rust代码中项目函数的路径前缀: 可执行文件的crate名来自`main.rs`，库来自包名
+fn crate_prefix(manifest: &Manifest) -> String {
+    match manifest.lib {
+        Some(_) => format!("{}::", manifest.crate_name()),
+        None => "main::".to_string(),
+    }
+}
+
+/// 用`nm`读取符号表，返回以`prefix`开头的函数及其大小，从大到小排列
+fn function_sizes(binary: &Path, prefix: &str) -> Result<Vec<(String, u64)>> {
+    let output = Command::new("nm")
+        .args(["--print-size", "--demangle", "--defined-only"])
+        .arg(binary)
+        .output()
+        .context("无法运行nm")?;
+    if !output.status.success() {
+        anyhow::bail!("nm失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let mut functions: Vec<(String, u64)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            // `<地址> <大小> <类型> <名称>`，只保留代码段 (t/T) 中的符号
+            let mut fields = line.splitn(4, ' ');
+            let (_, size, kind, name) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+            if !matches!(kind, "t" | "T") {
+                return None;
+            }
+            Some((name.strip_prefix(prefix)?.to_string(), u64::from_str_radix(size, 16).ok()?))
+        })
+        .collect();
+    functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(functions)
+}
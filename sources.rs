@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// 由`[package] include/exclude`决定的项目源文件集合。构建、示例和`ntfp info`
+/// 都通过它判断一个.ntf文件是否属于项目，保证各命令看到的文件列表一致
+#[derive(Debug)]
+pub struct SourceFilter {
+    /// 缺省时包含项目中的所有.ntf文件
+    include: Option<Vec<Vec<String>>>,
+    exclude: Vec<Vec<String>>,
+}
+
+impl SourceFilter {
+    pub fn new(include: Option<&[String]>, exclude: &[String]) -> Result<Self> {
+        let parse_all = |patterns: &[String]| patterns.iter().map(|p| parse_pattern(p)).collect::<Result<Vec<_>>>();
+        Ok(SourceFilter { include: include.map(parse_all).transpose()?, exclude: parse_all(exclude)? })
+    }
+
+    /// `relative`是相对于项目目录的路径，例如`src/main.ntf`
+    pub fn contains(&self, relative: &Path) -> bool {
+        let Some(segments) = path_segments(relative) else {
+            return false;
+        };
+        let included = match &self.include {
+            Some(include) => include.iter().any(|pattern| match_segments(pattern, &segments)),
+            None => true,
+        };
+        included && !self.exclude.iter().any(|pattern| match_segments(pattern, &segments))
+    }
+
+    /// 项目中属于集合的.ntf文件 (相对路径)，按路径排序。不进入target和以`.`开头的目录
+    pub fn files(&self, project_path: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative_dir) = pending.pop() {
+            let dir = project_path.join(&relative_dir);
+            let entries = fs::read_dir(&dir).with_context(|| format!("无法读取目录: {:?}", dir))?;
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                let relative = relative_dir.join(&name);
+                if entry.file_type()?.is_dir() {
+                    let skipped = name.to_string_lossy().starts_with('.')
+                        || (relative_dir.as_os_str().is_empty() && name == "target");
+                    if !skipped {
+                        pending.push(relative);
+                    }
+                } else if relative.extension().is_some_and(|ext| ext == "ntf") && self.contains(&relative) {
+                    files.push(relative);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// 模式使用`/`分隔，相对于项目目录: `*`匹配文件名中的任意字符，`?`匹配一个字符，
+/// `**`匹配任意层目录，例如`src/**/*.ntf`、`examples/draft_*.ntf`
+fn parse_pattern(pattern: &str) -> Result<Vec<String>> {
+    let segments: Vec<String> = pattern.split('/').map(String::from).collect();
+    if pattern.is_empty() || pattern.starts_with('/') || segments.iter().any(|s| s.is_empty() || s == "..") {
+        anyhow::bail!("无效的文件模式: \"{}\" (应为相对于项目目录的路径，例如\"src/**/*.ntf\")", pattern);
+    }
+    Ok(segments)
+}
+
+fn path_segments(path: &Path) -> Option<Vec<String>> {
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str().map(String::from),
+            _ => None,
+        })
+        .collect()
+}
+
+fn match_segments(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_name(&pattern, &name) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
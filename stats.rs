@@ -0,0 +1,105 @@
+use crate::compile::ASTNode;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub struct BuildStats {
+    pub tokens: usize,
+    pub node_counts: BTreeMap<&'static str, usize>,
+    pub global_symbols: usize,
+    pub local_symbols: usize,
+    pub peak_alloc: Option<usize>,
+}
+
+impl BuildStats {
+    pub fn collect(token_count: usize, ast: &[ASTNode], global_symbols: usize) -> Self {
+        let mut stats = BuildStats {
+            tokens: token_count,
+            global_symbols,
+            ..Default::default()
+        };
+
+        for node in ast {
+            node.walk(&mut |n| {
+                *stats.node_counts.entry(n.kind()).or_insert(0) += 1;
+                if let ASTNode::Function { params, body, .. } = n {
+                    let lets: usize = body
+                        .iter()
+                        .map(|s| match s {
+                            ASTNode::Let { .. } => 1,
+                            ASTNode::LetTuple { bindings, .. } => bindings.len(),
+                            _ => 0,
+                        })
+                        .sum();
+                    stats.local_symbols += params.len() + lets;
+                }
+            });
+        }
+
+        stats.peak_alloc = alloc::peak();
+        stats
+    }
+
+    pub fn print(&self) {
+        let total: usize = self.node_counts.values().sum();
+
+        println!("编译统计:");
+        println!("  词法单元: {}", self.tokens);
+        println!("  AST节点: {}", total);
+        for (kind, count) in &self.node_counts {
+            println!("    {:<14}{}", kind, count);
+        }
+        println!("  符号表: 全局 {}, 函数局部 {}", self.global_symbols, self.local_symbols);
+        match self.peak_alloc {
+            Some(bytes) => println!("  峰值内存: {} 字节", bytes),
+            None => println!("  峰值内存: 未启用 (需要使用`--features alloc-stats`编译ntfp)"),
+        }
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+pub mod alloc {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub struct CountingAllocator;
+
+    static CURRENT: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+                PEAK.fetch_max(current, Ordering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    /// Restarts peak tracking from the current live allocation size.
+    pub fn reset_peak() {
+        PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    pub fn peak() -> Option<usize> {
+        Some(PEAK.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(not(feature = "alloc-stats"))]
+pub mod alloc {
+    pub fn reset_peak() {}
+
+    pub fn peak() -> Option<usize> {
+        None
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::{self, GlobalConfig};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+use tracing::debug;
+
+/// 本地使用统计，只有在全局配置中设置`usage-stats = true`时才会记录，不会联网
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub builds: u64,
+    pub failed_builds: u64,
+    pub total_build_ms: u64,
+    pub max_build_ms: u64,
+    /// 错误类别 -> 出现次数
+    pub errors: BTreeMap<String, u64>,
+}
+
+fn stats_path() -> Option<PathBuf> {
+    config::ntfp_home().map(|home| home.join("usage-stats.toml"))
+}
+
+/// 把错误信息归类，去掉行号和具体内容，例如
+/// `语义分析错误: line 3: Undefined identifier: x` -> `语义分析错误: Undefined identifier`
+fn error_kind(error: &anyhow::Error) -> String {
+    let message = error.to_string();
+    let first_line = message.lines().next().unwrap_or_default();
+    first_line
+        .split(": ")
+        .filter(|part| !part.starts_with("line "))
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+impl UsageStats {
+    pub fn load() -> Result<Self> {
+        let Some(path) = stats_path().filter(|p| p.exists()) else {
+            return Ok(UsageStats::default());
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("无法读取文件: {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("无法解析使用统计: {:?}", path))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = stats_path().ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建目录: {:?}", parent))?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("无法写入文件: {:?}", path))
+    }
+
+    pub fn reset() -> Result<()> {
+        match stats_path().filter(|p| p.exists()) {
+            Some(path) => fs::remove_file(&path).with_context(|| format!("无法删除文件: {:?}", path)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("使用统计:");
+        println!("  构建次数: {} (失败 {})", self.builds, self.failed_builds);
+        if let Some(average) = self.total_build_ms.checked_div(self.builds) {
+            println!("  构建耗时: 平均 {} ms, 最长 {} ms", average, self.max_build_ms);
+        }
+        if self.errors.is_empty() {
+            return;
+        }
+
+        let mut errors: Vec<_> = self.errors.iter().collect();
+        errors.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        println!("  常见错误:");
+        for (kind, count) in errors {
+            println!("    {:>5}  {}", count, kind);
+        }
+    }
+}
+
+/// 记录一次构建，统计未开启时什么都不做；记录失败不影响构建结果
+pub fn record_build<T>(duration: Duration, result: &Result<T>) {
+    if !GlobalConfig::load().is_ok_and(|c| c.usage_stats) {
+        return;
+    }
+
+    let recorded = UsageStats::load().and_then(|mut stats| {
+        let ms = duration.as_millis() as u64;
+        stats.builds += 1;
+        stats.total_build_ms += ms;
+        stats.max_build_ms = stats.max_build_ms.max(ms);
+        if let Err(e) = result {
+            stats.failed_builds += 1;
+            *stats.errors.entry(error_kind(e)).or_insert(0) += 1;
+        }
+        stats.save()
+    });
+    if let Err(e) = recorded {
+        debug!(error = %e, "failed to record usage stats");
+    }
+}
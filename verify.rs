@@ -0,0 +1,126 @@
+use crate::compile;
+use crate::doctor::Report;
+use crate::lints::{LintLevels, LintOverrides};
+use crate::manifest::{self, Manifest};
+use anyhow::Result;
+use std::{fs, path::Path};
+
+/// 记录一项检查的结果，失败时给出修复建议
+fn check<T>(report: &mut Report, result: Result<T>, ok: &str, fix: &str) -> Option<T> {
+    match result {
+        Ok(value) => {
+            report.ok(ok);
+            Some(value)
+        }
+        Err(e) => {
+            report.fail(&format!("{:#}", e), fix);
+            None
+        }
+    }
+}
+
+/// 不运行语义分析和rustc，只检查配置、文件和语法，适合作为提交前的检查
+pub fn verify(project_path: &Path) -> Result<()> {
+    let mut report = Report { problems: 0 };
+
+    println!("配置文件:");
+    let manifest = match Manifest::load(project_path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            report.fail(&format!("{:#}", e), "修正ntfp.toml后重新运行`ntfp verify`");
+            anyhow::bail!("发现 {} 个问题", report.problems);
+        }
+    };
+    report.ok(&format!("{} 有效", manifest::FILE_NAME));
+    let edition = check(
+        &mut report,
+        manifest.edition(),
+        &format!("edition {}", manifest.package.edition),
+        "使用当前ntfp支持的edition，或升级ntfp",
+    );
+    let disabled =
+        check(&mut report, manifest.disabled_features(), "[language]", "只使用`ntfp help-lang`中列出的特性名");
+    check(
+        &mut report,
+        LintLevels::resolve(&manifest, &LintOverrides::default()),
+        "[lints]",
+        "级别只能是allow、warn或deny",
+    );
+    check(&mut report, manifest.analyzer(), "[config]", "只使用整数、字符串和布尔值，键名是合法的标识符");
+    let sources = check(&mut report, manifest.sources(), "[package] include/exclude", "使用相对于项目目录的模式");
+    check(
+        &mut report,
+        crate::release_rustc_args(&manifest.profile.release, manifest.lib.is_some()),
+        "[profile.release]",
+        "修正[profile.release]中的选项",
+    );
+
+    println!("名称:");
+    let package = &manifest.package;
+    if package.name.is_empty() || package.name.starts_with(|c: char| c.is_ascii_digit()) {
+        report.fail(&format!("无效的包名: \"{}\"", package.name), "包名不能为空，也不能以数字开头");
+    } else {
+        report.ok(&format!("包名 {}", package.name));
+    }
+    if manifest::parse_version(&package.version).is_some() {
+        report.ok(&format!("版本 {}", package.version));
+    } else {
+        report.fail(&format!("无效的版本号: \"{}\"", package.version), "使用`主版本.次版本.修订号`格式，例如0.1.0");
+    }
+    let output = match manifest.library_name() {
+        Ok(Some(name)) => Ok(name),
+        Ok(None) => manifest.binary_name(),
+        Err(e) => Err(e),
+    };
+    check(&mut report, output, "输出文件名", "修正out-name或[lib] crate-type");
+
+    println!("文件:");
+    let main_source = Path::new("src").join("main.ntf");
+    if !project_path.join(&main_source).is_file() {
+        report.fail("未找到主程序文件: `src/main.ntf`", "创建src/main.ntf");
+    } else if sources.as_ref().is_some_and(|s| !s.contains(&main_source)) {
+        report.fail("`src/main.ntf`被[package] include/exclude排除", "调整include/exclude");
+    } else {
+        report.ok("src/main.ntf");
+    }
+    let referenced = manifest
+        .rust
+        .sources
+        .iter()
+        .map(|p| ("[rust] sources", p))
+        .chain(manifest.c.link_search.iter().map(|p| ("[c] link-search", p)))
+        .chain(manifest.plugins.values().map(|p| ("[plugins]", p)));
+    for (section, path) in referenced {
+        if project_path.join(path).exists() {
+            report.ok(&format!("{} {}", section, path));
+        } else {
+            report.fail(&format!("{}中的{}不存在", section, path), "修正路径或删除该项");
+        }
+    }
+
+    println!("源文件:");
+    if let (Some(sources), Some(edition), Some(disabled)) = (sources, edition, disabled) {
+        let files = sources.files(project_path)?;
+        for file in &files {
+            let parsed = fs::read_to_string(project_path.join(file))
+                .map_err(|e| e.to_string())
+                .and_then(|source| {
+                    let mut parser = compile::Parser::with_edition(compile::lexer(&source)?, edition);
+                    parser.disabled = disabled.clone();
+                    compile::lower_top_level(parser.parse()?, manifest.language.top_level_statements)
+                });
+            match parsed {
+                Ok(_) => report.ok(&file.display().to_string()),
+                Err(e) => report.fail(&format!("{}: {}", file.display(), e), "修正语法错误"),
+            }
+        }
+    } else {
+        report.warn("配置有误，跳过源文件检查", "先修正上面的配置问题");
+    }
+
+    if report.problems > 0 {
+        anyhow::bail!("发现 {} 个问题", report.problems);
+    }
+    println!("验证通过!");
+    Ok(())
+}
@@ -0,0 +1,723 @@
+use crate::compile::{self, ASTNode, BinaryOp, FormatSpec, Method, Overflow, Type};
+use crate::interp::{array, binary, index, interpolate, interpolation_texts, len, map, negate, negative_literal, some, unquote, untuple, unwrap, variant, Native, ReloadHook, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::{debug, trace};
+
+/// One instruction of the stack machine. Locals live on the value stack
+/// above the frame's base, parameters first.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Const(u32),
+    Load(u32),
+    Store(u32),
+    Call { function: u32, argc: u32 },
+    /// Calls a function the program does not define; constant `name` is looked
+    /// up among the host's natives when the call runs.
+    CallNative { name: u32, argc: u32 },
+    Format(u32),
+    /// Pops one value per placeholder of template `n` and pushes the joined str.
+    Interpolate(u32),
+    /// Pops the right then the left operand, pushes the result.
+    Binary(BinaryOp),
+    /// Like `Binary`, for an operator written at source line `line`, which
+    /// its runtime errors name like the checks of generated code.
+    Operator { op: BinaryOp, line: u32 },
+    /// Pops an int and pushes its negation; errors name source line `n`.
+    Negate(u32),
+    /// Pops `n` elements and pushes the array of them, first element deepest.
+    Array(u32),
+    /// Pops `n` key-value pairs, each key below its value, and pushes the map
+    /// of them, first entry deepest.
+    Map(u32),
+    /// Pops `n` elements and pushes the tuple of them, first element deepest.
+    Tuple(u32),
+    /// Pops a tuple of `n` elements and pushes them, first element deepest.
+    Untuple(u32),
+    /// Pops a value and pushes the optional holding it.
+    Some,
+    /// Pops an optional that is `some` and pushes the value inside.
+    Unwrap,
+    /// Pops the index or key then the array or map, pushes the element.
+    Index,
+    /// Pops a str, array or map and pushes its length.
+    Len,
+    /// Pops an enum or optional value and pushes 1 if constant `n` names its
+    /// variant (`some` or `none` for optionals), 0 otherwise.
+    IsVariant(u32),
+    Print,
+    /// Like `Print`, then a line break.
+    Println,
+    Pop,
+    /// Continues at instruction `n` of the function.
+    Jump(u32),
+    /// Pops an int and jumps to `n` if it is 0.
+    JumpIfZero(u32),
+    Return,
+    /// Falling off the end of a body: `0` for int functions, `()` otherwise.
+    ReturnDefault,
+    /// Raises constant `n` (a string) as a runtime error. Used for programs the
+    /// AST walker would reject only when the offending code runs.
+    Fail(u32),
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    arity: usize,
+    locals: usize,
+    code: Vec<Op>,
+    default_return: Value,
+    /// Set for `extern` declarations, which cannot run in the interpreter.
+    extern_abi: Option<String>,
+}
+
+/// Bytecode for every function of a program.
+#[derive(Debug, Default)]
+struct Program {
+    functions: Vec<Function>,
+    index: HashMap<String, usize>,
+    constants: Vec<Value>,
+    specs: Vec<FormatSpec>,
+    /// Decoded texts of interpolated literals, see `interp::interpolation_texts`.
+    templates: Vec<Vec<String>>,
+}
+
+struct FunctionCompiler<'a> {
+    program: &'a mut Program,
+    /// Name -> slot of the binding currently visible; `let` always takes a new slot.
+    slots: HashMap<String, u32>,
+    locals: u32,
+    code: Vec<Op>,
+}
+
+impl FunctionCompiler<'_> {
+    fn constant(&mut self, value: Value) -> u32 {
+        self.program.constants.push(value);
+        (self.program.constants.len() - 1) as u32
+    }
+
+    /// Emits a jump whose target is set later by `patch`.
+    fn jump(&mut self, op: fn(u32) -> Op) -> usize {
+        self.code.push(op(0));
+        self.code.len() - 1
+    }
+
+    /// Points the jump at `at` to the next instruction emitted.
+    /// Backward jumps know their target up front and need no patching.
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len() as u32;
+        self.code[at] = match self.code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfZero(_) => Op::JumpIfZero(target),
+            op => unreachable!("{:?} is not a jump", op),
+        };
+    }
+
+    /// Compiles an `if` branch or loop body. Its `let` bindings take fresh slots that go out
+    /// of sight afterwards, so the names they shadowed resolve as before.
+    fn block(&mut self, body: &[ASTNode]) {
+        let outer = self.slots.clone();
+        for stmt in body {
+            self.statement(stmt);
+        }
+        self.slots = outer;
+    }
+
+    fn fail(&mut self, message: String) {
+        let message = self.constant(Value::Str(message.into()));
+        self.code.push(Op::Fail(message));
+    }
+
+    /// Calls `name` with the `argc` values on top of the stack.
+    fn call(&mut self, name: &str, argc: u32) {
+        match self.program.index.get(name) {
+            Some(&function) => self.code.push(Op::Call { function: function as u32, argc }),
+            None => {
+                let name = self.constant(Value::Str(name.into()));
+                self.code.push(Op::CallNative { name, argc });
+            }
+        }
+    }
+
+    fn statement(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Let { name, value, .. } => {
+                self.expression(value);
+                let slot = self.locals;
+                self.locals += 1;
+                self.slots.insert(name.clone(), slot);
+                self.code.push(Op::Store(slot));
+            }
+            ASTNode::LetTuple { bindings, value, .. } => {
+                self.expression(value);
+                self.code.push(Op::Untuple(bindings.len() as u32));
+                let first = self.locals;
+                self.locals += bindings.len() as u32;
+                for (slot, binding) in (first..).zip(bindings) {
+                    self.slots.insert(binding.name.clone(), slot);
+                }
+                // The last element is on top of the stack
+                for slot in (first..self.locals).rev() {
+                    self.code.push(Op::Store(slot));
+                }
+            }
+            ASTNode::Assign { name, value, .. } => {
+                self.expression(value);
+                match self.slots.get(name) {
+                    Some(&slot) => self.code.push(Op::Store(slot)),
+                    None => self.fail(format!("Undefined identifier: {}", name)),
+                }
+            }
+            ASTNode::Print { value, newline, .. } => {
+                self.expression(value);
+                self.code.push(if *newline { Op::Println } else { Op::Print });
+            }
+            ASTNode::Back { value: Some(value), .. } => {
+                self.expression(value);
+                self.code.push(Op::Return);
+            }
+            ASTNode::Back { value: None, .. } => {
+                let unit = self.constant(Value::Unit);
+                self.code.push(Op::Const(unit));
+                self.code.push(Op::Return);
+            }
+            ASTNode::FunctionCall { .. } | ASTNode::MethodCall { .. } => {
+                self.expression(node);
+                self.code.push(Op::Pop);
+            }
+            ASTNode::If { condition, then_body, else_body, .. } => {
+                self.expression(condition);
+                let to_else = self.jump(Op::JumpIfZero);
+                self.block(then_body);
+                if else_body.is_empty() {
+                    self.patch(to_else);
+                } else {
+                    let to_end = self.jump(Op::Jump);
+                    self.patch(to_else);
+                    self.block(else_body);
+                    self.patch(to_end);
+                }
+            }
+            ASTNode::While { condition, body, .. } => {
+                let start = self.code.len() as u32;
+                self.expression(condition);
+                let to_end = self.jump(Op::JumpIfZero);
+                self.block(body);
+                self.code.push(Op::Jump(start));
+                self.patch(to_end);
+            }
+            ASTNode::For { var, start, end, body, .. } => {
+                // The counter and the end take hidden slots; the body sees the counter as `var`.
+                let (counter, last) = (self.locals, self.locals + 1);
+                self.locals += 2;
+                self.expression(start);
+                self.code.push(Op::Store(counter));
+                self.expression(end);
+                self.code.push(Op::Store(last));
+                let top = self.code.len() as u32;
+                self.code.extend([Op::Load(counter), Op::Load(last), Op::Binary(BinaryOp::Less)]);
+                let to_end = self.jump(Op::JumpIfZero);
+                let outer = self.slots.clone();
+                self.slots.insert(var.clone(), counter);
+                self.block(body);
+                self.slots = outer;
+                let one = self.constant(Value::Int(1));
+                self.code.extend([Op::Load(counter), Op::Const(one), Op::Binary(BinaryOp::Add), Op::Store(counter)]);
+                self.code.push(Op::Jump(top));
+                self.patch(to_end);
+            }
+            ASTNode::Match { value, arms, else_body, .. } => {
+                // The value takes a hidden slot and is tested against each arm in turn.
+                let slot = self.locals;
+                self.locals += 1;
+                self.expression(value);
+                self.code.push(Op::Store(slot));
+                let mut to_end = Vec::new();
+                for arm in arms {
+                    let variant = self.constant(Value::Str(arm.variant.as_str().into()));
+                    self.code.extend([Op::Load(slot), Op::IsVariant(variant)]);
+                    let to_next = self.jump(Op::JumpIfZero);
+                    let outer = self.slots.clone();
+                    // `some(name)` binds the value inside to a slot of its own.
+                    if let Some(binding) = &arm.binding {
+                        let inner = self.locals;
+                        self.locals += 1;
+                        self.code.extend([Op::Load(slot), Op::Unwrap, Op::Store(inner)]);
+                        self.slots.insert(binding.name.clone(), inner);
+                    }
+                    self.block(&arm.body);
+                    self.slots = outer;
+                    to_end.push(self.jump(Op::Jump));
+                    self.patch(to_next);
+                }
+                if let Some(else_body) = else_body {
+                    self.block(else_body);
+                }
+                for jump in to_end {
+                    self.patch(jump);
+                }
+            }
+            ASTNode::Function { name, .. } => {
+                self.fail(format!("Nested function {} is not supported by the interpreter", name))
+            }
+            ASTNode::ExternFunction { .. } | ASTNode::Enum { .. } => {}
+            _ => self.fail(format!("{} is not a statement", node.kind())),
+        }
+    }
+
+    fn expression(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Number { value } => match value.parse() {
+                Ok(n) => {
+                    let n = self.constant(Value::Int(n));
+                    self.code.push(Op::Const(n));
+                }
+                Err(_) => self.fail(format!("Integer literal out of range: {}", value)),
+            },
+            ASTNode::String { value } => match unquote(value) {
+                Ok(s) => {
+                    let s = self.constant(Value::Str(s.into()));
+                    self.code.push(Op::Const(s));
+                }
+                Err(e) => self.fail(e),
+            },
+            ASTNode::Identifier { name, .. } => match self.slots.get(name) {
+                Some(&slot) => self.code.push(Op::Load(slot)),
+                None => self.fail(format!("Undefined identifier: {}", name)),
+            },
+            ASTNode::FunctionCall { name, args, .. } if name == "format" => {
+                match compile::format_call_args(args) {
+                    Ok((value, spec)) => {
+                        self.expression(value);
+                        self.program.specs.push(spec);
+                        self.code.push(Op::Format((self.program.specs.len() - 1) as u32));
+                    }
+                    Err(e) => self.fail(e),
+                }
+            }
+            ASTNode::FunctionCall { name, args, .. } if name == "some" => match args.as_slice() {
+                [value] => {
+                    self.expression(value);
+                    self.code.push(Op::Some);
+                }
+                _ => self.fail(format!("some expects 1 argument, got {}", args.len())),
+            },
+            ASTNode::None { ty, .. } => {
+                let none = self.constant(Value::Optional(ty.clone(), None));
+                self.code.push(Op::Const(none));
+            }
+            ASTNode::Interpolation { value, args, .. } => match interpolation_texts(value) {
+                Ok(texts) => {
+                    for arg in args {
+                        self.expression(arg);
+                    }
+                    self.program.templates.push(texts);
+                    self.code.push(Op::Interpolate((self.program.templates.len() - 1) as u32));
+                }
+                Err(e) => self.fail(e),
+            },
+            ASTNode::Binary { op, left, right, operand_type: Some(Type::Enum(_)), .. } => {
+                let Some(name) = op.overload() else {
+                    return self.fail(format!("`{}` is not defined for enums", op.symbol()));
+                };
+                self.expression(left);
+                self.expression(right);
+                self.call(name, 2);
+                // `eq` returns int; `!=` is true where it is 0.
+                if op.is_comparison() {
+                    let zero = self.constant(Value::Int(0));
+                    self.code.push(Op::Const(zero));
+                    let test = if *op == BinaryOp::Equal { BinaryOp::NotEqual } else { BinaryOp::Equal };
+                    self.code.push(Op::Binary(test));
+                }
+            }
+            ASTNode::Binary { op, left, right, span, .. } => {
+                self.expression(left);
+                self.expression(right);
+                self.code.push(Op::Operator { op: *op, line: span.line });
+            }
+            ASTNode::Negate { operand, span } => match &**operand {
+                ASTNode::Number { value } => match negative_literal(value) {
+                    Ok(n) => {
+                        let n = self.constant(Value::Int(n));
+                        self.code.push(Op::Const(n));
+                    }
+                    Err(e) => self.fail(e),
+                },
+                operand => {
+                    self.expression(operand);
+                    self.code.push(Op::Negate(span.line));
+                }
+            },
+            ASTNode::Array { elements, .. } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                self.code.push(Op::Array(elements.len() as u32));
+            }
+            ASTNode::Tuple { elements, .. } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                self.code.push(Op::Tuple(elements.len() as u32));
+            }
+            ASTNode::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                self.code.push(Op::Map(entries.len() as u32));
+            }
+            ASTNode::Index { collection, index, .. } => {
+                self.expression(collection);
+                self.expression(index);
+                self.code.push(Op::Index);
+            }
+            ASTNode::MethodCall { receiver, method: Some(Method::Len), .. } => {
+                self.expression(receiver);
+                self.code.push(Op::Len);
+            }
+            // The receiver is the first argument.
+            ASTNode::MethodCall { receiver, name, args, method: Some(Method::Function), .. } => {
+                self.expression(receiver);
+                for arg in args {
+                    self.expression(arg);
+                }
+                self.call(name, args.len() as u32 + 1);
+            }
+            ASTNode::Field { name, enum_type: Some(Type::Enum(enum_name)), .. } => {
+                let value = self.constant(Value::Enum(enum_name.clone(), name.as_str().into()));
+                self.code.push(Op::Const(value));
+            }
+            ASTNode::FunctionCall { name, args, .. } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+                self.call(name, args.len() as u32);
+            }
+            _ => self.fail(format!("{} is not an expression", node.kind())),
+        }
+    }
+}
+
+impl Program {
+    /// Compiles every top-level function. Compilation never fails: code the AST
+    /// walker would reject at runtime compiles to `Op::Fail` instead.
+    #[tracing::instrument(skip_all, fields(nodes = nodes.len()))]
+    fn compile(nodes: &[ASTNode]) -> Program {
+        let mut program = Program::default();
+        // Index every function first so bodies can call functions defined later.
+        let mut bodies: Vec<&ASTNode> = Vec::new();
+        for node in nodes {
+            if let ASTNode::Function { name, .. } | ASTNode::ExternFunction { name, .. } = node {
+                match program.index.get(name) {
+                    // Later definitions win, as in the AST walker's function table.
+                    Some(&i) => bodies[i] = node,
+                    None => {
+                        program.index.insert(name.clone(), bodies.len());
+                        bodies.push(node);
+                    }
+                }
+            }
+        }
+
+        for node in bodies {
+            let function = program.compile_function(node);
+            program.functions.push(function);
+        }
+        debug!(
+            functions = program.functions.len(),
+            ops = program.functions.iter().map(|f| f.code.len()).sum::<usize>(),
+            "bytecode compiled"
+        );
+        program
+    }
+
+    fn compile_function(&mut self, node: &ASTNode) -> Function {
+        let (name, params, return_type, body, extern_abi) = match node {
+            ASTNode::Function { name, params, return_type, body, .. } => (name, params, return_type, &body[..], None),
+            ASTNode::ExternFunction { abi, name, params, return_type, .. } => {
+                (name, params, return_type, &[][..], Some(abi.clone()))
+            }
+            _ => unreachable!("only functions are compiled"),
+        };
+
+        let mut compiler = FunctionCompiler {
+            program: self,
+            slots: HashMap::new(),
+            locals: 0,
+            code: Vec::new(),
+        };
+        for param in params {
+            let slot = compiler.locals;
+            compiler.locals += 1;
+            compiler.slots.insert(param.name.clone(), slot);
+        }
+        for stmt in body {
+            compiler.statement(stmt);
+        }
+        compiler.code.push(Op::ReturnDefault);
+
+        Function {
+            name: name.clone(),
+            arity: params.len(),
+            locals: compiler.locals as usize,
+            code: compiler.code,
+            default_return: match return_type {
+                Some(_) => Value::Int(0),
+                None => Value::Unit,
+            },
+            extern_abi,
+        }
+    }
+}
+
+/// A suspended caller.
+struct Frame {
+    program: Rc<Program>,
+    function: usize,
+    ip: usize,
+    base: usize,
+}
+
+/// Stack VM running the bytecode of a program; behaves like `interp::Interpreter`.
+pub struct Vm {
+    program: Rc<Program>,
+    stack: Vec<Value>,
+    reload: Option<ReloadHook>,
+    natives: HashMap<String, Native>,
+    overflow: Overflow,
+}
+
+impl Vm {
+    pub fn new(nodes: &[ASTNode]) -> Self {
+        Vm {
+            program: Rc::new(Program::compile(nodes)),
+            stack: Vec::new(),
+            reload: None,
+            natives: HashMap::new(),
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Same contract as `Interpreter::with_reload`: running calls finish with
+    /// the old bytecode, later calls use the new program.
+    pub fn with_reload(mut self, hook: ReloadHook) -> Self {
+        self.reload = Some(hook);
+        self
+    }
+
+    pub fn with_natives(mut self, natives: HashMap<String, Native>) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn run(&mut self) -> Result<(), String> {
+        let Some(&main) = self.program.index.get("main") else {
+            debug!("no `main` found, nothing to run");
+            return Ok(());
+        };
+        let program = Rc::clone(&self.program);
+        self.execute(program, main, Vec::new())?;
+        Ok(())
+    }
+
+    /// Same contract as `Interpreter::call`.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let Some(&function) = self.program.index.get(name) else {
+            return match self.natives.get(name) {
+                Some(native) => native(&args),
+                None => Err(format!("Undefined function: {}", name)),
+            };
+        };
+        let program = Rc::clone(&self.program);
+        self.execute(program, function, args)
+    }
+
+    /// Checks the callee and sets up its frame; returns where its locals start.
+    fn enter(&mut self, program: &Program, function: usize, argc: usize) -> Result<usize, String> {
+        let callee = &program.functions[function];
+        if let Some(abi) = &callee.extern_abi {
+            return Err(format!(
+                "extern {} function {} cannot be called by the interpreter; build with rustc instead",
+                abi, callee.name
+            ));
+        }
+        if callee.arity != argc {
+            return Err(format!(
+                "Function {} expects {} arguments, got {}",
+                callee.name, callee.arity, argc
+            ));
+        }
+        let base = self.stack.len() - argc;
+        self.stack.resize(base + callee.locals, Value::Unit);
+        Ok(base)
+    }
+
+    /// Resolves a call made from `caller`; after a reload the callee is looked
+    /// up by name so that it runs the new definition.
+    fn resolve(&mut self, caller: &Rc<Program>, function: usize) -> Result<(Rc<Program>, usize), String> {
+        if let Some(program) = self.reload.as_mut().and_then(|hook| hook()) {
+            debug!("reloading function definitions");
+            self.program = Rc::new(Program::compile(&program));
+        }
+        if Rc::ptr_eq(caller, &self.program) {
+            return Ok((Rc::clone(caller), function));
+        }
+        let name = &caller.functions[function].name;
+        match self.program.index.get(name) {
+            Some(&function) => Ok((Rc::clone(&self.program), function)),
+            None => Err(format!("Undefined function: {}", name)),
+        }
+    }
+
+    fn execute(&mut self, program: Rc<Program>, function: usize, args: Vec<Value>) -> Result<Value, String> {
+        let (mut program, mut function) = self.resolve(&program, function)?;
+        let argc = args.len();
+        self.stack.extend(args);
+        let mut base = self.enter(&program, function, argc)?;
+        let mut ip = 0;
+        let mut frames: Vec<Frame> = Vec::new();
+
+        loop {
+            let op = program.functions[function].code[ip];
+            trace!(?op, ip, function = %program.functions[function].name, "op");
+            ip += 1;
+            match op {
+                Op::Const(n) => self.stack.push(program.constants[n as usize].clone()),
+                Op::Load(slot) => self.stack.push(self.stack[base + slot as usize].clone()),
+                Op::Store(slot) => {
+                    let value = self.stack.pop().expect("value to store");
+                    self.stack[base + slot as usize] = value;
+                }
+                Op::Call { function: callee, argc } => {
+                    let (callee_program, callee) = self.resolve(&program, callee as usize)?;
+                    let callee_base = self.enter(&callee_program, callee, argc as usize)?;
+                    frames.push(Frame {
+                        program: std::mem::replace(&mut program, callee_program),
+                        function,
+                        ip,
+                        base,
+                    });
+                    function = callee;
+                    ip = 0;
+                    base = callee_base;
+                }
+                Op::CallNative { name, argc } => {
+                    let Value::Str(name) = &program.constants[name as usize] else {
+                        unreachable!("native names are string constants")
+                    };
+                    let Some(native) = self.natives.get(&**name) else {
+                        return Err(format!("Undefined function: {}", name));
+                    };
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    self.stack.push(native(&args)?);
+                }
+                Op::Format(spec) => match self.stack.pop() {
+                    Some(Value::Int(n)) => self.stack.push(Value::Str(program.specs[spec as usize].apply(n).into())),
+                    other => {
+                        return Err(format!(
+                            "format only supports int values, got {}",
+                            other.unwrap_or(Value::Unit)
+                        ))
+                    }
+                },
+                Op::Interpolate(template) => {
+                    let texts = &program.templates[template as usize];
+                    let values = self.stack.split_off(self.stack.len() - (texts.len() - 1));
+                    self.stack.push(interpolate(texts, &values));
+                }
+                Op::Binary(op) => {
+                    let right = self.stack.pop().expect("right operand");
+                    let left = self.stack.pop().expect("left operand");
+                    self.stack.push(binary(op, left, right, self.overflow)?);
+                }
+                Op::Operator { op, line } => {
+                    let right = self.stack.pop().expect("right operand");
+                    let left = self.stack.pop().expect("left operand");
+                    self.stack.push(binary(op, left, right, self.overflow).map_err(|e| format!("line {}: {}", line, e))?);
+                }
+                Op::Negate(line) => {
+                    let value = self.stack.pop().expect("operand");
+                    self.stack.push(negate(value, self.overflow).map_err(|e| format!("line {}: {}", line, e))?);
+                }
+                Op::Array(n) => {
+                    let elements = self.stack.split_off(self.stack.len() - n as usize);
+                    self.stack.push(array(elements)?);
+                }
+                Op::Tuple(n) => {
+                    let elements = self.stack.split_off(self.stack.len() - n as usize);
+                    self.stack.push(Value::Tuple(elements.into()));
+                }
+                Op::Untuple(n) => {
+                    let tuple = self.stack.pop().expect("tuple");
+                    self.stack.extend(untuple(tuple, n as usize)?);
+                }
+                Op::Some => {
+                    let value = self.stack.pop().expect("value to hold");
+                    self.stack.push(some(value)?);
+                }
+                Op::Unwrap => {
+                    let optional = self.stack.pop().expect("optional");
+                    self.stack.push(unwrap(optional)?);
+                }
+                Op::Map(n) => {
+                    let mut values = self.stack.split_off(self.stack.len() - 2 * n as usize).into_iter();
+                    let entries = std::iter::from_fn(|| Some((values.next()?, values.next()?))).collect();
+                    self.stack.push(map(entries)?);
+                }
+                Op::Index => {
+                    let position = self.stack.pop().expect("index");
+                    let collection = self.stack.pop().expect("array or map");
+                    self.stack.push(index(&collection, position)?);
+                }
+                Op::Len => {
+                    let value = self.stack.pop().expect("value with a length");
+                    self.stack.push(len(&value)?);
+                }
+                Op::IsVariant(expected) => {
+                    let Value::Str(expected) = &program.constants[expected as usize] else {
+                        unreachable!("variant names are string constants")
+                    };
+                    let value = self.stack.pop().expect("value to match");
+                    let current = variant(&value)?;
+                    self.stack.push(Value::Int(i32::from(current == &**expected)));
+                }
+                Op::Print => print!("{}", self.stack.pop().expect("value to print")),
+                Op::Println => println!("{}", self.stack.pop().expect("value to print")),
+                Op::Pop => {
+                    self.stack.pop();
+                }
+                Op::Jump(target) => ip = target as usize,
+                Op::JumpIfZero(target) => match self.stack.pop() {
+                    Some(Value::Int(0)) => ip = target as usize,
+                    Some(Value::Int(_)) => {}
+                    other => return Err(format!("condition must be int, got {}", other.unwrap_or(Value::Unit))),
+                },
+                Op::Return | Op::ReturnDefault => {
+                    let result = match op {
+                        Op::Return => self.stack.pop().expect("return value"),
+                        _ => program.functions[function].default_return.clone(),
+                    };
+                    self.stack.truncate(base);
+                    let Some(caller) = frames.pop() else {
+                        return Ok(result);
+                    };
+                    self.stack.push(result);
+                    program = caller.program;
+                    function = caller.function;
+                    ip = caller.ip;
+                    base = caller.base;
+                }
+                Op::Fail(message) => return Err(program.constants[message as usize].to_string()),
+            }
+        }
+    }
+}